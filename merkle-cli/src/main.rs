@@ -0,0 +1,293 @@
+use merkle_tree_lib::util::UserData;
+use merkle_tree_lib::{MerkleTree, NodeDirection, TraversePath};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::process::ExitCode;
+
+/// The tag the proof of reserve app hashes leaf nodes with.
+const TAG_LEAF: &str = "ProofOfReserve_Leaf";
+
+/// The tag the proof of reserve app hashes branch nodes with.
+const TAG_BRANCH: &str = "ProofOfReserve_Branch";
+
+/// The file the tree is saved to by `build` and loaded from by the other commands.
+const DEFAULT_TREE: &str = "merkle.tree";
+
+const USAGE: &str = "\
+Usage:
+  merkle build <users.csv> [--tree <path>]
+  merkle root [--tree <path>]
+  merkle prove --user-id <id> [--tree <path>]
+  merkle verify --root <hex> --proof <proof.json> [--user-id <id>]
+
+The CSV has one `id,balance` line per user, optionally preceded by a header line.
+The tree is saved to and loaded from merkle.tree unless --tree is given.";
+
+/// The proof printed by `prove`, in the format served by `/proof/<user-id>` of the proof of
+/// reserve app with the user id added.
+#[derive(Serialize, Deserialize)]
+struct MerkleProof {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    user_id: Option<u32>,
+    user_balance: u64,
+    proof: Vec<(String, u8)>,
+}
+
+/// The positional arguments and `--name value` options of a command.
+struct Args {
+    positional: Vec<String>,
+    options: HashMap<String, String>,
+}
+
+impl Args {
+    /// Splits the arguments after the command name.
+    ///
+    /// # Arguments
+    ///
+    /// * `args`: The arguments.
+    fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut positional = Vec::new();
+        let mut options = HashMap::new();
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            match arg.strip_prefix("--") {
+                Some(name) => {
+                    let value = args
+                        .next()
+                        .ok_or_else(|| format!("--{name} needs a value"))?;
+                    options.insert(name.to_string(), value);
+                }
+                None => positional.push(arg),
+            }
+        }
+
+        Ok(Args {
+            positional,
+            options,
+        })
+    }
+
+    /// Returns the value of an option, or an error if it is missing.
+    fn required(&self, name: &str) -> Result<&str, String> {
+        self.options
+            .get(name)
+            .map(String::as_str)
+            .ok_or_else(|| format!("--{name} is required"))
+    }
+
+    /// Returns the path of the tree file.
+    fn tree_path(&self) -> &str {
+        self.options
+            .get("tree")
+            .map_or(DEFAULT_TREE, String::as_str)
+    }
+
+    /// Returns the `--user-id` option, if given.
+    fn user_id(&self) -> Result<Option<u32>, String> {
+        self.options
+            .get("user-id")
+            .map(|id| id.parse().map_err(|_| format!("invalid user id {id}")))
+            .transpose()
+    }
+}
+
+/// Parses users from CSV lines of `id,balance`.
+///
+/// # Arguments
+///
+/// * `csv`: The CSV text, optionally starting with an `id,balance` header line in any case.
+///
+/// # Returns
+///
+/// The users, or an error naming the first malformed line or duplicate id.
+fn parse_users(csv: &str) -> Result<Vec<UserData<u64>>, String> {
+    let mut users = Vec::new();
+    let mut ids = HashSet::new();
+    let is_header = |line: &str| {
+        line.split_once(',').is_some_and(|(id, balance)| {
+            id.trim().eq_ignore_ascii_case("id") && balance.trim().eq_ignore_ascii_case("balance")
+        })
+    };
+
+    for (number, line) in csv.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || (number == 0 && is_header(line)) {
+            continue;
+        }
+
+        let fields = line.split_once(',').and_then(|(id, balance)| {
+            Some((id.trim().parse().ok()?, balance.trim().parse().ok()?))
+        });
+        let Some((id, balance)) = fields else {
+            return Err(format!("line {}: expected `id,balance`", number + 1));
+        };
+
+        if !ids.insert(id) {
+            return Err(format!("line {}: duplicate user id {id}", number + 1));
+        }
+        users.push(UserData { id, balance });
+    }
+
+    Ok(users)
+}
+
+/// Loads the tree saved by `build`.
+fn load_tree(path: &str) -> Result<MerkleTree<UserData<u64>>, String> {
+    let file = File::open(path).map_err(|err| format!("{path}: {err}"))?;
+    MerkleTree::read_from(TAG_LEAF, TAG_BRANCH, file).map_err(|err| format!("{path}: {err}"))
+}
+
+/// Builds the tree from a CSV file and saves it.
+fn build(args: &Args) -> Result<String, String> {
+    let [csv_path] = args.positional.as_slice() else {
+        return Err("build needs the path of a CSV file".to_string());
+    };
+    let csv = std::fs::read_to_string(csv_path).map_err(|err| format!("{csv_path}: {err}"))?;
+    let users = parse_users(&csv)?;
+
+    let tree: MerkleTree<UserData<u64>> = MerkleTree::build(TAG_LEAF, TAG_BRANCH, &users);
+    let path = args.tree_path();
+    let file = File::create(path).map_err(|err| format!("{path}: {err}"))?;
+    tree.write_to(file)
+        .map_err(|err| format!("{path}: {err}"))?;
+
+    let root = tree.root().map_err(|err| err.to_string())?;
+    Ok(format!(
+        "built a tree of {} users with root {root}",
+        users.len()
+    ))
+}
+
+/// Returns the root of the saved tree.
+fn root(args: &Args) -> Result<String, String> {
+    load_tree(args.tree_path())?
+        .root()
+        .map_err(|err| err.to_string())
+}
+
+/// Returns the proof of a user of the saved tree as JSON.
+fn prove(args: &Args) -> Result<String, String> {
+    let user_id = args.user_id()?.ok_or("--user-id is required")?;
+    let tree = load_tree(args.tree_path())?;
+    let (node, path) = tree
+        .search_with_path(|user| user.id == user_id)
+        .map_err(|err| format!("user {user_id}: {err}"))?;
+    let user = node.user_data.as_ref().ok_or("the leaf has no user data")?;
+
+    serde_json::to_string_pretty(&MerkleProof {
+        user_id: Some(user_id),
+        user_balance: user.balance,
+        proof: path.to_vec(),
+    })
+    .map_err(|err| err.to_string())
+}
+
+/// Verifies a proof against a root.
+///
+/// # Returns
+///
+/// Whether the proof is valid, or an error if it cannot be read.
+fn verify(args: &Args) -> Result<bool, String> {
+    let root = args.required("root")?;
+    let proof_path = args.required("proof")?;
+    let file = File::open(proof_path).map_err(|err| format!("{proof_path}: {err}"))?;
+    let proof: MerkleProof =
+        serde_json::from_reader(file).map_err(|err| format!("{proof_path}: {err}"))?;
+
+    let id = (args.user_id()?.or(proof.user_id))
+        .ok_or("the proof has no user id, pass it with --user-id")?;
+    let mut siblings = Vec::new();
+    let mut directions = Vec::new();
+    for (sibling, direction) in proof.proof {
+        siblings.push(sibling);
+        directions.push(
+            NodeDirection::try_from(direction).map_err(|err| format!("{proof_path}: {err}"))?,
+        );
+    }
+
+    let user = UserData {
+        id,
+        balance: proof.user_balance,
+    };
+    let path: TraversePath = TraversePath::from_steps(siblings, directions);
+    Ok(merkle_tree_lib::verify_proof(
+        TAG_LEAF, TAG_BRANCH, root, &user, &path,
+    ))
+}
+
+fn main() -> ExitCode {
+    let mut args = std::env::args().skip(1);
+    let command = args.next();
+    let result = Args::parse(args).and_then(|args| match command.as_deref() {
+        Some("build") => build(&args).map(|output| (output, true)),
+        Some("root") => root(&args).map(|output| (output, true)),
+        Some("prove") => prove(&args).map(|output| (output, true)),
+        Some("verify") => verify(&args).map(|valid| {
+            let output = if valid { "valid" } else { "invalid" };
+            (output.to_string(), valid)
+        }),
+        _ => Err(USAGE.to_string()),
+    });
+
+    match result {
+        Ok((output, success)) => {
+            println!("{output}");
+            if success {
+                ExitCode::SUCCESS
+            } else {
+                ExitCode::FAILURE
+            }
+        }
+        Err(err) => {
+            eprintln!("{err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Args {
+        Args::parse(args.iter().map(|arg| arg.to_string())).unwrap()
+    }
+
+    #[test]
+    fn it_can_parse_users_csv() {
+        let users = parse_users("id,balance\n1,1111\n\n 2, 2222\n3,2100000000000000\n").unwrap();
+        let users: Vec<_> = users.iter().map(|user| (user.id, user.balance)).collect();
+        assert_eq!(users, [(1, 1111), (2, 2222), (3, 2_100_000_000_000_000)]);
+
+        assert!(parse_users("1,1111\n2\n").is_err());
+        assert_eq!(
+            parse_users("1;1111\n2,2222\n").unwrap_err(),
+            "line 1: expected `id,balance`"
+        );
+        assert!(parse_users("1,abc\n2,2222\n").is_err());
+        assert!(parse_users("1,1111\n1,2222\n").is_err());
+    }
+
+    #[test]
+    fn it_can_build_prove_and_verify() {
+        let dir = std::env::temp_dir().join(format!("merkle-cli-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = |name: &str| dir.join(name).to_str().unwrap().to_string();
+        std::fs::write(path("users.csv"), "1,1111\n2,2222\n3,3333\n").unwrap();
+
+        build(&args(&["--tree", &path("tree"), &path("users.csv")])).unwrap();
+        let root = root(&args(&["--tree", &path("tree")])).unwrap();
+        let proof = prove(&args(&["--tree", &path("tree"), "--user-id", "3"])).unwrap();
+        std::fs::write(path("proof.json"), &proof).unwrap();
+
+        let verify_args = ["--root", &root, "--proof", &path("proof.json")];
+        assert!(verify(&args(&verify_args)).unwrap());
+        let other_user = [&verify_args[..], &["--user-id", "2"]].concat();
+        assert!(!verify(&args(&other_user)).unwrap());
+        assert!(prove(&args(&["--tree", &path("tree"), "--user-id", "4"])).is_err());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
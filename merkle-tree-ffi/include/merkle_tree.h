@@ -0,0 +1,198 @@
+#ifndef MERKLE_TREE_H
+#define MERKLE_TREE_H
+
+/* Generated by cbindgen from merkle-tree-ffi/src/lib.rs. Do not edit by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * The status code returned by every function of the C API.
+ */
+typedef enum MerkleStatus {
+  /**
+   * The call succeeded.
+   */
+  MERKLE_STATUS_OK = 0,
+  /**
+   * A required pointer argument is null.
+   */
+  MERKLE_STATUS_NULL_POINTER = 1,
+  /**
+   * A tag is not valid UTF-8.
+   */
+  MERKLE_STATUS_INVALID_UTF8 = 2,
+  /**
+   * The tree has no leaves.
+   */
+  MERKLE_STATUS_EMPTY_TREE = 3,
+  /**
+   * No leaf or proof step exists at the given index.
+   */
+  MERKLE_STATUS_LEAF_NOT_FOUND = 4,
+  /**
+   * The proof is malformed, such as having a direction other than 0 or 1.
+   */
+  MERKLE_STATUS_INVALID_PROOF = 5,
+  /**
+   * Any other error of the library.
+   */
+  MERKLE_STATUS_INTERNAL = 6,
+} MerkleStatus;
+
+/**
+ * An opaque handle to the proof of a leaf, created by `merkle_tree_proof` and released with
+ * `merkle_proof_free`.
+ */
+typedef struct MerkleProof MerkleProof;
+
+/**
+ * An opaque handle to a SHA-256 Merkle Tree, created by `merkle_tree_build` and released
+ * with `merkle_tree_free`.
+ */
+typedef struct MerkleTree MerkleTree;
+
+/**
+ * Builds a tree from leaves given as byte strings, hashed with the tagged hash of the
+ * library.
+ *
+ * # Arguments
+ *
+ * * `tag_leaf`: The NUL-terminated tag used for hashing leaf nodes.
+ * * `tag_branch`: The NUL-terminated tag used for hashing branch nodes.
+ * * `leaves`: An array of `count` pointers to the bytes of each leaf.
+ * * `leaf_lengths`: An array of `count` lengths of the leaves.
+ * * `count`: The number of leaves.
+ * * `out_tree`: Receives the handle of the tree.
+ *
+ * # Safety
+ *
+ * The tags must be NUL-terminated strings, `leaves` and `leaf_lengths` must point to `count`
+ * elements each, and each leaf must point to as many bytes as its length.
+ */
+MerkleStatus merkle_tree_build(const char *tag_leaf,
+                               const char *tag_branch,
+                               const uint8_t *const *leaves,
+                               const uintptr_t *leaf_lengths,
+                               uintptr_t count,
+                               MerkleTree **out_tree);
+
+/**
+ * Releases a tree. Passing null does nothing.
+ *
+ * # Safety
+ *
+ * `tree` must be null or a handle returned by `merkle_tree_build` that was not freed yet.
+ */
+void merkle_tree_free(MerkleTree *tree);
+
+/**
+ * Returns the number of leaves of a tree, or 0 if `tree` is null.
+ *
+ * # Safety
+ *
+ * `tree` must be null or a valid handle.
+ */
+uintptr_t merkle_tree_leaf_count(const MerkleTree *tree);
+
+/**
+ * Writes the 32 byte root hash of a tree.
+ *
+ * # Arguments
+ *
+ * * `tree`: The tree.
+ * * `out_root`: Receives the root, 32 bytes.
+ *
+ * # Safety
+ *
+ * `tree` must be a valid handle and `out_root` must point to 32 writable bytes.
+ */
+MerkleStatus merkle_tree_root(const MerkleTree *tree, uint8_t *out_root);
+
+/**
+ * Generates the proof for the leaf at the given index.
+ *
+ * # Arguments
+ *
+ * * `tree`: The tree.
+ * * `index`: The position of the leaf.
+ * * `out_proof`: Receives the handle of the proof.
+ *
+ * # Safety
+ *
+ * `tree` must be a valid handle and `out_proof` a valid pointer.
+ */
+MerkleStatus merkle_tree_proof(const MerkleTree *tree, uintptr_t index, MerkleProof **out_proof);
+
+/**
+ * Releases a proof. Passing null does nothing.
+ *
+ * # Safety
+ *
+ * `proof` must be null or a handle returned by `merkle_tree_proof` that was not freed yet.
+ */
+void merkle_proof_free(MerkleProof *proof);
+
+/**
+ * Returns the number of steps of a proof, or 0 if `proof` is null.
+ *
+ * # Safety
+ *
+ * `proof` must be null or a valid handle.
+ */
+uintptr_t merkle_proof_len(const MerkleProof *proof);
+
+/**
+ * Reads a step of a proof, ordered from the root down to the leaf.
+ *
+ * # Arguments
+ *
+ * * `proof`: The proof.
+ * * `step`: The position of the step.
+ * * `out_sibling`: Receives the hash of the sibling, 32 bytes.
+ * * `out_direction`: Receives the direction taken, 0 for left and 1 for right.
+ *
+ * # Safety
+ *
+ * `proof` must be a valid handle, `out_sibling` must point to 32 writable bytes and
+ * `out_direction` must be a valid pointer.
+ */
+MerkleStatus merkle_proof_step(const MerkleProof *proof,
+                               uintptr_t step,
+                               uint8_t *out_sibling,
+                               uint8_t *out_direction);
+
+/**
+ * Verifies that a leaf is committed to by a root.
+ *
+ * # Arguments
+ *
+ * * `tag_leaf`: The NUL-terminated tag used for hashing leaf nodes.
+ * * `tag_branch`: The NUL-terminated tag used for hashing branch nodes.
+ * * `root`: The 32 byte root hash.
+ * * `leaf`: The bytes of the leaf.
+ * * `leaf_length`: The number of bytes of the leaf.
+ * * `siblings`: The `steps` sibling hashes of the proof, 32 bytes each, from the root down.
+ * * `directions`: The `steps` directions of the proof, 0 for left and 1 for right.
+ * * `steps`: The number of steps of the proof.
+ * * `out_valid`: Receives whether the proof is valid.
+ *
+ * # Safety
+ *
+ * The tags must be NUL-terminated strings, `root` must point to 32 bytes, `leaf` to
+ * `leaf_length` bytes, `siblings` to `32 * steps` bytes, `directions` to `steps` bytes and
+ * `out_valid` must be a valid pointer.
+ */
+MerkleStatus merkle_verify_proof(const char *tag_leaf,
+                                 const char *tag_branch,
+                                 const uint8_t *root,
+                                 const uint8_t *leaf,
+                                 uintptr_t leaf_length,
+                                 const uint8_t *siblings,
+                                 const uint8_t *directions,
+                                 uintptr_t steps,
+                                 bool *out_valid);
+
+#endif /* MERKLE_TREE_H */
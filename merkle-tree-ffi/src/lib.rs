@@ -0,0 +1,451 @@
+use core::ffi::c_char;
+use core::slice;
+use merkle_tree_lib::{MerkleError, MerkleTreeData, NodeDirection, Sha256, TraversePath};
+use std::ffi::CStr;
+
+/// The size of the SHA-256 hashes of the tree.
+const HASH_SIZE: usize = 32;
+
+/// The status code returned by every function of the C API.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MerkleStatus {
+    /// The call succeeded.
+    Ok = 0,
+    /// A required pointer argument is null.
+    NullPointer = 1,
+    /// A tag is not valid UTF-8.
+    InvalidUtf8 = 2,
+    /// The tree has no leaves.
+    EmptyTree = 3,
+    /// No leaf or proof step exists at the given index.
+    LeafNotFound = 4,
+    /// The proof is malformed, such as having a direction other than 0 or 1.
+    InvalidProof = 5,
+    /// Any other error of the library.
+    Internal = 6,
+}
+
+impl From<MerkleError> for MerkleStatus {
+    fn from(err: MerkleError) -> Self {
+        match err {
+            MerkleError::EmptyTree => MerkleStatus::EmptyTree,
+            MerkleError::LeafNotFound => MerkleStatus::LeafNotFound,
+            MerkleError::InvalidProof => MerkleStatus::InvalidProof,
+            _ => MerkleStatus::Internal,
+        }
+    }
+}
+
+/// A leaf given as raw bytes, which are hashed as they are.
+#[derive(Debug, Default, Clone)]
+struct Leaf(Vec<u8>);
+
+impl MerkleTreeData for Leaf {
+    fn serialize(&self) -> Vec<u8> {
+        self.0.clone()
+    }
+
+    fn mermaid_node_label(&self) -> String {
+        format!("<br>{}", hex::encode(&self.0))
+    }
+}
+
+/// An opaque handle to a SHA-256 Merkle Tree, created by `merkle_tree_build` and released
+/// with `merkle_tree_free`.
+pub struct MerkleTree {
+    tree: merkle_tree_lib::MerkleTree<Leaf>,
+}
+
+/// An opaque handle to the proof of a leaf, created by `merkle_tree_proof` and released with
+/// `merkle_proof_free`.
+pub struct MerkleProof {
+    path: TraversePath,
+}
+
+/// Returns early with `MerkleStatus::NullPointer` if any of the pointers is null.
+macro_rules! check_null {
+    ($($ptr:expr),*) => {
+        if $($ptr.is_null())||* {
+            return MerkleStatus::NullPointer;
+        }
+    };
+}
+
+/// Reads a NUL-terminated tag.
+///
+/// # Safety
+///
+/// `tag` must be a valid pointer to a NUL-terminated string.
+unsafe fn read_tag<'a>(tag: *const c_char) -> Result<&'a str, MerkleStatus> {
+    CStr::from_ptr(tag)
+        .to_str()
+        .map_err(|_| MerkleStatus::InvalidUtf8)
+}
+
+/// Builds a tree from leaves given as byte strings, hashed with the tagged hash of the
+/// library.
+///
+/// # Arguments
+///
+/// * `tag_leaf`: The NUL-terminated tag used for hashing leaf nodes.
+/// * `tag_branch`: The NUL-terminated tag used for hashing branch nodes.
+/// * `leaves`: An array of `count` pointers to the bytes of each leaf.
+/// * `leaf_lengths`: An array of `count` lengths of the leaves.
+/// * `count`: The number of leaves.
+/// * `out_tree`: Receives the handle of the tree.
+///
+/// # Safety
+///
+/// The tags must be NUL-terminated strings, `leaves` and `leaf_lengths` must point to `count`
+/// elements each, and each leaf must point to as many bytes as its length.
+#[no_mangle]
+pub unsafe extern "C" fn merkle_tree_build(
+    tag_leaf: *const c_char,
+    tag_branch: *const c_char,
+    leaves: *const *const u8,
+    leaf_lengths: *const usize,
+    count: usize,
+    out_tree: *mut *mut MerkleTree,
+) -> MerkleStatus {
+    check_null!(tag_leaf, tag_branch, out_tree);
+    if count > 0 {
+        check_null!(leaves, leaf_lengths);
+    }
+    let (tag_leaf, tag_branch) = match (read_tag(tag_leaf), read_tag(tag_branch)) {
+        (Ok(tag_leaf), Ok(tag_branch)) => (tag_leaf, tag_branch),
+        (Err(status), _) | (_, Err(status)) => return status,
+    };
+
+    let mut input = Vec::with_capacity(count);
+    if count > 0 {
+        let leaves = slice::from_raw_parts(leaves, count);
+        let leaf_lengths = slice::from_raw_parts(leaf_lengths, count);
+        for (&leaf, &len) in leaves.iter().zip(leaf_lengths) {
+            if len == 0 {
+                input.push(Leaf::default());
+                continue;
+            }
+            check_null!(leaf);
+            input.push(Leaf(slice::from_raw_parts(leaf, len).to_vec()));
+        }
+    }
+
+    let tree = merkle_tree_lib::MerkleTree::build(tag_leaf, tag_branch, &input);
+    *out_tree = Box::into_raw(Box::new(MerkleTree { tree }));
+    MerkleStatus::Ok
+}
+
+/// Releases a tree. Passing null does nothing.
+///
+/// # Safety
+///
+/// `tree` must be null or a handle returned by `merkle_tree_build` that was not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn merkle_tree_free(tree: *mut MerkleTree) {
+    if !tree.is_null() {
+        drop(Box::from_raw(tree));
+    }
+}
+
+/// Returns the number of leaves of a tree, or 0 if `tree` is null.
+///
+/// # Safety
+///
+/// `tree` must be null or a valid handle.
+#[no_mangle]
+pub unsafe extern "C" fn merkle_tree_leaf_count(tree: *const MerkleTree) -> usize {
+    tree.as_ref().map_or(0, |tree| tree.tree.leaf_count())
+}
+
+/// Writes the 32 byte root hash of a tree.
+///
+/// # Arguments
+///
+/// * `tree`: The tree.
+/// * `out_root`: Receives the root, 32 bytes.
+///
+/// # Safety
+///
+/// `tree` must be a valid handle and `out_root` must point to 32 writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn merkle_tree_root(
+    tree: *const MerkleTree,
+    out_root: *mut u8,
+) -> MerkleStatus {
+    check_null!(tree, out_root);
+    match (*tree).tree.root_hash() {
+        Ok(root) => {
+            slice::from_raw_parts_mut(out_root, HASH_SIZE).copy_from_slice(&root);
+            MerkleStatus::Ok
+        }
+        Err(err) => err.into(),
+    }
+}
+
+/// Generates the proof for the leaf at the given index.
+///
+/// # Arguments
+///
+/// * `tree`: The tree.
+/// * `index`: The position of the leaf.
+/// * `out_proof`: Receives the handle of the proof.
+///
+/// # Safety
+///
+/// `tree` must be a valid handle and `out_proof` a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn merkle_tree_proof(
+    tree: *const MerkleTree,
+    index: usize,
+    out_proof: *mut *mut MerkleProof,
+) -> MerkleStatus {
+    check_null!(tree, out_proof);
+    match (*tree).tree.proof_by_index(index) {
+        Ok((_node, path)) => {
+            *out_proof = Box::into_raw(Box::new(MerkleProof { path }));
+            MerkleStatus::Ok
+        }
+        Err(err) => err.into(),
+    }
+}
+
+/// Releases a proof. Passing null does nothing.
+///
+/// # Safety
+///
+/// `proof` must be null or a handle returned by `merkle_tree_proof` that was not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn merkle_proof_free(proof: *mut MerkleProof) {
+    if !proof.is_null() {
+        drop(Box::from_raw(proof));
+    }
+}
+
+/// Returns the number of steps of a proof, or 0 if `proof` is null.
+///
+/// # Safety
+///
+/// `proof` must be null or a valid handle.
+#[no_mangle]
+pub unsafe extern "C" fn merkle_proof_len(proof: *const MerkleProof) -> usize {
+    proof.as_ref().map_or(0, |proof| proof.path.siblings.len())
+}
+
+/// Reads a step of a proof, ordered from the root down to the leaf.
+///
+/// # Arguments
+///
+/// * `proof`: The proof.
+/// * `step`: The position of the step.
+/// * `out_sibling`: Receives the hash of the sibling, 32 bytes.
+/// * `out_direction`: Receives the direction taken, 0 for left and 1 for right.
+///
+/// # Safety
+///
+/// `proof` must be a valid handle, `out_sibling` must point to 32 writable bytes and
+/// `out_direction` must be a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn merkle_proof_step(
+    proof: *const MerkleProof,
+    step: usize,
+    out_sibling: *mut u8,
+    out_direction: *mut u8,
+) -> MerkleStatus {
+    check_null!(proof, out_sibling, out_direction);
+    let path = &(*proof).path;
+    let (Some(sibling), Some(direction)) = (path.siblings.get(step), path.directions.get(step))
+    else {
+        return MerkleStatus::LeafNotFound;
+    };
+
+    let out_sibling = slice::from_raw_parts_mut(out_sibling, HASH_SIZE);
+    if hex::decode_to_slice(sibling, out_sibling).is_err() {
+        return MerkleStatus::Internal;
+    }
+    *out_direction = match direction {
+        NodeDirection::Left => 0,
+        _ => 1,
+    };
+    MerkleStatus::Ok
+}
+
+/// Verifies that a leaf is committed to by a root.
+///
+/// # Arguments
+///
+/// * `tag_leaf`: The NUL-terminated tag used for hashing leaf nodes.
+/// * `tag_branch`: The NUL-terminated tag used for hashing branch nodes.
+/// * `root`: The 32 byte root hash.
+/// * `leaf`: The bytes of the leaf.
+/// * `leaf_length`: The number of bytes of the leaf.
+/// * `siblings`: The `steps` sibling hashes of the proof, 32 bytes each, from the root down.
+/// * `directions`: The `steps` directions of the proof, 0 for left and 1 for right.
+/// * `steps`: The number of steps of the proof.
+/// * `out_valid`: Receives whether the proof is valid.
+///
+/// # Safety
+///
+/// The tags must be NUL-terminated strings, `root` must point to 32 bytes, `leaf` to
+/// `leaf_length` bytes, `siblings` to `32 * steps` bytes, `directions` to `steps` bytes and
+/// `out_valid` must be a valid pointer.
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub unsafe extern "C" fn merkle_verify_proof(
+    tag_leaf: *const c_char,
+    tag_branch: *const c_char,
+    root: *const u8,
+    leaf: *const u8,
+    leaf_length: usize,
+    siblings: *const u8,
+    directions: *const u8,
+    steps: usize,
+    out_valid: *mut bool,
+) -> MerkleStatus {
+    check_null!(tag_leaf, tag_branch, root, out_valid);
+    if leaf_length > 0 {
+        check_null!(leaf);
+    }
+    if steps > 0 {
+        check_null!(siblings, directions);
+    }
+    let (tag_leaf, tag_branch) = match (read_tag(tag_leaf), read_tag(tag_branch)) {
+        (Ok(tag_leaf), Ok(tag_branch)) => (tag_leaf, tag_branch),
+        (Err(status), _) | (_, Err(status)) => return status,
+    };
+
+    // The length of the siblings must not overflow, and no slice may be longer than
+    // `isize::MAX` bytes.
+    let siblings_length = match HASH_SIZE.checked_mul(steps) {
+        Some(length) if length <= isize::MAX as usize => length,
+        _ => return MerkleStatus::InvalidProof,
+    };
+
+    let mut path = Vec::with_capacity(steps);
+    if steps > 0 {
+        let siblings = slice::from_raw_parts(siblings, siblings_length);
+        let directions = slice::from_raw_parts(directions, steps);
+        for (sibling, direction) in siblings.chunks(HASH_SIZE).zip(directions) {
+            let direction = match direction {
+                0 => NodeDirection::Left,
+                1 => NodeDirection::Right,
+                _ => return MerkleStatus::InvalidProof,
+            };
+            path.push((hex::encode(sibling), direction));
+        }
+    }
+    let (siblings, directions) = path.into_iter().unzip();
+
+    let leaf = match leaf_length {
+        0 => Leaf::default(),
+        _ => Leaf(slice::from_raw_parts(leaf, leaf_length).to_vec()),
+    };
+    let root = hex::encode(slice::from_raw_parts(root, HASH_SIZE));
+    *out_valid = merkle_tree_lib::verify_proof(
+        tag_leaf,
+        tag_branch,
+        &root,
+        &leaf,
+        &TraversePath::<Sha256>::from_steps(siblings, directions),
+    );
+    MerkleStatus::Ok
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::ptr;
+
+    const TAG_LEAF: &CStr = c"ProofOfReserve_Leaf";
+    const TAG_BRANCH: &CStr = c"ProofOfReserve_Branch";
+
+    #[test]
+    fn it_can_build_prove_and_verify_through_the_c_api() {
+        let leaves: Vec<Vec<u8>> = (1..=5)
+            .map(|id| format!("{},{}", id, id * 1111).into_bytes())
+            .collect();
+        let pointers: Vec<*const u8> = leaves.iter().map(|leaf| leaf.as_ptr()).collect();
+        let lengths: Vec<usize> = leaves.iter().map(Vec::len).collect();
+
+        unsafe {
+            let mut tree = ptr::null_mut();
+            let status = merkle_tree_build(
+                TAG_LEAF.as_ptr(),
+                TAG_BRANCH.as_ptr(),
+                pointers.as_ptr(),
+                lengths.as_ptr(),
+                leaves.len(),
+                &mut tree,
+            );
+            assert_eq!(status, MerkleStatus::Ok);
+            assert_eq!(merkle_tree_leaf_count(tree), 5);
+
+            let mut root = [0; HASH_SIZE];
+            assert_eq!(merkle_tree_root(tree, root.as_mut_ptr()), MerkleStatus::Ok);
+
+            for (index, leaf) in leaves.iter().enumerate() {
+                let mut proof = ptr::null_mut();
+                assert_eq!(merkle_tree_proof(tree, index, &mut proof), MerkleStatus::Ok);
+
+                let steps = merkle_proof_len(proof);
+                let mut siblings = vec![0; HASH_SIZE * steps];
+                let mut directions = vec![0; steps];
+                for step in 0..steps {
+                    let status = merkle_proof_step(
+                        proof,
+                        step,
+                        siblings[HASH_SIZE * step..].as_mut_ptr(),
+                        &mut directions[step],
+                    );
+                    assert_eq!(status, MerkleStatus::Ok);
+                }
+                merkle_proof_free(proof);
+
+                let verify = |leaf: &[u8], valid: &mut bool| {
+                    merkle_verify_proof(
+                        TAG_LEAF.as_ptr(),
+                        TAG_BRANCH.as_ptr(),
+                        root.as_ptr(),
+                        leaf.as_ptr(),
+                        leaf.len(),
+                        siblings.as_ptr(),
+                        directions.as_ptr(),
+                        steps,
+                        valid,
+                    )
+                };
+                let mut valid = false;
+                assert_eq!(verify(leaf, &mut valid), MerkleStatus::Ok);
+                assert!(valid);
+                assert_eq!(verify(b"6,6666", &mut valid), MerkleStatus::Ok);
+                assert!(!valid);
+
+                // The length of the siblings overflows, so the proof is rejected before they
+                // are read.
+                let status = merkle_verify_proof(
+                    TAG_LEAF.as_ptr(),
+                    TAG_BRANCH.as_ptr(),
+                    root.as_ptr(),
+                    leaf.as_ptr(),
+                    leaf.len(),
+                    siblings.as_ptr(),
+                    directions.as_ptr(),
+                    usize::MAX / 16,
+                    &mut valid,
+                );
+                assert_eq!(status, MerkleStatus::InvalidProof);
+            }
+
+            let mut proof = ptr::null_mut();
+            assert_eq!(
+                merkle_tree_proof(tree, 5, &mut proof),
+                MerkleStatus::LeafNotFound
+            );
+            assert_eq!(
+                merkle_tree_root(tree, ptr::null_mut()),
+                MerkleStatus::NullPointer
+            );
+            merkle_tree_free(tree);
+        }
+    }
+}
@@ -1,7 +1,5 @@
 use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
 
-use merkle_tree_lib;
-
 fn bench_merkle_tree_lib_build(c: &mut Criterion) {
     let tag_leaf = "ProofOfReserve_Leaf";
     let tag_branch = "ProofOfReserve_Branch";
@@ -13,9 +11,11 @@ fn bench_merkle_tree_lib_build(c: &mut Criterion) {
             let user_data_large = merkle_tree_lib::util::generate_random_user_data(i);
 
             b.iter(|| {
-                std::hint::black_box({
-                    merkle_tree_lib::MerkleTree::build(tag_leaf, tag_branch, &user_data_large);
-                });
+                std::hint::black_box(merkle_tree_lib::MerkleTree::<_>::build(
+                    tag_leaf,
+                    tag_branch,
+                    &user_data_large,
+                ));
             });
         });
     }
@@ -23,5 +23,55 @@ fn bench_merkle_tree_lib_build(c: &mut Criterion) {
     group.finish();
 }
 
-criterion_group!(benches, bench_merkle_tree_lib_build);
+fn bench_merkle_tree_lib_hash_branch(c: &mut Criterion) {
+    let options =
+        merkle_tree_lib::TreeOptions::tagged("ProofOfReserve_Leaf", "ProofOfReserve_Branch");
+    let left = options.hash_leaf::<merkle_tree_lib::Sha256>(b"left");
+    let right = options.hash_leaf::<merkle_tree_lib::Sha256>(b"right");
+
+    c.bench_function("merkle_tree_lib::hash_branch", |b| {
+        b.iter(|| {
+            std::hint::black_box(options.hash_branch::<merkle_tree_lib::Sha256>(
+                std::hint::black_box(&left),
+                std::hint::black_box(&right),
+            ));
+        });
+    });
+}
+
+fn bench_merkle_tree_lib_from_leaf_hashes(c: &mut Criterion) {
+    let options =
+        merkle_tree_lib::TreeOptions::tagged("ProofOfReserve_Leaf", "ProofOfReserve_Branch");
+    let hashes: Vec<_> = merkle_tree_lib::util::generate_random_user_data(1_000_000)
+        .iter()
+        .map(|user_data| {
+            options.hash_leaf::<merkle_tree_lib::Sha256>(
+                merkle_tree_lib::MerkleTreeData::serialize(user_data).as_slice(),
+            )
+        })
+        .collect();
+
+    let mut group = c.benchmark_group("merkle_tree_lib::from_leaf_hashes");
+    group.sample_size(10);
+
+    group.bench_function("1000000", |b| {
+        b.iter(|| {
+            std::hint::black_box(
+                merkle_tree_lib::MerkleTree::<()>::from_leaf_hashes_with_options(
+                    options.clone(),
+                    &hashes,
+                ),
+            );
+        });
+    });
+
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_merkle_tree_lib_build,
+    bench_merkle_tree_lib_hash_branch,
+    bench_merkle_tree_lib_from_leaf_hashes
+);
 criterion_main!(benches);
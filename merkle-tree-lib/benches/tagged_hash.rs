@@ -1,7 +1,5 @@
 use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
 
-use merkle_tree_lib;
-
 fn bench_tagged_hash(c: &mut Criterion) {
     let tag_leaf = "ProofOfReserve_Leaf";
 
@@ -13,11 +11,14 @@ fn bench_tagged_hash(c: &mut Criterion) {
             max_range,
             |b, &max_range| {
                 b.iter(|| {
-                    std::hint::black_box({
-                        for _ in 0..max_range {
-                            merkle_tree_lib::tagged_hash(&tag_leaf, "aaa".as_bytes());
-                        }
-                    });
+                    for _ in 0..max_range {
+                        std::hint::black_box(
+                            merkle_tree_lib::tagged_hash::<merkle_tree_lib::Sha256>(
+                                tag_leaf,
+                                "aaa".as_bytes(),
+                            ),
+                        );
+                    }
                 });
             },
         );
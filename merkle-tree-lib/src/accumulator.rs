@@ -0,0 +1,158 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use sha2::digest::Output;
+use sha2::Digest;
+
+use crate::{MerkleError, MerkleTreeData, Padding, Sha256, TreeOptions};
+
+/// Computes the Merkle root of a stream of leaves without building the tree.
+///
+/// Only the roots of the complete subtrees seen so far (the frontier) are kept, one per
+/// level, so memory use is O(log n) in the number of leaves. The root is the same as the
+/// root of a `MerkleTree` built from the same leaves with the same options.
+pub struct RootAccumulator<D: Digest = Sha256> {
+    options: TreeOptions,
+    peaks: Vec<Option<Output<D>>>,
+    leaf_count: usize,
+}
+
+impl<D: Digest> RootAccumulator<D> {
+    /// Creates an empty accumulator.
+    ///
+    /// # Arguments
+    ///
+    /// * `options`: The hashing configuration of the tree.
+    pub fn new(options: TreeOptions) -> Self {
+        RootAccumulator {
+            options,
+            peaks: Vec::new(),
+            leaf_count: 0,
+        }
+    }
+
+    /// Returns the number of leaves pushed so far.
+    pub fn leaf_count(&self) -> usize {
+        self.leaf_count
+    }
+
+    /// Adds a leaf after the leaves pushed so far.
+    ///
+    /// # Arguments
+    ///
+    /// * `leaf`: The user data of the leaf.
+    pub fn push<T: MerkleTreeData>(&mut self, leaf: &T) {
+        let hash = self.options.hash_leaf_data::<D>(leaf);
+        self.push_leaf_hash(hash);
+    }
+
+    /// Adds a leaf hash that was computed elsewhere after the leaves pushed so far.
+    ///
+    /// # Arguments
+    ///
+    /// * `hash`: The hash of the leaf.
+    pub fn push_leaf_hash(&mut self, hash: Output<D>) {
+        let mut hash = hash;
+        let mut level = 0;
+
+        loop {
+            if level == self.peaks.len() {
+                self.peaks.push(None);
+            }
+
+            match self.peaks[level].take() {
+                Some(left) => {
+                    hash = self.options.hash_branch::<D>(&left, &hash);
+                    level += 1;
+                }
+                None => {
+                    self.peaks[level] = Some(hash);
+                    break;
+                }
+            }
+        }
+
+        self.leaf_count += 1;
+    }
+
+    /// Returns the Merkle root of the leaves pushed so far.
+    ///
+    /// The unpaired nodes at the end of each level are handled according to the padding
+    /// policy, as they would be in a complete tree. Without leaves the root is selected by
+    /// `TreeOptions::empty_root`.
+    ///
+    /// # Returns
+    ///
+    /// The hex encoded root, or `MerkleError::EmptyTree` if no leaves were pushed and the
+    /// empty root is `EmptyRoot::Undefined`.
+    pub fn root(&self) -> Result<String, MerkleError> {
+        if self.leaf_count == 0 {
+            return (self.options.empty_root_hash::<D>())
+                .map(hex::encode)
+                .ok_or(MerkleError::EmptyTree);
+        }
+
+        let mut carry: Option<Output<D>> = None;
+
+        for (level, peak) in self.peaks.iter().enumerate() {
+            let size = (self.leaf_count >> level) + usize::from(carry.is_some());
+            if size <= 1 {
+                return (carry.or_else(|| peak.clone()))
+                    .map(hex::encode)
+                    .ok_or(MerkleError::EmptyTree);
+            }
+
+            carry = match (peak, carry) {
+                (Some(left), Some(right)) => Some(self.options.hash_branch::<D>(left, &right)),
+                (Some(single), None) => Some(self.pad(single.clone())),
+                (None, Some(single)) => Some(self.pad(single)),
+                (None, None) => None,
+            };
+        }
+
+        carry.map(hex::encode).ok_or(MerkleError::EmptyTree)
+    }
+
+    /// Carries an unpaired node to the next level according to the padding policy.
+    ///
+    /// # Arguments
+    ///
+    /// * `single`: The hash of the unpaired node.
+    fn pad(&self, single: Output<D>) -> Output<D> {
+        match self.options.padding {
+            Padding::DuplicateLast => self.options.hash_branch::<D>(&single, &single),
+            Padding::Promote => single,
+            Padding::PadWithEmpty => self
+                .options
+                .hash_branch::<D>(&single, &self.options.hash_leaf::<D>(&[])),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{util, MerkleTree};
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(Padding::DuplicateLast)]
+    #[case(Padding::Promote)]
+    #[case(Padding::PadWithEmpty)]
+    fn it_can_accumulate_root(#[case] padding: Padding) {
+        let user_data = util::generate_random_user_data(70);
+        let options = TreeOptions::tagged("ProofOfReserve_Leaf", "ProofOfReserve_Branch")
+            .with_padding(padding);
+
+        let mut accumulator: RootAccumulator = RootAccumulator::new(options.clone());
+        assert_eq!(accumulator.root(), Err(MerkleError::EmptyTree));
+
+        for (count, item) in user_data.iter().enumerate().map(|(i, item)| (i + 1, item)) {
+            accumulator.push(item);
+
+            let tree: MerkleTree<_> =
+                MerkleTree::build_with_options(options.clone(), &user_data[..count]);
+            assert_eq!(accumulator.leaf_count(), count);
+            assert_eq!(accumulator.root(), tree.root());
+        }
+    }
+}
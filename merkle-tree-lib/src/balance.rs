@@ -0,0 +1,677 @@
+use alloc::vec::Vec;
+use core::fmt;
+use core::str::FromStr;
+
+use crate::witness::to_decimal;
+
+/// A type that balances are summed up in by a `SumMerkleTree`.
+///
+/// Implemented by `u32`, `u64` and `u128`, by `Decimal` for amounts with a fixed number of
+/// decimals such as fiat cents or 18-decimal tokens, and by `BigBalance` for integers of any
+/// size.
+pub trait Balance: Clone + Default + PartialEq + fmt::Debug {
+    /// The bytes of a value as hashed into the branches.
+    type Bytes: AsRef<[u8]>;
+
+    /// Adds two balances, or returns `None` on overflow.
+    fn checked_add(&self, other: &Self) -> Option<Self>;
+
+    /// Returns the bytes of the balance, which must be an unambiguous encoding: big-endian
+    /// with a fixed width, or preceded by their length.
+    fn to_be_bytes(&self) -> Self::Bytes;
+}
+
+macro_rules! impl_balance {
+    ($($int:ty),*) => {
+        $(
+            impl Balance for $int {
+                type Bytes = [u8; core::mem::size_of::<$int>()];
+
+                fn checked_add(&self, other: &Self) -> Option<Self> {
+                    <$int>::checked_add(*self, *other)
+                }
+
+                fn to_be_bytes(&self) -> Self::Bytes {
+                    <$int>::to_be_bytes(*self)
+                }
+            }
+        )*
+    };
+}
+
+impl_balance!(u32, u64, u128);
+
+/// The error returned when a string is not a valid balance.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseBalanceError;
+
+impl fmt::Display for ParseBalanceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("invalid balance")
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ParseBalanceError {}
+
+/// A non-negative decimal amount with `DECIMALS` digits after the point, stored exactly as a
+/// `u128` count of the smallest unit, such as cents with `Decimal<2>` or wei with
+/// `Decimal<18>`. `DECIMALS` should be at most 38, the digits of a `u128`, as no string parses
+/// into an amount with more decimals.
+///
+/// It parses from and formats to decimal strings like `"1234.56"`, so amounts are committed
+/// without the rounding of a float. A string with more decimals than `DECIMALS` is rejected
+/// rather than truncated. With the `serde` feature it serializes as such a string.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Decimal<const DECIMALS: u32> {
+    units: u128,
+}
+
+impl<const DECIMALS: u32> Decimal<DECIMALS> {
+    /// Creates an amount from a count of the smallest unit.
+    ///
+    /// # Arguments
+    ///
+    /// * `units`: The amount times 10 to the power of `DECIMALS`.
+    pub fn from_units(units: u128) -> Self {
+        Decimal { units }
+    }
+
+    /// Returns the amount as a count of the smallest unit.
+    pub fn units(&self) -> u128 {
+        self.units
+    }
+}
+
+impl<const DECIMALS: u32> FromStr for Decimal<DECIMALS> {
+    type Err = ParseBalanceError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (integer, fraction) = s.split_once('.').unwrap_or((s, ""));
+        let is_digits = |part: &str| part.bytes().all(|byte| byte.is_ascii_digit());
+        if (integer.is_empty() && fraction.is_empty())
+            || !is_digits(integer)
+            || !is_digits(fraction)
+            || fraction.len() > DECIMALS as usize
+            || s.ends_with('.')
+        {
+            return Err(ParseBalanceError);
+        }
+
+        let scale = 10u128.checked_pow(DECIMALS).ok_or(ParseBalanceError)?;
+        let integer = match integer {
+            "" => 0,
+            integer => integer.parse::<u128>().map_err(|_| ParseBalanceError)?,
+        };
+        let fraction = match fraction {
+            "" => 0,
+            fraction => {
+                fraction.parse::<u128>().map_err(|_| ParseBalanceError)?
+                    * 10u128.pow(DECIMALS - fraction.len() as u32)
+            }
+        };
+        let units = integer
+            .checked_mul(scale)
+            .and_then(|units| units.checked_add(fraction))
+            .ok_or(ParseBalanceError)?;
+        Ok(Decimal { units })
+    }
+}
+
+/// Formats the amount with exactly `DECIMALS` digits after the point.
+impl<const DECIMALS: u32> fmt::Display for Decimal<DECIMALS> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if DECIMALS == 0 {
+            return write!(f, "{}", self.units);
+        }
+        // With more than 38 decimals the scale exceeds any `u128`, so the integer part is 0.
+        let (integer, fraction) = match 10u128.checked_pow(DECIMALS) {
+            Some(scale) => (self.units / scale, self.units % scale),
+            None => (0, self.units),
+        };
+        write!(
+            f,
+            "{}.{:0width$}",
+            integer,
+            fraction,
+            width = DECIMALS as usize
+        )
+    }
+}
+
+impl<const DECIMALS: u32> Balance for Decimal<DECIMALS> {
+    type Bytes = [u8; 16];
+
+    fn checked_add(&self, other: &Self) -> Option<Self> {
+        Some(Decimal {
+            units: self.units.checked_add(other.units)?,
+        })
+    }
+
+    fn to_be_bytes(&self) -> Self::Bytes {
+        self.units.to_be_bytes()
+    }
+}
+
+/// A non-negative integer of any size, for balances that do not fit a `u128` or for sums that
+/// must never overflow.
+///
+/// It parses from and formats to decimal strings, and with the `serde` feature serializes as
+/// such a string. It is hashed as its minimal big-endian bytes preceded by their count as a
+/// big-endian `u32`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct BigBalance {
+    /// The big-endian bytes without leading zeros, empty for zero.
+    bytes: Vec<u8>,
+}
+
+impl BigBalance {
+    /// Creates a balance from big-endian bytes.
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes`: The big-endian bytes, with or without leading zeros.
+    pub fn from_be_bytes(bytes: &[u8]) -> Self {
+        let start = bytes
+            .iter()
+            .position(|byte| *byte != 0)
+            .unwrap_or(bytes.len());
+        BigBalance {
+            bytes: bytes[start..].to_vec(),
+        }
+    }
+
+    /// Returns the minimal big-endian bytes of the balance, empty for zero.
+    pub fn as_be_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+impl From<u128> for BigBalance {
+    fn from(value: u128) -> Self {
+        Self::from_be_bytes(&value.to_be_bytes())
+    }
+}
+
+impl FromStr for BigBalance {
+    type Err = ParseBalanceError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() || !s.bytes().all(|byte| byte.is_ascii_digit()) {
+            return Err(ParseBalanceError);
+        }
+
+        // Multiply by ten and add each digit, with the bytes in little-endian order.
+        let mut bytes: Vec<u8> = Vec::new();
+        for digit in s.bytes() {
+            let mut carry = u32::from(digit - b'0');
+            for byte in bytes.iter_mut() {
+                let value = u32::from(*byte) * 10 + carry;
+                *byte = value as u8;
+                carry = value >> 8;
+            }
+            if carry > 0 {
+                bytes.push(carry as u8);
+            }
+        }
+        bytes.reverse();
+        Ok(Self::from_be_bytes(&bytes))
+    }
+}
+
+impl fmt::Display for BigBalance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&to_decimal(&self.bytes))
+    }
+}
+
+impl Balance for BigBalance {
+    type Bytes = Vec<u8>;
+
+    fn checked_add(&self, other: &Self) -> Option<Self> {
+        let mut sum = Vec::with_capacity(self.bytes.len().max(other.bytes.len()) + 1);
+        let mut left = self.bytes.iter().rev();
+        let mut right = other.bytes.iter().rev();
+        let mut carry = 0u16;
+        loop {
+            let (a, b) = (left.next(), right.next());
+            if a.is_none() && b.is_none() {
+                break;
+            }
+            let value = u16::from(*a.unwrap_or(&0)) + u16::from(*b.unwrap_or(&0)) + carry;
+            sum.push(value as u8);
+            carry = value >> 8;
+        }
+        if carry > 0 {
+            sum.push(carry as u8);
+        }
+        sum.reverse();
+        Some(BigBalance { bytes: sum })
+    }
+
+    fn to_be_bytes(&self) -> Self::Bytes {
+        let mut bytes = (self.bytes.len() as u32).to_be_bytes().to_vec();
+        bytes.extend_from_slice(&self.bytes);
+        bytes
+    }
+}
+
+/// The balances of one user in several assets, such as BTC, ETH and USDT, committed to in a
+/// single leaf.
+///
+/// The position of an amount identifies its asset, so all leaves of a tree must list the
+/// assets in the same order. A `SumMerkleTree` sums up each asset separately, so its total
+/// and the sums in its proofs hold one total per asset. An overflow in any asset is an
+/// overflow of the whole balance. The amounts are hashed one after the other.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AssetBalances<B, const ASSETS: usize>(pub [B; ASSETS]);
+
+impl<B, const ASSETS: usize> AssetBalances<B, ASSETS> {
+    /// Returns the amount of the asset at the given position, or `None` if it is out of
+    /// bounds.
+    ///
+    /// # Arguments
+    ///
+    /// * `asset`: The position of the asset.
+    pub fn get(&self, asset: usize) -> Option<&B> {
+        self.0.get(asset)
+    }
+
+    /// Returns the amounts of all assets.
+    pub fn amounts(&self) -> &[B; ASSETS] {
+        &self.0
+    }
+}
+
+impl<B: Default, const ASSETS: usize> Default for AssetBalances<B, ASSETS> {
+    fn default() -> Self {
+        AssetBalances(core::array::from_fn(|_| B::default()))
+    }
+}
+
+impl<B, const ASSETS: usize> From<[B; ASSETS]> for AssetBalances<B, ASSETS> {
+    fn from(amounts: [B; ASSETS]) -> Self {
+        AssetBalances(amounts)
+    }
+}
+
+/// Formats the amounts separated by commas.
+impl<B: fmt::Display, const ASSETS: usize> fmt::Display for AssetBalances<B, ASSETS> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (asset, amount) in self.0.iter().enumerate() {
+            if asset > 0 {
+                f.write_str(",")?;
+            }
+            write!(f, "{amount}")?;
+        }
+        Ok(())
+    }
+}
+
+impl<B: Balance, const ASSETS: usize> Balance for AssetBalances<B, ASSETS> {
+    type Bytes = Vec<u8>;
+
+    fn checked_add(&self, other: &Self) -> Option<Self> {
+        let mut sum = self.clone();
+        for (amount, other) in sum.0.iter_mut().zip(&other.0) {
+            *amount = amount.checked_add(other)?;
+        }
+        Some(sum)
+    }
+
+    fn to_be_bytes(&self) -> Self::Bytes {
+        self.0
+            .iter()
+            .flat_map(|amount| amount.to_be_bytes().as_ref().to_vec())
+            .collect()
+    }
+}
+
+#[cfg(feature = "zeroize")]
+mod zeroize_impls {
+    use super::*;
+    use zeroize::Zeroize;
+
+    impl<const DECIMALS: u32> Zeroize for Decimal<DECIMALS> {
+        fn zeroize(&mut self) {
+            self.units.zeroize();
+        }
+    }
+
+    impl Zeroize for BigBalance {
+        fn zeroize(&mut self) {
+            self.bytes.zeroize();
+        }
+    }
+
+    impl<B: Zeroize, const ASSETS: usize> Zeroize for AssetBalances<B, ASSETS> {
+        fn zeroize(&mut self) {
+            self.0.zeroize();
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+mod serde_impls {
+    use super::*;
+    use alloc::string::ToString;
+    use serde::de::{self, Visitor};
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    /// Deserializes a balance from a decimal string.
+    struct BalanceVisitor<B>(core::marker::PhantomData<B>);
+
+    impl<B: FromStr> Visitor<'_> for BalanceVisitor<B> {
+        type Value = B;
+
+        fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str("a decimal string")
+        }
+
+        fn visit_str<E: de::Error>(self, value: &str) -> Result<B, E> {
+            value
+                .parse()
+                .map_err(|_| E::invalid_value(de::Unexpected::Str(value), &self))
+        }
+    }
+
+    impl<const DECIMALS: u32> Serialize for Decimal<DECIMALS> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.serialize_str(&self.to_string())
+        }
+    }
+
+    impl<'de, const DECIMALS: u32> Deserialize<'de> for Decimal<DECIMALS> {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            deserializer.deserialize_str(BalanceVisitor(core::marker::PhantomData))
+        }
+    }
+
+    impl Serialize for BigBalance {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.serialize_str(&self.to_string())
+        }
+    }
+
+    impl<'de> Deserialize<'de> for BigBalance {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            deserializer.deserialize_str(BalanceVisitor(core::marker::PhantomData))
+        }
+    }
+
+    impl<B: Serialize, const ASSETS: usize> Serialize for AssetBalances<B, ASSETS> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.collect_seq(&self.0)
+        }
+    }
+
+    impl<'de, B: Deserialize<'de>, const ASSETS: usize> Deserialize<'de> for AssetBalances<B, ASSETS> {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let amounts = Vec::<B>::deserialize(deserializer)?;
+            let len = amounts.len();
+            amounts
+                .try_into()
+                .map(AssetBalances)
+                .map_err(|_| de::Error::invalid_length(len, &"one amount per asset"))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{util, verify_sum_proof, MerkleError, MerkleTreeData, Sha256, SumMerkleTree};
+    use alloc::format;
+    use alloc::string::ToString;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("1234.56", Some(123456))]
+    #[case("0.05", Some(5))]
+    #[case(".5", Some(50))]
+    #[case("7", Some(700))]
+    #[case("1.234", None)]
+    #[case("-1.00", None)]
+    #[case("1.", None)]
+    #[case("", None)]
+    #[case("1e3", None)]
+    fn it_can_parse_decimal_balances(#[case] input: &str, #[case] units: Option<u128>) {
+        let parsed = input.parse::<Decimal<2>>();
+        assert_eq!(parsed.map(|amount| amount.units()).ok(), units);
+    }
+
+    #[test]
+    fn it_can_display_decimals_of_any_scale() {
+        assert_eq!(Decimal::<0>::from_units(42).to_string(), "42");
+        assert_eq!(Decimal::<2>::from_units(4205).to_string(), "42.05");
+        assert_eq!(
+            Decimal::<38>::from_units(u128::MAX).to_string(),
+            "3.40282366920938463463374607431768211455"
+        );
+        assert_eq!(
+            Decimal::<40>::from_units(123).to_string(),
+            format!("0.{}123", "0".repeat(37))
+        );
+        assert!("0.1".parse::<Decimal<40>>().is_err());
+    }
+
+    #[test]
+    fn it_can_sum_decimal_and_big_balances() {
+        let fiat: Vec<util::UserData<Decimal<2>>> = ["10.50", "0.25", "1999.99"]
+            .iter()
+            .zip(1..)
+            .map(|(balance, id)| util::UserData {
+                id,
+                balance: balance.parse().unwrap(),
+            })
+            .collect();
+        let tree: SumMerkleTree<_, Sha256, Decimal<2>> =
+            SumMerkleTree::build("Leaf", "Branch", &fiat).unwrap();
+        let total = tree.total().unwrap();
+        assert_eq!(total.to_string(), "2010.74");
+        let (leaf, proof) = tree.proof_by_index(1).unwrap();
+        assert_eq!(leaf.serialize(), b"2,0.25");
+        assert!(verify_sum_proof(
+            "Leaf",
+            "Branch",
+            &tree.root().unwrap(),
+            total,
+            leaf,
+            &proof
+        ));
+
+        // 18-decimal token amounts beyond u128 are summed without overflow.
+        let max = BigBalance::from(u128::MAX);
+        let tokens: Vec<util::UserData<BigBalance>> = (1..=4)
+            .map(|id| util::UserData {
+                id,
+                balance: max.clone(),
+            })
+            .collect();
+        let tree: SumMerkleTree<_, Sha256, BigBalance> =
+            SumMerkleTree::build("Leaf", "Branch", &tokens).unwrap();
+        let total = tree.total().unwrap();
+        assert_eq!(
+            total.to_string(),
+            "1361129467683753853853498429727072845820"
+        );
+        assert_eq!(total, total.to_string().parse().unwrap());
+        for index in 0..tokens.len() {
+            let (leaf, proof) = tree.proof_by_index(index).unwrap();
+            let root = tree.root().unwrap();
+            assert!(verify_sum_proof(
+                "Leaf",
+                "Branch",
+                &root,
+                total.clone(),
+                leaf,
+                &proof
+            ));
+            assert!(!verify_sum_proof(
+                "Leaf",
+                "Branch",
+                &root,
+                max.clone(),
+                leaf,
+                &proof
+            ));
+        }
+        assert_eq!(BigBalance::from(0).to_string(), "0");
+        assert!("12a".parse::<BigBalance>().is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn it_can_serialize_decimal_balances_as_strings() {
+        let user = util::UserData {
+            id: 1,
+            balance: "0.000000000000000001".parse::<Decimal<18>>().unwrap(),
+        };
+        let json = serde_json::to_string(&user).unwrap();
+        assert_eq!(json, r#"{"id":1,"balance":"0.000000000000000001"}"#);
+        let parsed: util::UserData<Decimal<18>> = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.balance.units(), 1);
+        assert!(serde_json::from_str::<util::UserData<Decimal<2>>>(&json).is_err());
+
+        let big: BigBalance =
+            serde_json::from_str(r#""340282366920938463463374607431768211456""#).unwrap();
+        assert_eq!(big.as_be_bytes().len(), 17);
+    }
+
+    #[test]
+    fn it_can_sum_balances_in_several_assets() {
+        // BTC in satoshis, ETH in gwei and USDT in cents.
+        let users: Vec<util::UserData<AssetBalances<u64, 3>>> = [
+            [100_000_000, 0, 5_000],
+            [0, 2_000_000_000, 0],
+            [50_000, 1_000, 123_456],
+            [1, 1, 1],
+            [7, 0, 99],
+        ]
+        .into_iter()
+        .zip(1..)
+        .map(|(balance, id)| util::UserData {
+            id,
+            balance: AssetBalances(balance),
+        })
+        .collect();
+        let tree: SumMerkleTree<_, Sha256, AssetBalances<u64, 3>> =
+            SumMerkleTree::build("Leaf", "Branch", &users).unwrap();
+        let total = tree.total().unwrap();
+        assert_eq!(total.amounts(), &[100_050_008, 2_000_001_001, 128_556]);
+        assert_eq!(total.to_string(), "100050008,2000001001,128556");
+        assert_eq!(users[2].serialize(), b"3,50000,1000,123456");
+
+        let root = tree.root().unwrap();
+        for index in 0..users.len() {
+            let (leaf, proof) = tree.proof_by_index(index).unwrap();
+            assert!(proof
+                .siblings
+                .iter()
+                .all(|(_, sum)| sum.amounts().len() == 3));
+            assert!(verify_sum_proof(
+                "Leaf", "Branch", &root, total, leaf, &proof
+            ));
+            // A total misstated in a single asset fails.
+            let mut understated = total;
+            understated.0[1] -= 1;
+            assert!(!verify_sum_proof(
+                "Leaf",
+                "Branch",
+                &root,
+                understated,
+                leaf,
+                &proof
+            ));
+        }
+
+        let overflowing = [
+            util::UserData {
+                id: 1,
+                balance: AssetBalances([1, u64::MAX, 1]),
+            },
+            util::UserData {
+                id: 2,
+                balance: AssetBalances([1, 1, 1]),
+            },
+        ];
+        let tree: Result<SumMerkleTree<_, Sha256, AssetBalances<u64, 3>>, _> =
+            SumMerkleTree::build("Leaf", "Branch", &overflowing);
+        assert_eq!(tree.err(), Some(MerkleError::Overflow));
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn it_can_serialize_asset_balances_as_arrays() {
+        let user = util::UserData {
+            id: 1,
+            balance: AssetBalances([1u64, 2, 3]),
+        };
+        let json = serde_json::to_string(&user).unwrap();
+        assert_eq!(json, r#"{"id":1,"balance":[1,2,3]}"#);
+        let parsed: util::UserData<AssetBalances<u64, 3>> = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.balance, user.balance);
+        assert!(serde_json::from_str::<util::UserData<AssetBalances<u64, 2>>>(&json).is_err());
+    }
+
+    #[test]
+    fn it_can_sum_u128_balances() {
+        // Balances in wei overflow both u32 and u64.
+        let user_data: Vec<util::UserData<u128>> = (1..=7u32)
+            .map(|id| util::UserData {
+                id,
+                balance: u128::from(id) * 10u128.pow(24),
+            })
+            .collect();
+        let tree: SumMerkleTree<_, Sha256, u128> =
+            SumMerkleTree::build("ProofOfReserve_Leaf", "ProofOfReserve_Branch", &user_data)
+                .unwrap();
+        let total = 28 * 10u128.pow(24);
+        assert_eq!(tree.total(), Ok(total));
+
+        let root = tree.root().unwrap();
+        for index in 0..user_data.len() {
+            let (leaf, proof) = tree.proof_by_index(index).unwrap();
+            assert!(verify_sum_proof(
+                "ProofOfReserve_Leaf",
+                "ProofOfReserve_Branch",
+                &root,
+                total,
+                leaf,
+                &proof
+            ));
+            assert!(!verify_sum_proof(
+                "ProofOfReserve_Leaf",
+                "ProofOfReserve_Branch",
+                &root,
+                total - 1,
+                leaf,
+                &proof
+            ));
+        }
+
+        let whales = [
+            util::UserData {
+                id: 1,
+                balance: u128::MAX,
+            },
+            util::UserData { id: 2, balance: 1 },
+        ];
+        let tree: Result<SumMerkleTree<_, Sha256, u128>, _> =
+            SumMerkleTree::build("Leaf", "Branch", &whales);
+        assert_eq!(tree.err(), Some(MerkleError::Overflow));
+
+        // u64 balances are summed up the same way as the u32 balances they widen.
+        let small: Vec<util::UserData<u64>> = util::generate_random_user_data(5)
+            .into_iter()
+            .map(|user| util::UserData {
+                id: user.id,
+                balance: user.balance.into(),
+            })
+            .collect();
+        let tree: SumMerkleTree<_> = SumMerkleTree::build("Leaf", "Branch", &small).unwrap();
+        let widened: SumMerkleTree<_> =
+            SumMerkleTree::build("Leaf", "Branch", &util::generate_random_user_data(5)).unwrap();
+        assert_eq!(tree.root(), widened.root());
+    }
+}
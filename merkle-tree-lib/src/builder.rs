@@ -0,0 +1,323 @@
+use alloc::vec::Vec;
+use core::fmt;
+use core::marker::PhantomData;
+use sha2::Digest;
+
+use crate::{
+    salt_with_seed, EmptyRoot, HashMode, KaryMerkleTree, MerkleTree, MerkleTreeData, Padding,
+    Salted, Sha256, TagStyle, TreeOptions,
+};
+
+/// Builds Merkle Trees from a configuration collected step by step.
+///
+/// Starts from the tagged hash construction with empty tags, like `TreeOptions::tagged("", "")`.
+/// Salting and the arity change the type of the tree returned by `build`, so
+/// `MerkleTreeBuilder::new().with_salt_seed(seed).with_arity(4).build(&leaves)` gives a
+/// `KaryMerkleTree<Salted<T>>`.
+///
+/// The builder can be reused to build several trees with the same configuration.
+pub struct MerkleTreeBuilder<D: Digest = Sha256, S = Unsalted, A = Binary> {
+    options: TreeOptions,
+    sorted: bool,
+    salting: S,
+    arity: A,
+    digest: PhantomData<fn() -> D>,
+}
+
+/// Marks a `MerkleTreeBuilder` whose leaves are committed as they are.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Unsalted;
+
+/// Marks a `MerkleTreeBuilder` that salts every leaf with a salt derived from a secret seed,
+/// as `salt_with_seed` does.
+#[derive(Debug, Clone)]
+pub struct SaltedWith {
+    seed: Vec<u8>,
+}
+
+/// Marks a `MerkleTreeBuilder` of binary `MerkleTree`s.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Binary;
+
+/// Marks a `MerkleTreeBuilder` of `KaryMerkleTree`s with the given number of children per
+/// branch node.
+#[derive(Debug, Clone, Copy)]
+pub struct Arity(pub usize);
+
+impl MerkleTreeBuilder {
+    /// Creates a builder of binary trees with SHA-256 and the default options.
+    pub fn new() -> Self {
+        Self::from_options(TreeOptions::tagged("", ""))
+    }
+
+    /// Creates a builder of binary trees with SHA-256 starting from the given options, such
+    /// as a preset like `TreeOptions::certificate_transparency()`.
+    ///
+    /// # Arguments
+    ///
+    /// * `options`: The hashing configuration of the trees.
+    pub fn from_options(options: TreeOptions) -> Self {
+        MerkleTreeBuilder {
+            options,
+            sorted: false,
+            salting: Unsalted,
+            arity: Binary,
+            digest: PhantomData,
+        }
+    }
+}
+
+impl Default for MerkleTreeBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<D: Digest, S, A> MerkleTreeBuilder<D, S, A> {
+    /// Returns the hashing configuration the trees are built with.
+    pub fn options(&self) -> &TreeOptions {
+        &self.options
+    }
+
+    /// Sets the tags used for hashing leaf and branch nodes.
+    ///
+    /// # Arguments
+    ///
+    /// * `tag_leaf`: The tag used for hashing leaf nodes.
+    /// * `tag_branch`: The tag used for hashing branch nodes.
+    pub fn with_tags(mut self, tag_leaf: &str, tag_branch: &str) -> Self {
+        self.options.tag_leaf = tag_leaf.into();
+        self.options.tag_branch = tag_branch.into();
+        self
+    }
+
+    /// Sets how leaves and branches are hashed.
+    ///
+    /// # Arguments
+    ///
+    /// * `hash_mode`: The hash construction.
+    pub fn with_hash_mode(mut self, hash_mode: HashMode) -> Self {
+        self.options.hash_mode = hash_mode;
+        self
+    }
+
+    /// Sets the tagged hash construction used by `HashMode::Tagged`.
+    ///
+    /// # Arguments
+    ///
+    /// * `tag_style`: The tagged hash construction.
+    pub fn with_tag_style(mut self, tag_style: TagStyle) -> Self {
+        self.options = self.options.with_tag_style(tag_style);
+        self
+    }
+
+    /// Sets whether leaf and branch inputs are prefixed to separate their domains.
+    ///
+    /// # Arguments
+    ///
+    /// * `domain_separation`: Whether to prepend `LEAF_PREFIX` and `BRANCH_PREFIX`.
+    pub fn with_domain_separation(mut self, domain_separation: bool) -> Self {
+        self.options = self.options.with_domain_separation(domain_separation);
+        self
+    }
+
+    /// Sets how a node without a sibling is handled.
+    ///
+    /// # Arguments
+    ///
+    /// * `padding`: The padding policy.
+    pub fn with_padding(mut self, padding: Padding) -> Self {
+        self.options = self.options.with_padding(padding);
+        self
+    }
+
+    /// Sets the root of a tree without leaves.
+    ///
+    /// # Arguments
+    ///
+    /// * `empty_root`: The root of an empty tree.
+    pub fn with_empty_root(mut self, empty_root: EmptyRoot) -> Self {
+        self.options = self.options.with_empty_root(empty_root);
+        self
+    }
+
+    /// Sets whether the leaves are sorted by their serialized bytes before building, so that
+    /// the same set of leaves gives the same root in any input order.
+    ///
+    /// Leaves are sorted before they are salted.
+    ///
+    /// # Arguments
+    ///
+    /// * `sorted`: Whether to sort the leaves.
+    pub fn with_sorting(mut self, sorted: bool) -> Self {
+        self.sorted = sorted;
+        self
+    }
+
+    /// Sets the digest the trees are hashed with.
+    pub fn with_digest<E: Digest>(self) -> MerkleTreeBuilder<E, S, A> {
+        MerkleTreeBuilder {
+            options: self.options,
+            sorted: self.sorted,
+            salting: self.salting,
+            arity: self.arity,
+            digest: PhantomData,
+        }
+    }
+
+    /// Salts every leaf with a salt derived from a secret seed, so the trees are built over
+    /// `Salted` leaves.
+    ///
+    /// # Arguments
+    ///
+    /// * `seed`: A secret seed.
+    pub fn with_salt_seed(self, seed: &[u8]) -> MerkleTreeBuilder<D, SaltedWith, A> {
+        MerkleTreeBuilder {
+            options: self.options,
+            sorted: self.sorted,
+            salting: SaltedWith {
+                seed: seed.to_vec(),
+            },
+            arity: self.arity,
+            digest: PhantomData,
+        }
+    }
+
+    /// Builds `KaryMerkleTree`s instead of binary trees.
+    ///
+    /// # Arguments
+    ///
+    /// * `arity`: The number of children of a branch node, at least 2.
+    pub fn with_arity(self, arity: usize) -> MerkleTreeBuilder<D, S, Arity> {
+        MerkleTreeBuilder {
+            options: self.options,
+            sorted: self.sorted,
+            salting: self.salting,
+            arity: Arity(arity),
+            digest: PhantomData,
+        }
+    }
+
+    /// Returns the leaves in the order they are committed in.
+    fn order<T: Clone + MerkleTreeData>(&self, leaves: &[T]) -> Vec<T> {
+        let mut leaves = leaves.to_vec();
+        if self.sorted {
+            leaves.sort_by_cached_key(|leaf| leaf.serialize());
+        }
+        leaves
+    }
+}
+
+impl<D: Digest> MerkleTreeBuilder<D, Unsalted, Binary> {
+    /// Builds a Merkle Tree from the given user data.
+    ///
+    /// # Arguments
+    ///
+    /// * `leaves`: The user data to commit to.
+    pub fn build<T>(&self, leaves: &[T]) -> MerkleTree<T, D>
+    where
+        T: Clone + fmt::Debug + MerkleTreeData + Default,
+    {
+        MerkleTree::build_from_iter_with_options(self.options.clone(), self.order(leaves))
+    }
+}
+
+impl<D: Digest> MerkleTreeBuilder<D, SaltedWith, Binary> {
+    /// Builds a Merkle Tree from the given user data, salting every leaf.
+    ///
+    /// # Arguments
+    ///
+    /// * `leaves`: The user data to commit to.
+    pub fn build<T>(&self, leaves: &[T]) -> MerkleTree<Salted<T>, D>
+    where
+        T: Clone + fmt::Debug + MerkleTreeData + Default,
+    {
+        let salted = salt_with_seed::<_, D>(&self.order(leaves), &self.salting.seed);
+        MerkleTree::build_from_iter_with_options(self.options.clone(), salted)
+    }
+}
+
+impl<D: Digest> MerkleTreeBuilder<D, Unsalted, Arity> {
+    /// Builds a k-ary Merkle Tree from the given user data.
+    ///
+    /// # Arguments
+    ///
+    /// * `leaves`: The user data to commit to.
+    pub fn build<T>(&self, leaves: &[T]) -> KaryMerkleTree<T, D>
+    where
+        T: Clone + MerkleTreeData,
+    {
+        KaryMerkleTree::build_with_options(self.options.clone(), self.arity.0, &self.order(leaves))
+    }
+}
+
+impl<D: Digest> MerkleTreeBuilder<D, SaltedWith, Arity> {
+    /// Builds a k-ary Merkle Tree from the given user data, salting every leaf.
+    ///
+    /// # Arguments
+    ///
+    /// * `leaves`: The user data to commit to.
+    pub fn build<T>(&self, leaves: &[T]) -> KaryMerkleTree<Salted<T>, D>
+    where
+        T: Clone + MerkleTreeData,
+    {
+        let salted = salt_with_seed::<_, D>(&self.order(leaves), &self.salting.seed);
+        KaryMerkleTree::build_with_options(self.options.clone(), self.arity.0, &salted)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util;
+
+    #[test]
+    fn it_can_build_trees_with_a_builder() {
+        let user_data = util::generate_random_user_data(7);
+        let options = TreeOptions::tagged("Leaf", "Branch")
+            .with_domain_separation(true)
+            .with_padding(Padding::Promote);
+        let builder = MerkleTreeBuilder::new()
+            .with_tags("Leaf", "Branch")
+            .with_domain_separation(true)
+            .with_padding(Padding::Promote);
+        assert_eq!(builder.options(), &options);
+
+        let expected: MerkleTree<_> = MerkleTree::build_with_options(options.clone(), &user_data);
+        assert_eq!(builder.build(&user_data).root(), expected.root());
+
+        // Sorted trees commit to the set of leaves regardless of the input order.
+        let sorted = builder.with_sorting(true);
+        let mut reversed = user_data.clone();
+        reversed.reverse();
+        let root = sorted.build(&user_data).root().unwrap();
+        assert_eq!(sorted.build(&reversed).root().unwrap(), root);
+
+        let salted = sorted.with_salt_seed(b"seed");
+        let tree = salted.build(&reversed);
+        let mut leaves = user_data.clone();
+        leaves.sort_by_key(|leaf| leaf.serialize());
+        let expected: MerkleTree<_> = MerkleTree::build_with_options(
+            options.clone(),
+            &salt_with_seed::<_, Sha256>(&leaves, b"seed"),
+        );
+        assert_eq!(tree.root(), expected.root());
+        assert_ne!(tree.root().unwrap(), root);
+
+        let kary = salted.with_arity(4).build(&leaves);
+        let expected: KaryMerkleTree<_> = KaryMerkleTree::build_with_options(
+            options.clone(),
+            4,
+            &salt_with_seed::<_, Sha256>(&leaves, b"seed"),
+        );
+        assert_eq!(kary.arity(), 4);
+        assert_eq!(kary.root(), expected.root());
+
+        let double_hash = MerkleTreeBuilder::from_options(TreeOptions::double_hash())
+            .with_hash_mode(HashMode::DoubleHash)
+            .build(&user_data);
+        let expected: MerkleTree<_> =
+            MerkleTree::build_with_options(TreeOptions::double_hash(), &user_data);
+        assert_eq!(double_hash.root(), expected.root());
+    }
+}
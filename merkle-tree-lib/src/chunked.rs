@@ -0,0 +1,330 @@
+use sha2::digest::Output;
+use sha2::Digest;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+use crate::multiproof::decode_hash;
+use crate::root::hash_matches;
+use crate::{MerkleError, MerkleTree, NodeDirection, Sha256, TraversePath, TreeOptions};
+
+/// A Merkle Tree over the fixed size chunks of a file, whose proofs show that a chunk read at
+/// a given offset belongs to the file, so that parts of a download can be verified against
+/// the root before the rest has arrived.
+///
+/// Each chunk is hashed as a leaf as it is. All chunks are `chunk_size` bytes long except the
+/// last one, which holds the rest of the file. An empty file has no chunks and no root.
+pub struct ChunkedTree<D: Digest = Sha256> {
+    tree: MerkleTree<(), D>,
+    chunk_size: usize,
+    file_size: u64,
+}
+
+/// A proof that a chunk is at the given offset of a file.
+pub struct ChunkProof<D = Sha256> {
+    /// The size of the file in bytes.
+    pub file_size: u64,
+    /// The size of the chunks in bytes.
+    pub chunk_size: usize,
+    /// The offset of the chunk in the file.
+    pub offset: u64,
+    /// The path from the root down to the chunk.
+    pub path: TraversePath<D>,
+}
+
+impl<D> Clone for ChunkProof<D> {
+    fn clone(&self) -> Self {
+        ChunkProof {
+            file_size: self.file_size,
+            chunk_size: self.chunk_size,
+            offset: self.offset,
+            path: self.path.clone(),
+        }
+    }
+}
+
+impl<D> std::fmt::Debug for ChunkProof<D> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ChunkProof")
+            .field("file_size", &self.file_size)
+            .field("chunk_size", &self.chunk_size)
+            .field("offset", &self.offset)
+            .field("path", &self.path)
+            .finish()
+    }
+}
+
+impl<D: Digest> ChunkedTree<D> {
+    /// Builds a tree over the chunks of a file.
+    ///
+    /// # Arguments
+    ///
+    /// * `tag_leaf`: The tag used for hashing leaf nodes.
+    /// * `tag_branch`: The tag used for hashing branch nodes.
+    /// * `path`: The path of the file.
+    /// * `chunk_size`: The size of the chunks in bytes, at least 1.
+    pub fn from_file(
+        tag_leaf: &str,
+        tag_branch: &str,
+        path: impl AsRef<Path>,
+        chunk_size: usize,
+    ) -> io::Result<Self> {
+        Self::from_file_with_options(TreeOptions::tagged(tag_leaf, tag_branch), path, chunk_size)
+    }
+
+    /// Builds a tree over the chunks of a file with the given hashing configuration.
+    ///
+    /// # Arguments
+    ///
+    /// * `options`: The hashing configuration of the tree.
+    /// * `path`: The path of the file.
+    /// * `chunk_size`: The size of the chunks in bytes, at least 1.
+    pub fn from_file_with_options(
+        options: TreeOptions,
+        path: impl AsRef<Path>,
+        chunk_size: usize,
+    ) -> io::Result<Self> {
+        Self::from_reader_with_options(options, File::open(path)?, chunk_size)
+    }
+
+    /// Builds a tree over the chunks read from a reader, one chunk at a time.
+    ///
+    /// # Arguments
+    ///
+    /// * `options`: The hashing configuration of the tree.
+    /// * `reader`: Where to read the content from.
+    /// * `chunk_size`: The size of the chunks in bytes, at least 1.
+    pub fn from_reader_with_options(
+        options: TreeOptions,
+        mut reader: impl Read,
+        chunk_size: usize,
+    ) -> io::Result<Self> {
+        let mut chunk = vec![0; chunk_size.max(1)];
+        let mut hashes: Vec<Output<D>> = Vec::new();
+        let mut file_size = 0;
+        loop {
+            let read = read_chunk(&mut reader, &mut chunk)?;
+            if read > 0 {
+                hashes.push(options.hash_leaf::<D>(&chunk[..read]));
+                file_size += read as u64;
+            }
+            if read < chunk.len() {
+                break;
+            }
+        }
+
+        Ok(ChunkedTree {
+            tree: MerkleTree::from_leaf_hashes_with_options(options, &hashes),
+            chunk_size: chunk.len(),
+            file_size,
+        })
+    }
+
+    /// Returns the tree over the chunks, whose leaves are in the order of the chunks.
+    pub fn tree(&self) -> &MerkleTree<(), D> {
+        &self.tree
+    }
+
+    /// Returns the size of the chunks in bytes.
+    pub fn chunk_size(&self) -> usize {
+        self.chunk_size
+    }
+
+    /// Returns the size of the file in bytes.
+    pub fn file_size(&self) -> u64 {
+        self.file_size
+    }
+
+    /// Returns the hex encoded root hash, or `MerkleError::EmptyTree` if the file is empty.
+    pub fn root(&self) -> Result<String, MerkleError> {
+        self.tree.root()
+    }
+
+    /// Generates the proof of the chunk at the given offset.
+    ///
+    /// # Arguments
+    ///
+    /// * `offset`: The offset of the chunk in the file, a multiple of the chunk size.
+    ///
+    /// # Returns
+    ///
+    /// The proof, `MerkleError::InvalidRange` if the offset is not at the start of a chunk or
+    /// `MerkleError::LeafNotFound` if it is past the end of the file.
+    pub fn proof(&self, offset: u64) -> Result<ChunkProof<D>, MerkleError> {
+        if !offset.is_multiple_of(self.chunk_size as u64) {
+            return Err(MerkleError::InvalidRange);
+        }
+        let index = usize::try_from(offset / self.chunk_size as u64)
+            .map_err(|_| MerkleError::LeafNotFound)?;
+        let (_, path) = self.tree.proof_by_index(index)?;
+
+        Ok(ChunkProof {
+            file_size: self.file_size,
+            chunk_size: self.chunk_size,
+            offset,
+            path,
+        })
+    }
+}
+
+/// Verifies that a chunk is at the offset of a file given by a proof.
+///
+/// # Arguments
+///
+/// * `tag_leaf`: The tag used for hashing leaf nodes.
+/// * `tag_branch`: The tag used for hashing branch nodes.
+/// * `root`: The hex encoded Merkle root of the file.
+/// * `chunk`: The bytes of the chunk.
+/// * `proof`: The proof returned by `ChunkedTree::proof`.
+///
+/// # Returns
+///
+/// `true` if the chunk is in the file at the offset of the proof, `false` otherwise.
+pub fn verify_chunk_proof<D: Digest>(
+    tag_leaf: &str,
+    tag_branch: &str,
+    root: &str,
+    chunk: &[u8],
+    proof: &ChunkProof<D>,
+) -> bool {
+    verify_chunk_proof_with_options(
+        &TreeOptions::tagged(tag_leaf, tag_branch),
+        root,
+        chunk,
+        proof,
+    )
+}
+
+/// Verifies that a chunk is at the offset of a file given by a proof, using the hashing
+/// configuration the tree was built with.
+///
+/// Besides the root, the path must lead to the leaf of the offset and the chunk must have the
+/// length of the chunk at that offset, so that a chunk cannot be passed off as another part
+/// of the file.
+///
+/// # Arguments
+///
+/// * `options`: The options the tree was built with.
+/// * `root`: The hex encoded Merkle root of the file.
+/// * `chunk`: The bytes of the chunk.
+/// * `proof`: The proof returned by `ChunkedTree::proof`.
+///
+/// # Returns
+///
+/// `true` if the chunk is in the file at the offset of the proof, `false` otherwise.
+pub fn verify_chunk_proof_with_options<D: Digest>(
+    options: &TreeOptions,
+    root: &str,
+    chunk: &[u8],
+    proof: &ChunkProof<D>,
+) -> bool {
+    let chunk_size = proof.chunk_size as u64;
+    if chunk_size == 0
+        || !proof.offset.is_multiple_of(chunk_size)
+        || proof.offset >= proof.file_size
+    {
+        return false;
+    }
+    let expected_len = chunk_size.min(proof.file_size - proof.offset);
+    let leaf_count = proof.file_size.div_ceil(chunk_size);
+    let (Ok(index), Ok(leaf_count)) = (
+        usize::try_from(proof.offset / chunk_size),
+        usize::try_from(leaf_count),
+    ) else {
+        return false;
+    };
+    if chunk.len() as u64 != expected_len
+        || proof.path.leaf_index(leaf_count, options.padding) != Ok(index)
+        || proof.path.siblings.len() != proof.path.directions.len()
+    {
+        return false;
+    }
+
+    let mut hash = options.hash_leaf::<D>(chunk);
+    for (sibling, direction) in proof.path.siblings.iter().zip(&proof.path.directions).rev() {
+        let Some(sibling) = decode_hash::<D>(sibling) else {
+            return false;
+        };
+        hash = match direction {
+            NodeDirection::Left => options.hash_branch::<D>(&hash, &sibling),
+            NodeDirection::Right => options.hash_branch::<D>(&sibling, &hash),
+            NodeDirection::Root => return false,
+        };
+    }
+    hash_matches(&hash, root)
+}
+
+/// Reads until the buffer is full or the reader is exhausted.
+///
+/// # Returns
+///
+/// The number of bytes read, less than the buffer length only at the end of the content.
+pub(crate) fn read_chunk(reader: &mut impl Read, buffer: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match reader.read(&mut buffer[filled..]) {
+            Ok(0) => break,
+            Ok(read) => filled += read,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(filled)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Padding;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(Padding::DuplicateLast)]
+    #[case(Padding::Promote)]
+    #[case(Padding::PadWithEmpty)]
+    fn it_can_prove_file_chunks(#[case] padding: Padding) {
+        let data: Vec<u8> = (0..1000u32).map(|i| (i * 13) as u8).collect();
+        let path = std::env::temp_dir().join(format!("merkle-chunks-{padding:?}.bin"));
+        std::fs::write(&path, &data).unwrap();
+        let options = TreeOptions::tagged("Chunk", "Branch").with_padding(padding);
+        let tree: ChunkedTree =
+            ChunkedTree::from_file_with_options(options.clone(), &path, 96).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(tree.file_size(), 1000);
+        assert_eq!(tree.tree().leaf_count(), 11);
+        let root = tree.root().unwrap();
+        for (i, chunk) in data.chunks(96).enumerate() {
+            let offset = i as u64 * 96;
+            let proof = tree.proof(offset).unwrap();
+            assert!(verify_chunk_proof_with_options(
+                &options, &root, chunk, &proof
+            ));
+
+            let mut tampered = chunk.to_vec();
+            tampered[0] ^= 1;
+            assert!(!verify_chunk_proof_with_options(
+                &options, &root, &tampered, &proof
+            ));
+            assert!(!verify_chunk_proof_with_options(
+                &options,
+                &root,
+                &chunk[1..],
+                &proof
+            ));
+
+            // The chunk cannot be claimed at another offset.
+            let mut moved = proof.clone();
+            moved.offset = (offset + 96) % 1056;
+            assert!(!verify_chunk_proof_with_options(
+                &options, &root, chunk, &moved
+            ));
+        }
+        assert_eq!(tree.proof(50).unwrap_err(), MerkleError::InvalidRange);
+        assert_eq!(tree.proof(1056).unwrap_err(), MerkleError::LeafNotFound);
+
+        let empty: ChunkedTree =
+            ChunkedTree::from_reader_with_options(options, &[][..], 96).unwrap();
+        assert_eq!(empty.root(), Err(MerkleError::EmptyTree));
+    }
+}
@@ -0,0 +1,338 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+use sha2::Digest;
+
+use crate::multiproof::decode_hash;
+use crate::{NodeDirection, TraversePath};
+
+/// The version of the binary proof format, the first byte of an encoded proof.
+const PROOF_FORMAT_VERSION: u8 = 1;
+
+/// The alphabet of the standard base64 encoding of RFC 4648.
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// The errors of encoding and decoding a `TraversePath` as a blob.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProofCodecError {
+    /// The text has a character that is not a hex digit at the given position, or an odd
+    /// number of digits.
+    InvalidHex(usize),
+    /// The text has a character that is not in the base64 alphabet or misplaced padding at the
+    /// given position, or its length is not a multiple of 4.
+    InvalidBase64(usize),
+    /// The blob is empty or ends in the middle of a step.
+    Truncated,
+    /// The blob was encoded in a newer version of the format.
+    UnsupportedVersion(u8),
+    /// The blob was encoded with hashes of another size than the digest produces.
+    HashSize { expected: usize, found: usize },
+    /// A step has a direction other than left (0) or right (1).
+    InvalidDirection { step: usize, direction: u8 },
+    /// A sibling of the path to encode is not a hex encoded hash of the digest.
+    InvalidSibling { step: usize },
+}
+
+impl fmt::Display for ProofCodecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProofCodecError::InvalidHex(position) => {
+                write!(f, "invalid hex at position {position}")
+            }
+            ProofCodecError::InvalidBase64(position) => {
+                write!(f, "invalid base64 at position {position}")
+            }
+            ProofCodecError::Truncated => f.write_str("the proof is truncated"),
+            ProofCodecError::UnsupportedVersion(version) => {
+                write!(f, "unsupported proof format version {version}")
+            }
+            ProofCodecError::HashSize { expected, found } => write!(
+                f,
+                "the proof has hashes of {found} bytes, but the digest produces {expected}"
+            ),
+            ProofCodecError::InvalidDirection { step, direction } => {
+                write!(f, "invalid direction {direction} at step {step}")
+            }
+            ProofCodecError::InvalidSibling { step } => {
+                write!(f, "invalid sibling hash at step {step}")
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ProofCodecError {}
+
+impl<D: Digest> TraversePath<D> {
+    /// Encodes the path in the binary proof format: the format version, the size of the
+    /// hashes, and then a direction byte, 0 for left and 1 for right, followed by the sibling
+    /// hash for each step from the root down to the leaf.
+    ///
+    /// # Returns
+    ///
+    /// The bytes, or `ProofCodecError::InvalidSibling` or `ProofCodecError::InvalidDirection`
+    /// if a step cannot be encoded.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, ProofCodecError> {
+        let hash_size = <D as Digest>::output_size();
+        let mut bytes = Vec::with_capacity(2 + self.siblings.len() * (hash_size + 1));
+        bytes.push(PROOF_FORMAT_VERSION);
+        bytes.push(
+            u8::try_from(hash_size).map_err(|_| ProofCodecError::HashSize {
+                expected: hash_size,
+                found: u8::MAX as usize,
+            })?,
+        );
+
+        if self.siblings.len() != self.directions.len() {
+            let step = self.siblings.len().min(self.directions.len());
+            return Err(ProofCodecError::InvalidSibling { step });
+        }
+        for (step, (sibling, direction)) in self.siblings.iter().zip(&self.directions).enumerate() {
+            let direction = u8::from(direction.clone());
+            if direction > 1 {
+                return Err(ProofCodecError::InvalidDirection { step, direction });
+            }
+            bytes.push(direction);
+            let sibling =
+                decode_hash::<D>(sibling).ok_or(ProofCodecError::InvalidSibling { step })?;
+            bytes.extend_from_slice(&sibling);
+        }
+        Ok(bytes)
+    }
+
+    /// Decodes a path from the binary proof format written by `to_bytes`.
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes`: The encoded proof.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ProofCodecError> {
+        let [version, hash_size, steps @ ..] = bytes else {
+            return Err(ProofCodecError::Truncated);
+        };
+        if *version != PROOF_FORMAT_VERSION {
+            return Err(ProofCodecError::UnsupportedVersion(*version));
+        }
+        let expected = <D as Digest>::output_size();
+        if *hash_size as usize != expected {
+            return Err(ProofCodecError::HashSize {
+                expected,
+                found: *hash_size as usize,
+            });
+        }
+        if !steps.len().is_multiple_of(expected + 1) {
+            return Err(ProofCodecError::Truncated);
+        }
+
+        let mut siblings = Vec::with_capacity(steps.len() / (expected + 1));
+        let mut directions = Vec::with_capacity(siblings.capacity());
+        for (step, chunk) in steps.chunks(expected + 1).enumerate() {
+            directions.push(match NodeDirection::try_from(chunk[0]) {
+                Ok(NodeDirection::Root) | Err(_) => {
+                    return Err(ProofCodecError::InvalidDirection {
+                        step,
+                        direction: chunk[0],
+                    })
+                }
+                Ok(direction) => direction,
+            });
+            siblings.push(hex::encode(&chunk[1..]));
+        }
+        Ok(TraversePath::from_steps(siblings, directions))
+    }
+
+    /// Encodes the path as a single hex string of the binary proof format.
+    pub fn to_hex_string(&self) -> Result<String, ProofCodecError> {
+        self.to_bytes().map(hex::encode)
+    }
+
+    /// Decodes a path from a hex string written by `to_hex_string`, of either case and
+    /// optionally prefixed with `0x`. Whitespace around the string is ignored.
+    ///
+    /// # Arguments
+    ///
+    /// * `text`: The hex string.
+    pub fn from_hex_string(text: &str) -> Result<Self, ProofCodecError> {
+        let text = text.trim();
+        let text = text.strip_prefix("0x").unwrap_or(text);
+        let bytes = hex::decode(text).map_err(|err| match err {
+            hex::FromHexError::InvalidHexCharacter { index, .. } => {
+                ProofCodecError::InvalidHex(index)
+            }
+            _ => ProofCodecError::InvalidHex(text.len()),
+        })?;
+        Self::from_bytes(&bytes)
+    }
+
+    /// Encodes the path as a single padded base64 string of the binary proof format.
+    pub fn to_base64(&self) -> Result<String, ProofCodecError> {
+        self.to_bytes().map(|bytes| encode_base64(&bytes))
+    }
+
+    /// Decodes a path from a padded base64 string written by `to_base64`. Whitespace around
+    /// the string is ignored.
+    ///
+    /// # Arguments
+    ///
+    /// * `text`: The base64 string.
+    pub fn from_base64(text: &str) -> Result<Self, ProofCodecError> {
+        Self::from_bytes(&decode_base64(text.trim())?)
+    }
+}
+
+/// Encodes bytes in the standard base64 encoding with padding.
+fn encode_base64(bytes: &[u8]) -> String {
+    let mut text = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let buffer = chunk.iter().enumerate().fold(0u32, |buffer, (i, byte)| {
+            buffer | u32::from(*byte) << (16 - 8 * i)
+        });
+        for i in 0..4 {
+            if i <= chunk.len() {
+                text.push(BASE64_ALPHABET[(buffer >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                text.push('=');
+            }
+        }
+    }
+    text
+}
+
+/// Decodes a padded base64 string, rejecting misplaced padding and non-zero unused bits.
+fn decode_base64(text: &str) -> Result<Vec<u8>, ProofCodecError> {
+    let text = text.as_bytes();
+    if !text.len().is_multiple_of(4) {
+        return Err(ProofCodecError::InvalidBase64(text.len()));
+    }
+
+    let mut bytes = Vec::with_capacity(text.len() / 4 * 3);
+    for (offset, chunk) in (0..).step_by(4).zip(text.chunks(4)) {
+        let is_last = offset + 4 == text.len();
+        let padding = chunk.iter().rev().take_while(|&&c| c == b'=').count();
+        if padding > 2 || (padding > 0 && !is_last) {
+            return Err(ProofCodecError::InvalidBase64(offset + 4 - padding));
+        }
+
+        let mut buffer = 0u32;
+        for (i, &c) in chunk[..4 - padding].iter().enumerate() {
+            let value = BASE64_ALPHABET
+                .iter()
+                .position(|&a| a == c)
+                .ok_or(ProofCodecError::InvalidBase64(offset + i))?;
+            buffer |= (value as u32) << (18 - 6 * i);
+        }
+        let decoded = [(buffer >> 16) as u8, (buffer >> 8) as u8, buffer as u8];
+        let len = 3 - padding;
+        if decoded[len..].iter().any(|&byte| byte != 0) {
+            return Err(ProofCodecError::InvalidBase64(offset + 3 - padding));
+        }
+        bytes.extend_from_slice(&decoded[..len]);
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{util, verify_proof, MerkleTree, Sha256, Sha512};
+    use alloc::format;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(1)]
+    #[case(2)]
+    #[case(7)]
+    #[case(16)]
+    fn it_can_encode_proofs_as_text(#[case] leaf_count: usize) {
+        let user_data = util::generate_random_user_data(leaf_count);
+        let tree: MerkleTree<_> = MerkleTree::build("Leaf", "Branch", &user_data);
+        let root = tree.root().unwrap();
+
+        for (index, leaf) in user_data.iter().enumerate() {
+            let (_, path) = tree.proof_by_index(index).unwrap();
+            let hex = path.to_hex_string().unwrap();
+            let base64 = path.to_base64().unwrap();
+            assert_eq!(hex.len(), 2 * (2 + 33 * path.siblings.len()));
+            assert!(base64.len().is_multiple_of(4));
+
+            for decoded in [
+                TraversePath::<Sha256>::from_hex_string(&hex).unwrap(),
+                TraversePath::from_hex_string(&format!("0x{}\n", hex.to_uppercase())).unwrap(),
+                TraversePath::from_base64(&base64).unwrap(),
+            ] {
+                assert_eq!(decoded.to_vec(), path.to_vec());
+                assert!(verify_proof("Leaf", "Branch", &root, leaf, &decoded));
+            }
+        }
+    }
+
+    #[test]
+    fn it_can_reject_malformed_proof_text() {
+        let user_data = util::generate_random_user_data(5);
+        let tree: MerkleTree<_> = MerkleTree::build("Leaf", "Branch", &user_data);
+        let (_, path) = tree.proof_by_index(2).unwrap();
+        let hex = path.to_hex_string().unwrap();
+        let base64 = path.to_base64().unwrap();
+
+        type Path = TraversePath<Sha256>;
+        assert_eq!(
+            Path::from_hex_string("01zz").err(),
+            Some(ProofCodecError::InvalidHex(2))
+        );
+        assert_eq!(
+            Path::from_hex_string(&hex[1..]).err(),
+            Some(ProofCodecError::InvalidHex(hex.len() - 1))
+        );
+        assert_eq!(
+            Path::from_hex_string(&hex[..hex.len() - 2]).err(),
+            Some(ProofCodecError::Truncated)
+        );
+        assert_eq!(
+            Path::from_hex_string("").err(),
+            Some(ProofCodecError::Truncated)
+        );
+        assert_eq!(
+            Path::from_hex_string(&format!("02{}", &hex[2..])).err(),
+            Some(ProofCodecError::UnsupportedVersion(2))
+        );
+        assert_eq!(
+            TraversePath::<Sha512>::from_hex_string(&hex).err(),
+            Some(ProofCodecError::HashSize {
+                expected: 64,
+                found: 32
+            })
+        );
+        assert_eq!(
+            Path::from_hex_string(&format!("{}05{}", &hex[..4], &hex[6..])).err(),
+            Some(ProofCodecError::InvalidDirection {
+                step: 0,
+                direction: 5
+            })
+        );
+
+        assert_eq!(
+            Path::from_base64(&base64[1..]).err(),
+            Some(ProofCodecError::InvalidBase64(base64.len() - 1))
+        );
+        assert_eq!(
+            Path::from_base64(&format!("!{}", &base64[1..])).err(),
+            Some(ProofCodecError::InvalidBase64(0))
+        );
+        assert_eq!(
+            Path::from_base64(&format!("AQ==AQ=={base64}")).err(),
+            Some(ProofCodecError::InvalidBase64(2))
+        );
+        // Non-zero bits after the last byte make a second spelling of the same bytes.
+        assert_eq!(
+            Path::from_base64("AQF=").err(),
+            Some(ProofCodecError::InvalidBase64(2))
+        );
+
+        let mut broken = path.clone();
+        broken.siblings[1] = "beef".into();
+        assert_eq!(
+            broken.to_base64().err(),
+            Some(ProofCodecError::InvalidSibling { step: 1 })
+        );
+    }
+}
@@ -0,0 +1,654 @@
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+use core::marker::PhantomData;
+use core::mem;
+use sha2::digest::Output;
+use sha2::Digest;
+
+use crate::multiproof::decode_hash;
+use crate::root::roots_match;
+use crate::sparse::get_bit;
+use crate::{MerkleTreeData, Sha256, TreeOptions, SPARSE_DEPTH};
+
+/// A compact sparse Merkle tree over 256-bit keys, in the style of the Jellyfish Merkle tree.
+///
+/// A subtree holding a single leaf is replaced by the leaf itself, and every empty subtree
+/// hashes to the same placeholder, `hash_leaf(&[])`. Only as many branches as needed to tell
+/// the keys apart are stored, so the tree and its proofs stay small however large the key
+/// space is. A leaf hashes to `hash_leaf(key || H(value))`, which lets a proof reveal the key
+/// of the leaf it ends at without revealing its value.
+pub struct CompactSparseMerkleTree<T, D: Digest = Sha256> {
+    options: TreeOptions,
+    placeholder: Output<D>,
+    root: Node<T, D>,
+    len: usize,
+}
+
+enum Node<T, D: Digest> {
+    Empty,
+    Leaf {
+        key: [u8; 32],
+        value: T,
+        value_hash: Output<D>,
+        hash: Output<D>,
+    },
+    Branch {
+        left: Box<Node<T, D>>,
+        right: Box<Node<T, D>>,
+        hash: Output<D>,
+    },
+}
+
+impl<T: MerkleTreeData, D: Digest> CompactSparseMerkleTree<T, D> {
+    /// Creates an empty compact sparse Merkle tree.
+    ///
+    /// # Arguments
+    ///
+    /// * `tag_leaf`: The tag used for hashing leaf nodes.
+    /// * `tag_branch`: The tag used for hashing branch nodes.
+    pub fn new(tag_leaf: &str, tag_branch: &str) -> Self {
+        Self::with_options(TreeOptions::tagged(tag_leaf, tag_branch))
+    }
+
+    /// Creates an empty compact sparse Merkle tree with the given hashing configuration.
+    ///
+    /// # Arguments
+    ///
+    /// * `options`: The hashing configuration of the tree. The padding policy is not used.
+    pub fn with_options(options: TreeOptions) -> Self {
+        CompactSparseMerkleTree {
+            placeholder: options.hash_leaf::<D>(&[]),
+            options,
+            root: Node::Empty,
+            len: 0,
+        }
+    }
+
+    /// Returns the hashing configuration of the tree.
+    pub fn options(&self) -> &TreeOptions {
+        &self.options
+    }
+
+    /// Returns the hash of the root node of the tree.
+    pub fn root(&self) -> String {
+        hex::encode(self.root.hash(&self.placeholder))
+    }
+
+    /// Returns the number of leaves.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the tree has no leaves.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the value stored at the given key.
+    ///
+    /// # Arguments
+    ///
+    /// * `key`: The key of the leaf.
+    pub fn get(&self, key: &[u8; 32]) -> Option<&T> {
+        let mut node = &self.root;
+        let mut depth = 0;
+        loop {
+            match node {
+                Node::Empty => return None,
+                Node::Leaf {
+                    key: found, value, ..
+                } => {
+                    return (found == key).then_some(value);
+                }
+                Node::Branch { left, right, .. } => {
+                    node = if get_bit(key, depth) { right } else { left };
+                    depth += 1;
+                }
+            }
+        }
+    }
+
+    /// Inserts or updates the value at the given key.
+    ///
+    /// # Arguments
+    ///
+    /// * `key`: The key of the leaf.
+    /// * `value`: The value to store.
+    ///
+    /// # Returns
+    ///
+    /// The value previously stored at the key, if any.
+    pub fn insert(&mut self, key: [u8; 32], value: T) -> Option<T> {
+        let value_hash = hash_value::<T, D>(&value);
+        let leaf = Node::Leaf {
+            hash: compact_leaf_hash::<D>(&self.options, &key, &value_hash),
+            key,
+            value,
+            value_hash,
+        };
+
+        let old = self.root.insert(0, leaf, &self.options, &self.placeholder);
+        if old.is_none() {
+            self.len += 1;
+        }
+        old
+    }
+
+    /// Removes the leaf at the given key, collapsing the branches that are left with a single
+    /// leaf.
+    ///
+    /// # Arguments
+    ///
+    /// * `key`: The key of the leaf.
+    ///
+    /// # Returns
+    ///
+    /// The value that was stored at the key, if any.
+    pub fn remove(&mut self, key: &[u8; 32]) -> Option<T> {
+        let old = self.root.remove(0, key, &self.options, &self.placeholder)?;
+        self.len -= 1;
+        Some(old)
+    }
+
+    /// Generates the proof for the given key.
+    ///
+    /// The proof follows the path of the key down to a leaf or an empty subtree. It shows the
+    /// value of the key if the path ends at its leaf, or that the key is absent if the path
+    /// ends at an empty subtree or at the leaf of another key.
+    ///
+    /// # Arguments
+    ///
+    /// * `key`: The key to prove.
+    pub fn proof(&self, key: &[u8; 32]) -> CompactSparseProof<D> {
+        let mut siblings = Vec::new();
+        let mut node = &self.root;
+        loop {
+            match node {
+                Node::Empty => return CompactSparseProof::new(siblings, None),
+                Node::Leaf {
+                    key, value_hash, ..
+                } => {
+                    let leaf = Some((*key, hex::encode(value_hash)));
+                    return CompactSparseProof::new(siblings, leaf);
+                }
+                Node::Branch { left, right, .. } => {
+                    let (next, sibling) = if get_bit(key, siblings.len()) {
+                        (right, left)
+                    } else {
+                        (left, right)
+                    };
+                    siblings.push(hex::encode(sibling.hash(&self.placeholder)));
+                    node = next;
+                }
+            }
+        }
+    }
+}
+
+impl<T, D: Digest> Node<T, D> {
+    /// Returns the hash of the node.
+    ///
+    /// # Arguments
+    ///
+    /// * `placeholder`: The hash of an empty subtree.
+    fn hash<'a>(&'a self, placeholder: &'a Output<D>) -> &'a Output<D> {
+        match self {
+            Node::Empty => placeholder,
+            Node::Leaf { hash, .. } | Node::Branch { hash, .. } => hash,
+        }
+    }
+
+    /// Creates a branch node over two children.
+    fn branch(left: Self, right: Self, options: &TreeOptions, placeholder: &Output<D>) -> Self {
+        Node::Branch {
+            hash: options.hash_branch::<D>(left.hash(placeholder), right.hash(placeholder)),
+            left: Box::new(left),
+            right: Box::new(right),
+        }
+    }
+
+    /// Inserts a leaf into the subtree rooted at this node.
+    ///
+    /// # Arguments
+    ///
+    /// * `depth`: The depth of this node, 0 being the root.
+    /// * `leaf`: The `Node::Leaf` to insert.
+    /// * `options`: The hashing configuration of the tree.
+    /// * `placeholder`: The hash of an empty subtree.
+    ///
+    /// # Returns
+    ///
+    /// The value previously stored at the key of the leaf, if any.
+    fn insert(
+        &mut self,
+        depth: usize,
+        leaf: Self,
+        options: &TreeOptions,
+        placeholder: &Output<D>,
+    ) -> Option<T> {
+        let Node::Leaf { key, .. } = &leaf else {
+            unreachable!("only leaves are inserted");
+        };
+        let key = *key;
+
+        match self {
+            Node::Empty => {
+                *self = leaf;
+                None
+            }
+            Node::Leaf { key: existing, .. } if *existing == key => {
+                match mem::replace(self, leaf) {
+                    Node::Leaf { value, .. } => Some(value),
+                    _ => unreachable!(),
+                }
+            }
+            Node::Leaf { .. } => {
+                let existing = mem::replace(self, Node::Empty);
+                *self = Self::split(existing, leaf, depth, options, placeholder);
+                None
+            }
+            Node::Branch { left, right, hash } => {
+                let child = if get_bit(&key, depth) {
+                    &mut **right
+                } else {
+                    &mut **left
+                };
+                let old = child.insert(depth + 1, leaf, options, placeholder);
+                *hash = options.hash_branch::<D>(left.hash(placeholder), right.hash(placeholder));
+                old
+            }
+        }
+    }
+
+    /// Builds the branches that separate two leaves with different keys.
+    ///
+    /// # Arguments
+    ///
+    /// * `a`: The first `Node::Leaf`.
+    /// * `b`: The second `Node::Leaf`.
+    /// * `depth`: The depth of the subtree holding both leaves.
+    /// * `options`: The hashing configuration of the tree.
+    /// * `placeholder`: The hash of an empty subtree.
+    fn split(
+        a: Self,
+        b: Self,
+        depth: usize,
+        options: &TreeOptions,
+        placeholder: &Output<D>,
+    ) -> Self {
+        let (Node::Leaf { key: key_a, .. }, Node::Leaf { key: key_b, .. }) = (&a, &b) else {
+            unreachable!("only leaves are split");
+        };
+        let (bit_a, bit_b) = (get_bit(key_a, depth), get_bit(key_b, depth));
+
+        let (left, right) = if bit_a == bit_b {
+            let child = Self::split(a, b, depth + 1, options, placeholder);
+            if bit_a {
+                (Node::Empty, child)
+            } else {
+                (child, Node::Empty)
+            }
+        } else if bit_a {
+            (b, a)
+        } else {
+            (a, b)
+        };
+        Self::branch(left, right, options, placeholder)
+    }
+
+    /// Removes the leaf with the given key from the subtree rooted at this node.
+    ///
+    /// # Arguments
+    ///
+    /// * `depth`: The depth of this node, 0 being the root.
+    /// * `key`: The key of the leaf.
+    /// * `options`: The hashing configuration of the tree.
+    /// * `placeholder`: The hash of an empty subtree.
+    ///
+    /// # Returns
+    ///
+    /// The value that was stored at the key, if any.
+    fn remove(
+        &mut self,
+        depth: usize,
+        key: &[u8; 32],
+        options: &TreeOptions,
+        placeholder: &Output<D>,
+    ) -> Option<T> {
+        match self {
+            Node::Empty => None,
+            Node::Leaf { key: existing, .. } if existing == key => {
+                match mem::replace(self, Node::Empty) {
+                    Node::Leaf { value, .. } => Some(value),
+                    _ => unreachable!(),
+                }
+            }
+            Node::Leaf { .. } => None,
+            Node::Branch { left, right, hash } => {
+                let child = if get_bit(key, depth) {
+                    &mut **right
+                } else {
+                    &mut **left
+                };
+                let old = child.remove(depth + 1, key, options, placeholder)?;
+
+                // A branch that is left with a single leaf is replaced by the leaf.
+                match (&mut **left, &mut **right) {
+                    (only @ Node::Leaf { .. }, Node::Empty)
+                    | (Node::Empty, only @ Node::Leaf { .. }) => {
+                        *self = mem::replace(only, Node::Empty);
+                    }
+                    _ => {
+                        *hash = options
+                            .hash_branch::<D>(left.hash(placeholder), right.hash(placeholder));
+                    }
+                }
+                Some(old)
+            }
+        }
+    }
+}
+
+/// A proof for a key of a `CompactSparseMerkleTree`.
+///
+/// Holds the sibling hashes ordered from the root down along the path of the key, and the
+/// key and hex encoded value hash of the leaf the path ends at, if it does not end at an
+/// empty subtree.
+pub struct CompactSparseProof<D = Sha256> {
+    pub siblings: Vec<String>,
+    pub leaf: Option<([u8; 32], String)>,
+    digest: PhantomData<fn() -> D>,
+}
+
+impl<D> Clone for CompactSparseProof<D> {
+    fn clone(&self) -> Self {
+        Self::new(self.siblings.clone(), self.leaf.clone())
+    }
+}
+
+impl<D> fmt::Debug for CompactSparseProof<D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CompactSparseProof")
+            .field("siblings", &self.siblings)
+            .field("leaf", &self.leaf)
+            .finish()
+    }
+}
+
+impl<D> CompactSparseProof<D> {
+    /// Creates a `CompactSparseProof` from its parts.
+    ///
+    /// # Arguments
+    ///
+    /// * `siblings`: The hex encoded sibling hashes, ordered from the root down.
+    /// * `leaf`: The key and hex encoded value hash of the leaf at the end of the path.
+    pub fn new(siblings: Vec<String>, leaf: Option<([u8; 32], String)>) -> Self {
+        CompactSparseProof {
+            siblings,
+            leaf,
+            digest: PhantomData,
+        }
+    }
+
+    /// Recomputes the root committed to by this proof for the given key and value.
+    ///
+    /// # Arguments
+    ///
+    /// * `options`: The options the tree was built with.
+    /// * `key`: The key to prove.
+    /// * `value`: The value of the key, or `None` to prove that the key is absent.
+    ///
+    /// # Returns
+    ///
+    /// The hex encoded root, or `None` if the proof is malformed or does not fit the claim.
+    pub fn compute_root_with_options<T>(
+        &self,
+        options: &TreeOptions,
+        key: &[u8; 32],
+        value: Option<&T>,
+    ) -> Option<String>
+    where
+        T: MerkleTreeData,
+        D: Digest,
+    {
+        let depth = self.siblings.len();
+        if depth > SPARSE_DEPTH {
+            return None;
+        }
+
+        let mut hash = match (value, &self.leaf) {
+            (Some(value), _) => compact_leaf_hash::<D>(options, key, &hash_value::<T, D>(value)),
+            (None, None) => options.hash_leaf::<D>(&[]),
+            // The path of an absent key may end at the leaf of another key sharing its
+            // first `depth` bits.
+            (None, Some((other, value_hash))) => {
+                if other == key || (0..depth).any(|bit| get_bit(other, bit) != get_bit(key, bit)) {
+                    return None;
+                }
+                compact_leaf_hash::<D>(options, other, &decode_hash::<D>(value_hash)?)
+            }
+        };
+
+        for (depth, sibling) in self.siblings.iter().enumerate().rev() {
+            let sibling = decode_hash::<D>(sibling)?;
+            hash = if get_bit(key, depth) {
+                options.hash_branch::<D>(&sibling, &hash)
+            } else {
+                options.hash_branch::<D>(&hash, &sibling)
+            };
+        }
+
+        Some(hex::encode(hash))
+    }
+}
+
+/// Verifies the value of a key in a compact sparse Merkle tree, or that the key is absent.
+///
+/// # Arguments
+///
+/// * `tag_leaf`: The tag used for hashing leaf nodes.
+/// * `tag_branch`: The tag used for hashing branch nodes.
+/// * `root`: The hex encoded root to verify against.
+/// * `key`: The key to verify.
+/// * `value`: The claimed value of the key, or `None` to verify that the key is absent.
+/// * `proof`: The proof returned by `CompactSparseMerkleTree::proof`.
+///
+/// # Returns
+///
+/// `true` if the proof reconstructs the given root, `false` otherwise.
+pub fn verify_compact_sparse_proof<T, D>(
+    tag_leaf: &str,
+    tag_branch: &str,
+    root: &str,
+    key: &[u8; 32],
+    value: Option<&T>,
+    proof: &CompactSparseProof<D>,
+) -> bool
+where
+    T: MerkleTreeData,
+    D: Digest,
+{
+    verify_compact_sparse_proof_with_options(
+        &TreeOptions::tagged(tag_leaf, tag_branch),
+        root,
+        key,
+        value,
+        proof,
+    )
+}
+
+/// Verifies the value of a key in a compact sparse Merkle tree, or that the key is absent,
+/// using the hashing configuration the tree was built with.
+///
+/// # Arguments
+///
+/// * `options`: The options the tree was built with.
+/// * `root`: The hex encoded root to verify against.
+/// * `key`: The key to verify.
+/// * `value`: The claimed value of the key, or `None` to verify that the key is absent.
+/// * `proof`: The proof returned by `CompactSparseMerkleTree::proof`.
+///
+/// # Returns
+///
+/// `true` if the proof reconstructs the given root, `false` otherwise.
+pub fn verify_compact_sparse_proof_with_options<T, D>(
+    options: &TreeOptions,
+    root: &str,
+    key: &[u8; 32],
+    value: Option<&T>,
+    proof: &CompactSparseProof<D>,
+) -> bool
+where
+    T: MerkleTreeData,
+    D: Digest,
+{
+    proof
+        .compute_root_with_options(options, key, value)
+        .is_some_and(|computed| roots_match(&computed, root))
+}
+
+/// Calculates the hash of a leaf from its key and the hash of its value.
+///
+/// # Arguments
+///
+/// * `options`: The hashing configuration of the tree.
+/// * `key`: The key of the leaf.
+/// * `value_hash`: The hash of the serialized value.
+fn compact_leaf_hash<D: Digest>(
+    options: &TreeOptions,
+    key: &[u8; 32],
+    value_hash: &Output<D>,
+) -> Output<D> {
+    let mut input = key.to_vec();
+    input.extend_from_slice(value_hash);
+    options.hash_leaf::<D>(&input)
+}
+
+/// Calculates the hash of a value by streaming its serialization into the digest.
+///
+/// # Arguments
+///
+/// * `value`: The value of a leaf.
+fn hash_value<T: MerkleTreeData, D: Digest>(value: &T) -> Output<D> {
+    let mut hasher = D::new();
+    value.hash_into(&mut hasher);
+    hasher.finalize()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util;
+
+    fn sparse_key(n: u8) -> [u8; 32] {
+        let mut key = [0u8; 32];
+        key[0] = n.wrapping_mul(37);
+        key[31] = n;
+        key
+    }
+
+    #[test]
+    fn it_can_insert_into_compact_sparse_tree() {
+        let user_data = util::generate_random_user_data(6);
+        let mut tree: CompactSparseMerkleTree<util::UserData> =
+            CompactSparseMerkleTree::new("ProofOfReserve_Leaf", "ProofOfReserve_Branch");
+        let empty_root = tree.root();
+
+        for (i, data) in user_data.iter().enumerate() {
+            assert!(tree.insert(sparse_key(i as u8), data.clone()).is_none());
+        }
+        assert_eq!(tree.len(), 6);
+        assert_eq!(tree.get(&sparse_key(4)).unwrap().id, user_data[4].id);
+        assert!(tree.get(&sparse_key(42)).is_none());
+
+        // The shape only depends on the keys, not on the insertion order.
+        let mut reversed: CompactSparseMerkleTree<util::UserData> =
+            CompactSparseMerkleTree::new("ProofOfReserve_Leaf", "ProofOfReserve_Branch");
+        for (i, data) in user_data.iter().enumerate().rev() {
+            reversed.insert(sparse_key(i as u8), data.clone());
+        }
+        assert_eq!(reversed.root(), tree.root());
+
+        // Removing a leaf collapses the branches back to what they were without it.
+        let root = tree.root();
+        let extra = util::UserData { id: 99, balance: 1 };
+        tree.insert(sparse_key(42), extra);
+        assert_ne!(tree.root(), root);
+        assert_eq!(tree.remove(&sparse_key(42)).unwrap().id, 99);
+        assert_eq!(tree.root(), root);
+
+        for i in 0..6 {
+            assert!(tree.remove(&sparse_key(i)).is_some());
+        }
+        assert!(tree.is_empty());
+        assert_eq!(tree.root(), empty_root);
+    }
+
+    #[test]
+    fn it_can_verify_compact_sparse_proofs() {
+        let user_data = util::generate_random_user_data(6);
+        let mut tree: CompactSparseMerkleTree<util::UserData> =
+            CompactSparseMerkleTree::new("ProofOfReserve_Leaf", "ProofOfReserve_Branch");
+        for (i, data) in user_data.iter().enumerate() {
+            tree.insert(sparse_key(i as u8), data.clone());
+        }
+        let root = tree.root();
+
+        let proof = tree.proof(&sparse_key(3));
+        assert!(proof.siblings.len() < 8);
+        assert!(verify_compact_sparse_proof(
+            "ProofOfReserve_Leaf",
+            "ProofOfReserve_Branch",
+            &root,
+            &sparse_key(3),
+            Some(&user_data[3]),
+            &proof
+        ));
+        assert!(!verify_compact_sparse_proof(
+            "ProofOfReserve_Leaf",
+            "ProofOfReserve_Branch",
+            &root,
+            &sparse_key(3),
+            Some(&user_data[2]),
+            &proof
+        ));
+        assert!(!verify_compact_sparse_proof::<util::UserData, _>(
+            "ProofOfReserve_Leaf",
+            "ProofOfReserve_Branch",
+            &root,
+            &sparse_key(3),
+            None,
+            &proof
+        ));
+
+        // Absent keys whose path ends at an empty subtree and at the leaf of another key.
+        let mut ends_at_leaf = false;
+        for n in 6..40 {
+            let absent = tree.proof(&sparse_key(n));
+            ends_at_leaf |= absent.leaf.is_some();
+            assert!(verify_compact_sparse_proof::<util::UserData, _>(
+                "ProofOfReserve_Leaf",
+                "ProofOfReserve_Branch",
+                &root,
+                &sparse_key(n),
+                None,
+                &absent
+            ));
+        }
+        assert!(ends_at_leaf);
+
+        // Moving the leaf of a present key off its path does not prove its absence.
+        let mut forged = proof.clone();
+        forged.leaf.as_mut().unwrap().0[31] ^= 0x80;
+        assert!(!verify_compact_sparse_proof::<util::UserData, _>(
+            "ProofOfReserve_Leaf",
+            "ProofOfReserve_Branch",
+            &root,
+            &sparse_key(3),
+            None,
+            &forged
+        ));
+    }
+}
@@ -0,0 +1,246 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+use core::marker::PhantomData;
+use sha2::Digest;
+
+use crate::{
+    level_sizes, verify_proof_with_options, MerkleError, MerkleTree, MerkleTreeData, NodeDirection,
+    NodeStore, Padding, Sha256, TraversePath, TreeOptions,
+};
+
+/// A Merkle proof for a single leaf that stores the position of the leaf instead of a
+/// direction per step.
+///
+/// The directions follow from the bits of the index, lowest bit at the leaf level, together
+/// with the shape of the tree for the levels where an unpaired node has no sibling. The
+/// sibling hashes are ordered from the root down to the leaf, as in `TraversePath`.
+pub struct CompressedProof<D = Sha256> {
+    /// The position of the leaf, in leaf order.
+    pub leaf_index: usize,
+    pub siblings: Vec<String>,
+    digest: PhantomData<fn() -> D>,
+}
+
+impl<D> Clone for CompressedProof<D> {
+    fn clone(&self) -> Self {
+        Self::new(self.leaf_index, self.siblings.clone())
+    }
+}
+
+impl<D> fmt::Debug for CompressedProof<D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CompressedProof")
+            .field("leaf_index", &self.leaf_index)
+            .field("siblings", &self.siblings)
+            .finish()
+    }
+}
+
+impl<D> CompressedProof<D> {
+    /// Creates a proof from the position of a leaf and its sibling hashes.
+    ///
+    /// # Arguments
+    ///
+    /// * `leaf_index`: The position of the leaf, in leaf order.
+    /// * `siblings`: The hex encoded sibling hashes, from the root down to the leaf.
+    pub fn new(leaf_index: usize, siblings: Vec<String>) -> Self {
+        CompressedProof {
+            leaf_index,
+            siblings,
+            digest: PhantomData,
+        }
+    }
+
+    /// Restores the directions of the proof.
+    ///
+    /// # Arguments
+    ///
+    /// * `leaf_count`: The number of leaves in the tree.
+    /// * `padding`: The padding policy the tree was built with.
+    ///
+    /// # Returns
+    ///
+    /// The path, or `MerkleError::InvalidProof` if the index or the number of siblings does not
+    /// fit a tree of that size.
+    pub fn expand(
+        &self,
+        leaf_count: usize,
+        padding: Padding,
+    ) -> Result<TraversePath<D>, MerkleError> {
+        if self.leaf_index >= leaf_count {
+            return Err(MerkleError::InvalidProof);
+        }
+
+        let mut directions = Vec::with_capacity(self.siblings.len());
+        let mut position = self.leaf_index;
+        let level_sizes = level_sizes(leaf_count);
+        for &size in &level_sizes[..level_sizes.len() - 1] {
+            let unpaired = position ^ 1 >= size;
+            if !(unpaired && padding == Padding::Promote) {
+                directions.push(if position & 1 == 0 {
+                    NodeDirection::Left
+                } else {
+                    NodeDirection::Right
+                });
+            }
+            position >>= 1;
+        }
+        directions.reverse();
+
+        if directions.len() != self.siblings.len() {
+            return Err(MerkleError::InvalidProof);
+        }
+        Ok(TraversePath::from_steps(self.siblings.clone(), directions))
+    }
+}
+
+impl<D> TraversePath<D> {
+    /// Drops the directions of the path in favor of the index of the leaf it leads to.
+    ///
+    /// # Arguments
+    ///
+    /// * `leaf_count`: The number of leaves in the tree.
+    /// * `padding`: The padding policy the tree was built with.
+    ///
+    /// # Returns
+    ///
+    /// The compressed proof, or `MerkleError::InvalidProof` if the path does not fit a tree of
+    /// that size.
+    pub fn compress(
+        &self,
+        leaf_count: usize,
+        padding: Padding,
+    ) -> Result<CompressedProof<D>, MerkleError> {
+        let leaf_index = self.leaf_index(leaf_count, padding)?;
+        Ok(CompressedProof::new(leaf_index, self.siblings.clone()))
+    }
+}
+
+impl<T, D: Digest, S: NodeStore<T, D>> MerkleTree<T, D, S> {
+    /// Generates the compressed proof of the leaf at the given index.
+    ///
+    /// # Arguments
+    ///
+    /// * `index`: The index of the leaf, in leaf order.
+    ///
+    /// # Returns
+    ///
+    /// The proof, or `MerkleError::LeafNotFound` if the index is out of range.
+    pub fn compressed_proof(&self, index: usize) -> Result<CompressedProof<D>, MerkleError> {
+        let (_, path) = self.proof_by_index(index)?;
+        Ok(CompressedProof::new(index, path.siblings))
+    }
+}
+
+/// Verifies a compressed proof of a leaf.
+///
+/// # Arguments
+///
+/// * `tag_leaf`: The tag used for hashing leaf nodes.
+/// * `tag_branch`: The tag used for hashing branch nodes.
+/// * `root`: The hex encoded Merkle root to verify against.
+/// * `leaf_count`: The number of leaves in the tree.
+/// * `leaf_data`: The user data that is claimed to be included in the tree.
+/// * `proof`: The proof returned by `MerkleTree::compressed_proof`.
+///
+/// # Returns
+///
+/// `true` if the leaf is included in the tree at the index of the proof, `false` otherwise.
+pub fn verify_compressed_proof<T, D>(
+    tag_leaf: &str,
+    tag_branch: &str,
+    root: &str,
+    leaf_count: usize,
+    leaf_data: &T,
+    proof: &CompressedProof<D>,
+) -> bool
+where
+    T: MerkleTreeData,
+    D: Digest,
+{
+    verify_compressed_proof_with_options(
+        &TreeOptions::tagged(tag_leaf, tag_branch),
+        root,
+        leaf_count,
+        leaf_data,
+        proof,
+    )
+}
+
+/// Verifies a compressed proof of a leaf, using the hashing configuration the tree was built
+/// with.
+///
+/// # Arguments
+///
+/// * `options`: The options the tree was built with.
+/// * `root`: The hex encoded Merkle root to verify against.
+/// * `leaf_count`: The number of leaves in the tree.
+/// * `leaf_data`: The user data that is claimed to be included in the tree.
+/// * `proof`: The proof returned by `MerkleTree::compressed_proof`.
+///
+/// # Returns
+///
+/// `true` if the leaf is included in the tree at the index of the proof, `false` otherwise.
+pub fn verify_compressed_proof_with_options<T, D>(
+    options: &TreeOptions,
+    root: &str,
+    leaf_count: usize,
+    leaf_data: &T,
+    proof: &CompressedProof<D>,
+) -> bool
+where
+    T: MerkleTreeData,
+    D: Digest,
+{
+    proof
+        .expand(leaf_count, options.padding)
+        .is_ok_and(|path| verify_proof_with_options(options, root, leaf_data, &path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util;
+    use alloc::vec;
+    use rstest::rstest;
+
+    #[rstest]
+    fn it_can_compress_proofs(
+        #[values(Padding::DuplicateLast, Padding::Promote, Padding::PadWithEmpty)] padding: Padding,
+        #[values(1, 2, 5, 6, 7, 11)] leaf_count: usize,
+    ) {
+        let user_data = util::generate_random_user_data(leaf_count);
+        let options = TreeOptions::tagged("Leaf", "Branch").with_padding(padding);
+        let tree: MerkleTree<_> = MerkleTree::build_with_options(options.clone(), &user_data);
+        let root = tree.root().unwrap();
+
+        for (index, leaf) in user_data.iter().enumerate() {
+            let (_, path) = tree.proof_by_index(index).unwrap();
+            let proof = tree.compressed_proof(index).unwrap();
+            assert_eq!(proof.leaf_index, index);
+            assert_eq!(
+                proof.expand(leaf_count, padding).unwrap().to_vec(),
+                path.to_vec()
+            );
+            assert_eq!(
+                path.compress(leaf_count, padding).unwrap().leaf_index,
+                index
+            );
+            assert!(verify_compressed_proof_with_options(
+                &options, &root, leaf_count, leaf, &proof
+            ));
+
+            // The same siblings at another index lead to another root.
+            let moved = CompressedProof::<Sha256>::new(index ^ 1, proof.siblings.clone());
+            assert!(!verify_compressed_proof_with_options(
+                &options, &root, leaf_count, leaf, &moved
+            ));
+        }
+        let past_end = CompressedProof::<Sha256>::new(leaf_count, vec![]);
+        assert_eq!(
+            past_end.expand(leaf_count, padding).err(),
+            Some(MerkleError::InvalidProof)
+        );
+    }
+}
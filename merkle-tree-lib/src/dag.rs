@@ -0,0 +1,316 @@
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+use sha2::{Digest, Sha256};
+
+use crate::ics23::{put_message, put_uint, put_varint};
+
+/// The multicodec of a block holding raw bytes.
+pub const RAW_CODEC: u64 = 0x55;
+
+/// The multicodec of a block holding a protobuf `PBNode` of the DAG-PB format.
+pub const DAG_PB_CODEC: u64 = 0x70;
+
+/// The multihash code of SHA-256.
+const SHA2_256: u64 = 0x12;
+
+/// The UnixFS type of a file node.
+const UNIXFS_FILE: u64 = 2;
+
+/// The alphabet of the base32 multibase, RFC 4648 in lower case.
+const BASE32_ALPHABET: &[u8; 32] = b"abcdefghijklmnopqrstuvwxyz234567";
+
+/// A version 1 content identifier, with a SHA-256 multihash as IPFS uses by default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Cid {
+    /// The multicodec of the content, `RAW_CODEC` or `DAG_PB_CODEC`.
+    pub codec: u64,
+    /// The SHA-256 digest of the content.
+    pub digest: [u8; 32],
+}
+
+impl Cid {
+    /// Creates the identifier of a block.
+    ///
+    /// # Arguments
+    ///
+    /// * `codec`: The multicodec of the block.
+    /// * `data`: The bytes of the block.
+    pub fn of(codec: u64, data: &[u8]) -> Self {
+        Cid {
+            codec,
+            digest: Sha256::digest(data).into(),
+        }
+    }
+
+    /// Returns the binary form of the identifier, the version, codec, hash code and digest
+    /// length as varints followed by the digest.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.digest.len() + 4);
+        put_varint(&mut bytes, 1);
+        put_varint(&mut bytes, self.codec);
+        put_varint(&mut bytes, SHA2_256);
+        put_varint(&mut bytes, self.digest.len() as u64);
+        bytes.extend_from_slice(&self.digest);
+        bytes
+    }
+}
+
+/// Formats the identifier in its canonical text form, the binary form in the base32
+/// multibase, as in `bafkrei...` or `bafybei...`.
+impl fmt::Display for Cid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut text = String::from("b");
+        let mut buffer = 0u32;
+        let mut bits = 0;
+        for byte in self.to_bytes() {
+            buffer = buffer << 8 | u32::from(byte);
+            bits += 8;
+            while bits >= 5 {
+                bits -= 5;
+                text.push(BASE32_ALPHABET[(buffer >> bits & 31) as usize] as char);
+            }
+        }
+        if bits > 0 {
+            text.push(BASE32_ALPHABET[(buffer << (5 - bits) & 31) as usize] as char);
+        }
+        f.write_str(&text)
+    }
+}
+
+/// The layout of a `MerkleDag`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DagOptions {
+    /// The size of the chunks the content is split into, at least 1.
+    pub chunk_size: usize,
+    /// The number of links of a branch node, at least 2.
+    pub max_links: usize,
+}
+
+/// The defaults of `ipfs add`, chunks of 256 KiB and up to 174 links per node.
+impl Default for DagOptions {
+    fn default() -> Self {
+        DagOptions {
+            chunk_size: 256 * 1024,
+            max_links: 174,
+        }
+    }
+}
+
+/// A block of a `MerkleDag`, to be stored or pinned under its identifier.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DagBlock {
+    /// The identifier of the block.
+    pub cid: Cid,
+    /// The bytes of the block.
+    pub data: Vec<u8>,
+}
+
+/// A content addressed Merkle DAG of a byte stream, laid out as an IPFS UnixFS file.
+///
+/// The content is split into chunks that become raw leaf blocks. Consecutive blocks are
+/// linked by DAG-PB branch nodes of up to `max_links` children, level by level, until a single
+/// root is left. This is the balanced layout of `ipfs add --cid-version=1 --raw-leaves`, so
+/// with the default options the root has the same CID as IPFS gives to the content.
+pub struct MerkleDag {
+    blocks: Vec<DagBlock>,
+    size: u64,
+}
+
+/// A node of a level while building a `MerkleDag`.
+struct DagLink {
+    cid: Cid,
+    /// The size of the content below the node.
+    file_size: u64,
+    /// The size of the blocks below the node, including itself.
+    total_size: u64,
+}
+
+impl MerkleDag {
+    /// Builds a DAG of the given content with the default options.
+    ///
+    /// # Arguments
+    ///
+    /// * `data`: The content.
+    pub fn build(data: &[u8]) -> Self {
+        Self::build_with_options(&DagOptions::default(), data)
+    }
+
+    /// Builds a DAG of the given content.
+    ///
+    /// # Arguments
+    ///
+    /// * `options`: The chunk size and the number of links per node.
+    /// * `data`: The content.
+    pub fn build_with_options(options: &DagOptions, data: &[u8]) -> Self {
+        let mut dag = MerkleDag {
+            blocks: Vec::new(),
+            size: 0,
+        };
+        let leaves = if data.is_empty() {
+            vec![dag.push_leaf(data)]
+        } else {
+            data.chunks(options.chunk_size.max(1))
+                .map(|chunk| dag.push_leaf(chunk))
+                .collect()
+        };
+        dag.link(options, leaves);
+        dag
+    }
+
+    /// Builds a DAG of the content of a reader, such as a `std::fs::File`, one chunk at a
+    /// time.
+    ///
+    /// # Arguments
+    ///
+    /// * `options`: The chunk size and the number of links per node.
+    /// * `reader`: Where to read the content from.
+    ///
+    /// # Returns
+    ///
+    /// The DAG, or the error of the reader.
+    #[cfg(feature = "std")]
+    pub fn read_from(
+        options: &DagOptions,
+        mut reader: impl std::io::Read,
+    ) -> std::io::Result<Self> {
+        let mut dag = MerkleDag {
+            blocks: Vec::new(),
+            size: 0,
+        };
+        let mut leaves = Vec::new();
+        let mut chunk = vec![0; options.chunk_size.max(1)];
+        loop {
+            let filled = crate::chunked::read_chunk(&mut reader, &mut chunk)?;
+            if filled > 0 || leaves.is_empty() {
+                leaves.push(dag.push_leaf(&chunk[..filled]));
+            }
+            if filled < chunk.len() {
+                break;
+            }
+        }
+        dag.link(options, leaves);
+        Ok(dag)
+    }
+
+    /// Returns the identifier of the root block, which identifies the whole content.
+    pub fn root_cid(&self) -> Cid {
+        self.blocks[self.blocks.len() - 1].cid
+    }
+
+    /// Returns the blocks of the DAG, leaves first and the root last.
+    pub fn blocks(&self) -> &[DagBlock] {
+        &self.blocks
+    }
+
+    /// Returns the size of the content in bytes.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Stores a chunk as a raw leaf block.
+    fn push_leaf(&mut self, chunk: &[u8]) -> DagLink {
+        let cid = Cid::of(RAW_CODEC, chunk);
+        self.blocks.push(DagBlock {
+            cid,
+            data: chunk.to_vec(),
+        });
+        self.size += chunk.len() as u64;
+        DagLink {
+            cid,
+            file_size: chunk.len() as u64,
+            total_size: chunk.len() as u64,
+        }
+    }
+
+    /// Links the nodes of a level by branch nodes until a single root is left.
+    fn link(&mut self, options: &DagOptions, mut level: Vec<DagLink>) {
+        while level.len() > 1 {
+            level = level
+                .chunks(options.max_links.max(2))
+                .map(|children| self.push_branch(children))
+                .collect();
+        }
+    }
+
+    /// Stores a UnixFS file node linking the given children as a DAG-PB block.
+    fn push_branch(&mut self, children: &[DagLink]) -> DagLink {
+        let file_size = children.iter().map(|child| child.file_size).sum();
+        let mut unixfs = Vec::new();
+        put_uint(&mut unixfs, 1, UNIXFS_FILE);
+        put_uint(&mut unixfs, 3, file_size);
+        for child in children {
+            put_uint(&mut unixfs, 4, child.file_size);
+        }
+
+        // DAG-PB serializes the links before the data, against the field order.
+        let mut data = Vec::new();
+        for child in children {
+            let mut link = Vec::new();
+            put_message(&mut link, 1, &child.cid.to_bytes());
+            put_message(&mut link, 2, &[]);
+            put_uint(&mut link, 3, child.total_size);
+            put_message(&mut data, 2, &link);
+        }
+        put_message(&mut data, 1, &unixfs);
+
+        let cid = Cid::of(DAG_PB_CODEC, &data);
+        let total_size =
+            data.len() as u64 + children.iter().map(|child| child.total_size).sum::<u64>();
+        self.blocks.push(DagBlock { cid, data });
+        DagLink {
+            cid,
+            file_size,
+            total_size,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    #[test]
+    fn it_can_build_a_merkle_dag() {
+        let dag = MerkleDag::build(b"");
+        assert_eq!(
+            dag.root_cid().to_string(),
+            "bafkreihdwdcefgh4dqkjv67uzcmw7ojee6xedzdetojuzjevtenxquvyku"
+        );
+        assert_eq!(dag.blocks().len(), 1);
+
+        let data: Vec<u8> = (0..1000u32).map(|i| (i * 7) as u8).collect();
+        let options = DagOptions {
+            chunk_size: 100,
+            max_links: 3,
+        };
+        let dag = MerkleDag::build_with_options(&options, &data);
+        assert_eq!(dag.size(), 1000);
+        // 10 chunks are linked by 4, then 2 and then 1 branch nodes.
+        assert_eq!(dag.blocks().len(), 17);
+        for block in dag.blocks() {
+            assert_eq!(block.cid, Cid::of(block.cid.codec, &block.data));
+        }
+        let root = dag.root_cid();
+        assert_eq!(root.codec, DAG_PB_CODEC);
+        assert!(root.to_string().starts_with("bafybei"));
+        assert_eq!(dag.blocks()[0].cid.codec, RAW_CODEC);
+        assert_eq!(dag.blocks()[0].data, data[..100]);
+
+        #[cfg(feature = "std")]
+        assert_eq!(
+            MerkleDag::read_from(&options, &data[..]).unwrap().blocks(),
+            dag.blocks()
+        );
+        assert_ne!(
+            MerkleDag::build_with_options(&options, &data[1..]).root_cid(),
+            root
+        );
+
+        // A single chunk is its own root.
+        let dag = MerkleDag::build(&data);
+        assert_eq!(dag.root_cid(), Cid::of(RAW_CODEC, &data));
+    }
+}
@@ -0,0 +1,172 @@
+use sha2::Digest;
+use std::collections::HashSet;
+use std::fmt;
+
+use crate::{MerkleTree, MerkleTreeData, MerkleTreeKey, NodeStore, TreeOptions};
+
+/// Selects how leaves that share a key are handled when building a tree with
+/// `MerkleTree::build_checked`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DuplicatePolicy {
+    /// The build fails with a `DuplicateKeyError` listing the shared keys.
+    #[default]
+    Reject,
+    /// Only the first leaf with each key is committed to.
+    KeepFirst,
+    /// Only the last leaf with each key is committed to, in its own position.
+    KeepLast,
+}
+
+/// The error returned when leaves share a key under `DuplicatePolicy::Reject`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DuplicateKeyError<K> {
+    /// The keys that appear more than once, in the order of their first repetition.
+    pub keys: Vec<K>,
+}
+
+impl<K: fmt::Debug> fmt::Display for DuplicateKeyError<K> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "duplicate leaf keys: {:?}", self.keys)
+    }
+}
+
+impl<K: fmt::Debug> std::error::Error for DuplicateKeyError<K> {}
+
+impl<T, D, S> MerkleTree<T, D, S>
+where
+    T: Clone + fmt::Debug + MerkleTreeData + MerkleTreeKey + Default,
+    D: Digest,
+    S: NodeStore<T, D>,
+{
+    /// Builds a Merkle Tree from the given user data, handling leaves that share a key
+    /// according to the policy.
+    ///
+    /// # Arguments
+    ///
+    /// * `tag_leaf`: The tag used for hashing leaf nodes.
+    /// * `tag_branch`: The tag used for hashing branch nodes.
+    /// * `input`: The user data to commit to, in leaf order.
+    /// * `policy`: How leaves with the same key are handled.
+    ///
+    /// # Returns
+    ///
+    /// The tree, or a `DuplicateKeyError` listing the shared keys if the policy rejects them.
+    pub fn build_checked(
+        tag_leaf: &str,
+        tag_branch: &str,
+        input: &[T],
+        policy: DuplicatePolicy,
+    ) -> Result<Self, DuplicateKeyError<T::Key>>
+    where
+        S: Default,
+    {
+        Self::build_checked_with_options(TreeOptions::tagged(tag_leaf, tag_branch), input, policy)
+    }
+
+    /// Builds a Merkle Tree from the given user data with the given hashing configuration,
+    /// handling leaves that share a key according to the policy.
+    ///
+    /// # Arguments
+    ///
+    /// * `options`: The hashing configuration of the tree.
+    /// * `input`: The user data to commit to, in leaf order.
+    /// * `policy`: How leaves with the same key are handled.
+    ///
+    /// # Returns
+    ///
+    /// The tree, or a `DuplicateKeyError` listing the shared keys if the policy rejects them.
+    pub fn build_checked_with_options(
+        options: TreeOptions,
+        input: &[T],
+        policy: DuplicatePolicy,
+    ) -> Result<Self, DuplicateKeyError<T::Key>>
+    where
+        S: Default,
+    {
+        let mut seen = HashSet::with_capacity(input.len());
+
+        match policy {
+            DuplicatePolicy::Reject => {
+                let mut reported = HashSet::new();
+                let mut keys = Vec::new();
+                for item in input {
+                    let key = item.key();
+                    if seen.contains(&key) {
+                        if reported.insert(item.key()) {
+                            keys.push(key);
+                        }
+                    } else {
+                        seen.insert(key);
+                    }
+                }
+
+                if !keys.is_empty() {
+                    return Err(DuplicateKeyError { keys });
+                }
+                Ok(Self::build_with_options(options, input))
+            }
+            DuplicatePolicy::KeepFirst => {
+                let leaves = input.iter().filter(|item| seen.insert(item.key())).cloned();
+                Ok(Self::build_from_iter_with_options(options, leaves))
+            }
+            DuplicatePolicy::KeepLast => {
+                let mut leaves: Vec<T> = input
+                    .iter()
+                    .rev()
+                    .filter(|item| seen.insert(item.key()))
+                    .cloned()
+                    .collect();
+                leaves.reverse();
+                Ok(Self::build_from_iter_with_options(options, leaves))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(DuplicatePolicy::KeepFirst, &[(1, 1000), (2, 2000), (3, 3000), (4, 4000)])]
+    #[case(DuplicatePolicy::KeepLast, &[(1, 1000), (2, 2001), (4, 4000), (3, 3001)])]
+    fn it_can_deduplicate_keys(#[case] policy: DuplicatePolicy, #[case] leaves: &[(u32, u32)]) {
+        let mut user_data = util::generate_random_user_data(4);
+        user_data.insert(
+            2,
+            util::UserData {
+                id: 2,
+                balance: 2001,
+            },
+        );
+        user_data.push(util::UserData {
+            id: 3,
+            balance: 3001,
+        });
+
+        let rejected: Result<MerkleTree<_>, _> = MerkleTree::build_checked(
+            "ProofOfReserve_Leaf",
+            "ProofOfReserve_Branch",
+            &user_data,
+            DuplicatePolicy::Reject,
+        );
+        assert_eq!(rejected.err().map(|error| error.keys), Some(vec![2, 3]));
+
+        let tree: MerkleTree<_> = MerkleTree::build_checked(
+            "ProofOfReserve_Leaf",
+            "ProofOfReserve_Branch",
+            &user_data,
+            policy,
+        )
+        .unwrap();
+        let expected: Vec<_> = leaves
+            .iter()
+            .map(|&(id, balance)| util::UserData { id, balance })
+            .collect();
+        let expected: MerkleTree<_> =
+            MerkleTree::build("ProofOfReserve_Leaf", "ProofOfReserve_Branch", &expected);
+        assert_eq!(tree.root(), expected.root());
+    }
+}
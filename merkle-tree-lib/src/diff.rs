@@ -0,0 +1,101 @@
+use alloc::vec::Vec;
+use sha2::Digest;
+
+use crate::{MerkleTree, NodeStore};
+
+impl<T, D: Digest, S: NodeStore<T, D>> MerkleTree<T, D, S> {
+    /// Returns the positions of the leaves that differ between this tree and another one, such
+    /// as the accounts that changed between two attestation snapshots.
+    ///
+    /// Both trees are walked top-down in step, and subtrees with the same hash over the same
+    /// leaves are skipped, so only the paths to the changed leaves are visited. Leaves that
+    /// only exist in one of the trees are reported as well. The trees are expected to be built
+    /// with the same `TreeOptions`.
+    ///
+    /// # Arguments
+    ///
+    /// * `other`: The tree to compare against.
+    ///
+    /// # Returns
+    ///
+    /// The positions of the differing leaves, in ascending order.
+    pub fn diff(&self, other: &Self) -> Vec<usize> {
+        let self_sizes = self.level_sizes();
+        let other_sizes = other.level_sizes();
+        let size_at = |level: usize| {
+            let size = |sizes: &[usize]| sizes.get(level).copied().unwrap_or(0);
+            size(&self_sizes).max(size(&other_sizes))
+        };
+
+        // Start from the highest level both trees have, where a node of each tree covers the
+        // same range of leaves.
+        let top = self_sizes.len().min(other_sizes.len()) - 1;
+        let mut stack: Vec<(usize, usize)> = (0..size_at(top)).rev().map(|i| (top, i)).collect();
+        let mut leaves = Vec::new();
+
+        while let Some((level, position)) = stack.pop() {
+            let end = (position + 1) << level;
+            let same_leaves = self.leaf_count.min(end) == other.leaf_count.min(end);
+            let same_hash = match (
+                self.node_at(level, position),
+                other.node_at(level, position),
+            ) {
+                (Some(a), Some(b)) => self.nodes[a].hash == other.nodes[b].hash,
+                _ => false,
+            };
+            if same_leaves && same_hash {
+                continue;
+            }
+
+            if level == 0 {
+                leaves.push(position);
+                continue;
+            }
+
+            for child in [position << 1 | 1, position << 1] {
+                if child < size_at(level - 1) {
+                    stack.push((level - 1, child));
+                }
+            }
+        }
+
+        leaves
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{util, Padding, TreeOptions};
+    use alloc::vec;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(Padding::DuplicateLast)]
+    #[case(Padding::Promote)]
+    #[case(Padding::PadWithEmpty)]
+    fn it_can_diff_trees(#[case] padding: Padding) {
+        let options = TreeOptions::tagged("ProofOfReserve_Leaf", "ProofOfReserve_Branch")
+            .with_padding(padding);
+        let build = |user_data: &[util::UserData]| -> MerkleTree<_> {
+            MerkleTree::build_with_options(options.clone(), user_data)
+        };
+        let before = util::generate_random_user_data(7);
+        let tree = build(&before);
+
+        assert_eq!(tree.diff(&build(&before)), Vec::<usize>::new());
+
+        let mut after = before.clone();
+        after[1].balance += 1;
+        after[5].balance -= 1;
+        assert_eq!(tree.diff(&build(&after)), vec![1, 5]);
+
+        // Appending a leaf equal to the last one must not be hidden by duplicated padding.
+        after.push(after[6].clone());
+        assert_eq!(tree.diff(&build(&after)), vec![1, 5, 7]);
+        assert_eq!(build(&after).diff(&tree), vec![1, 5, 7]);
+
+        assert_eq!(tree.diff(&build(&before[..3])), vec![3, 4, 5, 6]);
+        assert_eq!(tree.diff(&build(&[])), (0..7).collect::<Vec<_>>());
+    }
+}
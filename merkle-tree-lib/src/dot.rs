@@ -0,0 +1,112 @@
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt::Write;
+use sha2::Digest;
+
+use crate::{truncate_middle, MerkleTree, MerkleTreeData, NodeStore};
+
+/// Controls how `MerkleTree::to_dot_with_options` renders the nodes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DotOptions {
+    /// Shortens the hex encoded hashes to this many characters, or shows them in full if
+    /// `None`.
+    pub hash_length: Option<usize>,
+    /// Adds `MerkleTreeData::dot_node_label` of the user data below the hash of each leaf.
+    pub leaf_labels: bool,
+}
+
+impl Default for DotOptions {
+    fn default() -> Self {
+        DotOptions {
+            hash_length: Some(10),
+            leaf_labels: true,
+        }
+    }
+}
+
+impl<T, D, S> MerkleTree<T, D, S>
+where
+    T: MerkleTreeData,
+    D: Digest,
+    S: NodeStore<T, D>,
+{
+    /// Renders the Merkle Tree as a Graphviz DOT graph with truncated hashes and leaf labels.
+    /// Render it with `dot -Tsvg` or any other Graphviz tool.
+    pub fn to_dot(&self) -> String {
+        self.to_dot_with_options(&DotOptions::default())
+    }
+
+    /// Renders the Merkle Tree as a Graphviz DOT graph.
+    ///
+    /// Each node is declared once, so a node duplicated by `Padding::DuplicateLast` has two
+    /// edges from its parent.
+    ///
+    /// # Arguments
+    ///
+    /// * `options`: How the nodes are labeled.
+    pub fn to_dot_with_options(&self, options: &DotOptions) -> String {
+        let mut output = String::from("digraph MerkleTree {\n    node [shape=box];\n");
+        let mut declared = vec![false; self.nodes.len()];
+        let mut stack: Vec<usize> = self.root.into_iter().collect();
+
+        while let Some(node) = stack.pop() {
+            if core::mem::replace(&mut declared[node], true) {
+                continue;
+            }
+
+            let hash = hex::encode(&self.nodes[node].hash);
+            let mut label = match options.hash_length {
+                Some(length) => truncate_middle(&hash, length),
+                None => hash,
+            };
+            if let Some(data) = self.nodes[node].user_data.as_ref() {
+                if options.leaf_labels {
+                    label.push_str("\\n");
+                    label.push_str(&data.dot_node_label().replace('"', "\\\""));
+                }
+            }
+            let _ = writeln!(output, "    n{node} [label=\"{label}\"];");
+
+            if let (Some(left), Some(right)) = (self.nodes[node].left, self.nodes[node].right) {
+                let _ = writeln!(output, "    n{node} -> n{left};");
+                let _ = writeln!(output, "    n{node} -> n{right};");
+                stack.push(right);
+                stack.push(left);
+            }
+        }
+
+        output.push_str("}\n");
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util;
+    use alloc::format;
+
+    #[test]
+    fn it_can_export_dot() {
+        let user_data = util::generate_random_user_data(3);
+        let tree: MerkleTree<_> =
+            MerkleTree::build("ProofOfReserve_Leaf", "ProofOfReserve_Branch", &user_data);
+
+        let dot = tree.to_dot();
+        assert!(dot.starts_with("digraph MerkleTree {\n"));
+        assert!(dot.ends_with("}\n"));
+        // 6 nodes including the duplicated leaf, which is declared once with two edges.
+        assert_eq!(dot.matches("[label=").count(), 6);
+        assert_eq!(dot.matches(" -> ").count(), 6);
+        assert!(dot.contains("\\nUser ID: 3\\nBalance: 3000\"];"));
+
+        let root = tree.root().unwrap();
+        let plain = tree.to_dot_with_options(&DotOptions {
+            hash_length: None,
+            leaf_labels: false,
+        });
+        assert!(plain.contains(&format!("[label=\"{root}\"];")));
+        assert!(!plain.contains("User ID"));
+    }
+}
@@ -0,0 +1,137 @@
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use sha2::Digest;
+
+use crate::{MerkleSumData, MerkleTreeData, MerkleTreeKey};
+
+/// User data wrapped with the schema version of its encoding and the time of the snapshot it
+/// was attested in, both of which are hashed into its leaf.
+///
+/// The leaf is encoded as the schema version as a big-endian `u32`, the timestamp as a
+/// big-endian `u64` and then the user data. Since the header has a fixed size, a proof verifies
+/// only with the exact version and timestamp the tree was built with, so a proof of one
+/// attestation cannot be passed off as one of another time or format revision.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LeafEnvelope<T> {
+    /// The revision of the format of the user data.
+    pub schema_version: u32,
+    /// The time of the snapshot, in seconds since the Unix epoch.
+    pub timestamp: u64,
+    pub data: T,
+}
+
+impl<T> LeafEnvelope<T> {
+    /// Wraps user data in an envelope.
+    ///
+    /// # Arguments
+    ///
+    /// * `schema_version`: The revision of the format of the user data.
+    /// * `timestamp`: The time of the snapshot, in seconds since the Unix epoch.
+    /// * `data`: The user data.
+    pub fn new(schema_version: u32, timestamp: u64, data: T) -> Self {
+        LeafEnvelope {
+            schema_version,
+            timestamp,
+            data,
+        }
+    }
+
+    /// Returns the header hashed before the user data.
+    fn header(&self) -> [u8; 12] {
+        let mut header = [0; 12];
+        header[..4].copy_from_slice(&self.schema_version.to_be_bytes());
+        header[4..].copy_from_slice(&self.timestamp.to_be_bytes());
+        header
+    }
+}
+
+impl<T: MerkleTreeData> MerkleTreeData for LeafEnvelope<T> {
+    fn serialize(&self) -> Vec<u8> {
+        let mut bytes = self.header().to_vec();
+        bytes.extend_from_slice(&self.data.serialize());
+        bytes
+    }
+
+    fn hash_into(&self, hasher: &mut impl Digest) {
+        hasher.update(self.header());
+        self.data.hash_into(hasher);
+    }
+
+    fn mermaid_node_label(&self) -> String {
+        format!(
+            "{}<br>Schema: {}<br>Timestamp: {}",
+            self.data.mermaid_node_label(),
+            self.schema_version,
+            self.timestamp
+        )
+    }
+}
+
+impl<B, T: MerkleSumData<B>> MerkleSumData<B> for LeafEnvelope<T> {
+    fn balance(&self) -> B {
+        self.data.balance()
+    }
+}
+
+impl<T: MerkleTreeKey> MerkleTreeKey for LeafEnvelope<T> {
+    type Key = T::Key;
+
+    fn key(&self) -> Self::Key {
+        self.data.key()
+    }
+}
+
+/// Wraps every leaf of a snapshot in an envelope with the same schema version and timestamp.
+///
+/// # Arguments
+///
+/// * `input`: The user data of the snapshot.
+/// * `schema_version`: The revision of the format of the user data.
+/// * `timestamp`: The time of the snapshot, in seconds since the Unix epoch.
+pub fn envelop<T: Clone>(input: &[T], schema_version: u32, timestamp: u64) -> Vec<LeafEnvelope<T>> {
+    input
+        .iter()
+        .map(|data| LeafEnvelope::new(schema_version, timestamp, data.clone()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{util, verify_proof, MerkleTree};
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(1, 1_700_000_001)]
+    #[case(2, 1_700_000_000)]
+    fn it_can_bind_proofs_to_the_envelope(#[case] schema_version: u32, #[case] timestamp: u64) {
+        let user_data = util::generate_random_user_data(5);
+        let snapshot = envelop(&user_data, 1, 1_700_000_000);
+        let tree: MerkleTree<_> =
+            MerkleTree::build("ProofOfReserve_Leaf", "ProofOfReserve_Branch", &snapshot);
+        let root = tree.root().unwrap();
+
+        let (node, path) = tree.proof_by_index(3).unwrap();
+        let leaf = node.user_data.as_ref().unwrap();
+        assert_eq!((leaf.schema_version, leaf.timestamp), (1, 1_700_000_000));
+        assert_eq!(&leaf.serialize()[..12], b"\0\0\0\x01\0\0\0\0\x65\x53\xf1\0");
+        assert!(verify_proof(
+            "ProofOfReserve_Leaf",
+            "ProofOfReserve_Branch",
+            &root,
+            leaf,
+            &path
+        ));
+
+        let other = LeafEnvelope::new(schema_version, timestamp, leaf.data.clone());
+        assert!(!verify_proof(
+            "ProofOfReserve_Leaf",
+            "ProofOfReserve_Branch",
+            &root,
+            &other,
+            &path
+        ));
+    }
+}
@@ -0,0 +1,52 @@
+use core::fmt;
+
+/// The errors returned by the operations of a `MerkleTree` and the trees built on it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MerkleError {
+    /// The tree has no leaves.
+    EmptyTree,
+    /// No leaf exists at the given index or with the given key.
+    LeafNotFound,
+    /// A leaf with the same key is already in the tree.
+    DuplicateKey,
+    /// The proof is malformed or does not fit the tree it is checked against.
+    InvalidProof,
+    /// The given range of leaves or tree sizes is empty or out of bounds.
+    InvalidRange,
+    /// The operation requires a different `Padding` than the tree was built with.
+    UnsupportedPadding,
+    /// The operation requires a different `HashMode` than the tree was built with.
+    UnsupportedHashMode,
+    /// The digest is not supported by the operation, such as one producing hashes of another
+    /// size.
+    UnsupportedDigest,
+    /// The version of the tree was discarded when the tree was compacted.
+    StaleVersion,
+    /// A sum of balances does not fit the balance type.
+    Overflow,
+    /// The node store does not fit the tree, such as a populated store given for a new tree
+    /// or metadata pointing outside the store.
+    InvalidStore,
+}
+
+impl fmt::Display for MerkleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = match self {
+            MerkleError::EmptyTree => "the tree is empty",
+            MerkleError::LeafNotFound => "the leaf was not found",
+            MerkleError::DuplicateKey => "a leaf with the same key already exists",
+            MerkleError::InvalidProof => "the proof is invalid",
+            MerkleError::InvalidRange => "the range is out of bounds",
+            MerkleError::UnsupportedPadding => "the padding of the tree is not supported",
+            MerkleError::UnsupportedHashMode => "the hash mode of the tree is not supported",
+            MerkleError::UnsupportedDigest => "the digest is not supported",
+            MerkleError::StaleVersion => "the version of the tree was discarded",
+            MerkleError::Overflow => "the sum of the balances overflows",
+            MerkleError::InvalidStore => "the node store does not fit the tree",
+        };
+        f.write_str(message)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for MerkleError {}
@@ -0,0 +1,325 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use sha2::digest::Output;
+use sha2::Digest;
+
+use crate::multiproof::decode_hash;
+use crate::{
+    verify_consistency_proof_with_options, verify_inclusion_proof_with_options, MerkleError,
+    MerkleNode, MerkleTree, Padding, Sha256, TreeOptions, TreeVersion,
+};
+
+/// An append-only log of published Merkle roots, giving a tamper-evident timeline of
+/// attestations.
+///
+/// The published roots are the leaves of a Merkle Tree that promotes unpaired nodes, as a
+/// Certificate Transparency log does. The root of that tree after version `n` was published
+/// is the commitment to the whole history up to `n`. An `AncestryProof` shows that root `n`
+/// was published before root `m` in the history committed to at version `m`, and that the
+/// history committed to at version `n` was not rewritten since.
+pub struct HistoryTree<D: Digest = Sha256> {
+    log: MerkleTree<(), D>,
+    roots: Vec<Output<D>>,
+    versions: Vec<TreeVersion>,
+}
+
+/// A proof that a published root is an ancestor state of a later one, returned by
+/// `HistoryTree::ancestry_proof`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AncestryProof {
+    /// The version of the older root.
+    pub ancestor: usize,
+    /// The version of the newer root.
+    pub version: usize,
+    /// The hex encoded audit path of the older root in the history at `version`.
+    pub ancestor_path: Vec<String>,
+    /// The hex encoded audit path of the newer root in the history at `version`.
+    pub version_path: Vec<String>,
+    /// The hex encoded consistency proof from the history at `ancestor` to the history at
+    /// `version`.
+    pub consistency: Vec<String>,
+}
+
+impl<D: Digest> HistoryTree<D> {
+    /// Creates an empty history.
+    ///
+    /// # Arguments
+    ///
+    /// * `options`: The hashing configuration of the history. The padding policy is replaced
+    ///   by `Padding::Promote`, which consistency proofs require.
+    pub fn new(options: TreeOptions) -> Self {
+        HistoryTree {
+            log: MerkleTree::from_leaf_nodes(options.with_padding(Padding::Promote), Vec::new()),
+            roots: Vec::new(),
+            versions: Vec::new(),
+        }
+    }
+
+    /// Returns the hashing configuration of the history.
+    pub fn options(&self) -> &TreeOptions {
+        self.log.options()
+    }
+
+    /// Returns the number of roots published so far.
+    pub fn len(&self) -> usize {
+        self.roots.len()
+    }
+
+    /// Returns `true` if no root has been published.
+    pub fn is_empty(&self) -> bool {
+        self.roots.is_empty()
+    }
+
+    /// Appends a root to the history, such as `MerkleTree::root_hash` of a new attestation.
+    ///
+    /// # Arguments
+    ///
+    /// * `root`: The root to publish.
+    ///
+    /// # Returns
+    ///
+    /// The version of the published root.
+    pub fn publish(&mut self, root: Output<D>) -> usize {
+        let hash = self.log.options().hash_leaf::<D>(&root);
+        self.log.push_leaf_node(MerkleNode::new_leaf(hash, None));
+        self.roots.push(root);
+        self.versions.push(self.log.version());
+        self.roots.len() - 1
+    }
+
+    /// Returns the hex encoded root published at the given version.
+    ///
+    /// # Arguments
+    ///
+    /// * `version`: The version of the root.
+    ///
+    /// # Returns
+    ///
+    /// The root, or `MerkleError::LeafNotFound` if the version has not been published.
+    pub fn root(&self, version: usize) -> Result<String, MerkleError> {
+        self.roots
+            .get(version)
+            .map(hex::encode)
+            .ok_or(MerkleError::LeafNotFound)
+    }
+
+    /// Returns the hex encoded commitment to the history up to and including the given
+    /// version.
+    ///
+    /// # Arguments
+    ///
+    /// * `version`: The version of the latest root in the history.
+    ///
+    /// # Returns
+    ///
+    /// The commitment, or `MerkleError::LeafNotFound` if the version has not been published.
+    pub fn commitment(&self, version: usize) -> Result<String, MerkleError> {
+        let tree_version = self
+            .versions
+            .get(version)
+            .ok_or(MerkleError::LeafNotFound)?;
+        self.log.root_at(*tree_version)
+    }
+
+    /// Generates a proof that the root published at `ancestor` is an ancestor state of the
+    /// root published at `version`.
+    ///
+    /// # Arguments
+    ///
+    /// * `ancestor`: The version of the older root.
+    /// * `version`: The version of the newer root, at least `ancestor`.
+    ///
+    /// # Returns
+    ///
+    /// The proof, `MerkleError::LeafNotFound` if a version has not been published or
+    /// `MerkleError::InvalidRange` if `ancestor` is newer than `version`.
+    pub fn ancestry_proof(
+        &self,
+        ancestor: usize,
+        version: usize,
+    ) -> Result<AncestryProof, MerkleError> {
+        let tree_version = *self
+            .versions
+            .get(version)
+            .ok_or(MerkleError::LeafNotFound)?;
+        if ancestor > version {
+            return Err(MerkleError::InvalidRange);
+        }
+
+        let audit_path = |index| -> Result<Vec<String>, MerkleError> {
+            let (_, path) = self.log.proof_by_index_at(tree_version, index)?;
+            let mut siblings = path.siblings;
+            siblings.reverse();
+            Ok(siblings)
+        };
+
+        Ok(AncestryProof {
+            ancestor,
+            version,
+            ancestor_path: audit_path(ancestor)?,
+            version_path: audit_path(version)?,
+            consistency: self.log.consistency_proof(ancestor + 1, version + 1)?,
+        })
+    }
+}
+
+/// Verifies that a root is an ancestor state of a later root.
+///
+/// # Arguments
+///
+/// * `tag_leaf`: The tag the history was hashed with for leaf nodes.
+/// * `tag_branch`: The tag the history was hashed with for branch nodes.
+/// * `ancestor_root`: The hex encoded older root.
+/// * `root`: The hex encoded newer root.
+/// * `ancestor_commitment`: The hex encoded commitment to the history at the older root.
+/// * `commitment`: The hex encoded commitment to the history at the newer root.
+/// * `proof`: The proof returned by `HistoryTree::ancestry_proof`.
+///
+/// # Returns
+///
+/// `true` if both roots are in the history at their versions and the older history is a
+/// prefix of the newer one, `false` otherwise.
+pub fn verify_ancestry_proof<D: Digest>(
+    tag_leaf: &str,
+    tag_branch: &str,
+    ancestor_root: &str,
+    root: &str,
+    ancestor_commitment: &str,
+    commitment: &str,
+    proof: &AncestryProof,
+) -> bool {
+    verify_ancestry_proof_with_options::<D>(
+        &TreeOptions::tagged(tag_leaf, tag_branch),
+        ancestor_root,
+        root,
+        ancestor_commitment,
+        commitment,
+        proof,
+    )
+}
+
+/// Verifies that a root is an ancestor state of a later root, using the hashing
+/// configuration the history was created with.
+///
+/// # Arguments
+///
+/// * `options`: The options the history was created with.
+/// * `ancestor_root`: The hex encoded older root.
+/// * `root`: The hex encoded newer root.
+/// * `ancestor_commitment`: The hex encoded commitment to the history at the older root.
+/// * `commitment`: The hex encoded commitment to the history at the newer root.
+/// * `proof`: The proof returned by `HistoryTree::ancestry_proof`.
+///
+/// # Returns
+///
+/// `true` if both roots are in the history at their versions and the older history is a
+/// prefix of the newer one, `false` otherwise.
+pub fn verify_ancestry_proof_with_options<D: Digest>(
+    options: &TreeOptions,
+    ancestor_root: &str,
+    root: &str,
+    ancestor_commitment: &str,
+    commitment: &str,
+    proof: &AncestryProof,
+) -> bool {
+    let (Some(ancestor_root), Some(root)) =
+        (decode_hash::<D>(ancestor_root), decode_hash::<D>(root))
+    else {
+        return false;
+    };
+    if proof.ancestor > proof.version {
+        return false;
+    }
+
+    let size = proof.version + 1;
+    let included = |index, leaf: &Output<D>, path: &[String]| {
+        verify_inclusion_proof_with_options::<D>(options, commitment, index, size, leaf, path)
+    };
+
+    included(proof.ancestor, &ancestor_root, &proof.ancestor_path)
+        && included(proof.version, &root, &proof.version_path)
+        && verify_consistency_proof_with_options::<D>(
+            options,
+            ancestor_commitment,
+            commitment,
+            proof.ancestor + 1,
+            size,
+            &proof.consistency,
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util;
+
+    #[test]
+    fn it_can_prove_ancestry_of_published_roots() {
+        let user_data = util::generate_random_user_data(6);
+        let tag_leaf = "ProofOfReserve_History_Leaf";
+        let tag_branch = "ProofOfReserve_History_Branch";
+        let mut history: HistoryTree = HistoryTree::new(TreeOptions::tagged(tag_leaf, tag_branch));
+        assert!(history.is_empty());
+
+        let mut tree: MerkleTree<_> = MerkleTree::build(
+            "ProofOfReserve_Leaf",
+            "ProofOfReserve_Branch",
+            &user_data[..1],
+        );
+        for (epoch, item) in user_data.iter().enumerate().skip(1) {
+            assert_eq!(history.publish(tree.root_hash().unwrap()), epoch - 1);
+            tree.push(item.clone());
+        }
+        history.publish(tree.root_hash().unwrap());
+        assert_eq!(history.len(), 6);
+        assert_eq!(history.root(5), tree.root());
+
+        for version in 0..6 {
+            for ancestor in 0..=version {
+                let proof = history.ancestry_proof(ancestor, version).unwrap();
+                assert!(verify_ancestry_proof::<Sha256>(
+                    tag_leaf,
+                    tag_branch,
+                    &history.root(ancestor).unwrap(),
+                    &history.root(version).unwrap(),
+                    &history.commitment(ancestor).unwrap(),
+                    &history.commitment(version).unwrap(),
+                    &proof
+                ));
+            }
+        }
+
+        let proof = history.ancestry_proof(1, 4).unwrap();
+        let verify = |ancestor_root: &str, root: &str, ancestor_commitment: &str| {
+            verify_ancestry_proof::<Sha256>(
+                tag_leaf,
+                tag_branch,
+                ancestor_root,
+                root,
+                ancestor_commitment,
+                &history.commitment(4).unwrap(),
+                &proof,
+            )
+        };
+        let ancestor_root = history.root(1).unwrap();
+        let root = history.root(4).unwrap();
+        let ancestor_commitment = history.commitment(1).unwrap();
+        assert!(verify(&ancestor_root, &root, &ancestor_commitment));
+        // Swapping the roots claims that the newer root came first.
+        assert!(!verify(&root, &ancestor_root, &ancestor_commitment));
+        assert!(!verify(
+            &history.root(2).unwrap(),
+            &root,
+            &ancestor_commitment
+        ));
+        assert!(!verify(
+            &ancestor_root,
+            &root,
+            &history.commitment(2).unwrap()
+        ));
+
+        assert_eq!(history.ancestry_proof(4, 1), Err(MerkleError::InvalidRange));
+        assert_eq!(history.ancestry_proof(1, 6), Err(MerkleError::LeafNotFound));
+        assert_eq!(history.commitment(6), Err(MerkleError::LeafNotFound));
+    }
+}
@@ -0,0 +1,701 @@
+use alloc::vec;
+use alloc::vec::Vec;
+use sha2::digest::Output;
+use sha2::{Digest, Sha256, Sha512};
+
+use crate::root::hash_matches;
+use crate::{
+    HashMode, MerkleError, MerkleTree, MerkleTreeData, NodeDirection, NodeStore, TagStyle,
+    TreeOptions, BRANCH_PREFIX, LEAF_PREFIX,
+};
+
+/// The hash operation of an ICS-23 `LeafOp` or `InnerOp`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HashOp {
+    /// The input is used as it is.
+    #[default]
+    NoHash,
+    Sha256,
+    Sha512,
+    Keccak256,
+}
+
+impl HashOp {
+    /// Returns the hash operation computed by the digest.
+    ///
+    /// # Returns
+    ///
+    /// The hash operation, or `None` if ICS-23 has none for the digest.
+    pub fn of<D: Digest>() -> Option<Self> {
+        let empty = D::digest([]);
+        if empty.as_slice() == Sha256::digest([]).as_slice() {
+            return Some(HashOp::Sha256);
+        }
+        if empty.as_slice() == Sha512::digest([]).as_slice() {
+            return Some(HashOp::Sha512);
+        }
+        #[cfg(feature = "keccak")]
+        if empty.as_slice() == crate::Keccak256::digest([]).as_slice() {
+            return Some(HashOp::Keccak256);
+        }
+        None
+    }
+
+    /// Applies the operation with the digest, which must be the one it names.
+    ///
+    /// # Arguments
+    ///
+    /// * `input`: The input byte slice.
+    fn apply<D: Digest>(self, input: Vec<u8>) -> Result<Vec<u8>, MerkleError> {
+        match self {
+            HashOp::NoHash => Ok(input),
+            op if Some(op) == Self::of::<D>() => Ok(D::digest(input).to_vec()),
+            _ => Err(MerkleError::UnsupportedDigest),
+        }
+    }
+
+    fn code(self) -> u64 {
+        match self {
+            HashOp::NoHash => 0,
+            HashOp::Sha256 => 1,
+            HashOp::Sha512 => 2,
+            HashOp::Keccak256 => 3,
+        }
+    }
+
+    fn from_code(code: u64) -> Result<Self, MerkleError> {
+        match code {
+            0 => Ok(HashOp::NoHash),
+            1 => Ok(HashOp::Sha256),
+            2 => Ok(HashOp::Sha512),
+            3 => Ok(HashOp::Keccak256),
+            _ => Err(MerkleError::InvalidProof),
+        }
+    }
+}
+
+/// The length prefix of the key and value of an ICS-23 `LeafOp`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LengthOp {
+    /// The input is not prefixed.
+    #[default]
+    NoPrefix,
+    /// The input is prefixed with its length as a protobuf varint.
+    VarProto,
+}
+
+impl LengthOp {
+    /// Prefixes the input with its length.
+    ///
+    /// # Arguments
+    ///
+    /// * `input`: The input byte slice.
+    fn apply(self, input: Vec<u8>) -> Vec<u8> {
+        match self {
+            LengthOp::NoPrefix => input,
+            LengthOp::VarProto => {
+                let mut output = Vec::with_capacity(input.len() + 2);
+                put_varint(&mut output, input.len() as u64);
+                output.extend(input);
+                output
+            }
+        }
+    }
+
+    fn code(self) -> u64 {
+        match self {
+            LengthOp::NoPrefix => 0,
+            LengthOp::VarProto => 1,
+        }
+    }
+
+    fn from_code(code: u64) -> Result<Self, MerkleError> {
+        match code {
+            0 => Ok(LengthOp::NoPrefix),
+            1 => Ok(LengthOp::VarProto),
+            _ => Err(MerkleError::InvalidProof),
+        }
+    }
+}
+
+/// The ICS-23 `LeafOp`, hashing a leaf as `hash(prefix || length(prehash_key(key)) ||
+/// length(prehash_value(value)))`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct LeafOp {
+    pub hash: HashOp,
+    pub prehash_key: HashOp,
+    pub prehash_value: HashOp,
+    pub length: LengthOp,
+    pub prefix: Vec<u8>,
+}
+
+impl LeafOp {
+    fn apply<D: Digest>(&self, key: &[u8], value: &[u8]) -> Result<Vec<u8>, MerkleError> {
+        if key.is_empty() || value.is_empty() {
+            return Err(MerkleError::InvalidProof);
+        }
+
+        let mut input = self.prefix.clone();
+        input.extend(
+            self.length
+                .apply(self.prehash_key.apply::<D>(key.to_vec())?),
+        );
+        input.extend(
+            self.length
+                .apply(self.prehash_value.apply::<D>(value.to_vec())?),
+        );
+        self.hash.apply::<D>(input)
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        put_uint(&mut bytes, 1, self.hash.code());
+        put_uint(&mut bytes, 2, self.prehash_key.code());
+        put_uint(&mut bytes, 3, self.prehash_value.code());
+        put_uint(&mut bytes, 4, self.length.code());
+        put_bytes(&mut bytes, 5, &self.prefix);
+        bytes
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Self, MerkleError> {
+        let mut op = LeafOp::default();
+        for (field, value) in fields(bytes)? {
+            match (field, value) {
+                (1, Field::Varint(code)) => op.hash = HashOp::from_code(code)?,
+                (2, Field::Varint(code)) => op.prehash_key = HashOp::from_code(code)?,
+                (3, Field::Varint(code)) => op.prehash_value = HashOp::from_code(code)?,
+                (4, Field::Varint(code)) => op.length = LengthOp::from_code(code)?,
+                (5, Field::Bytes(prefix)) => op.prefix = prefix.to_vec(),
+                _ => {}
+            }
+        }
+        Ok(op)
+    }
+}
+
+/// The ICS-23 `InnerOp`, hashing a branch as `hash(prefix || child || suffix)`, where the
+/// sibling hashes are part of the prefix or the suffix.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct InnerOp {
+    pub hash: HashOp,
+    pub prefix: Vec<u8>,
+    pub suffix: Vec<u8>,
+}
+
+impl InnerOp {
+    fn apply<D: Digest>(&self, child: Vec<u8>) -> Result<Vec<u8>, MerkleError> {
+        let mut input = self.prefix.clone();
+        input.extend(child);
+        input.extend_from_slice(&self.suffix);
+        self.hash.apply::<D>(input)
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        put_uint(&mut bytes, 1, self.hash.code());
+        put_bytes(&mut bytes, 2, &self.prefix);
+        put_bytes(&mut bytes, 3, &self.suffix);
+        bytes
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Self, MerkleError> {
+        let mut op = InnerOp::default();
+        for (field, value) in fields(bytes)? {
+            match (field, value) {
+                (1, Field::Varint(code)) => op.hash = HashOp::from_code(code)?,
+                (2, Field::Bytes(prefix)) => op.prefix = prefix.to_vec(),
+                (3, Field::Bytes(suffix)) => op.suffix = suffix.to_vec(),
+                _ => {}
+            }
+        }
+        Ok(op)
+    }
+}
+
+/// An ICS-23 `ExistenceProof`, proving that a key and value are a leaf of a tree, as consumed
+/// by Cosmos SDK light clients.
+///
+/// The leaf data is split into a key and a value, which are concatenated again by `leaf`.
+/// The `path` goes from the leaf up to the root.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ExistenceProof {
+    pub key: Vec<u8>,
+    pub value: Vec<u8>,
+    pub leaf: LeafOp,
+    pub path: Vec<InnerOp>,
+}
+
+impl ExistenceProof {
+    /// Serializes the proof as the protobuf message `cosmos.ics23.v1.ExistenceProof`.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        put_bytes(&mut bytes, 1, &self.key);
+        put_bytes(&mut bytes, 2, &self.value);
+        put_message(&mut bytes, 3, &self.leaf.encode());
+        for op in &self.path {
+            put_message(&mut bytes, 4, &op.encode());
+        }
+        bytes
+    }
+
+    /// Parses a proof serialized by `to_bytes` or by another ICS-23 implementation.
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes`: The protobuf message.
+    ///
+    /// # Returns
+    ///
+    /// The proof, or `MerkleError::InvalidProof` if the message is malformed or uses
+    /// operations this crate does not implement.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, MerkleError> {
+        let mut proof = ExistenceProof::default();
+        for (field, value) in fields(bytes)? {
+            match (field, value) {
+                (1, Field::Bytes(key)) => proof.key = key.to_vec(),
+                (2, Field::Bytes(value)) => proof.value = value.to_vec(),
+                (3, Field::Bytes(leaf)) => proof.leaf = LeafOp::decode(leaf)?,
+                (4, Field::Bytes(op)) => proof.path.push(InnerOp::decode(op)?),
+                _ => {}
+            }
+        }
+        Ok(proof)
+    }
+
+    /// Computes the root committed to by the proof.
+    ///
+    /// # Returns
+    ///
+    /// The root hash, `MerkleError::InvalidProof` if the key or value is empty, or
+    /// `MerkleError::UnsupportedDigest` if an operation hashes with another digest than `D`.
+    pub fn calculate<D: Digest>(&self) -> Result<Output<D>, MerkleError> {
+        let mut hash = self.leaf.apply::<D>(&self.key, &self.value)?;
+        for op in &self.path {
+            hash = op.apply::<D>(hash)?;
+        }
+
+        if hash.len() != <D as Digest>::output_size() {
+            return Err(MerkleError::InvalidProof);
+        }
+        Ok(Output::<D>::clone_from_slice(&hash))
+    }
+
+    /// Verifies that the key and value are committed to by the root, checking the proof
+    /// against the spec as ICS-23 light clients do.
+    ///
+    /// # Arguments
+    ///
+    /// * `spec`: The spec of the tree, such as one returned by `ProofSpec::from_options`.
+    /// * `root`: The hex encoded Merkle root to verify against.
+    /// * `key`: The key that is claimed to be included in the tree.
+    /// * `value`: The value that is claimed to be stored under the key.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the proof fits the spec and the recomputed root matches `root`, `false`
+    /// otherwise.
+    pub fn verify<D: Digest>(
+        &self,
+        spec: &ProofSpec,
+        root: &str,
+        key: &[u8],
+        value: &[u8],
+    ) -> bool {
+        if self.key != key || self.value != value || !spec.accepts(self) {
+            return false;
+        }
+
+        self.calculate::<D>()
+            .is_ok_and(|computed| hash_matches(&computed, root))
+    }
+}
+
+/// The ICS-23 `InnerSpec`, describing how branch nodes are hashed.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct InnerSpec {
+    /// The order the children are hashed in, `[0, 1]` for a binary tree.
+    pub child_order: Vec<i32>,
+    /// The size of the hash of a child.
+    pub child_size: i32,
+    /// The shortest prefix of an `InnerOp` without the hashes of siblings.
+    pub min_prefix_length: i32,
+    /// The longest prefix of an `InnerOp` without the hashes of siblings.
+    pub max_prefix_length: i32,
+    /// The hash of an empty child, which is empty if there is none.
+    pub empty_child: Vec<u8>,
+    pub hash: HashOp,
+}
+
+impl InnerSpec {
+    fn encode(&self) -> Vec<u8> {
+        let mut child_order = Vec::new();
+        for child in &self.child_order {
+            put_varint(&mut child_order, *child as u64);
+        }
+
+        let mut bytes = Vec::new();
+        put_bytes(&mut bytes, 1, &child_order);
+        put_uint(&mut bytes, 2, self.child_size as u64);
+        put_uint(&mut bytes, 3, self.min_prefix_length as u64);
+        put_uint(&mut bytes, 4, self.max_prefix_length as u64);
+        put_bytes(&mut bytes, 5, &self.empty_child);
+        put_uint(&mut bytes, 6, self.hash.code());
+        bytes
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Self, MerkleError> {
+        let mut spec = InnerSpec::default();
+        for (field, value) in fields(bytes)? {
+            match (field, value) {
+                (1, Field::Varint(child)) => spec.child_order.push(child as i32),
+                (1, Field::Bytes(mut packed)) => {
+                    while !packed.is_empty() {
+                        spec.child_order.push(take_varint(&mut packed)? as i32);
+                    }
+                }
+                (2, Field::Varint(size)) => spec.child_size = size as i32,
+                (3, Field::Varint(length)) => spec.min_prefix_length = length as i32,
+                (4, Field::Varint(length)) => spec.max_prefix_length = length as i32,
+                (5, Field::Bytes(child)) => spec.empty_child = child.to_vec(),
+                (6, Field::Varint(code)) => spec.hash = HashOp::from_code(code)?,
+                _ => {}
+            }
+        }
+        Ok(spec)
+    }
+}
+
+/// The ICS-23 `ProofSpec`, the descriptor a light client is configured with to accept proofs
+/// of a tree construction.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ProofSpec {
+    pub leaf_spec: LeafOp,
+    pub inner_spec: InnerSpec,
+    /// The largest number of `InnerOp`s of a proof, or 0 for no limit.
+    pub max_depth: i32,
+    /// The smallest number of `InnerOp`s of a proof, or 0 for no limit.
+    pub min_depth: i32,
+}
+
+impl ProofSpec {
+    /// Describes the construction of trees built with the given options.
+    ///
+    /// Only `HashMode::Tagged` can be described, since ICS-23 can neither sort the children
+    /// nor hash twice. The leaf and branch prefixes must differ for light clients to accept
+    /// the proofs, so the tags must differ or domain separation must be enabled.
+    ///
+    /// # Arguments
+    ///
+    /// * `options`: The options the tree was built with.
+    ///
+    /// # Returns
+    ///
+    /// The spec, `MerkleError::UnsupportedHashMode` for another hash mode or
+    /// `MerkleError::UnsupportedDigest` if ICS-23 has no hash operation for the digest.
+    pub fn from_options<D: Digest>(options: &TreeOptions) -> Result<Self, MerkleError> {
+        let hash = HashOp::of::<D>().ok_or(MerkleError::UnsupportedDigest)?;
+        let branch_prefix = tag_prefix::<D>(options, &options.tag_branch, BRANCH_PREFIX)?;
+
+        Ok(ProofSpec {
+            leaf_spec: LeafOp {
+                hash,
+                prefix: tag_prefix::<D>(options, &options.tag_leaf, LEAF_PREFIX)?,
+                ..LeafOp::default()
+            },
+            inner_spec: InnerSpec {
+                child_order: vec![0, 1],
+                child_size: <D as Digest>::output_size() as i32,
+                min_prefix_length: branch_prefix.len() as i32,
+                max_prefix_length: branch_prefix.len() as i32,
+                empty_child: Vec::new(),
+                hash,
+            },
+            max_depth: 0,
+            min_depth: 0,
+        })
+    }
+
+    /// Serializes the spec as the protobuf message `cosmos.ics23.v1.ProofSpec`.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        put_message(&mut bytes, 1, &self.leaf_spec.encode());
+        put_message(&mut bytes, 2, &self.inner_spec.encode());
+        put_uint(&mut bytes, 3, self.max_depth as u64);
+        put_uint(&mut bytes, 4, self.min_depth as u64);
+        bytes
+    }
+
+    /// Parses a spec serialized by `to_bytes`.
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes`: The protobuf message.
+    ///
+    /// # Returns
+    ///
+    /// The spec, or `MerkleError::InvalidProof` if the message is malformed or uses
+    /// operations this crate does not implement.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, MerkleError> {
+        let mut spec = ProofSpec::default();
+        for (field, value) in fields(bytes)? {
+            match (field, value) {
+                (1, Field::Bytes(leaf)) => spec.leaf_spec = LeafOp::decode(leaf)?,
+                (2, Field::Bytes(inner)) => spec.inner_spec = InnerSpec::decode(inner)?,
+                (3, Field::Varint(depth)) => spec.max_depth = depth as i32,
+                (4, Field::Varint(depth)) => spec.min_depth = depth as i32,
+                _ => {}
+            }
+        }
+        Ok(spec)
+    }
+
+    /// Checks the operations of a proof against the spec, as the `CheckAgainstSpec` functions
+    /// of ICS-23 do.
+    fn accepts(&self, proof: &ExistenceProof) -> bool {
+        let depth = proof.path.len() as i32;
+        let siblings = self.inner_spec.child_order.len().saturating_sub(1) as i32;
+        let max_prefix_length =
+            self.inner_spec.max_prefix_length + siblings * self.inner_spec.child_size;
+
+        proof.leaf == self.leaf_spec
+            && (self.max_depth <= 0 || depth <= self.max_depth)
+            && (self.min_depth <= 0 || depth >= self.min_depth)
+            && proof.path.iter().all(|op| {
+                op.hash == self.inner_spec.hash
+                    && !op.prefix.starts_with(&self.leaf_spec.prefix)
+                    && op.prefix.len() as i32 >= self.inner_spec.min_prefix_length
+                    && op.prefix.len() as i32 <= max_prefix_length
+                    && self.inner_spec.child_size > 0
+                    && op.suffix.len() % self.inner_spec.child_size as usize == 0
+            })
+    }
+}
+
+impl<T, D, S> MerkleTree<T, D, S>
+where
+    T: MerkleTreeData,
+    D: Digest,
+    S: NodeStore<T, D>,
+{
+    /// Generates the ICS-23 existence proof for the leaf at the given index.
+    ///
+    /// The serialized leaf data is split into the key and the value of the proof, as ICS-23
+    /// requires both to be non-empty.
+    ///
+    /// # Arguments
+    ///
+    /// * `index`: The position of the leaf.
+    /// * `key_length`: The number of bytes of the serialized leaf data that form the key.
+    ///
+    /// # Returns
+    ///
+    /// The proof, `MerkleError::LeafNotFound` if the leaf does not exist or has no user data,
+    /// `MerkleError::InvalidRange` if the key or the value would be empty, or the errors of
+    /// `ProofSpec::from_options`.
+    pub fn to_existence_proof(
+        &self,
+        index: usize,
+        key_length: usize,
+    ) -> Result<ExistenceProof, MerkleError> {
+        let spec = ProofSpec::from_options::<D>(&self.options)?;
+        let branch_prefix =
+            tag_prefix::<D>(&self.options, &self.options.tag_branch, BRANCH_PREFIX)?;
+        let (node, path) = self.proof_by_index(index)?;
+        let data = node
+            .user_data
+            .as_ref()
+            .ok_or(MerkleError::LeafNotFound)?
+            .serialize();
+        if key_length == 0 || key_length >= data.len() {
+            return Err(MerkleError::InvalidRange);
+        }
+
+        let mut ops = Vec::with_capacity(path.siblings.len());
+        for (sibling, direction) in path.siblings.iter().zip(&path.directions).rev() {
+            let sibling = hex::decode(sibling).map_err(|_| MerkleError::InvalidProof)?;
+            let mut op = InnerOp {
+                hash: spec.inner_spec.hash,
+                prefix: branch_prefix.clone(),
+                suffix: Vec::new(),
+            };
+            match direction {
+                NodeDirection::Left => op.suffix = sibling,
+                NodeDirection::Right => op.prefix.extend(sibling),
+                NodeDirection::Root => return Err(MerkleError::InvalidProof),
+            }
+            ops.push(op);
+        }
+
+        let (key, value) = data.split_at(key_length);
+        Ok(ExistenceProof {
+            key: key.to_vec(),
+            value: value.to_vec(),
+            leaf: spec.leaf_spec,
+            path: ops,
+        })
+    }
+}
+
+/// Returns the bytes the tagged hash of the options feeds to the digest before the input.
+///
+/// # Arguments
+///
+/// * `options`: The options the tree was built with.
+/// * `tag`: The tag of the node.
+/// * `domain_prefix`: The byte added with domain separation.
+fn tag_prefix<D: Digest>(
+    options: &TreeOptions,
+    tag: &str,
+    domain_prefix: u8,
+) -> Result<Vec<u8>, MerkleError> {
+    let mut prefix = match (options.hash_mode, options.tag_style) {
+        (HashMode::Tagged, TagStyle::Bip340) => {
+            let tag_hash = D::digest(tag.as_bytes());
+            [tag_hash.as_slice(), tag_hash.as_slice()].concat()
+        }
+        (HashMode::Tagged, TagStyle::Prefix) => tag.as_bytes().to_vec(),
+        (HashMode::SortedPair | HashMode::DoubleHash, _) => {
+            return Err(MerkleError::UnsupportedHashMode)
+        }
+    };
+    if options.domain_separation {
+        prefix.push(domain_prefix);
+    }
+    Ok(prefix)
+}
+
+/// A field of a protobuf message.
+enum Field<'a> {
+    Varint(u64),
+    Bytes(&'a [u8]),
+}
+
+/// Splits a protobuf message into its fields, skipping fixed size fields, which none of the
+/// ICS-23 messages has.
+fn fields(mut bytes: &[u8]) -> Result<Vec<(u64, Field<'_>)>, MerkleError> {
+    let mut fields = Vec::new();
+    while !bytes.is_empty() {
+        let key = take_varint(&mut bytes)?;
+        let field = match key & 7 {
+            0 => Field::Varint(take_varint(&mut bytes)?),
+            2 => {
+                let len = usize::try_from(take_varint(&mut bytes)?)
+                    .map_err(|_| MerkleError::InvalidProof)?;
+                Field::Bytes(take(&mut bytes, len)?)
+            }
+            1 => {
+                take(&mut bytes, 8)?;
+                continue;
+            }
+            5 => {
+                take(&mut bytes, 4)?;
+                continue;
+            }
+            _ => return Err(MerkleError::InvalidProof),
+        };
+        fields.push((key >> 3, field));
+    }
+    Ok(fields)
+}
+
+fn take<'a>(bytes: &mut &'a [u8], len: usize) -> Result<&'a [u8], MerkleError> {
+    if bytes.len() < len {
+        return Err(MerkleError::InvalidProof);
+    }
+    let (head, tail) = bytes.split_at(len);
+    *bytes = tail;
+    Ok(head)
+}
+
+fn take_varint(bytes: &mut &[u8]) -> Result<u64, MerkleError> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let byte = take(bytes, 1)?[0];
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(MerkleError::InvalidProof)
+}
+
+pub(crate) fn put_varint(bytes: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        bytes.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+/// Appends a varint field, which protobuf omits if it has the default value 0.
+pub(crate) fn put_uint(bytes: &mut Vec<u8>, field: u64, value: u64) {
+    if value != 0 {
+        put_varint(bytes, field << 3);
+        put_varint(bytes, value);
+    }
+}
+
+/// Appends a bytes field, which protobuf omits if it is empty.
+fn put_bytes(bytes: &mut Vec<u8>, field: u64, value: &[u8]) {
+    if !value.is_empty() {
+        put_message(bytes, field, value);
+    }
+}
+
+/// Appends a length delimited field even if it is empty, as for a set message field.
+pub(crate) fn put_message(bytes: &mut Vec<u8>, field: u64, value: &[u8]) {
+    put_varint(bytes, field << 3 | 2);
+    put_varint(bytes, value.len() as u64);
+    bytes.extend_from_slice(value);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{util, Padding};
+    use alloc::format;
+    use alloc::string::ToString;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(TreeOptions::tagged("ProofOfReserve_Leaf", "ProofOfReserve_Branch"), 9)]
+    #[case(TreeOptions::certificate_transparency(), 6)]
+    #[case(TreeOptions::tagged("Leaf", "Branch").with_padding(Padding::Promote), 11)]
+    fn it_can_export_ics23_existence_proofs(
+        #[case] options: TreeOptions,
+        #[case] leaf_count: usize,
+    ) {
+        let user_data: Vec<_> = (1..=leaf_count as u32)
+            .map(|id| util::UserData {
+                id,
+                balance: id * 1111,
+            })
+            .collect();
+        let tree: MerkleTree<_> = MerkleTree::build_with_options(options, &user_data);
+        let root = tree.root().unwrap();
+
+        let spec = ProofSpec::from_options::<Sha256>(tree.options()).unwrap();
+        assert_eq!(ProofSpec::from_bytes(&spec.to_bytes()), Ok(spec.clone()));
+
+        for (index, user) in user_data.iter().enumerate() {
+            let key_length = user.id.to_string().len();
+            let proof = tree.to_existence_proof(index, key_length).unwrap();
+            let proof = ExistenceProof::from_bytes(&proof.to_bytes()).unwrap();
+
+            let key = user.id.to_string().into_bytes();
+            let value = format!(",{}", user.balance).into_bytes();
+            assert!(proof.verify::<Sha256>(&spec, &root, &key, &value));
+            assert!(!proof.verify::<Sha256>(&spec, &root, &key, b",0"));
+        }
+
+        assert_eq!(
+            tree.to_existence_proof(0, 0),
+            Err(MerkleError::InvalidRange)
+        );
+        assert_eq!(
+            ProofSpec::from_options::<Sha256>(&TreeOptions::sorted_pair()),
+            Err(MerkleError::UnsupportedHashMode)
+        );
+        assert!(ExistenceProof::from_bytes(&[0x0a, 0x05, 0x01]).is_err());
+    }
+}
@@ -0,0 +1,548 @@
+use sha2::digest::Output;
+use sha2::Digest;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::ops::Deref;
+
+use crate::{
+    MerkleError, MerkleNode, MerkleTree, MerkleTreeData, MerkleTreeKey, Sha256, TraversePath,
+    TreeOptions, TreeVersion,
+};
+
+/// A Merkle Tree that keeps an index from leaf keys and leaf hashes to leaf positions.
+///
+/// Leaves with equal data have equal hashes, so each hash maps to the positions of all leaves
+/// with that hash.
+///
+/// Dereferences to the underlying `MerkleTree`, so all of its methods are available.
+pub struct IndexedMerkleTree<T: MerkleTreeKey, D: Digest = Sha256> {
+    tree: MerkleTree<T, D>,
+    index: HashMap<T::Key, usize>,
+    hashes: HashMap<Output<D>, Vec<usize>>,
+}
+
+impl<T, D> IndexedMerkleTree<T, D>
+where
+    T: Clone + Debug + MerkleTreeData + MerkleTreeKey + Default,
+    D: Digest,
+{
+    /// Builds the indexed Merkle Tree from the given input data.
+    ///
+    /// # Arguments
+    ///
+    /// * `tag_leaf`: The tag used for hashing leaf nodes.
+    /// * `tag_branch`: The tag used for hashing branch nodes.
+    /// * `input`: A slice of data to be stored in the Merkle Tree.
+    ///
+    /// # Returns
+    ///
+    /// The tree, or `MerkleError::DuplicateKey` if several leaves share a key.
+    pub fn build(tag_leaf: &str, tag_branch: &str, input: &[T]) -> Result<Self, MerkleError> {
+        Self::build_with_options(TreeOptions::tagged(tag_leaf, tag_branch), input)
+    }
+
+    /// Builds the indexed Merkle Tree from the given input data with the given options.
+    ///
+    /// # Arguments
+    ///
+    /// * `options`: The hashing configuration of the tree.
+    /// * `input`: A slice of data to be stored in the Merkle Tree.
+    ///
+    /// # Returns
+    ///
+    /// The tree, or `MerkleError::DuplicateKey` if several leaves share a key.
+    pub fn build_with_options(options: TreeOptions, input: &[T]) -> Result<Self, MerkleError> {
+        let mut index = HashMap::with_capacity(input.len());
+        for (i, item) in input.iter().enumerate() {
+            if index.insert(item.key(), i).is_some() {
+                return Err(MerkleError::DuplicateKey);
+            }
+        }
+
+        let tree: MerkleTree<T, D> = MerkleTree::build_with_options(options, input);
+        let mut hashes: HashMap<Output<D>, Vec<usize>> = HashMap::new();
+        for (i, node) in tree.leaf_nodes().into_iter().enumerate() {
+            hashes
+                .entry(tree.nodes[node].hash.clone())
+                .or_default()
+                .push(i);
+        }
+
+        Ok(IndexedMerkleTree {
+            tree,
+            index,
+            hashes,
+        })
+    }
+
+    /// Appends a leaf to the tree and the index without rebuilding the tree.
+    ///
+    /// # Arguments
+    ///
+    /// * `leaf`: The user data of the new leaf.
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` if the leaf was appended, or `MerkleError::DuplicateKey` if a leaf with the same
+    /// key is already in the tree.
+    pub fn push(&mut self, leaf: T) -> Result<(), MerkleError> {
+        match self.index.entry(leaf.key()) {
+            Entry::Occupied(_) => return Err(MerkleError::DuplicateKey),
+            Entry::Vacant(entry) => entry.insert(self.tree.leaf_count()),
+        };
+        self.tree.push(leaf);
+        let index = self.tree.leaf_count() - 1;
+        self.insert_hash(index)?;
+        Ok(())
+    }
+
+    /// Replaces the user data of a leaf and updates the index without rebuilding the tree.
+    ///
+    /// # Arguments
+    ///
+    /// * `index`: The position of the leaf.
+    /// * `new_data`: The new user data of the leaf.
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` if the leaf was updated, `MerkleError::LeafNotFound` if the index is out of
+    /// range or `MerkleError::DuplicateKey` if another leaf has the new key.
+    pub fn update(&mut self, index: usize, new_data: T) -> Result<(), MerkleError> {
+        let old_key = self
+            .tree
+            .leaf(index)?
+            .user_data
+            .as_ref()
+            .map(|data| data.key());
+        let new_key = new_data.key();
+        if self
+            .index
+            .get(&new_key)
+            .is_some_and(|position| *position != index)
+        {
+            return Err(MerkleError::DuplicateKey);
+        }
+
+        if let Some(old_key) = old_key {
+            self.index.remove(&old_key);
+        }
+        self.index.insert(new_key, index);
+        self.remove_hash(index)?;
+        self.tree.update(index, new_data)?;
+        self.insert_hash(index)?;
+        Ok(())
+    }
+
+    /// Adds the position of a leaf to the positions of its hash, keeping them sorted.
+    ///
+    /// # Arguments
+    ///
+    /// * `index`: The position of the leaf.
+    fn insert_hash(&mut self, index: usize) -> Result<(), MerkleError> {
+        let hash = self.tree.leaf(index)?.hash.clone();
+        let positions = self.hashes.entry(hash).or_default();
+        if let Err(at) = positions.binary_search(&index) {
+            positions.insert(at, index);
+        }
+        Ok(())
+    }
+
+    /// Removes the position of a leaf from the positions of its hash, and the hash once no
+    /// leaf has it anymore.
+    ///
+    /// # Arguments
+    ///
+    /// * `index`: The position of the leaf.
+    fn remove_hash(&mut self, index: usize) -> Result<(), MerkleError> {
+        let hash = &self.tree.leaf(index)?.hash;
+        if let Some(positions) = self.hashes.get_mut(hash) {
+            positions.retain(|position| *position != index);
+            if positions.is_empty() {
+                self.hashes.remove(hash);
+            }
+        }
+        Ok(())
+    }
+
+    /// Removes the leaf at the given index and updates the index.
+    ///
+    /// The tree is compacted, see `MerkleTree::remove`, so the positions of the following
+    /// leaves are shifted down by one in the index.
+    ///
+    /// # Arguments
+    ///
+    /// * `index`: The position of the leaf to remove.
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` if the leaf was removed, or `MerkleError::LeafNotFound` if the index is out of
+    /// range.
+    pub fn remove(&mut self, index: usize) -> Result<(), MerkleError> {
+        self.remove_hash(index)?;
+        self.tree.remove(index)?;
+
+        self.index.retain(|_, position| *position != index);
+        shift_positions(self.index.values_mut(), index);
+        for positions in self.hashes.values_mut() {
+            shift_positions(positions.iter_mut(), index);
+        }
+        Ok(())
+    }
+
+    /// Returns a handle to the current version of the tree, see `MerkleTree::version`.
+    pub fn version(&mut self) -> TreeVersion {
+        self.tree.version()
+    }
+
+    /// Discards the nodes that are only used by earlier versions of the tree, see
+    /// `MerkleTree::compact`.
+    ///
+    /// Once a version of the tree was taken, `push` and `update` add the changed nodes next to
+    /// the old ones, so the tree keeps growing until it is compacted. The positions of the
+    /// leaves do not change, so the index stays valid.
+    pub fn compact(&mut self) {
+        self.tree.compact();
+    }
+}
+
+impl<T: MerkleTreeKey, D: Digest> IndexedMerkleTree<T, D> {
+    /// Returns the position of the leaf with the given key.
+    ///
+    /// # Arguments
+    ///
+    /// * `key`: The key of the leaf.
+    pub fn index_of(&self, key: &T::Key) -> Option<usize> {
+        self.index.get(key).copied()
+    }
+
+    /// Returns `true` if a leaf with the given key is in the tree.
+    ///
+    /// # Arguments
+    ///
+    /// * `key`: The key of the leaf.
+    pub fn contains_key(&self, key: &T::Key) -> bool {
+        self.index.contains_key(key)
+    }
+
+    /// Returns `true` if a leaf with the given hash is in the tree.
+    ///
+    /// # Arguments
+    ///
+    /// * `hash`: The hash of the leaf, as computed by `TreeOptions::hash_leaf`.
+    pub fn contains_hash(&self, hash: &[u8]) -> bool {
+        self.hashes.contains_key(hash)
+    }
+
+    /// Returns the positions of the leaves with the given hash, in ascending order.
+    ///
+    /// # Arguments
+    ///
+    /// * `hash`: The hash of the leaf, as computed by `TreeOptions::hash_leaf`.
+    ///
+    /// # Returns
+    ///
+    /// The positions, or an empty slice if no leaf has the hash.
+    pub fn positions_of_hash(&self, hash: &[u8]) -> &[usize] {
+        self.hashes.get(hash).map_or(&[], Vec::as_slice)
+    }
+
+    /// Generates the proof for the leaf with the given key in O(log n) time.
+    ///
+    /// # Arguments
+    ///
+    /// * `key`: The key of the leaf.
+    ///
+    /// # Returns
+    ///
+    /// A tuple of `(&MerkleNode, TraversePath)`, or `MerkleError::LeafNotFound` if no leaf
+    /// has the key.
+    pub fn proof_for_key(
+        &self,
+        key: &T::Key,
+    ) -> Result<(&MerkleNode<T, D>, TraversePath<D>), MerkleError> {
+        let index = self.index_of(key).ok_or(MerkleError::LeafNotFound)?;
+        self.tree.proof_by_index(index)
+    }
+}
+
+impl<T: MerkleTreeKey, D: Digest> Deref for IndexedMerkleTree<T, D> {
+    type Target = MerkleTree<T, D>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.tree
+    }
+}
+
+/// Copies the tree with its indexes.
+impl<T, D> Clone for IndexedMerkleTree<T, D>
+where
+    T: MerkleTreeKey + Clone,
+    T::Key: Clone,
+    D: Digest + Clone,
+{
+    fn clone(&self) -> Self {
+        IndexedMerkleTree {
+            tree: self.tree.clone(),
+            index: self.index.clone(),
+            hashes: self.hashes.clone(),
+        }
+    }
+}
+
+/// Wipes the tree and empties the indexes, leaving an empty tree.
+#[cfg(feature = "zeroize")]
+impl<T: MerkleTreeKey + zeroize::Zeroize, D: Digest> zeroize::Zeroize for IndexedMerkleTree<T, D> {
+    fn zeroize(&mut self) {
+        self.tree.zeroize();
+        self.index.clear();
+        self.hashes.clear();
+    }
+}
+
+/// Shifts the positions of the leaves following a removed leaf down by one.
+///
+/// # Arguments
+///
+/// * `positions`: The positions of leaves in an index.
+/// * `index`: The position of the removed leaf.
+fn shift_positions<'a>(positions: impl Iterator<Item = &'a mut usize>, index: usize) {
+    for position in positions {
+        if *position > index {
+            *position -= 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{util, verify_proof};
+
+    #[test]
+    fn it_can_generate_proof_for_key() {
+        let user_data = util::generate_random_user_data(10);
+
+        let tree: IndexedMerkleTree<_> =
+            IndexedMerkleTree::build("ProofOfReserve_Leaf", "ProofOfReserve_Branch", &user_data)
+                .unwrap();
+        let root = tree.root().unwrap();
+
+        for (index, item) in user_data.iter().enumerate() {
+            assert_eq!(tree.index_of(&item.id), Some(index));
+
+            let (node, path) = tree.proof_for_key(&item.id).unwrap();
+            assert_eq!(node.user_data.as_ref().unwrap().balance, item.balance);
+            assert!(verify_proof(
+                "ProofOfReserve_Leaf",
+                "ProofOfReserve_Branch",
+                &root,
+                item,
+                &path
+            ));
+        }
+
+        assert!(matches!(
+            tree.proof_for_key(&11),
+            Err(MerkleError::LeafNotFound)
+        ));
+    }
+
+    #[test]
+    fn it_can_keep_key_index_on_push_and_update() {
+        let user_data = util::generate_random_user_data(5);
+
+        let mut tree: IndexedMerkleTree<_> =
+            IndexedMerkleTree::build("ProofOfReserve_Leaf", "ProofOfReserve_Branch", &user_data)
+                .unwrap();
+
+        let leaf = util::UserData {
+            id: 6,
+            balance: 6000,
+        };
+        assert_eq!(tree.push(leaf.clone()), Ok(()));
+        assert_eq!(tree.push(leaf), Err(MerkleError::DuplicateKey));
+        assert_eq!(tree.index_of(&6), Some(5));
+
+        assert_eq!(
+            tree.update(
+                2,
+                util::UserData {
+                    id: 30,
+                    balance: 3000,
+                }
+            ),
+            Ok(())
+        );
+        assert_eq!(
+            tree.update(0, user_data[3].clone()),
+            Err(MerkleError::DuplicateKey)
+        );
+        assert_eq!(tree.index_of(&3), None);
+        assert_eq!(tree.index_of(&30), Some(2));
+
+        assert_eq!(tree.remove(0), Ok(()));
+        assert_eq!(tree.index_of(&1), None);
+        assert_eq!(tree.index_of(&30), Some(1));
+        assert_eq!(tree.index_of(&6), Some(4));
+
+        let root = tree.root().unwrap();
+        let (node, path) = tree.proof_for_key(&30).unwrap();
+        assert!(verify_proof(
+            "ProofOfReserve_Leaf",
+            "ProofOfReserve_Branch",
+            &root,
+            node.user_data.as_ref().unwrap(),
+            &path
+        ));
+    }
+
+    #[test]
+    fn it_can_clone_indexed_trees() {
+        let user_data = util::generate_random_user_data(6);
+        let tree: IndexedMerkleTree<_> =
+            IndexedMerkleTree::build("ProofOfReserve_Leaf", "ProofOfReserve_Branch", &user_data)
+                .unwrap();
+        let root = tree.root().unwrap();
+
+        let mut copy = tree.clone();
+        copy.update(2, util::UserData { id: 3, balance: 1 })
+            .unwrap();
+        copy.remove(0).unwrap();
+        assert_ne!(copy.root().unwrap(), root);
+        assert_eq!(copy.index_of(&3), Some(1));
+
+        assert_eq!(tree.root().unwrap(), root);
+        assert_eq!(tree.index_of(&3), Some(2));
+        let (node, _path) = tree.proof_for_key(&3).unwrap();
+        assert_eq!(node.user_data.as_ref().unwrap().balance, 3000);
+    }
+
+    #[test]
+    fn it_can_compact_indexed_trees() {
+        let user_data = util::generate_random_user_data(8);
+        let mut tree: IndexedMerkleTree<_> =
+            IndexedMerkleTree::build("ProofOfReserve_Leaf", "ProofOfReserve_Branch", &user_data)
+                .unwrap();
+        let node_count = tree.stats().node_count;
+
+        for balance in 0..5 {
+            tree.update(3, util::UserData { id: 4, balance }).unwrap();
+        }
+        assert_eq!(tree.stats().node_count, node_count);
+
+        let previous = tree.version();
+        for balance in 5..10 {
+            tree.update(3, util::UserData { id: 4, balance }).unwrap();
+        }
+        assert!(tree.stats().node_count > node_count);
+        assert_ne!(tree.root_at(previous), tree.root());
+
+        let root = tree.root().unwrap();
+        tree.compact();
+        assert_eq!(tree.stats().node_count, node_count);
+        assert_eq!(tree.root().unwrap(), root);
+        assert_eq!(tree.index_of(&4), Some(3));
+
+        let (node, path) = tree.proof_for_key(&4).unwrap();
+        assert_eq!(node.user_data.as_ref().unwrap().balance, 9);
+        assert!(verify_proof(
+            "ProofOfReserve_Leaf",
+            "ProofOfReserve_Branch",
+            &root,
+            node.user_data.as_ref().unwrap(),
+            &path
+        ));
+    }
+
+    #[test]
+    fn it_can_check_membership_by_key_and_hash() {
+        let user_data = util::generate_random_user_data(5);
+        let options = TreeOptions::tagged("ProofOfReserve_Leaf", "ProofOfReserve_Branch");
+        let leaf_hash =
+            |user: &util::UserData| options.hash_leaf::<Sha256>(&user.serialize()).to_vec();
+
+        let mut tree: IndexedMerkleTree<_> =
+            IndexedMerkleTree::build_with_options(options.clone(), &user_data).unwrap();
+        assert!(tree.contains_key(&3));
+        assert!(!tree.contains_key(&6));
+        assert!(tree.contains_hash(&leaf_hash(&user_data[2])));
+
+        let new_user = util::UserData {
+            id: 30,
+            balance: 3000,
+        };
+        tree.update(2, new_user.clone()).unwrap();
+        assert!(!tree.contains_key(&3));
+        assert!(!tree.contains_hash(&leaf_hash(&user_data[2])));
+        assert!(tree.contains_key(&30));
+        assert!(tree.contains_hash(&leaf_hash(&new_user)));
+
+        let pushed = util::UserData {
+            id: 6,
+            balance: 6000,
+        };
+        tree.push(pushed.clone()).unwrap();
+        assert!(tree.contains_hash(&leaf_hash(&pushed)));
+
+        tree.remove(0).unwrap();
+        assert!(!tree.contains_hash(&leaf_hash(&user_data[0])));
+        assert!(tree.contains_hash(&leaf_hash(&user_data[4])));
+        assert!(!tree.contains_hash(&[0; 32]));
+    }
+
+    #[test]
+    fn it_can_keep_positions_of_duplicate_leaves() {
+        /// A named document whose leaf commits only to its content, so documents with equal
+        /// content have equal leaves.
+        #[derive(Clone, Debug, Default)]
+        struct Document {
+            name: u32,
+            content: &'static str,
+        }
+
+        impl MerkleTreeData for Document {
+            fn serialize(&self) -> Vec<u8> {
+                self.content.as_bytes().to_vec()
+            }
+
+            fn mermaid_node_label(&self) -> String {
+                format!("<br>{}", self.content)
+            }
+        }
+
+        impl MerkleTreeKey for Document {
+            type Key = u32;
+
+            fn key(&self) -> u32 {
+                self.name
+            }
+        }
+
+        let document = |name, content| Document { name, content };
+        let options = TreeOptions::tagged("ProofOfReserve_Leaf", "ProofOfReserve_Branch");
+        let leaf_hash = |content: &str| options.hash_leaf::<Sha256>(content.as_bytes()).to_vec();
+
+        let documents = [document(1, "aaa"), document(2, "bbb"), document(3, "aaa")];
+        let mut tree: IndexedMerkleTree<_> =
+            IndexedMerkleTree::build_with_options(options.clone(), &documents).unwrap();
+        assert_eq!(tree.positions_of_hash(&leaf_hash("aaa")), &[0, 2]);
+
+        tree.push(document(4, "aaa")).unwrap();
+        assert_eq!(tree.positions_of_hash(&leaf_hash("aaa")), &[0, 2, 3]);
+
+        tree.update(0, document(1, "ccc")).unwrap();
+        assert!(tree.contains_hash(&leaf_hash("aaa")));
+        assert_eq!(tree.positions_of_hash(&leaf_hash("aaa")), &[2, 3]);
+        assert_eq!(tree.positions_of_hash(&leaf_hash("ccc")), &[0]);
+
+        tree.remove(2).unwrap();
+        assert!(tree.contains_hash(&leaf_hash("aaa")));
+        assert_eq!(tree.positions_of_hash(&leaf_hash("aaa")), &[2]);
+        assert_eq!(tree.positions_of_hash(&leaf_hash("bbb")), &[1]);
+
+        tree.remove(2).unwrap();
+        assert!(!tree.contains_hash(&leaf_hash("aaa")));
+        assert!(tree.positions_of_hash(&leaf_hash("aaa")).is_empty());
+    }
+}
@@ -0,0 +1,282 @@
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+use serde::de::DeserializeOwned;
+use serde::ser::{Serialize, SerializeMap, Serializer};
+use serde_json::Value;
+use sha2::digest::Output;
+use sha2::Digest;
+
+use crate::{MerkleNode, MerkleTree, MerkleTreeData, NodeStore, TreeOptions};
+
+/// The error returned when a tree cannot be exported to or reloaded from JSON.
+#[derive(Debug)]
+pub enum JsonTreeError {
+    /// The JSON could not be written or parsed, or a leaf payload does not match `T`.
+    Json(serde_json::Error),
+    /// A node is missing its hash, has a single child, or the leaf indices are not
+    /// contiguous.
+    Malformed,
+    /// A hash in the JSON does not match the hash recomputed from the leaves.
+    HashMismatch,
+}
+
+impl fmt::Display for JsonTreeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JsonTreeError::Json(err) => write!(f, "invalid JSON tree: {err}"),
+            JsonTreeError::Malformed => f.write_str("the JSON tree is malformed"),
+            JsonTreeError::HashMismatch => {
+                f.write_str("the JSON tree does not match the hashes of its leaves")
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for JsonTreeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            JsonTreeError::Json(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<serde_json::Error> for JsonTreeError {
+    fn from(err: serde_json::Error) -> Self {
+        JsonTreeError::Json(err)
+    }
+}
+
+/// Serializes the subtree below `node` without building an intermediate `Value`.
+struct JsonNode<'a, T, D: Digest, N> {
+    tree: &'a MerkleTree<T, D, N>,
+    leaf_indices: &'a [Option<usize>],
+    node: usize,
+}
+
+impl<T, D, N> Serialize for JsonNode<'_, T, D, N>
+where
+    T: Serialize,
+    D: Digest,
+    N: NodeStore<T, D>,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let node = &self.tree.nodes[self.node];
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("hash", &hex::encode(&node.hash))?;
+
+        if let (Some(left), Some(right)) = (node.left, node.right) {
+            let child = |node| JsonNode {
+                tree: self.tree,
+                leaf_indices: self.leaf_indices,
+                node,
+            };
+            map.serialize_entry("children", &[child(left), child(right)])?;
+        } else {
+            if let Some(index) = self.leaf_indices[self.node] {
+                map.serialize_entry("index", &index)?;
+            }
+            if let Some(user_data) = node.user_data.as_ref() {
+                map.serialize_entry("leaf", user_data)?;
+            }
+        }
+
+        map.end()
+    }
+}
+
+impl<T, D, S> MerkleTree<T, D, S>
+where
+    T: MerkleTreeData,
+    D: Digest,
+    S: NodeStore<T, D>,
+{
+    /// Exports the whole tree as nested JSON, for example to drive a web-based tree explorer.
+    ///
+    /// Every node is an object with its hex encoded `hash`. Branches list their left and right
+    /// node in `children`. Leaves carry their position in `index` and their user data in
+    /// `leaf`. A node duplicated by `Padding::DuplicateLast` appears under both edges with the
+    /// same `index`, and the empty leaf of `Padding::PadWithEmpty` has no `index`.
+    ///
+    /// # Returns
+    ///
+    /// The JSON text, `"null"` for an empty tree, or an error if the user data cannot be
+    /// serialized.
+    pub fn to_json_tree(&self) -> Result<String, JsonTreeError>
+    where
+        T: Serialize,
+    {
+        let mut leaf_indices = vec![None; self.nodes.len()];
+        for (index, node) in self.leaf_nodes().into_iter().enumerate() {
+            leaf_indices[node] = Some(index);
+        }
+
+        let root = self.root.map(|node| JsonNode {
+            tree: self,
+            leaf_indices: &leaf_indices,
+            node,
+        });
+        Ok(serde_json::to_string(&root)?)
+    }
+
+    /// Reloads a tree exported with `to_json_tree`.
+    ///
+    /// # Arguments
+    ///
+    /// * `tag_leaf`: The tag the tree was hashed with for leaf nodes.
+    /// * `tag_branch`: The tag the tree was hashed with for branch nodes.
+    /// * `json`: The JSON text returned by `to_json_tree`.
+    ///
+    /// # Returns
+    ///
+    /// The tree, or an error if the JSON is malformed or does not match its own hashes.
+    pub fn from_json_tree(
+        tag_leaf: &str,
+        tag_branch: &str,
+        json: &str,
+    ) -> Result<Self, JsonTreeError>
+    where
+        T: DeserializeOwned,
+        S: Default,
+    {
+        Self::from_json_tree_with_options(TreeOptions::tagged(tag_leaf, tag_branch), json)
+    }
+
+    /// Reloads a tree exported with `to_json_tree` that was built with the given hashing
+    /// configuration.
+    ///
+    /// The tree is rebuilt from the leaves, so every hash in the JSON is checked: leaves with
+    /// user data must hash to their `hash` and the rebuilt root must match the root `hash`.
+    ///
+    /// # Arguments
+    ///
+    /// * `options`: The options the tree was built with.
+    /// * `json`: The JSON text returned by `to_json_tree`.
+    ///
+    /// # Returns
+    ///
+    /// The tree, or an error if the JSON is malformed or does not match its own hashes.
+    pub fn from_json_tree_with_options(
+        options: TreeOptions,
+        json: &str,
+    ) -> Result<Self, JsonTreeError>
+    where
+        T: DeserializeOwned,
+        S: Default,
+    {
+        let value: Value = serde_json::from_str(json)?;
+        if value.is_null() {
+            return Ok(Self::from_leaf_nodes(options, Vec::new()));
+        }
+
+        let mut leaves: Vec<MerkleNode<T, D>> = Vec::new();
+        let mut stack = vec![&value];
+
+        while let Some(node) = stack.pop() {
+            let hash = parse_hash::<D>(node.get("hash"))?;
+
+            if let Some(children) = node.get("children") {
+                match children.as_array().map(Vec::as_slice) {
+                    Some([left, right]) => {
+                        stack.push(right);
+                        stack.push(left);
+                    }
+                    _ => return Err(JsonTreeError::Malformed),
+                }
+                continue;
+            }
+
+            // The empty leaf used for padding has no index and is recreated by the build.
+            let Some(index) = node.get("index") else {
+                continue;
+            };
+            let index = index.as_u64().ok_or(JsonTreeError::Malformed)? as usize;
+
+            let user_data = match node.get("leaf") {
+                Some(leaf) => Some(T::deserialize(leaf)?),
+                None => None,
+            };
+            if let Some(user_data) = user_data.as_ref() {
+                if options.hash_leaf_data::<D>(user_data) != hash {
+                    return Err(JsonTreeError::HashMismatch);
+                }
+            }
+
+            // A node duplicated for padding appears again with the index it was first seen at.
+            if index < leaves.len() {
+                continue;
+            }
+            if index != leaves.len() {
+                return Err(JsonTreeError::Malformed);
+            }
+            leaves.push(MerkleNode::new_leaf(hash, user_data));
+        }
+
+        let root_hash = parse_hash::<D>(value.get("hash"))?;
+        let tree = Self::from_leaf_nodes(options, leaves);
+        if tree.root_hash().ok() != Some(root_hash) {
+            return Err(JsonTreeError::HashMismatch);
+        }
+
+        Ok(tree)
+    }
+}
+
+/// Decodes a hex encoded hash of the digest `D` from a JSON string.
+fn parse_hash<D: Digest>(value: Option<&Value>) -> Result<Output<D>, JsonTreeError> {
+    let mut hash = Output::<D>::default();
+    let hex = value
+        .and_then(Value::as_str)
+        .ok_or(JsonTreeError::Malformed)?;
+    hex::decode_to_slice(hex, &mut hash).map_err(|_| JsonTreeError::Malformed)?;
+    Ok(hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{util, MerkleError, Padding};
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(Padding::DuplicateLast)]
+    #[case(Padding::Promote)]
+    #[case(Padding::PadWithEmpty)]
+    fn it_can_export_and_reload_json_tree(#[case] padding: Padding) {
+        let options = TreeOptions::tagged("ProofOfReserve_Leaf", "ProofOfReserve_Branch")
+            .with_padding(padding);
+        let user_data = util::generate_random_user_data(5);
+        let tree: MerkleTree<_> = MerkleTree::build_with_options(options.clone(), &user_data);
+
+        let json = tree.to_json_tree().unwrap();
+        let root = tree.root().unwrap();
+        assert!(json.starts_with(&format!("{{\"hash\":\"{root}\",\"children\":[")));
+        assert!(json.contains("\"index\":4,\"leaf\":{\"id\":5,\"balance\":5000}"));
+
+        let reloaded: MerkleTree<util::UserData> =
+            MerkleTree::from_json_tree_with_options(options.clone(), &json).unwrap();
+        assert_eq!(reloaded.root(), tree.root());
+        assert_eq!(reloaded.leaf_count(), 5);
+        assert_eq!(reloaded.leaf(4).unwrap().user_data.as_ref().unwrap().id, 5);
+
+        let tampered = json.replace("\"balance\":5000", "\"balance\":5001");
+        assert!(matches!(
+            MerkleTree::<util::UserData>::from_json_tree_with_options(options.clone(), &tampered),
+            Err(JsonTreeError::HashMismatch)
+        ));
+        assert!(matches!(
+            MerkleTree::<util::UserData>::from_json_tree_with_options(options.clone(), "{}"),
+            Err(JsonTreeError::Malformed)
+        ));
+
+        let empty: MerkleTree<util::UserData> =
+            MerkleTree::build_with_options(options.clone(), &[]);
+        assert_eq!(empty.to_json_tree().unwrap(), "null");
+        let reloaded: MerkleTree<util::UserData> =
+            MerkleTree::from_json_tree_with_options(options, "null").unwrap();
+        assert_eq!(reloaded.root(), Err(MerkleError::EmptyTree));
+    }
+}
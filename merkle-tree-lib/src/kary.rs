@@ -0,0 +1,393 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+use core::marker::PhantomData;
+use sha2::digest::Output;
+use sha2::Digest;
+
+use crate::multiproof::decode_hash;
+use crate::root::roots_match;
+use crate::{MerkleError, MerkleTreeData, Padding, Sha256, TreeOptions};
+
+/// A Merkle Tree whose branch nodes have up to `arity` children.
+///
+/// A branch hashes the concatenation of its children's hashes with
+/// `TreeOptions::hash_children`. Wider nodes make the tree shallower, so building it takes
+/// fewer hashing rounds and a proof has fewer steps, at the cost of `arity - 1` sibling hashes
+/// per step.
+///
+/// The last group of a level may have fewer than `arity` nodes. It is filled up with copies
+/// of its last node for `Padding::DuplicateLast` and with the hash of an empty leaf for
+/// `Padding::PadWithEmpty`. For `Padding::Promote` a single node is moved up unchanged and a
+/// smaller group is hashed as it is. With an arity of 2 the root is the same as the root of a
+/// `MerkleTree` built with the same options.
+pub struct KaryMerkleTree<T, D: Digest = Sha256> {
+    options: TreeOptions,
+    arity: usize,
+    levels: Vec<Vec<Output<D>>>,
+    leaves: Vec<T>,
+}
+
+impl<T, D> KaryMerkleTree<T, D>
+where
+    T: Clone + MerkleTreeData,
+    D: Digest,
+{
+    /// Builds a k-ary Merkle Tree from the given user data.
+    ///
+    /// # Arguments
+    ///
+    /// * `tag_leaf`: The tag used for hashing leaf nodes.
+    /// * `tag_branch`: The tag used for hashing branch nodes.
+    /// * `arity`: The number of children of a branch node, at least 2.
+    /// * `input`: The user data to commit to, in leaf order.
+    pub fn build(tag_leaf: &str, tag_branch: &str, arity: usize, input: &[T]) -> Self {
+        Self::build_with_options(TreeOptions::tagged(tag_leaf, tag_branch), arity, input)
+    }
+
+    /// Builds a k-ary Merkle Tree from the given user data with the given hashing
+    /// configuration.
+    ///
+    /// # Arguments
+    ///
+    /// * `options`: The hashing configuration of the tree.
+    /// * `arity`: The number of children of a branch node, at least 2.
+    /// * `input`: The user data to commit to, in leaf order.
+    pub fn build_with_options(options: TreeOptions, arity: usize, input: &[T]) -> Self {
+        let arity = arity.max(2);
+        let mut level: Vec<Output<D>> = input
+            .iter()
+            .map(|data| options.hash_leaf_data::<D>(data))
+            .collect();
+        let mut levels = Vec::new();
+
+        while level.len() > 1 {
+            let next_level = level
+                .chunks(arity)
+                .map(|group| match group {
+                    [single] if options.padding == Padding::Promote => single.clone(),
+                    _ => {
+                        let children = pad_group::<D>(&options, arity, group);
+                        options.hash_children::<D>(&children.iter().collect::<Vec<_>>())
+                    }
+                })
+                .collect();
+
+            levels.push(level);
+            level = next_level;
+        }
+        levels.push(level);
+
+        KaryMerkleTree {
+            options,
+            arity,
+            levels,
+            leaves: input.to_vec(),
+        }
+    }
+}
+
+impl<T, D: Digest> KaryMerkleTree<T, D> {
+    /// Returns the hashing configuration the tree was built with.
+    pub fn options(&self) -> &TreeOptions {
+        &self.options
+    }
+
+    /// Returns the number of children of a branch node.
+    pub fn arity(&self) -> usize {
+        self.arity
+    }
+
+    /// Returns the number of leaves in the tree.
+    pub fn leaf_count(&self) -> usize {
+        self.leaves.len()
+    }
+
+    /// Returns the hash of the root node of the tree.
+    ///
+    /// Without leaves the root is selected by `TreeOptions::empty_root`.
+    ///
+    /// # Returns
+    ///
+    /// The hex encoded root, or `MerkleError::EmptyTree` if the tree has no leaves and the
+    /// empty root is `EmptyRoot::Undefined`.
+    pub fn root(&self) -> Result<String, MerkleError> {
+        match self.levels.last().and_then(|level| level.first()) {
+            Some(root) => Ok(hex::encode(root)),
+            None => (self.options.empty_root_hash::<D>())
+                .map(hex::encode)
+                .ok_or(MerkleError::EmptyTree),
+        }
+    }
+
+    /// Generates the proof for the leaf at the given index.
+    ///
+    /// # Arguments
+    ///
+    /// * `index`: The position of the leaf in the input the tree was built from.
+    ///
+    /// # Returns
+    ///
+    /// A tuple of the leaf's user data and its `KaryProof`, or `MerkleError::LeafNotFound` if
+    /// the index is out of range.
+    pub fn proof_by_index(&self, index: usize) -> Result<(&T, KaryProof<D>), MerkleError> {
+        let leaf = self.leaves.get(index).ok_or(MerkleError::LeafNotFound)?;
+        let mut siblings = Vec::with_capacity(self.levels.len());
+        let mut positions = Vec::with_capacity(self.levels.len());
+        let mut position = index;
+
+        for nodes in self.levels.iter().take(self.levels.len() - 1) {
+            let start = position - position % self.arity;
+            let group = &nodes[start..(start + self.arity).min(nodes.len())];
+            let child = position - start;
+            position /= self.arity;
+
+            if group.len() == 1 && self.options.padding == Padding::Promote {
+                // The single node is promoted without siblings.
+                continue;
+            }
+
+            let mut children = pad_group::<D>(&self.options, self.arity, group);
+            children.remove(child);
+            siblings.push(children.iter().map(hex::encode).collect());
+            positions.push(child);
+        }
+
+        siblings.reverse();
+        positions.reverse();
+        Ok((leaf, KaryProof::from_steps(siblings, positions)))
+    }
+}
+
+/// Fills up the last group of a level to the arity according to the padding policy.
+///
+/// # Arguments
+///
+/// * `options`: The hashing configuration of the tree.
+/// * `arity`: The number of children of a branch node.
+/// * `group`: The nodes of the group.
+fn pad_group<D: Digest>(
+    options: &TreeOptions,
+    arity: usize,
+    group: &[Output<D>],
+) -> Vec<Output<D>> {
+    let mut children = group.to_vec();
+    let filler = match options.padding {
+        Padding::DuplicateLast => group.last().cloned(),
+        Padding::PadWithEmpty => Some(options.hash_leaf::<D>(&[])),
+        Padding::Promote => None,
+    };
+    if let Some(filler) = filler {
+        children.resize(arity, filler);
+    }
+    children
+}
+
+/// A proof for a leaf of a `KaryMerkleTree`.
+///
+/// Each step records the hashes of the other children of a branch node and the position of
+/// the child that was taken, generalizing the directions of a `TraversePath`. The steps are
+/// ordered from the root down to the leaf.
+pub struct KaryProof<D = Sha256> {
+    pub siblings: Vec<Vec<String>>,
+    pub positions: Vec<usize>,
+    digest: PhantomData<fn() -> D>,
+}
+
+impl<D> Clone for KaryProof<D> {
+    fn clone(&self) -> Self {
+        Self::from_steps(self.siblings.clone(), self.positions.clone())
+    }
+}
+
+impl<D> fmt::Debug for KaryProof<D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KaryProof")
+            .field("siblings", &self.siblings)
+            .field("positions", &self.positions)
+            .finish()
+    }
+}
+
+impl<D> KaryProof<D> {
+    /// Creates a `KaryProof` from sibling hashes and child positions ordered from the root
+    /// down.
+    ///
+    /// # Arguments
+    ///
+    /// * `siblings`: The hex encoded hashes of the other children at each step.
+    /// * `positions`: The position of the child taken at each step.
+    pub fn from_steps(siblings: Vec<Vec<String>>, positions: Vec<usize>) -> Self {
+        KaryProof {
+            siblings,
+            positions,
+            digest: PhantomData,
+        }
+    }
+
+    /// Recomputes the root committed to by this proof for the given leaf.
+    ///
+    /// # Arguments
+    ///
+    /// * `options`: The options the tree was built with.
+    /// * `leaf_data`: The user data of the leaf this proof belongs to.
+    ///
+    /// # Returns
+    ///
+    /// The hex encoded root, or `None` if the proof is malformed.
+    pub fn compute_root_with_options<T>(
+        &self,
+        options: &TreeOptions,
+        leaf_data: &T,
+    ) -> Option<String>
+    where
+        T: MerkleTreeData,
+        D: Digest,
+    {
+        if self.siblings.len() != self.positions.len() {
+            return None;
+        }
+
+        let mut hash = options.hash_leaf_data::<D>(leaf_data);
+
+        for (siblings, position) in self.siblings.iter().zip(self.positions.iter()).rev() {
+            if *position > siblings.len() {
+                return None;
+            }
+            let mut children = siblings
+                .iter()
+                .map(|sibling| decode_hash::<D>(sibling))
+                .collect::<Option<Vec<_>>>()?;
+            children.insert(*position, hash);
+            hash = options.hash_children::<D>(&children.iter().collect::<Vec<_>>());
+        }
+
+        Some(hex::encode(hash))
+    }
+}
+
+/// Verifies a leaf of a k-ary Merkle Tree.
+///
+/// # Arguments
+///
+/// * `tag_leaf`: The tag used for hashing leaf nodes.
+/// * `tag_branch`: The tag used for hashing branch nodes.
+/// * `root`: The hex encoded root to verify against.
+/// * `leaf_data`: The user data that is claimed to be included in the tree.
+/// * `proof`: The proof returned by `KaryMerkleTree::proof_by_index`.
+///
+/// # Returns
+///
+/// `true` if the proof reconstructs the given root, `false` otherwise.
+pub fn verify_kary_proof<T, D>(
+    tag_leaf: &str,
+    tag_branch: &str,
+    root: &str,
+    leaf_data: &T,
+    proof: &KaryProof<D>,
+) -> bool
+where
+    T: MerkleTreeData,
+    D: Digest,
+{
+    verify_kary_proof_with_options(
+        &TreeOptions::tagged(tag_leaf, tag_branch),
+        root,
+        leaf_data,
+        proof,
+    )
+}
+
+/// Verifies a leaf of a k-ary Merkle Tree, using the hashing configuration the tree was built
+/// with.
+///
+/// # Arguments
+///
+/// * `options`: The options the tree was built with.
+/// * `root`: The hex encoded root to verify against.
+/// * `leaf_data`: The user data that is claimed to be included in the tree.
+/// * `proof`: The proof returned by `KaryMerkleTree::proof_by_index`.
+///
+/// # Returns
+///
+/// `true` if the proof reconstructs the given root, `false` otherwise.
+pub fn verify_kary_proof_with_options<T, D>(
+    options: &TreeOptions,
+    root: &str,
+    leaf_data: &T,
+    proof: &KaryProof<D>,
+) -> bool
+where
+    T: MerkleTreeData,
+    D: Digest,
+{
+    proof
+        .compute_root_with_options(options, leaf_data)
+        .is_some_and(|computed| roots_match(&computed, root))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{util, MerkleTree};
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(Padding::DuplicateLast)]
+    #[case(Padding::Promote)]
+    #[case(Padding::PadWithEmpty)]
+    fn it_can_build_binary_kary_tree(#[case] padding: Padding) {
+        let options = TreeOptions::tagged("ProofOfReserve_Leaf", "ProofOfReserve_Branch")
+            .with_padding(padding);
+
+        for count in [1, 2, 5, 7, 11, 16] {
+            let user_data = util::generate_random_user_data(count);
+            let tree: MerkleTree<_> = MerkleTree::build_with_options(options.clone(), &user_data);
+            let kary: KaryMerkleTree<_> =
+                KaryMerkleTree::build_with_options(options.clone(), 2, &user_data);
+            assert_eq!(kary.root(), tree.root());
+        }
+    }
+
+    #[rstest]
+    #[case(Padding::DuplicateLast, 4)]
+    #[case(Padding::Promote, 4)]
+    #[case(Padding::PadWithEmpty, 4)]
+    #[case(Padding::DuplicateLast, 16)]
+    #[case(Padding::Promote, 16)]
+    #[case(Padding::PadWithEmpty, 16)]
+    fn it_can_verify_kary_proofs(#[case] padding: Padding, #[case] arity: usize) {
+        let options = TreeOptions::tagged("ProofOfReserve_Leaf", "ProofOfReserve_Branch")
+            .with_padding(padding);
+        let user_data = util::generate_random_user_data(37);
+        let tree: KaryMerkleTree<_> =
+            KaryMerkleTree::build_with_options(options.clone(), arity, &user_data);
+        let root = tree.root().unwrap();
+        assert_eq!(tree.arity(), arity);
+
+        for index in 0..user_data.len() {
+            let (leaf, proof) = tree.proof_by_index(index).unwrap();
+            assert_eq!(leaf.id, user_data[index].id);
+            assert!(proof.positions.len() <= 37usize.ilog(arity) as usize + 1);
+            assert!(verify_kary_proof_with_options(
+                &options, &root, leaf, &proof
+            ));
+            assert!(!verify_kary_proof_with_options(
+                &options,
+                &root,
+                &user_data[(index + 1) % 37],
+                &proof
+            ));
+        }
+        assert_eq!(
+            tree.proof_by_index(37).err(),
+            Some(MerkleError::LeafNotFound)
+        );
+
+        let (leaf, mut proof) = tree.proof_by_index(5).unwrap();
+        proof.positions[0] = arity;
+        assert!(!verify_kary_proof_with_options(
+            &options, &root, leaf, &proof
+        ));
+    }
+}
@@ -0,0 +1,196 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use sha2::digest::core_api::BlockSizeUser;
+use sha2::digest::Output;
+use sha2::Digest;
+
+use crate::{wipe, MerkleSumData, MerkleTreeData, MerkleTreeKey};
+
+/// User data with a MAC of it under a secret key held by the operator, both of which are
+/// hashed into its leaf.
+///
+/// Like a salt, the MAC keeps third parties from brute-forcing small id/balance combinations
+/// from the sibling hashes of published proofs, since the MACs of other users cannot be
+/// computed without the key. Unlike a random salt, the MAC is derived from the data itself, so
+/// the operator can recompute it for any leaf from the key alone. The MAC must be handed to
+/// the user together with the proof, which verifies as usual.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Keyed<T> {
+    pub data: T,
+    pub mac: Vec<u8>,
+}
+
+impl<T: MerkleTreeData> Keyed<T> {
+    /// Attaches the HMAC of the serialized user data under a key.
+    ///
+    /// # Arguments
+    ///
+    /// * `data`: The user data.
+    /// * `key`: The secret key of the operator.
+    pub fn new<D: Digest + BlockSizeUser>(data: T, key: &[u8]) -> Self {
+        let mut hmac = Hmac::<D>::new(key);
+        data.hash_into(&mut hmac.inner);
+        Keyed {
+            mac: hmac.finalize().to_vec(),
+            data,
+        }
+    }
+}
+
+impl<T: MerkleTreeData> MerkleTreeData for Keyed<T> {
+    fn serialize(&self) -> Vec<u8> {
+        let mut bytes = self.data.serialize();
+        bytes.extend_from_slice(&self.mac);
+        bytes
+    }
+
+    fn hash_into(&self, hasher: &mut impl Digest) {
+        self.data.hash_into(hasher);
+        hasher.update(&self.mac);
+    }
+
+    fn mermaid_node_label(&self) -> String {
+        self.data.mermaid_node_label()
+    }
+}
+
+impl<B, T: MerkleSumData<B>> MerkleSumData<B> for Keyed<T> {
+    fn balance(&self) -> B {
+        self.data.balance()
+    }
+}
+
+impl<T: MerkleTreeKey> MerkleTreeKey for Keyed<T> {
+    type Key = T::Key;
+
+    fn key(&self) -> Self::Key {
+        self.data.key()
+    }
+}
+
+/// Attaches the HMAC under a secret key to every leaf.
+///
+/// # Arguments
+///
+/// * `input`: The user data to commit to.
+/// * `key`: The secret key of the operator.
+pub fn mac_with_key<T, D>(input: &[T], key: &[u8]) -> Vec<Keyed<T>>
+where
+    T: Clone + MerkleTreeData,
+    D: Digest + BlockSizeUser,
+{
+    input
+        .iter()
+        .map(|data| Keyed::new::<D>(data.clone(), key))
+        .collect()
+}
+
+/// Calculates the HMAC of a message under a key, as defined by RFC 2104.
+///
+/// # Arguments
+///
+/// * `key`: The secret key.
+/// * `message`: The message to authenticate.
+pub fn hmac<D: Digest + BlockSizeUser>(key: &[u8], message: &[u8]) -> Output<D> {
+    let mut hmac = Hmac::<D>::new(key);
+    hmac.inner.update(message);
+    hmac.finalize()
+}
+
+/// An HMAC being calculated, whose message is fed into the inner digest.
+struct Hmac<D> {
+    inner: D,
+    outer_pad: Vec<u8>,
+}
+
+impl<D: Digest + BlockSizeUser> Hmac<D> {
+    /// Starts an HMAC under a key.
+    fn new(key: &[u8]) -> Self {
+        let mut block = if key.len() > D::block_size() {
+            D::digest(key).to_vec()
+        } else {
+            key.to_vec()
+        };
+        block.resize(D::block_size(), 0);
+
+        let mut inner = D::new();
+        let mut inner_pad: Vec<u8> = block.iter().map(|byte| byte ^ 0x36).collect();
+        inner.update(&inner_pad);
+        let outer_pad = block.iter().map(|byte| byte ^ 0x5c).collect();
+        wipe(&mut inner_pad);
+        wipe(&mut block);
+
+        Hmac { inner, outer_pad }
+    }
+
+    /// Returns the HMAC of the message fed into the inner digest.
+    fn finalize(self) -> Output<D> {
+        let Hmac {
+            inner,
+            mut outer_pad,
+        } = self;
+        let mut outer = D::new();
+        outer.update(&outer_pad);
+        outer.update(inner.finalize());
+        wipe(&mut outer_pad);
+        outer.finalize()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{util, verify_proof, MerkleTree, Sha256};
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(
+        b"Jefe",
+        b"what do ya want for nothing?",
+        "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+    )]
+    #[case(
+        &[0xaa; 131],
+        b"Test Using Larger Than Block-Size Key - Hash Key First",
+        "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54"
+    )]
+    fn it_can_calculate_hmac(#[case] key: &[u8], #[case] message: &[u8], #[case] expected: &str) {
+        assert_eq!(hex::encode(hmac::<Sha256>(key, message)), expected);
+    }
+
+    #[test]
+    fn it_can_verify_proof_with_keyed_leaves() {
+        let user_data = util::generate_random_user_data(6);
+        let keyed = mac_with_key::<_, Sha256>(&user_data, b"operator key");
+        let tree: MerkleTree<_> =
+            MerkleTree::build("ProofOfReserve_Leaf", "ProofOfReserve_Branch", &keyed);
+        let root = tree.root().unwrap();
+
+        let (node, path) = tree.proof_by_index(4).unwrap();
+        let leaf = node.user_data.as_ref().unwrap();
+        assert_eq!(
+            leaf.mac,
+            hmac::<Sha256>(b"operator key", &user_data[4].serialize()).to_vec()
+        );
+        assert_eq!(
+            Keyed::new::<Sha256>(user_data[4].clone(), b"operator key").mac,
+            leaf.mac
+        );
+        assert!(verify_proof(
+            "ProofOfReserve_Leaf",
+            "ProofOfReserve_Branch",
+            &root,
+            leaf,
+            &path
+        ));
+
+        let other_key = Keyed::new::<Sha256>(leaf.data.clone(), b"guessed key");
+        assert!(!verify_proof(
+            "ProofOfReserve_Leaf",
+            "ProofOfReserve_Branch",
+            &root,
+            &other_key,
+            &path
+        ));
+    }
+}
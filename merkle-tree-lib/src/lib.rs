@@ -1,30 +1,171 @@
-use sha2::{Digest, Sha256};
-use std::fmt;
-
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+use alloc::collections::VecDeque;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+use core::hash::Hash;
+use core::marker::PhantomData;
+use core::ops::ControlFlow;
+use sha2::digest::consts::U32;
+use sha2::digest::{Output, OutputSizeUser};
+
+pub use sha2::{Digest, Sha224, Sha256, Sha384, Sha512};
+
+#[cfg(feature = "keccak")]
+pub use sha3::Keccak256;
+
+#[cfg(feature = "blake3")]
+pub use blake3::Hasher as Blake3;
+
+mod accumulator;
+mod balance;
+pub mod builder;
+#[cfg(feature = "std")]
+mod chunked;
+mod codec;
+mod compact_sparse;
+mod compressed;
+mod dag;
+#[cfg(feature = "std")]
+mod dedup;
+mod diff;
+mod dot;
+mod envelope;
+mod error;
+mod history;
+mod ics23;
+#[cfg(feature = "std")]
+mod indexed;
+#[cfg(feature = "json")]
+mod json;
+mod kary;
+mod keyed;
+#[cfg(feature = "std")]
+mod mapped;
+mod mermaid;
+mod mmr;
+mod multiproof;
+mod options;
+mod partial;
+mod patricia;
+#[cfg(feature = "poseidon")]
+mod poseidon;
+mod rlp;
+mod root;
+mod salted;
+mod scheme;
+#[cfg(feature = "zeroize")]
+mod secret;
+mod shared;
+mod shuffle;
+#[cfg(feature = "sled")]
+mod sled_store;
+mod solidity;
+mod sorted;
+mod sparse;
+mod split;
+mod stats;
+mod store;
+#[cfg(all(feature = "std", feature = "json"))]
+mod stream;
+mod sum_tree;
+mod transparency;
 pub mod util;
-
-type Hash = sha2::digest::Output<Sha256>;
-const SHA256_LEN: usize = std::mem::size_of::<Hash>();
+mod utreexo;
+mod version;
+mod witness;
+
+pub use accumulator::RootAccumulator;
+pub use balance::{AssetBalances, Balance, BigBalance, Decimal, ParseBalanceError};
+pub use builder::MerkleTreeBuilder;
+#[cfg(feature = "std")]
+pub use chunked::{verify_chunk_proof, verify_chunk_proof_with_options, ChunkProof, ChunkedTree};
+pub use codec::ProofCodecError;
+pub use compact_sparse::{
+    verify_compact_sparse_proof, verify_compact_sparse_proof_with_options, CompactSparseMerkleTree,
+    CompactSparseProof,
+};
+pub use compressed::{
+    verify_compressed_proof, verify_compressed_proof_with_options, CompressedProof,
+};
+pub use dag::{Cid, DagBlock, DagOptions, MerkleDag, DAG_PB_CODEC, RAW_CODEC};
+#[cfg(feature = "std")]
+pub use dedup::{DuplicateKeyError, DuplicatePolicy};
+pub use dot::DotOptions;
+pub use envelope::{envelop, LeafEnvelope};
+pub use error::MerkleError;
+pub use history::{
+    verify_ancestry_proof, verify_ancestry_proof_with_options, AncestryProof, HistoryTree,
+};
+pub use ics23::{ExistenceProof, HashOp, InnerOp, InnerSpec, LeafOp, LengthOp, ProofSpec};
+#[cfg(feature = "std")]
+pub use indexed::IndexedMerkleTree;
+#[cfg(feature = "json")]
+pub use json::JsonTreeError;
+pub use kary::{verify_kary_proof, verify_kary_proof_with_options, KaryMerkleTree, KaryProof};
+pub use keyed::{hmac, mac_with_key, Keyed};
+#[cfg(feature = "std")]
+pub use mapped::{MappedTree, TreeFileError};
+pub use mermaid::{MermaidDirection, MermaidOptions};
+pub use mmr::{verify_mmr_proof, verify_mmr_proof_with_options, MerkleMountainRange, MmrProof};
+pub use multiproof::{verify_multiproof, verify_multiproof_with_options, MultiProof};
+pub use options::{
+    EmptyRoot, HashMode, Padding, TagStyle, TreeOptions, BRANCH_PREFIX, LEAF_PREFIX,
+};
+pub use partial::PartialMerkleTree;
+pub use patricia::{verify_patricia_proof, PatriciaProof, PatriciaTrie};
+#[cfg(feature = "poseidon")]
+pub use poseidon::Poseidon;
+pub use root::{constant_time_eq, MerkleRoot};
+pub use salted::{salt_with_seed, Salted};
+pub use scheme::{ProofOfReserveScheme, TagScheme, TaggedProof, TaggedRoot, TaggedTree};
+pub use shared::SharedMerkleTree;
+pub use shuffle::shuffle_with_seed;
+#[cfg(feature = "sled")]
+pub use sled_store::SledNodeStore;
+pub use solidity::solidity_verifier;
+pub use sorted::{
+    verify_absence_proof, verify_absence_proof_with_options, AbsenceProof, SortedMerkleTree,
+};
+pub use sparse::{
+    verify_sparse_proof, verify_sparse_proof_with_options, SparseMerkleTree, SparseProof,
+    SPARSE_DEPTH,
+};
+pub use split::{split_balances, verify_split_proofs, verify_split_proofs_with_options, SplitLeaf};
+pub use stats::TreeStats;
+pub use store::{NodeStore, TreeMetadata};
+pub use sum_tree::{
+    verify_sum_proof, verify_sum_proof_with_options, MerkleSumData, SumMerkleTree, SumProof,
+};
+pub use transparency::{
+    verify_consistency_proof, verify_consistency_proof_with_options, verify_inclusion_proof,
+    verify_inclusion_proof_with_options,
+};
+pub use utreexo::{UtreexoAccumulator, UtreexoForest, UtreexoProof};
+pub use version::TreeVersion;
+pub use witness::CircuitWitness;
 
 #[derive(Clone, Default)]
-pub struct MerkleNode<T> {
-    hash: Hash,
+pub struct MerkleNode<T, D: Digest = Sha256> {
+    hash: Output<D>,
     left: Option<usize>,
     right: Option<usize>,
     pub user_data: Option<T>,
 }
 
-impl<T> MerkleNode<T>
-where
-    T: Clone + fmt::Debug,
-{
+impl<T, D: Digest> MerkleNode<T, D> {
     /// Creates a new leaf node with the given hash and user data.
     ///
     /// # Arguments
     ///
     /// * `hash`: The hash of the leaf node's data.
     /// * `user_data`: The user data associated with the leaf node.
-    fn new_leaf(hash: Hash, user_data: Option<T>) -> Self {
+    fn new_leaf(hash: Output<D>, user_data: Option<T>) -> Self {
         MerkleNode {
             hash,
             left: None,
@@ -32,525 +173,2890 @@ where
             user_data,
         }
     }
+
+    /// Returns the hash of the node as the digest's fixed-size output array.
+    pub fn hash(&self) -> &Output<D> {
+        &self.hash
+    }
+
+    /// Returns the index of the left child node, to be looked up with `MerkleTree::node`, or
+    /// `None` for a leaf.
+    pub fn left(&self) -> Option<usize> {
+        self.left
+    }
+
+    /// Returns the index of the right child node, to be looked up with `MerkleTree::node`, or
+    /// `None` for a leaf.
+    ///
+    /// With `Padding::DuplicateLast` the right child of a node over an unpaired node is the
+    /// same as its left child.
+    pub fn right(&self) -> Option<usize> {
+        self.right
+    }
+
+    /// Returns `true` if the node has no children.
+    pub fn is_leaf(&self) -> bool {
+        self.left.is_none() && self.right.is_none()
+    }
 }
 
-impl<T> MerkleTree<T> {
-    /// Creates a new branch node with the given left and right children and tag.
-    /// The hash of the branch node is calculated by concatenating the hashes of its children
-    /// and applying the `tagged_hash` function witsh the provided tag.
+impl<T, D: Digest, S: NodeStore<T, D>> MerkleTree<T, D, S> {
+    /// Builds a Merkle Tree from 32-byte leaf hashes that were computed elsewhere, such as
+    /// transaction ids or externally salted commitments.
+    ///
+    /// The leaf tag is not used since the leaves are not hashed again. The resulting leaves
+    /// carry no user data.
     ///
     /// # Arguments
     ///
-    /// * `left`: The left child node.
-    /// * `right`: The right child node.
-    /// * `tag`: The tag used for calculating the branch node's hash.
-    fn new_branch(&mut self, left: usize, right: usize, tag: &str) -> usize {
-        let mut combined = [0u8; SHA256_LEN * 2];
-        combined[..SHA256_LEN].copy_from_slice(&self.nodes[left].hash);
-        combined[SHA256_LEN..].copy_from_slice(&self.nodes[right].hash);
-        let hash = tagged_hash(tag, &combined);
-        let ret = self.nodes.len();
-        self.nodes.push(MerkleNode {
-            hash,
-            left: Some(left),
-            right: Some(right),
-            user_data: None,
-        });
-        ret
+    /// * `tag_branch`: The tag used for hashing branch nodes.
+    /// * `hashes`: The leaf hashes, in leaf order.
+    pub fn from_leaf_hashes(tag_branch: &str, hashes: &[[u8; 32]]) -> Self
+    where
+        D: OutputSizeUser<OutputSize = U32>,
+        S: Default,
+    {
+        let nodes = hashes
+            .iter()
+            .map(|hash| MerkleNode::new_leaf(Output::<D>::from(*hash), None))
+            .collect();
+
+        Self::from_leaf_nodes(TreeOptions::tagged("", tag_branch), nodes)
     }
-}
 
-impl<T> fmt::Display for MerkleNode<T>
-where
-    T: fmt::Display,
-{
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let formatted = self
-            .user_data
-            .as_ref()
-            .map_or(hex::encode(&self.hash), |user_data| {
-                format!("{} ({})", hex::encode(&self.hash), user_data)
-            });
+    /// Builds a Merkle Tree from leaf hashes that were computed elsewhere.
+    ///
+    /// The leaf hashes are used as they are, only the branch nodes are hashed according to
+    /// `options`. The resulting leaves carry no user data.
+    ///
+    /// # Arguments
+    ///
+    /// * `options`: The hashing configuration of the tree.
+    /// * `hashes`: The leaf hashes, in leaf order.
+    pub fn from_leaf_hashes_with_options(options: TreeOptions, hashes: &[Output<D>]) -> Self
+    where
+        S: Default,
+    {
+        let nodes = hashes
+            .iter()
+            .map(|hash| MerkleNode::new_leaf(hash.clone(), None))
+            .collect();
 
-        write!(f, "{}", formatted)
+        Self::from_leaf_nodes(options, nodes)
     }
-}
-
-#[derive(Debug, Clone, PartialEq)]
-pub enum NodeDirection {
-    Left,
-    Right,
-    Root,
-}
 
-impl NodeDirection {
-    fn value(&self) -> u8 {
-        match self {
-            NodeDirection::Left => 0,
-            NodeDirection::Right => 1,
-            NodeDirection::Root => 2,
-        }
+    /// Builds a Merkle Tree over raw byte records without implementing `MerkleTreeData`.
+    ///
+    /// Each record is hashed as a leaf as it is. The resulting leaves carry no user data.
+    ///
+    /// # Arguments
+    ///
+    /// * `tag_leaf`: The tag used for hashing leaf nodes.
+    /// * `tag_branch`: The tag used for hashing branch nodes.
+    /// * `leaves`: The serialized leaf records, in leaf order.
+    pub fn from_leaf_bytes<'a, I>(tag_leaf: &str, tag_branch: &str, leaves: I) -> Self
+    where
+        I: IntoIterator<Item = &'a [u8]>,
+        S: Default,
+    {
+        Self::from_leaf_bytes_with_options(TreeOptions::tagged(tag_leaf, tag_branch), leaves)
     }
-}
 
-impl fmt::Display for NodeDirection {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            NodeDirection::Left => write!(f, "Left"),
-            NodeDirection::Right => write!(f, "Right"),
-            NodeDirection::Root => write!(f, "Root"),
-        }
+    /// Builds a Merkle Tree over raw byte records with the given hashing configuration.
+    ///
+    /// # Arguments
+    ///
+    /// * `options`: The hashing configuration of the tree.
+    /// * `leaves`: The serialized leaf records, in leaf order.
+    pub fn from_leaf_bytes_with_options<'a, I>(options: TreeOptions, leaves: I) -> Self
+    where
+        I: IntoIterator<Item = &'a [u8]>,
+        S: Default,
+    {
+        let nodes = leaves
+            .into_iter()
+            .map(|bytes| MerkleNode::new_leaf(options.hash_leaf::<D>(bytes), None))
+            .collect();
+
+        Self::from_leaf_nodes(options, nodes)
     }
-}
 
-#[derive(Debug, Clone)]
-pub struct TraversePath {
-    pub hashes: Vec<String>,
-    pub directions: Vec<NodeDirection>,
-}
+    /// Builds the branch levels on top of the given leaf nodes.
+    ///
+    /// # Arguments
+    ///
+    /// * `options`: The hashing configuration of the tree.
+    /// * `nodes`: The leaf nodes, in leaf order.
+    fn from_leaf_nodes(options: TreeOptions, nodes: Vec<MerkleNode<T, D>>) -> Self
+    where
+        S: Default,
+    {
+        let mut tree = Self::from_parts(options, S::default(), TreeMetadata::default());
+        tree.build_levels(nodes);
+        tree
+    }
 
-impl TraversePath {
-    fn new() -> Self {
-        TraversePath {
-            hashes: Vec::new(),
-            directions: Vec::new(),
+    /// Creates an empty tree whose nodes are kept in the given store, such as one backed by
+    /// external storage. Leaves are added with `push`.
+    ///
+    /// # Arguments
+    ///
+    /// * `options`: The hashing configuration of the tree.
+    /// * `store`: An empty node store.
+    ///
+    /// # Returns
+    ///
+    /// The tree, or `MerkleError::InvalidStore` if the store already holds nodes, which are
+    /// reattached with `from_store` instead.
+    pub fn with_store(options: TreeOptions, store: S) -> Result<Self, MerkleError> {
+        if !store.is_empty() {
+            return Err(MerkleError::InvalidStore);
         }
+        Ok(Self::from_parts(options, store, TreeMetadata::default()))
     }
 
-    /// Adds a step to the `TraversePath`.
+    /// Reattaches a tree to a store that holds its nodes, such as one reopened from external
+    /// storage after a restart.
     ///
     /// # Arguments
     ///
-    /// * `hash`: The hash of the node visited in this step.
-    /// * `direction`: The direction taken to reach the node (Left or Right).
-    fn add_step(&mut self, hash: String, direction: NodeDirection) {
-        self.hashes.push(hash);
-        self.directions.push(direction);
+    /// * `options`: The hashing configuration the tree was built with.
+    /// * `store`: The node store of the tree.
+    /// * `metadata`: The metadata the tree returned from `metadata` when its nodes were
+    ///   persisted.
+    ///
+    /// # Returns
+    ///
+    /// The tree, or `MerkleError::InvalidStore` if the metadata points outside the store or
+    /// does not fit the number of leaves.
+    pub fn from_store(
+        options: TreeOptions,
+        store: S,
+        metadata: TreeMetadata,
+    ) -> Result<Self, MerkleError> {
+        let in_store = |node: Option<usize>| node.is_none_or(|node| node < store.len());
+        if !in_store(metadata.root)
+            || !in_store(metadata.empty_leaf)
+            || metadata.root.is_none() != (metadata.leaf_count == 0)
+            || metadata.leaf_count > store.len()
+        {
+            return Err(MerkleError::InvalidStore);
+        }
+        Ok(Self::from_parts(options, store, metadata))
     }
 
-    /// Converts the `TraversePath` to a vector of (hash, direction) tuples.
-    /// The direction is represented as a `u8` (0 for Left, 1 for Right, 2 for Root).
+    /// Creates a tree from its parts without checking them.
     ///
-    /// # Returns
+    /// # Arguments
     ///
-    /// A `Vec<(String, u8)>` representing the path.
-    pub fn to_vec(&self) -> Vec<(String, u8)> {
-        self.hashes
-            .iter()
-            .zip(self.directions.iter())
-            .map(|(hash, direction)| (hash.to_string(), direction.value()))
-            .collect()
+    /// * `options`: The hashing configuration of the tree.
+    /// * `store`: The node store of the tree.
+    /// * `metadata`: The positions of the root and the padding leaf, and the number of leaves.
+    fn from_parts(options: TreeOptions, store: S, metadata: TreeMetadata) -> Self {
+        Self {
+            root: metadata.root,
+            nodes: store,
+            options,
+            leaf_count: metadata.leaf_count,
+            empty_leaf: metadata.empty_leaf,
+            generation: 0,
+            pinned: 0,
+            node: PhantomData,
+        }
     }
-}
 
-pub struct MerkleTree<T> {
-    root: Option<usize>,
-    nodes: Vec<MerkleNode<T>>,
-}
+    /// Returns what a tree needs besides its nodes to be reattached to its store with
+    /// `from_store`, to be persisted together with the store.
+    pub fn metadata(&self) -> TreeMetadata {
+        TreeMetadata {
+            root: self.root,
+            leaf_count: self.leaf_count,
+            empty_leaf: self.empty_leaf,
+        }
+    }
 
-struct TraverseStep {
-    parent_node: Option<usize>,
-    current_node: usize,
-    level: u32,
-    direction: NodeDirection,
-}
+    /// Returns the store that holds the nodes of the tree.
+    pub fn store(&self) -> &S {
+        &self.nodes
+    }
 
-pub trait MerkleTreeData {
-    fn serialize(&self) -> Vec<u8>;
-    fn mermaid_node_label(&self) -> String;
-}
+    /// Releases the memory the store keeps to speed up reads, see `NodeStore::release_cache`.
+    ///
+    /// A workload that only queries a tree in external storage calls it from time to time,
+    /// as nothing else borrows the store mutably then.
+    pub fn release_cache(&mut self) {
+        self.nodes.release_cache();
+    }
 
-impl<T> MerkleTree<T>
-where
-    T: Clone + fmt::Debug + MerkleTreeData + Default,
-{
-    /// Builds a Merkle Tree from the given user data.
+    /// Stores the given leaf nodes and builds the branch levels on top of them, replacing the
+    /// tree. The node store must be empty.
     ///
     /// # Arguments
     ///
-    /// * `tag_leaf`: The tag used for hashing leaf nodes.
-    /// * `tag_branch`: The tag used for hashing branch nodes.
-    /// * `user_data`: A slice of tuples, where each tuple contains a user ID and balance.
-    pub fn build(tag_leaf: &str, tag_branch: &str, input: &Vec<T>) -> Self {
-        if input.is_empty() {
-            return MerkleTree {
-                root: None,
-                nodes: vec![],
-            };
+    /// * `nodes`: The leaf nodes, in leaf order.
+    fn build_levels(&mut self, nodes: Vec<MerkleNode<T, D>>) {
+        self.leaf_count = nodes.len();
+        self.empty_leaf = None;
+        for node in nodes {
+            self.nodes.push(node);
         }
 
-        let nodes: Vec<MerkleNode<T>> = input
-            .iter()
-            .map(|data| {
-                MerkleNode::new_leaf(
-                    tagged_hash(tag_leaf, data.serialize().as_slice()),
-                    Some(data.clone()),
-                )
+        let tree = self;
+        let mut level: Vec<usize> = (0..tree.nodes.len()).collect();
+
+        while level.len() > 1 {
+            let mut next_level = Vec::with_capacity(level.len().div_ceil(2));
+
+            for pair in level.chunks(2) {
+                let parent = match *pair {
+                    [left, right] => tree.new_branch(left, right),
+                    [single] => match tree.options.padding {
+                        Padding::DuplicateLast => tree.new_branch(single, single),
+                        Padding::Promote => single,
+                        Padding::PadWithEmpty => {
+                            let empty = tree.empty_leaf();
+                            tree.new_branch(single, empty)
+                        }
+                    },
+                    _ => unreachable!(),
+                };
+                next_level.push(parent);
+            }
+
+            level = next_level;
+        }
+
+        tree.root = level.first().copied();
+    }
+
+    /// Returns the leaf node without user data whose hash is the hash of empty input, creating
+    /// it on first use. It is shared as the sibling of unpaired nodes with
+    /// `Padding::PadWithEmpty`.
+    fn empty_leaf(&mut self) -> usize {
+        if let Some(empty) = self.empty_leaf {
+            return empty;
+        }
+
+        let hash = self.options.hash_leaf::<D>(&[]);
+        self.nodes.push(MerkleNode::new_leaf(hash, None));
+        self.empty_leaf = Some(self.nodes.len() - 1);
+        self.nodes.len() - 1
+    }
+
+    /// Appends a leaf node and recomputes the branch nodes on its path to the root.
+    ///
+    /// Only the last node of each level can change when a leaf is appended. They are found in
+    /// a single walk down from the root, and O(log n) hashes are computed. Branch nodes of the old tree that are replaced are overwritten in
+    /// place, unless an earlier version can still reach them, see `replace_node`.
+    ///
+    /// # Arguments
+    ///
+    /// * `leaf`: The new leaf node.
+    fn push_leaf_node(&mut self, leaf: MerkleNode<T, D>) {
+        let index = self.leaf_count;
+        let spine = self.spine().unwrap_or_default();
+
+        // The last node of a level is replaced if the new leaf falls below it as well.
+        let replaced: Vec<Option<usize>> = (0..spine.len())
+            .map(|level| (index >> level == (index - 1) >> level).then(|| spine[level]))
+            .collect();
+
+        // The left siblings are roots of full subtrees, which the push leaves unchanged. A
+        // left sibling is the last node of its level, or the left child of the parent of it.
+        let left_siblings: Vec<Option<usize>> = (0..spine.len())
+            .map(|level| {
+                let position = index >> level;
+                if position & 1 == 0 {
+                    None
+                } else if position - 1 == (index - 1) >> level {
+                    Some(spine[level])
+                } else {
+                    self.nodes[spine[level + 1]].left
+                }
             })
             .collect();
 
-        let mut tree = Self { root: None, nodes };
+        self.nodes.push(leaf);
+        self.leaf_count += 1;
 
-        let mut start = 0;
+        let level_sizes = self.level_sizes();
+        let mut node = self.nodes.len() - 1;
 
-        while tree.nodes.len() - start > 1 {
-            let next_start = tree.nodes.len();
-            for i in (start..tree.nodes.len()).step_by(2) {
-                let left = i;
-                let right = (i + 1).min(next_start - 1);
+        for level in 0..level_sizes.len() - 1 {
+            let position = index >> level;
 
-                tree.new_branch(left, right, tag_branch);
-            }
-            start = next_start;
+            // A node promoted from the level below is still in use there, either on the path
+            // or as the left sibling.
+            let slot = replaced.get(level + 1).copied().flatten().filter(|&slot| {
+                Some(slot) != replaced[level]
+                    && Some(slot) != left_siblings[level]
+                    && self.nodes[slot].left.is_some()
+            });
+
+            node = if position & 1 == 1 {
+                let left = left_siblings[level].unwrap();
+                self.replace_branch(slot, left, node)
+            } else {
+                match self.options.padding {
+                    Padding::DuplicateLast => self.replace_branch(slot, node, node),
+                    Padding::Promote => node,
+                    Padding::PadWithEmpty => {
+                        let empty = self.empty_leaf();
+                        self.replace_branch(slot, node, empty)
+                    }
+                }
+            };
         }
 
-        tree.root = Some(tree.nodes.len() - 1);
-        tree
+        self.root = Some(node);
+    }
+
+    /// Returns the hashing configuration the tree was built with.
+    pub fn options(&self) -> &TreeOptions {
+        &self.options
     }
 
     /// Returns the hash of the root node of the Merkle Tree.
-    pub fn root(&self) -> Option<String> {
-        self.root.map(|node| hex::encode(&self.nodes[node].hash))
+    ///
+    /// A tree without leaves has the root selected by `TreeOptions::empty_root`.
+    ///
+    /// # Returns
+    ///
+    /// The hex encoded root, or `MerkleError::EmptyTree` if the tree has no leaves and the
+    /// empty root is `EmptyRoot::Undefined`.
+    pub fn root(&self) -> Result<String, MerkleError> {
+        self.root_hash().map(hex::encode)
     }
 
-    /// Iterates over the tree level by level and applies the given function to each node.
+    /// Returns the hash of the root node as the digest's fixed-size output array, without
+    /// hex encoding it.
+    ///
+    /// # Returns
+    ///
+    /// The root hash, or `MerkleError::EmptyTree` if the tree has no leaves and the empty root
+    /// is `EmptyRoot::Undefined`.
+    pub fn root_hash(&self) -> Result<Output<D>, MerkleError> {
+        match self.root {
+            Some(node) => Ok(self.nodes[node].hash.clone()),
+            None => self
+                .options
+                .empty_root_hash::<D>()
+                .ok_or(MerkleError::EmptyTree),
+        }
+    }
+
+    /// Returns the number of leaves in the tree.
+    pub fn leaf_count(&self) -> usize {
+        self.leaf_count
+    }
+
+    /// Returns the index of the root node, to be looked up with `node`.
+    pub fn root_index(&self) -> Option<usize> {
+        self.root
+    }
+
+    /// Returns the node at the given index of the tree's node storage.
+    ///
+    /// Together with `root_index`, `MerkleNode::left` and `MerkleNode::right` this allows
+    /// walking the structure of the tree.
     ///
     /// # Arguments
     ///
-    /// * `map_fn`: A function that takes a `&TraverseStep` and returns a String.
-    ///              This function is called for each node in the tree.
+    /// * `index`: The index of the node.
+    pub fn node(&self, index: usize) -> Option<&MerkleNode<T, D>> {
+        self.nodes.get(index)
+    }
+
+    /// Generates the proof for the leaf at the given index.
+    ///
+    /// The leaf is located by walking down from the root along the bits of the index, so
+    /// this takes O(log n) time unlike the predicate search of `search_with_path`.
+    ///
+    /// # Arguments
+    ///
+    /// * `index`: The position of the leaf in the input the tree was built from.
     ///
     /// # Returns
     ///
-    /// An `Option` containing a `Vec<String>` if the tree is not empty, `None` otherwise.
-    /// Each string in the vector is the result of applying `map_fn` to a node.
-    fn iterate_tree(&self, map_fn: impl Fn(&TraverseStep) -> String) -> Option<Vec<String>> {
-        self.root.map(|root| {
-            let mut output = Vec::new();
+    /// A tuple of `(&MerkleNode, TraversePath)`, or `MerkleError::LeafNotFound` if the index
+    /// is out of range.
+    pub fn proof_by_index(
+        &self,
+        index: usize,
+    ) -> Result<(&MerkleNode<T, D>, TraversePath<D>), MerkleError> {
+        self.proof_in(self.root, self.leaf_count, index)
+    }
 
-            let mut stack: Vec<TraverseStep> = vec![TraverseStep {
-                parent_node: None,
-                current_node: root,
-                level: 0,
-                direction: NodeDirection::Root,
-            }];
+    /// Generates the proof for the leaf at the given index of the tree with the given root,
+    /// which may be an earlier version of this tree.
+    ///
+    /// # Arguments
+    ///
+    /// * `root`: The root node of the tree.
+    /// * `leaf_count`: The number of leaves below `root`.
+    /// * `index`: The position of the leaf.
+    fn proof_in(
+        &self,
+        root: Option<usize>,
+        leaf_count: usize,
+        index: usize,
+    ) -> Result<(&MerkleNode<T, D>, TraversePath<D>), MerkleError> {
+        if index >= leaf_count {
+            return Err(MerkleError::LeafNotFound);
+        }
 
-            while let Some(step) = stack.pop() {
-                output.push(map_fn(&step));
+        let level_sizes = level_sizes(leaf_count);
+        let mut node = root.ok_or(MerkleError::EmptyTree)?;
+        let mut path = TraversePath::new();
 
-                if let Some(right) = self.nodes[step.current_node].right {
-                    stack.push(TraverseStep {
-                        parent_node: Some(step.current_node),
-                        current_node: right,
-                        level: step.level + 1,
-                        direction: NodeDirection::Right,
-                    });
-                }
+        for level in (0..level_sizes.len() - 1).rev() {
+            let position = index >> level;
+            let unpaired = position & 1 == 0 && position == level_sizes[level] - 1;
+            if unpaired && self.options.padding == Padding::Promote {
+                continue;
+            }
 
-                if let Some(left) = self.nodes[step.current_node].left {
-                    stack.push(TraverseStep {
-                        parent_node: Some(step.current_node),
-                        current_node: left,
-                        level: step.level + 1,
-                        direction: NodeDirection::Left,
-                    });
-                }
+            let (left, right) = match (self.nodes[node].left, self.nodes[node].right) {
+                (Some(left), Some(right)) => (left, right),
+                _ => return Err(MerkleError::LeafNotFound),
+            };
+
+            if position & 1 == 0 {
+                path.add_step(hex::encode(&self.nodes[right].hash), NodeDirection::Left);
+                node = left;
+            } else {
+                path.add_step(hex::encode(&self.nodes[left].hash), NodeDirection::Right);
+                node = right;
             }
+        }
 
-            output
-        })
+        Ok((&self.nodes[node], path))
     }
 
-    /// Displays the Merkle Tree in an indented format.
-    pub fn display_tree(&self) -> String {
-        match self.iterate_tree(|step| {
-            let indent = " ".repeat(step.level as usize);
-            format!(
-                "{}{}: {}",
-                indent,
-                step.direction,
-                truncate_middle(
-                    hex::encode(&self.nodes[step.current_node].hash).as_str(),
-                    10
-                )
-            )
-        }) {
-            Some(output) => output.join("\n"),
-            None => format!("Tree is empty."),
-        }
+    /// Returns the leaf node at the given index.
+    ///
+    /// # Arguments
+    ///
+    /// * `index`: The position of the leaf in the input the tree was built from.
+    ///
+    /// # Returns
+    ///
+    /// The leaf node, or `MerkleError::LeafNotFound` if the index is out of range.
+    pub fn leaf(&self, index: usize) -> Result<&MerkleNode<T, D>, MerkleError> {
+        self.node_at(0, index)
+            .map(|node| &self.nodes[node])
+            .ok_or(MerkleError::LeafNotFound)
     }
 
-    /// Displays the Merkle Tree as a Mermaid diagram.
-    /// Use the mermaid editor to visualize the diagram https://mermaid.live/
-    pub fn display_mermaid_diagram(&self) -> String {
-        match self.iterate_tree(|step| {
-            let current_node_hash = hex::encode(&self.nodes[step.current_node].hash);
-            let truncated_current_node_hash = truncate_middle(current_node_hash.as_str(), 10);
-            let current_node_label = (self.nodes[step.current_node].user_data.as_ref())
-                .map_or(String::from(""), |item| item.mermaid_node_label());
-            println!("{current_node_label} lable");
-            let node_mermaid = format!(
-                "Node_{current_node_hash}[{truncated_current_node_hash}{current_node_label}]",
-            );
+    /// Returns an iterator over the user data of the leaves, in leaf order.
+    ///
+    /// Leaves built from bare hashes, such as with `from_leaf_hashes_with_options`, carry no
+    /// user data and are skipped, but the indices of the other leaves are kept.
+    ///
+    /// # Returns
+    ///
+    /// An iterator of `(index, &T)` tuples, where `index` is the position of the leaf.
+    pub fn leaves(&self) -> impl Iterator<Item = (usize, &T)> + '_ {
+        self.leaf_nodes()
+            .into_iter()
+            .enumerate()
+            .filter_map(|(index, node)| Some((index, self.nodes[node].user_data.as_ref()?)))
+    }
 
-            let node_connection_mermaid = (step.direction != NodeDirection::Root)
-                .then(|| {
-                    let parent_node_hash = hex::encode(&self.nodes[step.parent_node.unwrap()].hash);
+    /// Consumes the tree and returns an iterator over the owned user data of the leaves, in
+    /// leaf order.
+    ///
+    /// # Returns
+    ///
+    /// An iterator of `(index, T)` tuples, where `index` is the position of the leaf.
+    pub fn into_leaves(mut self) -> impl Iterator<Item = (usize, T)> {
+        let leaves = self.leaf_nodes();
+        let mut nodes: Vec<Option<T>> = (self.nodes.take_nodes().into_iter())
+            .map(|node| node.user_data)
+            .collect();
 
-                    format!("\nNode_{} --> Node_{}", parent_node_hash, current_node_hash)
-                })
-                .unwrap_or_default();
+        leaves
+            .into_iter()
+            .enumerate()
+            .filter_map(move |(index, node)| Some((index, nodes[node].take()?)))
+    }
+
+    /// Returns an iterator over the nodes of the tree level by level, from the root down and
+    /// from left to right within a level.
+    ///
+    /// A node that is a child of its parent twice, as with `Padding::DuplicateLast`, is
+    /// visited twice.
+    ///
+    /// # Returns
+    ///
+    /// An iterator of `(&MerkleNode, depth)` tuples, where the root has depth 0.
+    pub fn iter_level_order(&self) -> impl Iterator<Item = (&MerkleNode<T, D>, usize)> + '_ {
+        let mut queue: VecDeque<(usize, usize)> =
+            self.root.map(|root| (root, 0)).into_iter().collect();
+
+        core::iter::from_fn(move || {
+            let (node, depth) = queue.pop_front()?;
+            queue.extend(self.children(node).map(|child| (child, depth + 1)));
+            Some((&self.nodes[node], depth))
+        })
+    }
+
+    /// Returns an iterator over the nodes of the tree in depth-first pre-order, visiting a
+    /// node before its left and right subtrees.
+    ///
+    /// # Returns
+    ///
+    /// An iterator of `(&MerkleNode, depth)` tuples, where the root has depth 0.
+    pub fn iter_pre_order(&self) -> impl Iterator<Item = (&MerkleNode<T, D>, usize)> + '_ {
+        let mut stack: Vec<(usize, usize)> = self.root.map(|root| (root, 0)).into_iter().collect();
+
+        core::iter::from_fn(move || {
+            let (node, depth) = stack.pop()?;
+            stack.extend(self.children(node).rev().map(|child| (child, depth + 1)));
+            Some((&self.nodes[node], depth))
+        })
+    }
+
+    /// Returns an iterator over the nodes of the tree in depth-first post-order, visiting a
+    /// node after its left and right subtrees, so the root comes last.
+    ///
+    /// # Returns
+    ///
+    /// An iterator of `(&MerkleNode, depth)` tuples, where the root has depth 0.
+    pub fn iter_post_order(&self) -> impl Iterator<Item = (&MerkleNode<T, D>, usize)> + '_ {
+        // The flag records whether the children of the node have already been pushed.
+        let mut stack: Vec<(usize, usize, bool)> =
+            self.root.map(|root| (root, 0, false)).into_iter().collect();
+
+        core::iter::from_fn(move || loop {
+            let (node, depth, expanded) = stack.pop()?;
+            if expanded {
+                return Some((&self.nodes[node], depth));
+            }
+            stack.push((node, depth, true));
+            stack.extend(
+                self.children(node)
+                    .rev()
+                    .map(|child| (child, depth + 1, false)),
+            );
+        })
+    }
+
+    /// Returns the left and right children of a node, or nothing for a leaf.
+    fn children(&self, node: usize) -> impl DoubleEndedIterator<Item = usize> {
+        let children = match (self.nodes[node].left, self.nodes[node].right) {
+            (Some(left), Some(right)) => vec![left, right],
+            _ => Vec::new(),
+        };
+        children.into_iter()
+    }
+
+    /// Walks the tree depth-first in pre-order and calls the visitor on each node.
+    ///
+    /// The visitor decides whether to descend: returning `ControlFlow::Continue(())` visits
+    /// the children of the node next, while `ControlFlow::Break(())` prunes its subtree and
+    /// moves on to the next node.
+    ///
+    /// # Arguments
+    ///
+    /// * `visitor`: A function that takes the node, its depth, with the root at 0, and its
+    ///   position among the nodes at that depth, counting from the left as if the tree was
+    ///   complete. The bits of the position are the directions taken from the root.
+    pub fn traverse<F>(&self, mut visitor: F)
+    where
+        F: FnMut(&MerkleNode<T, D>, usize, usize) -> ControlFlow<()>,
+    {
+        let mut stack: Vec<(usize, usize, usize)> =
+            self.root.map(|root| (root, 0, 0)).into_iter().collect();
+
+        while let Some((node, depth, position)) = stack.pop() {
+            if visitor(&self.nodes[node], depth, position).is_break() {
+                continue;
+            }
+
+            if let (Some(left), Some(right)) = (self.nodes[node].left, self.nodes[node].right) {
+                stack.push((right, depth + 1, position << 1 | 1));
+                stack.push((left, depth + 1, position << 1));
+            }
+        }
+    }
+
+    /// Removes the leaf at the given index.
+    ///
+    /// The tree is compacted rather than tombstoned: the remaining leaves are rebuilt into a
+    /// new tree, so the leaves after `index` move down by one position and the root is the
+    /// same as building a tree without the removed leaf. This takes O(n) time and invalidates
+    /// the earlier versions of the tree.
+    ///
+    /// # Arguments
+    ///
+    /// * `index`: The position of the leaf to remove.
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` if the leaf was removed, or `MerkleError::LeafNotFound` if the index is out of
+    /// range.
+    pub fn remove(&mut self, index: usize) -> Result<(), MerkleError> {
+        if index >= self.leaf_count {
+            return Err(MerkleError::LeafNotFound);
+        }
+
+        let mut leaves = self.leaf_nodes();
+        leaves.remove(index);
+
+        let mut nodes: Vec<Option<MerkleNode<T, D>>> =
+            self.nodes.take_nodes().into_iter().map(Some).collect();
+        let leaves = leaves
+            .into_iter()
+            .filter_map(|leaf| nodes[leaf].take())
+            .collect();
+
+        self.generation += 1;
+        self.pinned = 0;
+        self.build_levels(leaves);
+        Ok(())
+    }
+
+    /// Returns the leaf nodes in leaf order.
+    fn leaf_nodes(&self) -> Vec<usize> {
+        let mut leaves = Vec::with_capacity(self.leaf_count);
+        let mut stack: Vec<usize> = self.root.into_iter().collect();
+
+        while let Some(node) = stack.pop() {
+            match (self.nodes[node].left, self.nodes[node].right) {
+                (Some(left), Some(right)) => {
+                    // Skip the duplicated node and the empty leaf used for padding.
+                    if right != left && Some(right) != self.empty_leaf {
+                        stack.push(right);
+                    }
+                    stack.push(left);
+                }
+                _ => leaves.push(node),
+            }
+        }
+
+        leaves
+    }
+
+    /// Replaces the hash and user data of a leaf node and rehashes its ancestors.
+    ///
+    /// The leaf and its ancestors are overwritten in place, unless an earlier version can
+    /// still reach them, in which case they are added as new nodes so that the earlier version
+    /// stays intact and shares the unchanged subtrees. See `replace_node`.
+    ///
+    /// # Arguments
+    ///
+    /// * `index`: The position of the leaf.
+    /// * `hash`: The new hash of the leaf.
+    /// * `user_data`: The new user data of the leaf.
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` if the leaf was updated, or `MerkleError::LeafNotFound` if the index is out of
+    /// range.
+    fn update_leaf_node(
+        &mut self,
+        index: usize,
+        hash: Output<D>,
+        user_data: Option<T>,
+    ) -> Result<(), MerkleError> {
+        if index >= self.leaf_count {
+            return Err(MerkleError::LeafNotFound);
+        }
+
+        let level_sizes = self.level_sizes();
+        let mut node = self.root.ok_or(MerkleError::EmptyTree)?;
+        let mut ancestors = Vec::with_capacity(level_sizes.len());
+
+        for level in (0..level_sizes.len() - 1).rev() {
+            let position = index >> level;
+            let unpaired = position & 1 == 0 && position == level_sizes[level] - 1;
+            if unpaired && self.options.padding == Padding::Promote {
+                continue;
+            }
+
+            ancestors.push(node);
+            let next = if position & 1 == 0 {
+                self.nodes[node].left
+            } else {
+                self.nodes[node].right
+            };
+            node = next.ok_or(MerkleError::LeafNotFound)?;
+        }
+
+        let mut copied = self.replace_node(node, MerkleNode::new_leaf(hash, user_data));
+
+        for &ancestor in ancestors.iter().rev() {
+            let (Some(left), Some(right)) = (self.nodes[ancestor].left, self.nodes[ancestor].right)
+            else {
+                continue;
+            };
+            // A node duplicated for padding is replaced on both sides.
+            let left = if left == node { copied } else { left };
+            let right = if right == node { copied } else { right };
+            node = ancestor;
+            copied = self.replace_branch(Some(ancestor), left, right);
+        }
+
+        self.root = Some(copied);
+        Ok(())
+    }
+
+    /// Finds the last node of every level by walking down from the root to the last leaf.
+    ///
+    /// # Returns
+    ///
+    /// The nodes, indexed by level with 0 being the leaves, or `None` if the tree is empty.
+    fn spine(&self) -> Option<Vec<usize>> {
+        let level_sizes = self.level_sizes();
+        let mut node = self.root?;
+        let mut spine = vec![node; level_sizes.len()];
+
+        for level in (0..level_sizes.len() - 1).rev() {
+            let position = level_sizes[level] - 1;
+            let unpaired = position & 1 == 0;
+            if !(unpaired && self.options.padding == Padding::Promote) {
+                node = if position & 1 == 0 {
+                    self.nodes[node].left?
+                } else {
+                    self.nodes[node].right?
+                };
+            }
+            spine[level] = node;
+        }
+
+        Some(spine)
+    }
+
+    /// Finds the node at the given position of the given level by walking down from the root.
+    ///
+    /// # Arguments
+    ///
+    /// * `level`: The level of the node, 0 being the leaves.
+    /// * `position`: The position of the node within its level.
+    fn node_at(&self, level: usize, position: usize) -> Option<usize> {
+        self.node_in(self.root, self.leaf_count, level, position)
+    }
+
+    /// Finds the node at the given position of the given level of the tree with the given
+    /// root, which may be an earlier version of this tree.
+    ///
+    /// # Arguments
+    ///
+    /// * `root`: The root node of the tree.
+    /// * `leaf_count`: The number of leaves below `root`.
+    /// * `level`: The level of the node, 0 being the leaves.
+    /// * `position`: The position of the node within its level.
+    fn node_in(
+        &self,
+        root: Option<usize>,
+        leaf_count: usize,
+        level: usize,
+        position: usize,
+    ) -> Option<usize> {
+        let level_sizes = level_sizes(leaf_count);
+        if position >= *level_sizes.get(level)? {
+            return None;
+        }
+
+        let mut node = root?;
+
+        for current in (level..level_sizes.len() - 1).rev() {
+            let child_position = position >> (current - level);
+            let unpaired = child_position & 1 == 0 && child_position == level_sizes[current] - 1;
+            if unpaired && self.options.padding == Padding::Promote {
+                continue;
+            }
+
+            node = if child_position & 1 == 0 {
+                self.nodes[node].left?
+            } else {
+                self.nodes[node].right?
+            };
+        }
+
+        Some(node)
+    }
+
+    /// Returns the number of nodes on each level of the tree, from the leaves up to the root.
+    fn level_sizes(&self) -> Vec<usize> {
+        level_sizes(self.leaf_count)
+    }
+
+    /// Creates a new branch node with the given left and right children.
+    /// The hash of the branch node is calculated from the hashes of its children
+    /// according to the tree's `TreeOptions`.
+    ///
+    /// # Arguments
+    ///
+    /// * `left`: The left child node.
+    /// * `right`: The right child node.
+    fn new_branch(&mut self, left: usize, right: usize) -> usize {
+        let hash = self
+            .options
+            .hash_branch::<D>(&self.nodes[left].hash, &self.nodes[right].hash);
+        let ret = self.nodes.len();
+        self.nodes.push(MerkleNode {
+            hash,
+            left: Some(left),
+            right: Some(right),
+            user_data: None,
+        });
+        ret
+    }
+
+    /// Creates a branch node with the given left and right children in place of the given
+    /// node, or as a new node if there is no node to replace.
+    ///
+    /// # Arguments
+    ///
+    /// * `slot`: The branch node to replace, if any.
+    /// * `left`: The left child node.
+    /// * `right`: The right child node.
+    fn replace_branch(&mut self, slot: Option<usize>, left: usize, right: usize) -> usize {
+        let Some(slot) = slot else {
+            return self.new_branch(left, right);
+        };
+
+        let hash = self
+            .options
+            .hash_branch::<D>(&self.nodes[left].hash, &self.nodes[right].hash);
+        self.replace_node(
+            slot,
+            MerkleNode {
+                hash,
+                left: Some(left),
+                right: Some(right),
+                user_data: None,
+            },
+        )
+    }
+
+    /// Stores a node that replaces the given one in the current version of the tree.
+    ///
+    /// The node is overwritten in place if no version handed out by `version` can reach it
+    /// and the store supports it, so a tree that is changed without taking versions does not
+    /// grow. Otherwise the node is added as a new node and the old one is kept until
+    /// `compact`.
+    ///
+    /// # Arguments
+    ///
+    /// * `old`: The node to replace.
+    /// * `node`: The new node.
+    ///
+    /// # Returns
+    ///
+    /// The position of the new node.
+    fn replace_node(&mut self, old: usize, node: MerkleNode<T, D>) -> usize {
+        if old >= self.pinned {
+            if let Some(slot) = self.nodes.get_mut(old) {
+                *slot = node;
+                return old;
+            }
+        }
+
+        self.nodes.push(node);
+        self.nodes.len() - 1
+    }
+}
+
+impl<T, D> fmt::Display for MerkleNode<T, D>
+where
+    T: fmt::Display,
+    D: Digest,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let formatted = self
+            .user_data
+            .as_ref()
+            .map_or(hex::encode(&self.hash), |user_data| {
+                format!("{} ({})", hex::encode(&self.hash), user_data)
+            });
+
+        write!(f, "{}", formatted)
+    }
+}
+
+/// Renders the tree with `MerkleTree::render`. The precision sets the length hashes are
+/// truncated to, so `{:.64}` shows SHA-256 hashes in full.
+impl<T, D, S> fmt::Display for MerkleTree<T, D, S>
+where
+    T: Clone + fmt::Debug + MerkleTreeData + Default,
+    D: Digest,
+    S: NodeStore<T, D>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let hash_length = f.precision().unwrap_or(10);
+        self.render_with_hash_length(f, Some(hash_length))
+    }
+}
+
+/// Copies the tree with all of its nodes, so that the copy can be changed while the original
+/// is still in use.
+impl<T, D: Digest, S: NodeStore<T, D> + Clone> Clone for MerkleTree<T, D, S> {
+    fn clone(&self) -> Self {
+        MerkleTree {
+            root: self.root,
+            nodes: self.nodes.clone(),
+            options: self.options.clone(),
+            leaf_count: self.leaf_count,
+            empty_leaf: self.empty_leaf,
+            generation: self.generation,
+            pinned: self.pinned,
+            node: PhantomData,
+        }
+    }
+}
+
+/// The direction taken from a parent node, or `Root` for the root itself.
+///
+/// Converts to and from the bytes 0 (`Left`), 1 (`Right`) and 2 (`Root`), as in
+/// `TraversePath::to_vec`. With the `serde` feature it serializes as that byte.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(into = "u8", try_from = "u8")
+)]
+pub enum NodeDirection {
+    Left,
+    Right,
+    Root,
+}
+
+/// The error returned when a byte is not a valid `NodeDirection`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvalidDirectionError(pub u8);
+
+impl fmt::Display for InvalidDirectionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid direction {}", self.0)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for InvalidDirectionError {}
+
+impl From<NodeDirection> for u8 {
+    fn from(direction: NodeDirection) -> Self {
+        match direction {
+            NodeDirection::Left => 0,
+            NodeDirection::Right => 1,
+            NodeDirection::Root => 2,
+        }
+    }
+}
+
+impl TryFrom<u8> for NodeDirection {
+    type Error = InvalidDirectionError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(NodeDirection::Left),
+            1 => Ok(NodeDirection::Right),
+            2 => Ok(NodeDirection::Root),
+            _ => Err(InvalidDirectionError(value)),
+        }
+    }
+}
+
+impl fmt::Display for NodeDirection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NodeDirection::Left => write!(f, "Left"),
+            NodeDirection::Right => write!(f, "Right"),
+            NodeDirection::Root => write!(f, "Root"),
+        }
+    }
+}
+
+/// A Merkle proof for a single leaf.
+///
+/// Each step records the hash of the sibling node and the direction taken from the parent
+/// node, ordered from the root down to the leaf. The digest type parameter records which
+/// hash algorithm the sibling hashes were produced with.
+pub struct TraversePath<D = Sha256> {
+    pub siblings: Vec<String>,
+    pub directions: Vec<NodeDirection>,
+    digest: PhantomData<fn() -> D>,
+}
+
+impl<D> Clone for TraversePath<D> {
+    fn clone(&self) -> Self {
+        Self::from_steps(self.siblings.clone(), self.directions.clone())
+    }
+}
+
+impl<D> fmt::Debug for TraversePath<D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TraversePath")
+            .field("siblings", &self.siblings)
+            .field("directions", &self.directions)
+            .finish()
+    }
+}
+
+impl<D> TraversePath<D> {
+    fn new() -> Self {
+        Self::from_steps(Vec::new(), Vec::new())
+    }
+
+    /// Creates a `TraversePath` from sibling hashes and directions ordered from the root down.
+    ///
+    /// # Arguments
+    ///
+    /// * `siblings`: The hex encoded sibling hashes.
+    /// * `directions`: The direction taken at each step.
+    pub fn from_steps(siblings: Vec<String>, directions: Vec<NodeDirection>) -> Self {
+        TraversePath {
+            siblings,
+            directions,
+            digest: PhantomData,
+        }
+    }
+
+    /// Adds a step to the `TraversePath`.
+    ///
+    /// # Arguments
+    ///
+    /// * `sibling`: The hash of the child that is not taken in this step.
+    /// * `direction`: The direction taken to reach the node (Left or Right).
+    fn add_step(&mut self, sibling: String, direction: NodeDirection) {
+        self.siblings.push(sibling);
+        self.directions.push(direction);
+    }
+
+    /// Removes the last step from the `TraversePath`.
+    fn pop_step(&mut self) {
+        self.siblings.pop();
+        self.directions.pop();
+    }
+
+    /// Converts the `TraversePath` to a vector of (sibling hash, direction) tuples.
+    /// The direction is represented as a `u8` (0 for Left, 1 for Right, 2 for Root).
+    ///
+    /// # Returns
+    ///
+    /// A `Vec<(String, u8)>` representing the path.
+    pub fn to_vec(&self) -> Vec<(String, u8)> {
+        self.siblings
+            .iter()
+            .zip(self.directions.iter())
+            .map(|(hash, direction)| (hash.to_string(), u8::from(direction.clone())))
+            .collect()
+    }
+
+    /// Converts the `TraversePath` of a tree built with `TreeOptions::sorted_pair()` to the
+    /// `bytes32[]` proof taken by OpenZeppelin's `MerkleProof.verify`: the `0x` prefixed
+    /// sibling hashes from the leaf up to the root, without directions.
+    ///
+    /// # Returns
+    ///
+    /// A `Vec<String>` of the sibling hashes in the order they are hashed in.
+    pub fn to_sorted_pair_proof(&self) -> Vec<String> {
+        self.siblings
+            .iter()
+            .rev()
+            .map(|sibling| format!("0x{sibling}"))
+            .collect()
+    }
+
+    /// Returns the position of the leaf this path leads to.
+    ///
+    /// The position follows from the directions, and from the shape of the tree for the
+    /// levels where an unpaired node has no sibling.
+    ///
+    /// # Arguments
+    ///
+    /// * `leaf_count`: The number of leaves in the tree.
+    /// * `padding`: The padding policy the tree was built with.
+    ///
+    /// # Returns
+    ///
+    /// The leaf index, or `MerkleError::InvalidProof` if the path does not fit a tree of that
+    /// size.
+    pub fn leaf_index(&self, leaf_count: usize, padding: Padding) -> Result<usize, MerkleError> {
+        let level_sizes = level_sizes(leaf_count);
+        let mut directions = self.directions.iter();
+        let mut position = 0;
+
+        for &size in level_sizes.iter().rev().skip(1) {
+            let unpaired = position * 2 + 1 >= size;
+            position = if unpaired && padding == Padding::Promote {
+                position * 2
+            } else {
+                match (
+                    directions.next().ok_or(MerkleError::InvalidProof)?,
+                    unpaired,
+                ) {
+                    (NodeDirection::Left, _) => position * 2,
+                    (NodeDirection::Right, false) => position * 2 + 1,
+                    _ => return Err(MerkleError::InvalidProof),
+                }
+            };
+        }
+
+        if directions.next().is_some() || leaf_count == 0 {
+            return Err(MerkleError::InvalidProof);
+        }
+        Ok(position)
+    }
+
+    /// Recomputes the Merkle root committed to by this path for the given leaf data.
+    ///
+    /// The leaf hash is folded together with the sibling hashes, starting from the leaf
+    /// and walking up to the root.
+    ///
+    /// # Arguments
+    ///
+    /// * `tag_leaf`: The tag used for hashing leaf nodes.
+    /// * `tag_branch`: The tag used for hashing branch nodes.
+    /// * `leaf_data`: The user data of the leaf this path belongs to.
+    ///
+    /// # Returns
+    ///
+    /// The hex encoded root, or `MerkleError::InvalidProof` if the path is malformed.
+    pub fn compute_root<T>(
+        &self,
+        tag_leaf: &str,
+        tag_branch: &str,
+        leaf_data: &T,
+    ) -> Result<String, MerkleError>
+    where
+        T: MerkleTreeData,
+        D: Digest,
+    {
+        self.compute_root_with_options(&TreeOptions::tagged(tag_leaf, tag_branch), leaf_data)
+    }
+
+    /// Recomputes the Merkle root committed to by this path for the given leaf data, using
+    /// the hashing configuration the tree was built with.
+    ///
+    /// # Arguments
+    ///
+    /// * `options`: The options the tree was built with.
+    /// * `leaf_data`: The user data of the leaf this path belongs to.
+    ///
+    /// # Returns
+    ///
+    /// The hex encoded root, or `MerkleError::InvalidProof` if the path is malformed.
+    pub fn compute_root_with_options<T>(
+        &self,
+        options: &TreeOptions,
+        leaf_data: &T,
+    ) -> Result<String, MerkleError>
+    where
+        T: MerkleTreeData,
+        D: Digest,
+    {
+        if self.siblings.len() != self.directions.len() {
+            return Err(MerkleError::InvalidProof);
+        }
+
+        let mut hash = options.hash_leaf_data::<D>(leaf_data);
+
+        for (sibling, direction) in self.siblings.iter().zip(self.directions.iter()).rev() {
+            let sibling = match hex::decode(sibling) {
+                Ok(bytes) if bytes.len() == hash.len() => Output::<D>::clone_from_slice(&bytes),
+                _ => return Err(MerkleError::InvalidProof),
+            };
+
+            hash = match direction {
+                NodeDirection::Left => options.hash_branch::<D>(&hash, &sibling),
+                NodeDirection::Right => options.hash_branch::<D>(&sibling, &hash),
+                NodeDirection::Root => return Err(MerkleError::InvalidProof),
+            };
+        }
+
+        Ok(hex::encode(hash))
+    }
+}
+
+/// A binary Merkle Tree over user data of type `T`, hashed with the digest `D` and with its
+/// nodes kept in the store `S`.
+///
+/// A tree over `Send + Sync` user data is `Send + Sync` itself, so it can be read from several
+/// threads at once, such as through a `SharedMerkleTree`.
+pub struct MerkleTree<T, D: Digest = Sha256, S = Vec<MerkleNode<T, D>>> {
+    root: Option<usize>,
+    nodes: S,
+    options: TreeOptions,
+    leaf_count: usize,
+    empty_leaf: Option<usize>,
+    generation: usize,
+    /// The number of nodes when the last version was handed out. The nodes before it may be
+    /// reachable from a `TreeVersion`, so they are copied rather than overwritten.
+    pinned: usize,
+    node: PhantomData<fn() -> MerkleNode<T, D>>,
+}
+
+struct TraverseStep {
+    parent_node: Option<usize>,
+    current_node: usize,
+    level: u32,
+    direction: NodeDirection,
+}
+
+pub trait MerkleTreeData {
+    fn serialize(&self) -> Vec<u8>;
+    fn mermaid_node_label(&self) -> String;
+
+    /// Feeds the serialized data into a hasher, which must give the same bytes as `serialize`.
+    ///
+    /// Defaults to hashing the result of `serialize`. Override it to stream large payloads,
+    /// such as documents or blobs, into the hasher without building them in memory.
+    fn hash_into(&self, hasher: &mut impl Digest) {
+        let mut bytes = self.serialize();
+        hasher.update(&bytes);
+        wipe(&mut bytes);
+    }
+
+    /// Returns the label of the leaf in a Graphviz DOT graph, with `\n` separating lines.
+    ///
+    /// Defaults to `mermaid_node_label` with its `<br>` line breaks replaced.
+    fn dot_node_label(&self) -> String {
+        self.mermaid_node_label()
+            .trim_start_matches("<br>")
+            .replace("<br>", "\\n")
+    }
+}
+
+/// Wipes a buffer that held serialized user data before it is freed, if the `zeroize` feature
+/// is enabled.
+///
+/// # Arguments
+///
+/// * `bytes`: The buffer to wipe.
+#[cfg(feature = "zeroize")]
+pub(crate) fn wipe(bytes: &mut [u8]) {
+    zeroize::Zeroize::zeroize(bytes);
+}
+
+#[cfg(not(feature = "zeroize"))]
+pub(crate) fn wipe(_bytes: &mut [u8]) {}
+
+/// Extracts the key a leaf can be looked up by, such as a user id.
+pub trait MerkleTreeKey {
+    type Key: Hash + Eq;
+
+    fn key(&self) -> Self::Key;
+}
+
+impl<T, D, S> MerkleTree<T, D, S>
+where
+    T: Clone + fmt::Debug + MerkleTreeData + Default,
+    D: Digest,
+    S: NodeStore<T, D>,
+{
+    /// Builds a Merkle Tree from the given user data.
+    ///
+    /// # Arguments
+    ///
+    /// * `tag_leaf`: The tag used for hashing leaf nodes.
+    /// * `tag_branch`: The tag used for hashing branch nodes.
+    /// * `user_data`: A slice of tuples, where each tuple contains a user ID and balance.
+    pub fn build(tag_leaf: &str, tag_branch: &str, input: &[T]) -> Self
+    where
+        S: Default,
+    {
+        Self::build_with_options(TreeOptions::tagged(tag_leaf, tag_branch), input)
+    }
+
+    /// Builds a Merkle Tree from the given user data with the given hashing configuration.
+    ///
+    /// # Arguments
+    ///
+    /// * `options`: The hashing configuration of the tree.
+    /// * `input`: The user data to commit to, in leaf order.
+    pub fn build_with_options(options: TreeOptions, input: &[T]) -> Self
+    where
+        S: Default,
+    {
+        Self::build_from_iter_with_options(options, input.iter().cloned())
+    }
+
+    /// Builds a Merkle Tree from a stream of user data.
+    ///
+    /// Unlike `build`, the leaves are taken by value, so they can come from a reader or a
+    /// database cursor without being collected into a slice first.
+    ///
+    /// # Arguments
+    ///
+    /// * `tag_leaf`: The tag used for hashing leaf nodes.
+    /// * `tag_branch`: The tag used for hashing branch nodes.
+    /// * `input`: The user data to commit to, in leaf order.
+    pub fn build_from_iter<I>(tag_leaf: &str, tag_branch: &str, input: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        S: Default,
+    {
+        Self::build_from_iter_with_options(TreeOptions::tagged(tag_leaf, tag_branch), input)
+    }
+
+    /// Builds a Merkle Tree from a stream of user data with the given hashing configuration.
+    ///
+    /// # Arguments
+    ///
+    /// * `options`: The hashing configuration of the tree.
+    /// * `input`: The user data to commit to, in leaf order.
+    pub fn build_from_iter_with_options<I>(options: TreeOptions, input: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        S: Default,
+    {
+        let nodes = input
+            .into_iter()
+            .map(|data| MerkleNode::new_leaf(options.hash_leaf_data::<D>(&data), Some(data)))
+            .collect();
+
+        Self::from_leaf_nodes(options, nodes)
+    }
+
+    /// Builds a Merkle Tree from the given user data in a canonical order, so the same set of
+    /// leaves produces the same root regardless of the order they arrive in.
+    ///
+    /// The leaves are sorted by the given key. Leaves with equal keys are ordered by their
+    /// serialized bytes, so ties do not depend on the input order either.
+    ///
+    /// # Arguments
+    ///
+    /// * `tag_leaf`: The tag used for hashing leaf nodes.
+    /// * `tag_branch`: The tag used for hashing branch nodes.
+    /// * `input`: The user data to commit to, in any order.
+    /// * `key`: A function that returns the sort key of a leaf, such as its user id.
+    pub fn build_canonical<K, F>(tag_leaf: &str, tag_branch: &str, input: &[T], key: F) -> Self
+    where
+        K: Ord,
+        F: Fn(&T) -> K,
+        S: Default,
+    {
+        Self::build_canonical_with_options(TreeOptions::tagged(tag_leaf, tag_branch), input, key)
+    }
+
+    /// Builds a Merkle Tree from the given user data in a canonical order with the given
+    /// hashing configuration.
+    ///
+    /// # Arguments
+    ///
+    /// * `options`: The hashing configuration of the tree.
+    /// * `input`: The user data to commit to, in any order.
+    /// * `key`: A function that returns the sort key of a leaf, such as its user id.
+    pub fn build_canonical_with_options<K, F>(options: TreeOptions, input: &[T], key: F) -> Self
+    where
+        K: Ord,
+        F: Fn(&T) -> K,
+        S: Default,
+    {
+        let mut leaves: Vec<(K, Vec<u8>, &T)> = input
+            .iter()
+            .map(|data| (key(data), data.serialize(), data))
+            .collect();
+        leaves.sort_by(|a, b| (&a.0, &a.1).cmp(&(&b.0, &b.1)));
+
+        Self::build_from_iter_with_options(
+            options,
+            leaves.into_iter().map(|(_, mut bytes, data)| {
+                wipe(&mut bytes);
+                data.clone()
+            }),
+        )
+    }
+
+    /// Appends a leaf to the tree without rebuilding it.
+    ///
+    /// Only the branch nodes on the path from the new leaf to the root are rehashed, so
+    /// this takes O(log n) time.
+    ///
+    /// # Arguments
+    ///
+    /// * `leaf`: The user data of the new leaf.
+    pub fn push(&mut self, leaf: T) {
+        let hash = self.options.hash_leaf_data::<D>(&leaf);
+        self.push_leaf_node(MerkleNode::new_leaf(hash, Some(leaf)));
+    }
+
+    /// Replaces the user data of a leaf without rebuilding the tree.
+    ///
+    /// Only the branch nodes on the path from the leaf to the root are rehashed, so this
+    /// takes O(log n) time.
+    ///
+    /// # Arguments
+    ///
+    /// * `index`: The position of the leaf.
+    /// * `new_data`: The new user data of the leaf.
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` if the leaf was updated, or `MerkleError::LeafNotFound` if the index is out of
+    /// range.
+    pub fn update(&mut self, index: usize, new_data: T) -> Result<(), MerkleError> {
+        let hash = self.options.hash_leaf_data::<D>(&new_data);
+        self.update_leaf_node(index, hash, Some(new_data))
+    }
+
+    /// Iterates over the tree level by level and applies the given function to each node.
+    ///
+    /// # Arguments
+    ///
+    /// * `map_fn`: A function that takes a `&TraverseStep` and returns a String.
+    ///   This function is called for each node in the tree.
+    ///
+    /// # Returns
+    ///
+    /// An `Option` containing a `Vec<String>` if the tree is not empty, `None` otherwise.
+    /// Each string in the vector is the result of applying `map_fn` to a node.
+    fn iterate_tree(&self, map_fn: impl Fn(&TraverseStep) -> String) -> Option<Vec<String>> {
+        self.root.map(|root| self.iterate_subtree(root, map_fn))
+    }
+
+    /// Maps every node below and including `root` in pre-order, with levels relative to it.
+    fn iterate_subtree(
+        &self,
+        root: usize,
+        map_fn: impl Fn(&TraverseStep) -> String,
+    ) -> Vec<String> {
+        let mut output = Vec::new();
+
+        let mut stack: Vec<TraverseStep> = vec![TraverseStep {
+            parent_node: None,
+            current_node: root,
+            level: 0,
+            direction: NodeDirection::Root,
+        }];
+
+        while let Some(step) = stack.pop() {
+            output.push(map_fn(&step));
+
+            if let Some(right) = self.nodes[step.current_node].right {
+                stack.push(TraverseStep {
+                    parent_node: Some(step.current_node),
+                    current_node: right,
+                    level: step.level + 1,
+                    direction: NodeDirection::Right,
+                });
+            }
+
+            if let Some(left) = self.nodes[step.current_node].left {
+                stack.push(TraverseStep {
+                    parent_node: Some(step.current_node),
+                    current_node: left,
+                    level: step.level + 1,
+                    direction: NodeDirection::Left,
+                });
+            }
+        }
+
+        output
+    }
+
+    /// Displays the Merkle Tree in an indented format.
+    pub fn display_tree(&self) -> String {
+        let mut output = String::new();
+        let _ = self.render(&mut output);
+        output
+    }
+
+    /// Writes the Merkle Tree in an indented format, one node per line with hashes truncated
+    /// to 10 characters.
+    ///
+    /// # Arguments
+    ///
+    /// * `w`: The writer to render the tree into, such as a `String` or a `fmt::Formatter`.
+    pub fn render(&self, w: &mut impl fmt::Write) -> fmt::Result {
+        self.render_with_hash_length(w, Some(10))
+    }
+
+    /// Writes the Merkle Tree in an indented format, one node per line.
+    ///
+    /// # Arguments
+    ///
+    /// * `w`: The writer to render the tree into, such as a `String` or a `fmt::Formatter`.
+    /// * `hash_length`: Shortens the hex encoded hashes to this many characters, or shows them
+    ///   in full if `None`.
+    pub fn render_with_hash_length(
+        &self,
+        w: &mut impl fmt::Write,
+        hash_length: Option<usize>,
+    ) -> fmt::Result {
+        let Some(lines) = self.iterate_tree(|step| {
+            let indent = " ".repeat(step.level as usize);
+            let hash = hex::encode(&self.nodes[step.current_node].hash);
+            let hash = match hash_length {
+                Some(length) => truncate_middle(&hash, length),
+                None => hash,
+            };
+            format!("{}{}: {}", indent, step.direction, hash)
+        }) else {
+            return w.write_str("Tree is empty.");
+        };
+
+        for (i, line) in lines.iter().enumerate() {
+            if i != 0 {
+                w.write_char('\n')?;
+            }
+            w.write_str(line)?;
+        }
+        Ok(())
+    }
+
+    /// Displays the Merkle Tree as a Mermaid diagram.
+    /// Use the mermaid editor to visualize the diagram https://mermaid.live/
+    pub fn display_mermaid_diagram(&self) -> String {
+        self.display_mermaid_diagram_with_options(&MermaidOptions::default())
+    }
+
+    /// Searches for a user with the given predicate.
+    ///
+    /// # Arguments
+    ///
+    /// * `predicate`: A function that takes a `&UserData` and returns a boolean.
+    ///   It returns true if the user data matches the search criteria, false otherwise.
+    ///
+    /// # Returns
+    ///
+    /// A tuple of `(&MerkleNode, TraversePath)` if a matching user is found,
+    /// `MerkleError::EmptyTree` if the tree has no leaves and `MerkleError::LeafNotFound`
+    /// otherwise.
+    pub fn search_with_path<F>(
+        &self,
+        predicate: F,
+    ) -> Result<(&MerkleNode<T, D>, TraversePath<D>), MerkleError>
+    where
+        F: Fn(&T) -> bool,
+    {
+        if self.root.is_none() {
+            return Err(MerkleError::EmptyTree);
+        }
+
+        let mut result = None;
+        self.walk_with_paths(|node, path| {
+            let node = &self.nodes[node];
+            match &node.user_data {
+                Some(user_data) if predicate(user_data) => {
+                    result = Some((node, path.clone()));
+                    ControlFlow::Break(())
+                }
+                _ => ControlFlow::Continue(()),
+            }
+        });
+        result.ok_or(MerkleError::LeafNotFound)
+    }
+
+    /// Searches for all the users matching the given predicate in one pass.
+    ///
+    /// # Arguments
+    ///
+    /// * `predicate`: A function that takes a `&UserData` and returns a boolean.
+    ///   It returns true if the user data matches the search criteria, false otherwise.
+    ///
+    /// # Returns
+    ///
+    /// A `Vec` of `(&MerkleNode, TraversePath)` tuples for the matching leaves in leaf order,
+    /// which is empty if no user matches.
+    pub fn search_all_with_paths<F>(
+        &self,
+        predicate: F,
+    ) -> Vec<(&MerkleNode<T, D>, TraversePath<D>)>
+    where
+        F: Fn(&T) -> bool,
+    {
+        let mut results = Vec::new();
+        self.walk_with_paths(|node, path| {
+            let node = &self.nodes[node];
+            if node.user_data.as_ref().is_some_and(&predicate) {
+                results.push((node, path.clone()));
+            }
+            ControlFlow::Continue(())
+        });
+        results
+    }
+
+    /// Generates the proofs of all the leaves in a single pass over the tree.
+    ///
+    /// Each node is visited once and a proof is copied out at each leaf, so this takes
+    /// O(n log n) time, compared to O(n^2) for calling `search_with_path` for every leaf.
+    ///
+    /// # Returns
+    ///
+    /// A `Vec` of the `TraversePath` of every leaf, indexed by the position of the leaf.
+    pub fn all_proofs(&self) -> Vec<TraversePath<D>> {
+        let mut proofs = Vec::with_capacity(self.leaf_count);
+        self.walk_with_paths(|node, path| {
+            if self.nodes[node].is_leaf() && Some(node) != self.empty_leaf {
+                proofs.push(path.clone());
+            }
+            ControlFlow::Continue(())
+        });
+        proofs
+    }
+
+    /// Generates the proofs of all the leaves, splitting the leaves between the given number
+    /// of threads.
+    ///
+    /// Each thread generates the proofs of its leaves with `proof_by_index`, so this also
+    /// takes O(n log n) time in total.
+    ///
+    /// # Arguments
+    ///
+    /// * `threads`: The number of threads to use, at least 1.
+    ///
+    /// # Returns
+    ///
+    /// A `Vec` of the `TraversePath` of every leaf, indexed by the position of the leaf.
+    #[cfg(feature = "std")]
+    pub fn all_proofs_parallel(&self, threads: usize) -> Vec<TraversePath<D>>
+    where
+        T: Sync,
+        S: Sync,
+    {
+        let chunk_size = self.leaf_count.div_ceil(threads.max(1)).max(1);
+        let indices: Vec<usize> = (0..self.leaf_count).collect();
+
+        std::thread::scope(|scope| {
+            let handles: Vec<_> = indices
+                .chunks(chunk_size)
+                .map(|chunk| {
+                    scope.spawn(move || {
+                        chunk
+                            .iter()
+                            .filter_map(|&index| self.proof_by_index(index).ok())
+                            .map(|(_, path)| path)
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+
+            handles
+                .into_iter()
+                .flat_map(|handle| handle.join().expect("proof generation panicked"))
+                .collect()
+        })
+    }
+
+    /// Walks the tree depth-first from left to right and calls `on_node` with each node and
+    /// its path.
+    ///
+    /// The walk uses an explicit stack rather than recursion, so the depth of the tree is not
+    /// limited by the call stack.
+    ///
+    /// # Arguments
+    ///
+    /// * `on_node`: A function that receives the index of each node and its path, and returns
+    ///   `ControlFlow::Break(())` to stop the walk.
+    fn walk_with_paths<M>(&self, mut on_node: M)
+    where
+        M: FnMut(usize, &TraversePath<D>) -> ControlFlow<()>,
+    {
+        let mut path = TraversePath::new();
+        let mut stack: Vec<TraverseStep> = self
+            .root
+            .map(|root| TraverseStep {
+                parent_node: None,
+                current_node: root,
+                level: 0,
+                direction: NodeDirection::Root,
+            })
+            .into_iter()
+            .collect();
+
+        while let Some(step) = stack.pop() {
+            while path.siblings.len() >= (step.level as usize).max(1) {
+                path.pop_step();
+            }
+            if let Some(parent) = step.parent_node {
+                let sibling = match step.direction {
+                    NodeDirection::Left => self.nodes[parent].right,
+                    _ => self.nodes[parent].left,
+                };
+                if let Some(sibling) = sibling {
+                    path.add_step(hex::encode(&self.nodes[sibling].hash), step.direction);
+                }
+            }
+
+            if on_node(step.current_node, &path).is_break() {
+                return;
+            }
+
+            let node = &self.nodes[step.current_node];
+
+            if let (Some(left), Some(right)) = (node.left, node.right) {
+                // A duplicated node is the same subtree, so its leaves are not visited twice.
+                if right != left {
+                    stack.push(TraverseStep {
+                        parent_node: Some(step.current_node),
+                        current_node: right,
+                        level: step.level + 1,
+                        direction: NodeDirection::Right,
+                    });
+                }
+                stack.push(TraverseStep {
+                    parent_node: Some(step.current_node),
+                    current_node: left,
+                    level: step.level + 1,
+                    direction: NodeDirection::Left,
+                });
+            }
+        }
+    }
+}
+
+/// Verifies that the given leaf data is committed to by the given Merkle root.
+///
+/// The verification does not require access to the tree that produced the path.
+///
+/// # Arguments
+///
+/// * `tag_leaf`: The tag used for hashing leaf nodes when the tree was built.
+/// * `tag_branch`: The tag used for hashing branch nodes when the tree was built.
+/// * `root`: The hex encoded Merkle root to verify against.
+/// * `leaf_data`: The user data that is claimed to be included in the tree.
+/// * `path`: The path returned by `MerkleTree::search_with_path`.
+///
+/// # Returns
+///
+/// `true` if the recomputed root matches `root`, `false` otherwise.
+pub fn verify_proof<T, D>(
+    tag_leaf: &str,
+    tag_branch: &str,
+    root: &str,
+    leaf_data: &T,
+    path: &TraversePath<D>,
+) -> bool
+where
+    T: MerkleTreeData,
+    D: Digest,
+{
+    verify_proof_with_options(
+        &TreeOptions::tagged(tag_leaf, tag_branch),
+        root,
+        leaf_data,
+        path,
+    )
+}
+
+/// Verifies that the given leaf data is committed to by the given Merkle root, using the
+/// hashing configuration the tree was built with.
+///
+/// # Arguments
+///
+/// * `options`: The options the tree was built with.
+/// * `root`: The hex encoded Merkle root to verify against.
+/// * `leaf_data`: The user data that is claimed to be included in the tree.
+/// * `path`: The path returned by `MerkleTree::search_with_path`.
+///
+/// # Returns
+///
+/// `true` if the recomputed root matches `root`, `false` otherwise.
+pub fn verify_proof_with_options<T, D>(
+    options: &TreeOptions,
+    root: &str,
+    leaf_data: &T,
+    path: &TraversePath<D>,
+) -> bool
+where
+    T: MerkleTreeData,
+    D: Digest,
+{
+    path.compute_root_with_options(options, leaf_data)
+        .is_ok_and(|computed| root::roots_match(&computed, root))
+}
+
+/// Verifies a proof of a tree built with `TreeOptions::sorted_pair()` the way OpenZeppelin's
+/// `MerkleProof.verify` does, folding the sibling hashes into the leaf hash without directions.
+///
+/// # Arguments
+///
+/// * `root`: The hex encoded Merkle root to verify against, with or without a `0x` prefix.
+/// * `leaf_data`: The user data that is claimed to be included in the tree.
+/// * `proof`: The sibling hashes returned by `TraversePath::to_sorted_pair_proof`.
+///
+/// # Returns
+///
+/// `true` if the recomputed root matches `root`, `false` otherwise.
+pub fn verify_sorted_pair_proof<T, D>(root: &str, leaf_data: &T, proof: &[String]) -> bool
+where
+    T: MerkleTreeData,
+    D: Digest,
+{
+    let options = TreeOptions::sorted_pair();
+    let mut hash = options.hash_leaf_data::<D>(leaf_data);
+    for sibling in proof {
+        let sibling = sibling.strip_prefix("0x").unwrap_or(sibling);
+        let Some(sibling) = multiproof::decode_hash::<D>(sibling) else {
+            return false;
+        };
+        hash = options.hash_branch::<D>(&hash, &sibling);
+    }
+
+    let root = root.strip_prefix("0x").unwrap_or(root);
+    root::hash_matches(&hash, root)
+}
+
+/// Returns the number of nodes on each level of a tree with the given number of leaves, from
+/// the leaves up to the root.
+///
+/// # Arguments
+///
+/// * `leaf_count`: The number of leaves.
+fn level_sizes(leaf_count: usize) -> Vec<usize> {
+    let mut sizes = vec![leaf_count];
+    while let Some(&size) = sizes.last().filter(|size| **size > 1) {
+        sizes.push(size.div_ceil(2));
+    }
+    sizes
+}
+
+/// Truncates a string in the middle if it exceeds the maximum length.
+///
+/// If the input string's length is less than or equal to `max_len`, it returns the original string.
+/// Otherwise, it returns a new string with the first `max_len / 2` characters, an ellipsis ("..."),
+/// and the last `max_len - (max_len / 2)` characters.
+///
+/// # Arguments
+///
+/// * `input`: The string to truncate.
+/// * `max_len`: The maximum length of the string.
+///
+/// # Returns
+///
+/// A string of truncated text.
+fn truncate_middle(input: &str, max_len: usize) -> String {
+    let len = input.len();
+    if len <= max_len {
+        return input.to_string();
+    }
+
+    let half_len = max_len / 2;
+    let start = &input[..half_len];
+    let end = &input[len - (max_len - half_len)..];
+
+    format!("{}...{}", start, end)
+}
+
+/// Calculates a tagged hash using the digest `D`.
+///
+/// This function takes a tag and an input byte slice, calculates the hash of the tag,
+/// then calculates the hash of the concatenation of the tag's hash (twice) and the input.
+/// With the `Sha256` digest this is the tagged hash defined by BIP340.
+///
+/// # Arguments
+///
+/// * `tag`: The tag string.
+/// * `input`: The input byte slice.
+///
+/// # Returns
+///
+/// The tagged hash as the digest's output array.
+pub fn tagged_hash<D: Digest>(tag: &str, input: &[u8]) -> Output<D> {
+    let mut hasher = tagged_hasher::<D>(tag);
+    hasher.update(input);
+    hasher.finalize()
+}
+
+/// Creates a hasher that has already consumed the BIP340 tag prefix, so the input of a
+/// tagged hash can be fed to it in several parts.
+///
+/// # Arguments
+///
+/// * `tag`: The tag string.
+fn tagged_hasher<D: Digest>(tag: &str) -> D {
+    let tag_hash = D::digest(tag.as_bytes());
+
+    let mut hasher = D::new();
+    hasher.update(&tag_hash);
+    hasher.update(&tag_hash);
+    hasher
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("abcdefghijklmnopqrstuvwxyz", 10, "abcde...vwxyz")]
+    #[case("abcdefghijklmnopqrstuvwxyz", 5, "ab...xyz")]
+    #[case("abcdefghijklmnopqrstuvwxyz", 2, "a...z")]
+    #[case("abcdefghijklmnopqrstuvwxyz", 1, "...z")]
+    fn it_can_truncate_middle(#[case] input: &str, #[case] max_len: usize, #[case] expected: &str) {
+        let actual = super::truncate_middle(input, max_len);
+        assert_eq!(actual, expected);
+    }
+
+    #[rstest]
+    #[case(
+        "Bitcoin_Transaction",
+        "aaa",
+        "d2d838724571ff750eb7f498a667c32f522efae2b403eae6f678207ac6f978de"
+    )]
+    #[case(
+        "Bitcoin_Transaction",
+        "bbb",
+        "7cdf701413062eaba020af83441a6762ee2910e36b1805bad072103b0257f441"
+    )]
+    #[case(
+        "hello",
+        "aaa",
+        "aa7deacc6231c611d10b4a2b14bec43c30251b977610fd5a322550003f2b216b"
+    )]
+    fn it_can_tagged_hash(#[case] tag: &str, #[case] input: &str, #[case] expected: &str) {
+        let actual = super::tagged_hash::<Sha256>(tag, input.as_bytes());
+        assert_eq!(hex::encode(actual), expected);
+    }
+
+    #[test]
+    fn it_can_tagged_hash_bip341_tapleaf() {
+        // Leaf hash of the first script in the BIP341 wallet test vectors.
+        let script =
+            hex::decode("20d85a959b0290bf19bb89ed43c916be835475d013da4b362117393e25a48229b8ac")
+                .unwrap();
+        let mut input = vec![0xc0, script.len() as u8];
+        input.extend_from_slice(&script);
+
+        let actual = super::tagged_hash::<Sha256>("TapLeaf", &input);
+        assert_eq!(
+            hex::encode(actual),
+            "5b75adecf53548f3ec6ad7d78383bf84cc57b55a3127c72b9a2481752dd88b21"
+        );
+    }
+
+    #[test]
+    fn it_can_tagged_hash_with_sha512() {
+        let actual = super::tagged_hash::<Sha512>("Bitcoin_Transaction", "aaa".as_bytes());
+        assert_eq!(
+            hex::encode(actual),
+            "a30b37bb07e7e498c16bca87390c3beed64bc99e99897a0a0742742f4e49dc7f\
+             fccc979f8debd431d0f88e8ef45d5afdb58792af1ed63543d3b6a6a47e595343"
+        );
+    }
+
+    #[cfg(feature = "blake3")]
+    #[test]
+    fn it_can_tagged_hash_with_blake3() {
+        let actual = super::tagged_hash::<Blake3>("Bitcoin_Transaction", "aaa".as_bytes());
+        let expected = {
+            let tag_hash = blake3::hash("Bitcoin_Transaction".as_bytes());
+            let mut hasher = blake3::Hasher::new();
+            hasher.update(tag_hash.as_bytes());
+            hasher.update(tag_hash.as_bytes());
+            hasher.update("aaa".as_bytes());
+            // `Digest::finalize` would be picked over the inherent method otherwise.
+            blake3::Hasher::finalize(&hasher)
+        };
+        assert_eq!(actual.as_slice(), expected.as_bytes());
+    }
+
+    #[derive(Clone, Debug, Default)]
+    #[allow(non_camel_case_types)]
+    pub struct UserItem_A {
+        value: String,
+    }
+
+    impl MerkleTreeData for UserItem_A {
+        fn serialize(&self) -> Vec<u8> {
+            self.value.as_bytes().to_vec()
+        }
+
+        fn mermaid_node_label(&self) -> String {
+            format!("<br>{}", self.value)
+        }
+    }
+
+    fn generate_user_item_a() -> Vec<UserItem_A> {
+        vec!["aaa", "bbb", "ccc", "ddd", "eee"]
+            .into_iter()
+            .map(|v| UserItem_A {
+                value: String::from(v),
+            })
+            .collect()
+    }
+
+    #[derive(Clone, Debug, Default)]
+    #[allow(non_camel_case_types)]
+    pub struct UserItem_B {
+        pub id: u32,
+        pub balance: u32,
+    }
+
+    impl MerkleTreeData for UserItem_B {
+        fn serialize(&self) -> Vec<u8> {
+            format!("({},{})", self.id, self.balance)
+                .as_bytes()
+                .to_vec()
+        }
+
+        fn mermaid_node_label(&self) -> String {
+            format!("<br>User ID: {}<br>Balance: {}", self.id, self.balance)
+        }
+    }
+
+    fn generate_user_item_b() -> Vec<UserItem_B> {
+        vec![(1, 1111), (2, 2222), (3, 3333), (4, 4444), (5, 5555)]
+            .into_iter()
+            .map(|(id, balance)| UserItem_B { id, balance })
+            .collect()
+    }
+
+    #[derive(Clone, Debug, Default)]
+    pub struct RawItem(Vec<u8>);
+
+    impl MerkleTreeData for RawItem {
+        fn serialize(&self) -> Vec<u8> {
+            self.0.clone()
+        }
+
+        fn mermaid_node_label(&self) -> String {
+            String::new()
+        }
+    }
+
+    #[rstest]
+    #[case(Padding::DuplicateLast, 1)]
+    #[case(Padding::DuplicateLast, 9)]
+    #[case(Padding::Promote, 11)]
+    #[case(Padding::PadWithEmpty, 6)]
+    fn it_can_compute_leaf_index_from_path(#[case] padding: Padding, #[case] leaf_count: usize) {
+        let user_data = util::generate_random_user_data(leaf_count);
+        let options = TreeOptions::tagged("ProofOfReserve_Leaf", "ProofOfReserve_Branch")
+            .with_padding(padding);
+        let tree: MerkleTree<_> = MerkleTree::build_with_options(options, &user_data);
+
+        for index in 0..leaf_count {
+            let (_node, path) = tree.proof_by_index(index).unwrap();
+            assert_eq!(path.leaf_index(leaf_count, padding), Ok(index));
+            assert_eq!(
+                path.leaf_index(leaf_count * 4 + 1, padding),
+                Err(MerkleError::InvalidProof)
+            );
+        }
+    }
+
+    #[rstest]
+    #[case(NodeDirection::Left, 0)]
+    #[case(NodeDirection::Right, 1)]
+    #[case(NodeDirection::Root, 2)]
+    fn it_can_convert_node_directions(#[case] direction: NodeDirection, #[case] value: u8) {
+        assert_eq!(u8::from(direction.clone()), value);
+        assert_eq!(NodeDirection::try_from(value), Ok(direction.clone()));
+        assert_eq!(
+            NodeDirection::try_from(value + 3),
+            Err(InvalidDirectionError(value + 3))
+        );
+
+        #[cfg(feature = "json")]
+        {
+            let json = serde_json::to_string(&direction).unwrap();
+            assert_eq!(json, value.to_string());
+            assert_eq!(
+                serde_json::from_str::<NodeDirection>(&json).unwrap(),
+                direction
+            );
+            assert!(serde_json::from_str::<NodeDirection>("3").is_err());
+        }
+    }
+
+    #[test]
+    fn it_can_return_fixed_size_hashes() {
+        let user_data = util::generate_random_user_data(5);
+
+        let tree: MerkleTree<_> =
+            MerkleTree::build("ProofOfReserve_Leaf", "ProofOfReserve_Branch", &user_data);
+
+        let root_hash: [u8; 32] = tree.root_hash().unwrap().into();
+        assert_eq!(hex::encode(root_hash), tree.root().unwrap());
+
+        let leaf = tree.leaf(3).unwrap();
+        assert_eq!(
+            leaf.hash(),
+            &tagged_hash::<Sha256>("ProofOfReserve_Leaf", &user_data[3].serialize())
+        );
+    }
+
+    #[test]
+    fn it_can_build_from_leaf_bytes() {
+        let chunks: Vec<&[u8]> = vec![b"aaa", b"bbb", b"ccc", b"", b"eeeee"];
+
+        let tree: MerkleTree<()> = MerkleTree::from_leaf_bytes(
+            "ProofOfReserve_Leaf",
+            "ProofOfReserve_Branch",
+            chunks.iter().copied(),
+        );
+        let items: Vec<_> = chunks.iter().map(|chunk| RawItem(chunk.to_vec())).collect();
+        let expected: MerkleTree<_> =
+            MerkleTree::build("ProofOfReserve_Leaf", "ProofOfReserve_Branch", &items);
+
+        let root = tree.root().unwrap();
+        assert_eq!(Some(&root), expected.root().ok().as_ref());
+
+        let (node, path) = tree.proof_by_index(2).unwrap();
+        assert!(node.user_data.is_none());
+        assert!(verify_proof(
+            "ProofOfReserve_Leaf",
+            "ProofOfReserve_Branch",
+            &root,
+            &items[2],
+            &path
+        ));
+    }
+
+    #[test]
+    fn it_can_build_a_tree_with_empty_input() {
+        let input: Vec<UserItem_A> = vec![];
+
+        let tag_leaf = "Bitcoin_Transaction";
+        let tag_branch = "Bitcoin_Transaction";
+
+        let tree: MerkleTree<_> = MerkleTree::build(tag_leaf, tag_branch, &input);
+
+        assert_eq!(tree.root(), Err(MerkleError::EmptyTree));
+    }
+
+    #[test]
+    fn it_can_build_a_tree_user_item_a() {
+        let user_data = generate_user_item_a();
+
+        let tag_leaf = "Bitcoin_Transaction";
+        let tag_branch = "Bitcoin_Transaction";
+
+        let tree: MerkleTree<_> = MerkleTree::build(tag_leaf, tag_branch, &user_data);
+
+        assert_eq!(
+            tree.root().unwrap(),
+            "4aa906745f72053498ecc74f79813370a4fe04f85e09421df2d5ef760dfa94b5"
+        );
+    }
+
+    #[test]
+    fn it_can_build_a_tree_user_item_b() {
+        let user_data = generate_user_item_b();
+
+        let tag_leaf = "ProofOfReserve_Leaf";
+        let tag_branch = "ProofOfReserve_Branch";
+
+        let tree: MerkleTree<_> = MerkleTree::build(tag_leaf, tag_branch, &user_data);
+
+        assert_eq!(
+            tree.root().unwrap(),
+            "e752d40ca9a0626be5fea078ef35216a9c50554934a54dfbe2eb60195af66c85"
+        );
+    }
+
+    #[test]
+    fn it_can_build_a_tree_with_prefix_tag_style() {
+        let user_data = generate_user_item_b();
+
+        let options = TreeOptions::tagged("ProofOfReserve_Leaf", "ProofOfReserve_Branch")
+            .with_tag_style(TagStyle::Prefix);
+        let tree: MerkleTree<_> = MerkleTree::build_with_options(options, &user_data);
+        let root = tree.root().unwrap();
+
+        assert_eq!(
+            root,
+            "e9d431842a85a356452ef992c2ca94c5a2002e454b1d72b50c2f8d44816c368a"
+        );
+
+        let (_node, path) = tree
+            .search_with_path(|user_data| user_data.id == 2)
+            .unwrap();
+        assert!(verify_proof_with_options(
+            tree.options(),
+            &root,
+            &user_data[1],
+            &path
+        ));
+    }
+
+    #[rstest]
+    #[case(false, true)]
+    #[case(true, false)]
+    fn it_can_separate_leaf_and_branch_domains(
+        #[case] domain_separation: bool,
+        #[case] roots_collide: bool,
+    ) {
+        // With identical tags, a single leaf holding the concatenated leaf hashes of a
+        // two-leaf tree has the same root unless the domains are separated.
+        let options =
+            TreeOptions::tagged("Same_Tag", "Same_Tag").with_domain_separation(domain_separation);
+        let leaves = vec![RawItem(b"aaa".to_vec()), RawItem(b"bbb".to_vec())];
+        let tree: MerkleTree<_> = MerkleTree::build_with_options(options.clone(), &leaves);
+
+        let mut forged = options.hash_leaf::<Sha256>(&leaves[0].0).to_vec();
+        forged.extend_from_slice(&options.hash_leaf::<Sha256>(&leaves[1].0));
+        let forged_tree: MerkleTree<_> =
+            MerkleTree::build_with_options(options, &[RawItem(forged)]);
+
+        assert_eq!(tree.root() == forged_tree.root(), roots_collide);
+    }
+
+    #[rstest]
+    #[case(
+        Padding::DuplicateLast,
+        "e752d40ca9a0626be5fea078ef35216a9c50554934a54dfbe2eb60195af66c85"
+    )]
+    #[case(
+        Padding::Promote,
+        "f9b7bbb810bfed975f4eabcdd7e49efc8f00541232d8a49bae17e860f5290337"
+    )]
+    #[case(
+        Padding::PadWithEmpty,
+        "8126bb34a5b776fe70636ece25f7c4e95568d8c0857887baf34da8542dc394ef"
+    )]
+    fn it_can_build_a_tree_with_padding(#[case] padding: Padding, #[case] expected: &str) {
+        let user_data = generate_user_item_b();
+
+        let options = TreeOptions::tagged("ProofOfReserve_Leaf", "ProofOfReserve_Branch")
+            .with_padding(padding);
+        let tree: MerkleTree<_> = MerkleTree::build_with_options(options, &user_data);
+        let root = tree.root().unwrap();
+
+        assert_eq!(root, expected);
+
+        for item in &user_data {
+            let (_node, path) = tree
+                .search_with_path(|user_data| user_data.id == item.id)
+                .unwrap();
+            assert!(verify_proof_with_options(
+                tree.options(),
+                &root,
+                item,
+                &path
+            ));
+        }
+    }
+
+    #[test]
+    fn it_can_build_a_tree_with_sha512() {
+        let user_data = generate_user_item_a();
+
+        let tag_leaf = "Bitcoin_Transaction";
+        let tag_branch = "Bitcoin_Transaction";
+
+        let tree: MerkleTree<_, Sha512> = MerkleTree::build(tag_leaf, tag_branch, &user_data);
+        let root = tree.root().unwrap();
+
+        assert_eq!(
+            root,
+            "14fa3026826db09f30a7e5b9107f6ab96251f37fa0085b5953808942fd0f8502\
+             0054d39ed04217cdd53ac12db926f7e51c5c896b4a61c850ed863509cb169734"
+        );
+
+        let (_node, path) = tree
+            .search_with_path(|user_data| user_data.value == "ddd")
+            .unwrap();
+        assert!(verify_proof(
+            tag_leaf,
+            tag_branch,
+            &root,
+            &user_data[3],
+            &path
+        ));
+    }
+
+    #[test]
+    fn it_can_build_a_sorted_pair_tree() {
+        let user_data = generate_user_item_a();
+
+        let tree: MerkleTree<_> =
+            MerkleTree::build_with_options(TreeOptions::sorted_pair(), &user_data);
+        let root = tree.root().unwrap();
+
+        assert_eq!(
+            root,
+            "ff9fe85054e00794e24b233df48d7f35337935e8c463902bd3c178e53c6d87c5"
+        );
+
+        let (_node, path) = tree
+            .search_with_path(|user_data| user_data.value == "ccc")
+            .unwrap();
+        assert!(verify_proof_with_options(
+            tree.options(),
+            &root,
+            &user_data[2],
+            &path
+        ));
+
+        // The children are sorted before hashing, so the directions do not matter.
+        let flipped = TraversePath::<Sha256>::from_steps(
+            path.siblings.clone(),
+            vec![NodeDirection::Right; path.directions.len()],
+        );
+        assert!(verify_proof_with_options(
+            tree.options(),
+            &root,
+            &user_data[2],
+            &flipped
+        ));
+    }
+
+    #[cfg(feature = "keccak")]
+    #[test]
+    fn it_can_build_a_keccak_sorted_pair_tree() {
+        let user_data = generate_user_item_a();
+
+        let tree: MerkleTree<_, Keccak256> =
+            MerkleTree::build_with_options(TreeOptions::sorted_pair(), &user_data);
+
+        assert_eq!(
+            tree.root().unwrap(),
+            "864aade34ca60dd2d42129a52079d154543a635b2d3ece21d3e06edc8cdd0620"
+        );
+    }
+
+    #[rstest]
+    #[case(Padding::DuplicateLast, 1)]
+    #[case(Padding::DuplicateLast, 9)]
+    #[case(Padding::Promote, 11)]
+    #[case(Padding::PadWithEmpty, 6)]
+    fn it_can_verify_sorted_pair_proofs_without_directions(
+        #[case] padding: Padding,
+        #[case] leaf_count: usize,
+    ) {
+        let user_data = util::generate_random_user_data(leaf_count);
+        let options = TreeOptions::sorted_pair().with_padding(padding);
+        let tree: MerkleTree<_> = MerkleTree::build_with_options(options, &user_data);
+        let root = format!("0x{}", tree.root().unwrap());
+
+        for (index, leaf) in user_data.iter().enumerate() {
+            let (_node, path) = tree.proof_by_index(index).unwrap();
+            let proof = path.to_sorted_pair_proof();
+            assert!(proof.iter().all(|sibling| sibling.starts_with("0x")));
+            assert!(verify_sorted_pair_proof::<_, Sha256>(&root, leaf, &proof));
+
+            let other = &user_data[(index + 1) % leaf_count];
+            if other.id != leaf.id {
+                assert!(!verify_sorted_pair_proof::<_, Sha256>(&root, other, &proof));
+            }
+        }
+    }
+
+    #[cfg(feature = "blake3")]
+    #[test]
+    fn it_can_build_a_tree_with_blake3() {
+        let user_data = generate_user_item_a();
+
+        let tag_leaf = "Bitcoin_Transaction";
+        let tag_branch = "Bitcoin_Transaction";
+
+        let tree: MerkleTree<_, Blake3> = MerkleTree::build(tag_leaf, tag_branch, &user_data);
+
+        assert_eq!(
+            tree.root().unwrap(),
+            "92fd9d7791e06aa8be54945c3ddc1f4e276cd0c4e87cd0bac837506c0c8ef322"
+        );
+    }
+
+    #[test]
+    fn it_can_verify_proof_with_double_hash() {
+        let user_data = generate_user_item_b();
+
+        let tree: MerkleTree<_> =
+            MerkleTree::build_with_options(TreeOptions::double_hash(), &user_data);
+        let root = tree.root().unwrap();
+        let (_node, path) = tree
+            .search_with_path(|user_data| user_data.id == 5)
+            .unwrap();
 
-            format!("{node_mermaid}{node_connection_mermaid}")
-        }) {
-            Some(output) => format!("flowchart TD\n{}", output.join("\n")),
-            None => format!("Tree is empty."),
-        }
+        assert!(verify_proof_with_options(
+            tree.options(),
+            &root,
+            &user_data[4],
+            &path
+        ));
+        assert!(!verify_proof(
+            "ProofOfReserve_Leaf",
+            "ProofOfReserve_Branch",
+            &root,
+            &user_data[4],
+            &path
+        ));
     }
 
-    /// Searches for a user with the given predicate.
-    ///
-    /// # Arguments
-    ///
-    /// * `predicate`: A function that takes a `&UserData` and returns a boolean.
-    ///              It returns true if the user data matches the search criteria, false otherwise.
-    ///
-    /// # Returns
-    ///
-    /// An `Option` containing a tuple of `(&MerkleNode, TraversePath)` if a matching user is found, `None` otherwise.
-    pub fn search_with_path<F>(&self, predicate: F) -> Option<(&MerkleNode<T>, TraversePath)>
-    where
-        F: Fn(&T) -> bool,
-    {
-        if let Some(root) = self.root {
-            let mut path = TraversePath::new();
-            self.search_node_with_path(&self.nodes[root], &predicate, &mut path)
-        } else {
-            None
+    #[rstest]
+    #[case(Padding::DuplicateLast, 1)]
+    #[case(Padding::DuplicateLast, 7)]
+    #[case(Padding::DuplicateLast, 8)]
+    #[case(Padding::Promote, 5)]
+    #[case(Padding::Promote, 11)]
+    #[case(Padding::PadWithEmpty, 6)]
+    #[case(Padding::PadWithEmpty, 13)]
+    fn it_can_generate_proof_by_index(#[case] padding: Padding, #[case] leaf_count: usize) {
+        let user_data = util::generate_random_user_data(leaf_count);
+
+        let options = TreeOptions::tagged("ProofOfReserve_Leaf", "ProofOfReserve_Branch")
+            .with_padding(padding);
+        let tree: MerkleTree<_> = MerkleTree::build_with_options(options, &user_data);
+        let root = tree.root().unwrap();
+
+        for (index, item) in user_data.iter().enumerate() {
+            let (node, path) = tree.proof_by_index(index).unwrap();
+            let (_node, searched) = tree
+                .search_with_path(|user_data| user_data.id == item.id)
+                .unwrap();
+
+            assert_eq!(node.user_data.as_ref().unwrap().id, item.id);
+            assert_eq!(path.to_vec(), searched.to_vec());
+            assert!(verify_proof_with_options(
+                tree.options(),
+                &root,
+                item,
+                &path
+            ));
         }
+
+        assert!(matches!(
+            tree.proof_by_index(leaf_count),
+            Err(MerkleError::LeafNotFound)
+        ));
     }
 
-    fn search_node_with_path<'a, F>(
-        &'a self,
-        node: &'a MerkleNode<T>,
-        predicate: &F,
-        path: &mut TraversePath,
-    ) -> Option<(&'a MerkleNode<T>, TraversePath)>
-    where
-        F: Fn(&T) -> bool,
-    {
-        if let Some(user_data) = &node.user_data {
-            if predicate(user_data) {
-                return Some((
-                    node,
-                    TraversePath {
-                        directions: path.directions.clone(),
-                        hashes: path.hashes.clone(),
-                    },
-                ));
+    #[rstest]
+    #[case(Padding::DuplicateLast)]
+    #[case(Padding::Promote)]
+    #[case(Padding::PadWithEmpty)]
+    fn it_can_push_leaves(#[case] padding: Padding) {
+        let user_data = util::generate_random_user_data(40);
+        let options = TreeOptions::tagged("ProofOfReserve_Leaf", "ProofOfReserve_Branch")
+            .with_padding(padding);
+
+        let mut tree: MerkleTree<_> = MerkleTree::build_with_options(options.clone(), &[]);
+        assert_eq!(tree.root(), Err(MerkleError::EmptyTree));
+
+        for (count, item) in user_data.iter().enumerate().map(|(i, item)| (i + 1, item)) {
+            tree.push(item.clone());
+
+            let rebuilt: MerkleTree<_> =
+                MerkleTree::build_with_options(options.clone(), &user_data[..count]);
+            assert_eq!(tree.leaf_count(), count);
+            assert_eq!(tree.root(), rebuilt.root());
+            assert!(tree.nodes.len() <= rebuilt.nodes.len() + 1);
+
+            let root = tree.root().unwrap();
+            for (index, item) in user_data[..count].iter().enumerate() {
+                let (_node, path) = tree.proof_by_index(index).unwrap();
+                assert!(verify_proof_with_options(&options, &root, item, &path));
             }
         }
+    }
 
-        if let Some(left) = node.left {
-            path.add_step(hex::encode(&node.hash), NodeDirection::Left);
-            if let Some(result) = self.search_node_with_path(&self.nodes[left], predicate, path) {
-                return Some(result);
-            }
-            path.hashes.pop();
-            path.directions.pop();
-        }
+    #[test]
+    fn it_can_build_from_iter() {
+        let user_data = util::generate_random_user_data(25);
+
+        let tree: MerkleTree<_> =
+            MerkleTree::build("ProofOfReserve_Leaf", "ProofOfReserve_Branch", &user_data);
+        let streamed: MerkleTree<_> = MerkleTree::build_from_iter(
+            "ProofOfReserve_Leaf",
+            "ProofOfReserve_Branch",
+            (1..=25).map(|id| util::UserData {
+                id,
+                balance: id * 1000,
+            }),
+        );
 
-        if let Some(right) = node.right {
-            path.add_step(hex::encode(&node.hash), NodeDirection::Right);
-            if let Some(result) = self.search_node_with_path(&self.nodes[right], predicate, path) {
-                return Some(result);
-            }
-            path.hashes.pop();
-            path.directions.pop();
+        assert_eq!(streamed.leaf_count(), 25);
+        assert_eq!(streamed.root(), tree.root());
+    }
+
+    #[rstest]
+    #[case(Padding::DuplicateLast, 9)]
+    #[case(Padding::Promote, 11)]
+    #[case(Padding::PadWithEmpty, 13)]
+    fn it_can_update_leaves(#[case] padding: Padding, #[case] leaf_count: usize) {
+        let mut user_data = util::generate_random_user_data(leaf_count);
+        let options = TreeOptions::tagged("ProofOfReserve_Leaf", "ProofOfReserve_Branch")
+            .with_padding(padding);
+
+        let mut tree: MerkleTree<_> = MerkleTree::build_with_options(options.clone(), &user_data);
+        let node_count = tree.nodes.len();
+
+        for index in [0, leaf_count / 2, leaf_count - 1] {
+            user_data[index].balance += 1;
+            assert_eq!(tree.update(index, user_data[index].clone()), Ok(()));
+
+            let rebuilt: MerkleTree<_> =
+                MerkleTree::build_with_options(options.clone(), &user_data);
+            assert_eq!(tree.root(), rebuilt.root());
+            assert_eq!(
+                tree.leaf(index)
+                    .unwrap()
+                    .user_data
+                    .as_ref()
+                    .unwrap()
+                    .balance,
+                user_data[index].balance
+            );
         }
 
-        None
+        assert_eq!(tree.nodes.len(), node_count);
+        assert_eq!(
+            tree.update(leaf_count, user_data[0].clone()),
+            Err(MerkleError::LeafNotFound)
+        );
     }
-}
 
-/// Truncates a string in the middle if it exceeds the maximum length.
-///
-/// If the input string's length is less than or equal to `max_len`, it returns the original string.
-/// Otherwise, it returns a new string with the first `max_len / 2` characters, an ellipsis ("..."),
-/// and the last `max_len - (max_len / 2)` characters.
-///
-/// # Arguments
-///
-/// * `input`: The string to truncate.
-/// * `max_len`: The maximum length of the string.
-///
-/// # Returns
-///
-/// A string of truncated text.
-fn truncate_middle(input: &str, max_len: usize) -> String {
-    let len = input.len();
-    if len <= max_len {
-        return input.to_string();
+    #[rstest]
+    #[case(Padding::DuplicateLast, 9)]
+    #[case(Padding::Promote, 11)]
+    #[case(Padding::PadWithEmpty, 13)]
+    fn it_can_remove_leaves(#[case] padding: Padding, #[case] leaf_count: usize) {
+        let mut user_data = util::generate_random_user_data(leaf_count);
+        let options = TreeOptions::tagged("ProofOfReserve_Leaf", "ProofOfReserve_Branch")
+            .with_padding(padding);
+
+        let mut tree: MerkleTree<_> = MerkleTree::build_with_options(options.clone(), &user_data);
+
+        for index in [leaf_count / 2, leaf_count - 2, 0] {
+            user_data.remove(index);
+            assert_eq!(tree.remove(index), Ok(()));
+
+            let rebuilt: MerkleTree<_> =
+                MerkleTree::build_with_options(options.clone(), &user_data);
+            assert_eq!(tree.leaf_count(), user_data.len());
+            assert_eq!(tree.root(), rebuilt.root());
+        }
+
+        assert_eq!(tree.remove(user_data.len()), Err(MerkleError::LeafNotFound));
+
+        while tree.leaf_count() > 0 {
+            assert_eq!(tree.remove(0), Ok(()));
+        }
+        assert_eq!(tree.root(), Err(MerkleError::EmptyTree));
     }
 
-    let half_len = max_len / 2;
-    let start = &input[..half_len];
-    let end = &input[len - (max_len - half_len)..];
+    #[rstest]
+    #[case(Padding::DuplicateLast, 9)]
+    #[case(Padding::Promote, 11)]
+    #[case(Padding::PadWithEmpty, 13)]
+    fn it_can_iterate_leaves(#[case] padding: Padding, #[case] leaf_count: usize) {
+        let user_data = util::generate_random_user_data(leaf_count);
+        let options = TreeOptions::tagged("ProofOfReserve_Leaf", "ProofOfReserve_Branch")
+            .with_padding(padding);
+
+        let tree: MerkleTree<_> = MerkleTree::build_with_options(options, &user_data);
+
+        let leaves: Vec<_> = tree.leaves().collect();
+        assert_eq!(leaves.len(), leaf_count);
+        for (index, item) in leaves {
+            assert_eq!(item.id, user_data[index].id);
+        }
 
-    format!("{}...{}", start, end)
-}
+        let owned: Vec<_> = tree.into_leaves().map(|(_, item)| item.id).collect();
+        let expected: Vec<_> = user_data.iter().map(|item| item.id).collect();
+        assert_eq!(owned, expected);
 
-/// Calculates a tagged hash using SHA256.
-///
-/// This function takes a tag and an input byte slice, calculates the SHA256 hash of the tag,
-/// then calculates the SHA256 hash of the concatenation of the tag's hash (twice) and the input.
-///
-/// # Arguments
-///
-/// * `tag`: The tag string.
-/// * `input`: The input byte slice.
-///
-/// # Returns
-///
-/// The tagged SHA256 hash as a `Vec<u8>`.
-pub fn tagged_hash(tag: &str, input: &[u8]) -> Hash {
-    let mut hasher = Sha256::new();
-    hasher.update(tag.as_bytes());
-    let tag_hash = hasher.finalize();
+        let hashes: Vec<Output<Sha256>> = vec![Default::default(); 3];
+        let tree: MerkleTree<util::UserData> =
+            MerkleTree::from_leaf_hashes_with_options(TreeOptions::double_hash(), &hashes);
+        assert_eq!(tree.leaves().count(), 0);
+    }
 
-    let mut hasher = Sha256::new();
-    hasher.update(&tag_hash);
-    hasher.update(&tag_hash);
-    hasher.update(input);
-    hasher.finalize()
-}
+    #[test]
+    fn it_can_iterate_nodes() {
+        let user_data = util::generate_random_user_data(3);
+        let options = TreeOptions::tagged("ProofOfReserve_Leaf", "ProofOfReserve_Branch")
+            .with_padding(Padding::Promote);
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use rstest::rstest;
+        let tree: MerkleTree<_> = MerkleTree::build_with_options(options, &user_data);
+        let label = |(node, depth): (&MerkleNode<util::UserData>, usize)| {
+            (node.user_data.as_ref().map(|item| item.id), depth)
+        };
 
-    #[rstest]
-    #[case("abcdefghijklmnopqrstuvwxyz", 10, "abcde...vwxyz")]
-    #[case("abcdefghijklmnopqrstuvwxyz", 5, "ab...xyz")]
-    #[case("abcdefghijklmnopqrstuvwxyz", 2, "a...z")]
-    #[case("abcdefghijklmnopqrstuvwxyz", 1, "...z")]
-    fn it_can_truncate_middle(#[case] input: &str, #[case] max_len: usize, #[case] expected: &str) {
-        let actual = super::truncate_middle(input, max_len);
-        assert_eq!(actual, expected);
-    }
+        let level_order: Vec<_> = tree.iter_level_order().map(label).collect();
+        assert_eq!(
+            level_order,
+            [
+                (None, 0),
+                (None, 1),
+                (Some(3), 1),
+                (Some(1), 2),
+                (Some(2), 2)
+            ]
+        );
 
-    #[rstest]
-    #[case(
-        "Bitcoin_Transaction",
-        "aaa",
-        "d2d838724571ff750eb7f498a667c32f522efae2b403eae6f678207ac6f978de"
-    )]
-    #[case(
-        "Bitcoin_Transaction",
-        "bbb",
-        "7cdf701413062eaba020af83441a6762ee2910e36b1805bad072103b0257f441"
-    )]
-    #[case(
-        "hello",
-        "aaa",
-        "aa7deacc6231c611d10b4a2b14bec43c30251b977610fd5a322550003f2b216b"
-    )]
-    fn it_can_tagged_hash(#[case] tag: &str, #[case] input: &str, #[case] expected: &str) {
-        let actual = super::tagged_hash(tag, input.as_bytes());
-        assert_eq!(hex::encode(actual), expected);
+        let pre_order: Vec<_> = tree.iter_pre_order().map(label).collect();
+        assert_eq!(
+            pre_order,
+            [
+                (None, 0),
+                (None, 1),
+                (Some(1), 2),
+                (Some(2), 2),
+                (Some(3), 1)
+            ]
+        );
+
+        let post_order: Vec<_> = tree.iter_post_order().map(label).collect();
+        assert_eq!(
+            post_order,
+            [
+                (Some(1), 2),
+                (Some(2), 2),
+                (None, 1),
+                (Some(3), 1),
+                (None, 0)
+            ]
+        );
+        assert_eq!(post_order.last(), level_order.first());
+        assert_eq!(tree.iter_pre_order().count(), tree.nodes.len());
     }
 
-    #[derive(Clone, Debug, Default)]
-    #[allow(non_camel_case_types)]
-    pub struct UserItem_A {
-        value: String,
+    #[test]
+    fn it_can_traverse_with_visitor() {
+        let user_data = util::generate_random_user_data(8);
+        let tree: MerkleTree<_> =
+            MerkleTree::build("ProofOfReserve_Leaf", "ProofOfReserve_Branch", &user_data);
+
+        let mut leaves = Vec::new();
+        tree.traverse(|node, depth, position| {
+            if let Some(item) = &node.user_data {
+                leaves.push((item.id, depth, position));
+            }
+            ControlFlow::Continue(())
+        });
+        let expected: Vec<_> = user_data
+            .iter()
+            .enumerate()
+            .map(|(index, item)| (item.id, 3, index))
+            .collect();
+        assert_eq!(leaves, expected);
+
+        let mut visited = Vec::new();
+        tree.traverse(|_, depth, position| {
+            visited.push((depth, position));
+            if depth == 2 {
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
+            }
+        });
+        assert_eq!(
+            visited,
+            [(0, 0), (1, 0), (2, 0), (2, 1), (1, 1), (2, 2), (2, 3)]
+        );
     }
 
-    impl MerkleTreeData for UserItem_A {
-        fn serialize(&self) -> Vec<u8> {
-            format!("{}", self.value).as_bytes().to_vec()
+    #[test]
+    fn it_can_walk_nodes_by_index() {
+        let user_data = util::generate_random_user_data(5);
+        let tree: MerkleTree<_> =
+            MerkleTree::build("ProofOfReserve_Leaf", "ProofOfReserve_Branch", &user_data);
+
+        let root = tree.node(tree.root_index().unwrap()).unwrap();
+        assert_eq!(Ok(hex::encode(root.hash())), tree.root());
+        assert!(!root.is_leaf());
+
+        let mut node = root;
+        while let Some(left) = node.left() {
+            assert!(node.right().is_some());
+            node = tree.node(left).unwrap();
         }
+        assert!(node.is_leaf());
+        assert_eq!(node.user_data.as_ref().unwrap().id, 1);
+        assert_eq!(node.hash(), tree.leaf(0).unwrap().hash());
 
-        fn mermaid_node_label(&self) -> String {
-            format!("<br>{}", self.value)
+        let mut node = root;
+        while let Some(right) = node.right() {
+            node = tree.node(right).unwrap();
         }
-    }
+        assert_eq!(node.user_data.as_ref().unwrap().id, 5);
 
-    fn generate_user_item_a() -> Vec<UserItem_A> {
-        vec!["aaa", "bbb", "ccc", "ddd", "eee"]
-            .into_iter()
-            .map(|v| UserItem_A {
-                value: String::from(v),
-            })
-            .collect()
+        assert!(tree.node(tree.nodes.len()).is_none());
     }
 
-    #[derive(Clone, Debug, Default)]
-    #[allow(non_camel_case_types)]
-    pub struct UserItem_B {
-        pub id: u32,
-        pub balance: u32,
+    #[test]
+    fn it_can_verify_against_roots_of_either_case() {
+        let user_data = util::generate_random_user_data(5);
+        let tree: MerkleTree<_> =
+            MerkleTree::build("ProofOfReserve_Leaf", "ProofOfReserve_Branch", &user_data);
+        let root = tree.root().unwrap();
+        let (node, path) = tree.proof_by_index(1).unwrap();
+        let leaf = node.user_data.as_ref().unwrap();
+        let verify = |root: &str| {
+            verify_proof(
+                "ProofOfReserve_Leaf",
+                "ProofOfReserve_Branch",
+                root,
+                leaf,
+                &path,
+            )
+        };
+
+        assert!(verify(&root));
+        assert!(verify(&root.to_uppercase()));
+        assert!(!verify(&root[2..]));
+        assert!(!verify(&format!("{root}00")));
+        assert!(!verify(&root.replace('a', "g")));
+        assert!(!verify(""));
     }
 
-    impl MerkleTreeData for UserItem_B {
-        fn serialize(&self) -> Vec<u8> {
-            format!("({},{})", self.id, self.balance)
-                .as_bytes()
-                .to_vec()
-        }
+    #[test]
+    fn it_can_build_in_canonical_order() {
+        let user_data = util::generate_random_user_data(9);
+        let mut shuffled = user_data.clone();
+        shuffle_with_seed::<_, Sha256>(&mut shuffled, b"shard");
+        shuffled.push(util::UserData { id: 4, balance: 1 });
+        let mut reversed = shuffled.clone();
+        reversed.reverse();
+
+        let tree: MerkleTree<_> = MerkleTree::build_canonical(
+            "ProofOfReserve_Leaf",
+            "ProofOfReserve_Branch",
+            &shuffled,
+            |data| data.id,
+        );
+        let other: MerkleTree<_> = MerkleTree::build_canonical(
+            "ProofOfReserve_Leaf",
+            "ProofOfReserve_Branch",
+            &reversed,
+            |data| data.id,
+        );
+        assert_eq!(tree.root(), other.root());
 
-        fn mermaid_node_label(&self) -> String {
-            format!("<br>User ID: {}<br>Balance: {}", self.id, self.balance)
-        }
+        let ids: Vec<_> = tree
+            .leaves()
+            .map(|(_, data)| (data.id, data.balance))
+            .collect();
+        assert_eq!(
+            ids[..5],
+            [(1, 1000), (2, 2000), (3, 3000), (4, 1), (4, 4000)]
+        );
     }
 
-    fn generate_user_item_b() -> Vec<UserItem_B> {
-        vec![(1, 1111), (2, 2222), (3, 3333), (4, 4444), (5, 5555)]
-            .into_iter()
-            .map(|(id, balance)| UserItem_B { id, balance })
-            .collect()
-    }
+    #[rstest]
+    #[case(Padding::DuplicateLast)]
+    #[case(Padding::Promote)]
+    #[case(Padding::PadWithEmpty)]
+    fn it_can_search_all_with_paths(#[case] padding: Padding) {
+        let user_data: Vec<_> = (1..=11)
+            .map(|i| util::UserData {
+                id: i % 4,
+                balance: i * 1000,
+            })
+            .collect();
+        let options = TreeOptions::tagged("ProofOfReserve_Leaf", "ProofOfReserve_Branch")
+            .with_padding(padding);
 
-    #[test]
-    fn it_can_build_a_tree_with_empty_input() {
-        let input: Vec<UserItem_A> = vec![];
+        let tree: MerkleTree<_> = MerkleTree::build_with_options(options.clone(), &user_data);
+        let root = tree.root().unwrap();
 
-        let tag_leaf = "Bitcoin_Transaction";
-        let tag_branch = "Bitcoin_Transaction";
+        let results = tree.search_all_with_paths(|data| data.id == 3);
+        let balances: Vec<_> = results
+            .iter()
+            .map(|(node, _)| node.user_data.as_ref().unwrap().balance)
+            .collect();
+        assert_eq!(balances, [3000, 7000, 11000]);
+        for (node, path) in &results {
+            let data = node.user_data.as_ref().unwrap();
+            assert!(verify_proof_with_options(&options, &root, data, path));
+        }
 
-        let tree = MerkleTree::build(tag_leaf, tag_branch, &input);
+        assert!(tree.search_all_with_paths(|data| data.id == 4).is_empty());
+    }
 
-        assert!(tree.root().is_none());
+    #[rstest]
+    #[case(Padding::DuplicateLast, 9)]
+    #[case(Padding::Promote, 11)]
+    #[case(Padding::PadWithEmpty, 13)]
+    fn it_can_search_with_path_iteratively(#[case] padding: Padding, #[case] leaf_count: usize) {
+        let user_data = util::generate_random_user_data(leaf_count);
+        let options = TreeOptions::tagged("ProofOfReserve_Leaf", "ProofOfReserve_Branch")
+            .with_padding(padding);
+
+        let tree: MerkleTree<_> = MerkleTree::build_with_options(options, &user_data);
+
+        for (index, item) in user_data.iter().enumerate() {
+            let (node, path) = tree.search_with_path(|data| data.id == item.id).unwrap();
+            let (_, expected) = tree.proof_by_index(index).unwrap();
+            assert_eq!(node.user_data.as_ref().unwrap().id, item.id);
+            assert_eq!(path.to_vec(), expected.to_vec());
+        }
+        assert!(matches!(
+            tree.search_with_path(|data| data.id == 0),
+            Err(MerkleError::LeafNotFound)
+        ));
     }
 
-    #[test]
-    fn it_can_build_a_tree_user_item_a() {
-        let user_data = generate_user_item_a();
+    #[rstest]
+    #[case(Padding::DuplicateLast, 9)]
+    #[case(Padding::Promote, 11)]
+    #[case(Padding::PadWithEmpty, 13)]
+    fn it_can_generate_all_proofs(#[case] padding: Padding, #[case] leaf_count: usize) {
+        let user_data = util::generate_random_user_data(leaf_count);
+        let options = TreeOptions::tagged("ProofOfReserve_Leaf", "ProofOfReserve_Branch")
+            .with_padding(padding);
+
+        let tree: MerkleTree<_> = MerkleTree::build_with_options(options.clone(), &user_data);
+        let root = tree.root().unwrap();
+
+        let proofs = tree.all_proofs();
+        assert_eq!(proofs.len(), leaf_count);
+        for (index, path) in proofs.iter().enumerate() {
+            let (_, expected) = tree.proof_by_index(index).unwrap();
+            assert_eq!(path.to_vec(), expected.to_vec());
+            assert!(verify_proof_with_options(
+                &options,
+                &root,
+                &user_data[index],
+                path
+            ));
+        }
 
-        let tag_leaf = "Bitcoin_Transaction";
-        let tag_branch = "Bitcoin_Transaction";
+        #[cfg(feature = "std")]
+        for threads in [1, 4, 64] {
+            let parallel = tree.all_proofs_parallel(threads);
+            let parallel: Vec<_> = parallel.iter().map(TraversePath::to_vec).collect();
+            let expected: Vec<_> = proofs.iter().map(TraversePath::to_vec).collect();
+            assert_eq!(parallel, expected);
+        }
+    }
 
-        let tree = MerkleTree::build(tag_leaf, tag_branch, &user_data);
+    #[test]
+    fn it_can_render_into_writer() {
+        let user_data = util::generate_random_user_data(3);
+        let tree: MerkleTree<_> =
+            MerkleTree::build("ProofOfReserve_Leaf", "ProofOfReserve_Branch", &user_data);
+
+        let mut rendered = String::new();
+        tree.render(&mut rendered).unwrap();
+        assert_eq!(rendered, tree.display_tree());
+        assert_eq!(rendered, tree.to_string());
+        assert_eq!(rendered.lines().count(), 7);
+        assert!(rendered.starts_with("Root: "));
+        assert!(rendered.contains("\n  Left: "));
+
+        let root = tree.root().unwrap();
+        let full = format!("{tree:.64}");
+        assert_eq!(full.lines().next(), Some(format!("Root: {root}").as_str()));
+
+        let mut untruncated = String::new();
+        tree.render_with_hash_length(&mut untruncated, None)
+            .unwrap();
+        assert_eq!(untruncated, full);
 
-        assert_eq!(
-            tree.root().unwrap(),
-            "4aa906745f72053498ecc74f79813370a4fe04f85e09421df2d5ef760dfa94b5"
-        );
+        let empty: MerkleTree<util::UserData> =
+            MerkleTree::build("ProofOfReserve_Leaf", "ProofOfReserve_Branch", &[]);
+        assert_eq!(empty.to_string(), "Tree is empty.");
     }
 
     #[test]
-    fn it_can_build_a_tree_user_item_b() {
-        let user_data = generate_user_item_b();
+    fn it_can_build_from_32_byte_leaf_hashes() {
+        let user_data = util::generate_random_user_data(5);
+        let options = TreeOptions::tagged("ProofOfReserve_Leaf", "ProofOfReserve_Branch");
+        let tree: MerkleTree<_> = MerkleTree::build_with_options(options.clone(), &user_data);
 
-        let tag_leaf = "ProofOfReserve_Leaf";
-        let tag_branch = "ProofOfReserve_Branch";
-
-        let tree = MerkleTree::build(tag_leaf, tag_branch, &user_data);
+        let hashes: Vec<[u8; 32]> = user_data
+            .iter()
+            .map(|user| options.hash_leaf::<Sha256>(&user.serialize()).into())
+            .collect();
+        let prehashed: MerkleTree<util::UserData> =
+            MerkleTree::from_leaf_hashes("ProofOfReserve_Branch", &hashes);
 
+        assert_eq!(prehashed.root(), tree.root());
+        assert_eq!(prehashed.leaf_count(), 5);
+        assert!(prehashed.leaf(0).unwrap().user_data.is_none());
         assert_eq!(
-            tree.root().unwrap(),
-            "e752d40ca9a0626be5fea078ef35216a9c50554934a54dfbe2eb60195af66c85"
+            prehashed.leaf(3).unwrap().hash.as_slice(),
+            hashes[3].as_slice()
         );
     }
 
@@ -561,7 +3067,7 @@ mod tests {
         let tag_leaf = "Bitcoin_Transaction";
         let tag_branch = "Bitcoin_Transaction";
 
-        let tree = MerkleTree::build(tag_leaf, tag_branch, &user_data);
+        let tree: MerkleTree<_> = MerkleTree::build(tag_leaf, tag_branch, &user_data);
         let user_id = "aaa";
         let (_node, path) = tree
             .search_with_path(|user_data| user_data.value == user_id)
@@ -571,15 +3077,15 @@ mod tests {
             path.to_vec(),
             vec![
                 (
-                    "4aa906745f72053498ecc74f79813370a4fe04f85e09421df2d5ef760dfa94b5".to_string(),
+                    "77c15425a85c8156d246ef757c922594237f53f35f5939ab5f9527f1aae3a763".to_string(),
                     0u8
                 ),
                 (
-                    "718b18c132f71dad76a3977a587e40c876bab3436b0f9a0446dbfadca2c13ea3".to_string(),
+                    "e53121f11854ee780fc618c462675b7ab2b56642d536b78d6d0e204a52cc4d4b".to_string(),
                     0u8
                 ),
                 (
-                    "631bae42ba587408a741fa7d482a955d059caa471c5d66548d44a6ed234e782c".to_string(),
+                    "7cdf701413062eaba020af83441a6762ee2910e36b1805bad072103b0257f441".to_string(),
                     0u8
                 )
             ]
@@ -593,7 +3099,7 @@ mod tests {
         let tag_leaf = "ProofOfReserve_Leaf";
         let tag_branch = "ProofOfReserve_Branch";
 
-        let tree = MerkleTree::build(tag_leaf, tag_branch, &user_data);
+        let tree: MerkleTree<_> = MerkleTree::build(tag_leaf, tag_branch, &user_data);
         let user_id = 3u32;
         let (_node, path) = tree
             .search_with_path(|user_data| user_data.id == user_id)
@@ -603,18 +3109,112 @@ mod tests {
             path.to_vec(),
             vec![
                 (
-                    "e752d40ca9a0626be5fea078ef35216a9c50554934a54dfbe2eb60195af66c85".to_string(),
+                    "4cddfd6f17a3bff443eda2e1fa69e2259e5ad9117b6730c3ced7052e1f42fbeb".to_string(),
                     0u8
                 ),
                 (
-                    "fafe4ecc00e37d340d72f581fbbda4e179ad24bdc2f45713dcc2a38ebfc30439".to_string(),
+                    "99fb04c9b8fd37e66b2dde367d91f2c930b2ab162dbbf7298e9313c309c7925f".to_string(),
                     1u8
                 ),
                 (
-                    "d185af244042b0fecba7ee16c9933d73b10c5482104538274dd777b6b120eae1".to_string(),
+                    "8520072399ad3462db395a7a9803c6fe3f4143d502a0eb145e6c69ba7ec6d22d".to_string(),
                     0u8
                 )
             ]
         );
     }
+
+    #[rstest]
+    #[case("aaa")]
+    #[case("ccc")]
+    #[case("eee")]
+    fn it_can_verify_proof_user_item_a(#[case] value: &str) {
+        let user_data = generate_user_item_a();
+
+        let tag_leaf = "Bitcoin_Transaction";
+        let tag_branch = "Bitcoin_Transaction";
+
+        let tree: MerkleTree<_> = MerkleTree::build(tag_leaf, tag_branch, &user_data);
+        let (node, path) = tree
+            .search_with_path(|user_data| user_data.value == value)
+            .unwrap();
+        let leaf = node.user_data.as_ref().unwrap();
+        let root = tree.root().unwrap();
+
+        assert!(verify_proof(tag_leaf, tag_branch, &root, leaf, &path));
+    }
+
+    #[test]
+    fn it_can_verify_proof_for_every_user_item_b() {
+        let user_data = generate_user_item_b();
+
+        let tag_leaf = "ProofOfReserve_Leaf";
+        let tag_branch = "ProofOfReserve_Branch";
+
+        let tree: MerkleTree<_> = MerkleTree::build(tag_leaf, tag_branch, &user_data);
+        let root = tree.root().unwrap();
+
+        for item in &user_data {
+            let (_node, path) = tree
+                .search_with_path(|user_data| user_data.id == item.id)
+                .unwrap();
+
+            assert!(verify_proof(tag_leaf, tag_branch, &root, item, &path));
+        }
+    }
+
+    #[test]
+    fn it_can_compute_root_from_sibling_path() {
+        let user_data = generate_user_item_a();
+
+        let tag_leaf = "Bitcoin_Transaction";
+        let tag_branch = "Bitcoin_Transaction";
+
+        let tree: MerkleTree<_> = MerkleTree::build(tag_leaf, tag_branch, &user_data);
+        let (_node, path) = tree
+            .search_with_path(|user_data| user_data.value == "eee")
+            .unwrap();
+
+        assert_eq!(
+            path.compute_root(tag_leaf, tag_branch, &user_data[4]),
+            tree.root()
+        );
+    }
+
+    #[test]
+    fn it_rejects_proof_with_tampered_input() {
+        let user_data = generate_user_item_b();
+
+        let tag_leaf = "ProofOfReserve_Leaf";
+        let tag_branch = "ProofOfReserve_Branch";
+
+        let tree: MerkleTree<_> = MerkleTree::build(tag_leaf, tag_branch, &user_data);
+        let root = tree.root().unwrap();
+        let (_node, path) = tree
+            .search_with_path(|user_data| user_data.id == 3)
+            .unwrap();
+
+        let forged = UserItem_B {
+            id: 3,
+            balance: 9999,
+        };
+        assert!(!verify_proof(tag_leaf, tag_branch, &root, &forged, &path));
+
+        let other_root = "00".repeat(32);
+        assert!(!verify_proof(
+            tag_leaf,
+            tag_branch,
+            &other_root,
+            &user_data[2],
+            &path
+        ));
+
+        assert!(!verify_proof(
+            tag_leaf,
+            "Other_Branch",
+            &root,
+            &user_data[2],
+            &path
+        ));
+    }
 }
@@ -0,0 +1,413 @@
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+use core::marker::PhantomData;
+use sha2::digest::Output;
+use sha2::Digest;
+use std::io::{self, Write};
+
+use crate::{
+    level_sizes, MerkleError, MerkleTree, NodeDirection, NodeStore, Padding, Sha256, TraversePath,
+    TreeOptions,
+};
+
+/// The first bytes of a tree file.
+const MAGIC: &[u8; 8] = b"MRKLTREE";
+
+/// The size of the header before the node records.
+const HEADER_SIZE: usize = 40;
+
+/// Marks a missing node in a tree file.
+pub(crate) const NONE: u64 = u64::MAX;
+
+/// The error returned when a tree file cannot be read.
+#[derive(Debug)]
+pub enum TreeFileError {
+    /// The file could not be opened or mapped.
+    Io(io::Error),
+    /// The file is not a tree file, is truncated or refers to nodes it does not contain.
+    Malformed,
+    /// The file was written with a different digest or padding than the given options.
+    OptionsMismatch,
+}
+
+impl fmt::Display for TreeFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TreeFileError::Io(err) => write!(f, "failed to read the tree file: {err}"),
+            TreeFileError::Malformed => f.write_str("the tree file is malformed"),
+            TreeFileError::OptionsMismatch => {
+                f.write_str("the tree file was written with different options")
+            }
+        }
+    }
+}
+
+impl std::error::Error for TreeFileError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            TreeFileError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for TreeFileError {
+    fn from(err: io::Error) -> Self {
+        TreeFileError::Io(err)
+    }
+}
+
+impl<T, D: Digest, S: NodeStore<T, D>> MerkleTree<T, D, S> {
+    /// Writes the current version of the tree as a tree file, to be opened with `MappedTree`.
+    ///
+    /// The file has a 40 byte header followed by one fixed-width record per node: the hash
+    /// and the little-endian `u64` indices of the left and right child. User data is not
+    /// written, so the file serves roots, leaf hashes and proofs only.
+    ///
+    /// # Arguments
+    ///
+    /// * `writer`: Where to write the file to, such as a `std::fs::File`.
+    pub fn write_tree_file(&self, writer: impl io::Write) -> io::Result<()> {
+        let mut remap = vec![None; self.nodes.len()];
+        let mut order = Vec::new();
+        let mut stack: Vec<usize> = self.root.into_iter().collect();
+
+        while let Some(node) = stack.pop() {
+            if remap[node].is_some() {
+                continue;
+            }
+            remap[node] = Some(order.len() as u64);
+            order.push(node);
+            stack.extend(self.nodes[node].right);
+            stack.extend(self.nodes[node].left);
+        }
+        let new_index = |node: Option<usize>| node.map_or(NONE, |node| remap[node].unwrap());
+
+        let mut writer = io::BufWriter::new(writer);
+        writer.write_all(MAGIC)?;
+        writer.write_all(&(<D as Digest>::output_size() as u32).to_le_bytes())?;
+        writer.write_all(&[padding_code(self.options.padding), 0, 0, 0])?;
+        writer.write_all(&(self.leaf_count as u64).to_le_bytes())?;
+        writer.write_all(&new_index(self.root).to_le_bytes())?;
+        writer.write_all(&(order.len() as u64).to_le_bytes())?;
+
+        for node in order {
+            let node = &self.nodes[node];
+            writer.write_all(&node.hash)?;
+            writer.write_all(&new_index(node.left).to_le_bytes())?;
+            writer.write_all(&new_index(node.right).to_le_bytes())?;
+        }
+
+        writer.flush()
+    }
+}
+
+/// A read-only Merkle Tree that answers queries directly from the bytes of a tree file
+/// written by `MerkleTree::write_tree_file`, without deserializing it.
+///
+/// Opening a tree only checks the header, so with a memory-mapped file it takes constant
+/// time regardless of the size of the tree, and only the pages of the nodes that are
+/// visited are read from disk.
+pub struct MappedTree<B, D: Digest = Sha256> {
+    bytes: B,
+    options: TreeOptions,
+    leaf_count: usize,
+    root: Option<usize>,
+    node_count: usize,
+    digest: PhantomData<fn() -> D>,
+}
+
+#[cfg(feature = "mmap")]
+impl<D: Digest> MappedTree<memmap2::Mmap, D> {
+    /// Memory-maps a tree file.
+    ///
+    /// The file must not be modified while it is mapped.
+    ///
+    /// # Arguments
+    ///
+    /// * `options`: The options the tree was built with.
+    /// * `path`: The path of the file written by `MerkleTree::write_tree_file`.
+    ///
+    /// # Returns
+    ///
+    /// The tree, or an error if the file cannot be mapped or is not a tree file written with
+    /// the digest and padding of `options`.
+    pub fn open(
+        options: TreeOptions,
+        path: impl AsRef<std::path::Path>,
+    ) -> Result<Self, TreeFileError> {
+        let file = std::fs::File::open(path)?;
+        // Safety: the caller guarantees the file is not modified while it is mapped, and the
+        // bytes are only ever read through bounds-checked slices.
+        let bytes = unsafe { memmap2::Mmap::map(&file)? };
+        Self::from_bytes(options, bytes)
+    }
+}
+
+impl<B: AsRef<[u8]>, D: Digest> MappedTree<B, D> {
+    /// Opens a tree file that is already in memory or memory-mapped.
+    ///
+    /// # Arguments
+    ///
+    /// * `options`: The options the tree was built with.
+    /// * `bytes`: The contents of the file written by `MerkleTree::write_tree_file`.
+    ///
+    /// # Returns
+    ///
+    /// The tree, or an error if the bytes are not a tree file written with the digest and
+    /// padding of `options`.
+    pub fn from_bytes(options: TreeOptions, bytes: B) -> Result<Self, TreeFileError> {
+        let header = bytes
+            .as_ref()
+            .get(..HEADER_SIZE)
+            .ok_or(TreeFileError::Malformed)?;
+        if &header[..8] != MAGIC {
+            return Err(TreeFileError::Malformed);
+        }
+
+        let hash_size = u32::from_le_bytes(header[8..12].try_into().unwrap()) as usize;
+        if hash_size != <D as Digest>::output_size() || header[12] != padding_code(options.padding)
+        {
+            return Err(TreeFileError::OptionsMismatch);
+        }
+
+        let field = |offset: usize| read_u64(header, offset);
+        let leaf_count = field(16) as usize;
+        let root = field(24);
+        let node_count = field(32) as usize;
+
+        let size = (node_count.checked_mul(hash_size + 16))
+            .and_then(|records| records.checked_add(HEADER_SIZE));
+        if size != Some(bytes.as_ref().len()) || (root != NONE && root >= node_count as u64) {
+            return Err(TreeFileError::Malformed);
+        }
+
+        Ok(MappedTree {
+            bytes,
+            options,
+            leaf_count,
+            root: (root != NONE).then_some(root as usize),
+            node_count,
+            digest: PhantomData,
+        })
+    }
+
+    /// Returns the hashing configuration the tree was built with.
+    pub fn options(&self) -> &TreeOptions {
+        &self.options
+    }
+
+    /// Returns the number of leaves in the tree.
+    pub fn leaf_count(&self) -> usize {
+        self.leaf_count
+    }
+
+    /// Returns the number of node records in the file.
+    pub fn node_count(&self) -> usize {
+        self.node_count
+    }
+
+    /// Returns the hash of the root node of the tree.
+    ///
+    /// # Returns
+    ///
+    /// The hex encoded root, or `MerkleError::EmptyTree` if the tree has no leaves and the
+    /// empty root is `EmptyRoot::Undefined`.
+    pub fn root(&self) -> Result<String, MerkleError> {
+        self.root_hash().map(hex::encode)
+    }
+
+    /// Returns the hash of the root node as the digest's fixed-size output array.
+    ///
+    /// # Returns
+    ///
+    /// The root hash, or `MerkleError::EmptyTree` if the tree has no leaves and the empty root
+    /// is `EmptyRoot::Undefined`.
+    pub fn root_hash(&self) -> Result<Output<D>, MerkleError> {
+        match self.root {
+            Some(node) => Ok(Output::<D>::clone_from_slice(self.hash(node))),
+            None => self
+                .options
+                .empty_root_hash::<D>()
+                .ok_or(MerkleError::EmptyTree),
+        }
+    }
+
+    /// Returns the hash of the leaf at the given index.
+    ///
+    /// # Arguments
+    ///
+    /// * `index`: The position of the leaf in the input the tree was built from.
+    ///
+    /// # Returns
+    ///
+    /// The leaf hash, or `MerkleError::LeafNotFound` if the index is out of range.
+    pub fn leaf_hash(&self, index: usize) -> Result<Output<D>, MerkleError> {
+        self.proof_by_index(index).map(|(hash, _)| hash)
+    }
+
+    /// Generates the proof for the leaf at the given index, as `MerkleTree::proof_by_index`
+    /// does.
+    ///
+    /// # Arguments
+    ///
+    /// * `index`: The position of the leaf in the input the tree was built from.
+    ///
+    /// # Returns
+    ///
+    /// A tuple of the leaf hash and the `TraversePath`, or `MerkleError::LeafNotFound` if the
+    /// index is out of range or the file refers to nodes it does not contain.
+    pub fn proof_by_index(
+        &self,
+        index: usize,
+    ) -> Result<(Output<D>, TraversePath<D>), MerkleError> {
+        if index >= self.leaf_count {
+            return Err(MerkleError::LeafNotFound);
+        }
+
+        let level_sizes = level_sizes(self.leaf_count);
+        let mut node = self.root.ok_or(MerkleError::EmptyTree)?;
+        let mut path = TraversePath::new();
+
+        for level in (0..level_sizes.len() - 1).rev() {
+            let position = index >> level;
+            let unpaired = position & 1 == 0 && position == level_sizes[level] - 1;
+            if unpaired && self.options.padding == Padding::Promote {
+                continue;
+            }
+
+            let (left, right) = match self.children(node) {
+                (Some(left), Some(right)) => (left, right),
+                _ => return Err(MerkleError::LeafNotFound),
+            };
+
+            if position & 1 == 0 {
+                path.add_step(hex::encode(self.hash(right)), NodeDirection::Left);
+                node = left;
+            } else {
+                path.add_step(hex::encode(self.hash(left)), NodeDirection::Right);
+                node = right;
+            }
+        }
+
+        Ok((Output::<D>::clone_from_slice(self.hash(node)), path))
+    }
+
+    /// Returns the record of a node.
+    fn record(&self, node: usize) -> &[u8] {
+        let size = <D as Digest>::output_size() + 16;
+        let start = HEADER_SIZE + node * size;
+        &self.bytes.as_ref()[start..start + size]
+    }
+
+    /// Returns the hash of a node.
+    fn hash(&self, node: usize) -> &[u8] {
+        &self.record(node)[..<D as Digest>::output_size()]
+    }
+
+    /// Returns the left and right child of a node, or `None` for missing children and
+    /// children outside the file.
+    fn children(&self, node: usize) -> (Option<usize>, Option<usize>) {
+        let record = self.record(node);
+        let child = |offset: usize| {
+            let child = read_u64(record, offset);
+            (child < self.node_count as u64).then_some(child as usize)
+        };
+        let size = <D as Digest>::output_size();
+        (child(size), child(size + 8))
+    }
+}
+
+/// Returns the byte that identifies a padding policy in the file header.
+pub(crate) fn padding_code(padding: Padding) -> u8 {
+    match padding {
+        Padding::DuplicateLast => 0,
+        Padding::Promote => 1,
+        Padding::PadWithEmpty => 2,
+    }
+}
+
+/// Reads a little-endian `u64` at the given offset.
+fn read_u64(bytes: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{util, verify_proof_with_options};
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(Padding::DuplicateLast)]
+    #[case(Padding::Promote)]
+    #[case(Padding::PadWithEmpty)]
+    fn it_can_query_a_mapped_tree_file(#[case] padding: Padding) {
+        let options = TreeOptions::tagged("ProofOfReserve_Leaf", "ProofOfReserve_Branch")
+            .with_padding(padding);
+        let user_data = util::generate_random_user_data(13);
+        let mut tree: MerkleTree<_> = MerkleTree::build_with_options(options.clone(), &user_data);
+        // Keeps the nodes of the earlier version, which are not written to the file.
+        tree.version();
+        tree.update(5, user_data[0].clone()).unwrap();
+
+        let mut bytes = Vec::new();
+        tree.write_tree_file(&mut bytes).unwrap();
+        let mapped: MappedTree<_> = MappedTree::from_bytes(options.clone(), &bytes[..]).unwrap();
+
+        assert_eq!(mapped.leaf_count(), 13);
+        assert!(mapped.node_count() < tree.store().len());
+        assert_eq!(mapped.root(), tree.root());
+        let root = mapped.root().unwrap();
+        for index in 0..13 {
+            let (hash, path) = mapped.proof_by_index(index).unwrap();
+            let (node, expected) = tree.proof_by_index(index).unwrap();
+            assert_eq!(hash, node.hash);
+            assert_eq!(path.siblings, expected.siblings);
+            let item = if index == 5 {
+                &user_data[0]
+            } else {
+                &user_data[index]
+            };
+            assert!(verify_proof_with_options(&options, &root, item, &path));
+        }
+        assert_eq!(mapped.leaf_hash(13), Err(MerkleError::LeafNotFound));
+
+        let other = options.clone().with_padding(match padding {
+            Padding::DuplicateLast => Padding::Promote,
+            _ => Padding::DuplicateLast,
+        });
+        assert!(matches!(
+            MappedTree::<_>::from_bytes(other, &bytes[..]),
+            Err(TreeFileError::OptionsMismatch)
+        ));
+        assert!(matches!(
+            MappedTree::<_>::from_bytes(options, &bytes[..bytes.len() - 1]),
+            Err(TreeFileError::Malformed)
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "mmap")]
+    fn it_can_open_a_mapped_tree_file() {
+        let options = TreeOptions::tagged("ProofOfReserve_Leaf", "ProofOfReserve_Branch");
+        let user_data = util::generate_random_user_data(1000);
+        let tree: MerkleTree<_> = MerkleTree::build_with_options(options.clone(), &user_data);
+
+        let path = std::env::temp_dir().join("it_can_open_a_mapped_tree_file.tree");
+        tree.write_tree_file(std::fs::File::create(&path).unwrap())
+            .unwrap();
+        let mapped: MappedTree<_> = MappedTree::open(options.clone(), &path).unwrap();
+
+        assert_eq!(mapped.root(), tree.root());
+        let (_hash, path) = mapped.proof_by_index(999).unwrap();
+        assert!(verify_proof_with_options(
+            &options,
+            &mapped.root().unwrap(),
+            &user_data[999],
+            &path
+        ));
+    }
+}
@@ -0,0 +1,188 @@
+use alloc::format;
+use alloc::string::String;
+use core::fmt;
+use sha2::Digest;
+
+use crate::{truncate_middle, MerkleTree, MerkleTreeData, NodeDirection, NodeStore};
+
+/// The direction a Mermaid flowchart is laid out in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MermaidDirection {
+    /// From the root at the top down to the leaves (`TD`).
+    #[default]
+    TopDown,
+    /// From the root on the left to the leaves on the right (`LR`).
+    LeftRight,
+}
+
+impl fmt::Display for MermaidDirection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MermaidDirection::TopDown => write!(f, "TD"),
+            MermaidDirection::LeftRight => write!(f, "LR"),
+        }
+    }
+}
+
+/// Controls how `MerkleTree::display_mermaid_diagram_with_options` renders the nodes.
+///
+/// The default reproduces `MerkleTree::display_mermaid_diagram`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MermaidOptions {
+    /// The direction the flowchart is laid out in.
+    pub direction: MermaidDirection,
+    /// Shortens the hex encoded hashes to this many characters, or shows them in full if
+    /// `None`.
+    pub hash_length: Option<usize>,
+    /// Labels the leaves with their user data only and leaves the branch nodes blank.
+    pub leaf_labels_only: bool,
+    /// Renders only the subtree below the node with this index, as returned by
+    /// `MerkleTree::root_index` or `MerkleNode::left`, instead of the whole tree.
+    pub subtree_root: Option<usize>,
+    /// Assigns the `root`, `branch` and `leaf` classes to the nodes and defines their styles,
+    /// which can be overridden by appending further `classDef` lines.
+    pub node_classes: bool,
+}
+
+impl Default for MermaidOptions {
+    fn default() -> Self {
+        MermaidOptions {
+            direction: MermaidDirection::TopDown,
+            hash_length: Some(10),
+            leaf_labels_only: false,
+            subtree_root: None,
+            node_classes: false,
+        }
+    }
+}
+
+impl<T, D, S> MerkleTree<T, D, S>
+where
+    T: Clone + fmt::Debug + MerkleTreeData + Default,
+    D: Digest,
+    S: NodeStore<T, D>,
+{
+    /// Displays the Merkle Tree as a Mermaid diagram rendered with the given options.
+    /// Use the mermaid editor to visualize the diagram https://mermaid.live/
+    ///
+    /// # Arguments
+    ///
+    /// * `options`: How the diagram is laid out and the nodes are labeled.
+    ///
+    /// # Returns
+    ///
+    /// The diagram, or `"Tree is empty."` if the tree or the selected subtree has no nodes.
+    pub fn display_mermaid_diagram_with_options(&self, options: &MermaidOptions) -> String {
+        let Some(root) = options.subtree_root.or(self.root) else {
+            return String::from("Tree is empty.");
+        };
+        if root >= self.nodes.len() {
+            return String::from("Tree is empty.");
+        }
+
+        let output = self.iterate_subtree(root, |step| {
+            let node = &self.nodes[step.current_node];
+            let current_node_hash = hex::encode(&node.hash);
+            let user_label = node
+                .user_data
+                .as_ref()
+                .map(|item| item.mermaid_node_label());
+            let current_node_label = match (options.leaf_labels_only, user_label) {
+                (true, Some(label)) => String::from(label.trim_start_matches("<br>")),
+                (true, None) => String::from("\" \""),
+                (false, label) => {
+                    let hash = match options.hash_length {
+                        Some(length) => truncate_middle(&current_node_hash, length),
+                        None => current_node_hash.clone(),
+                    };
+                    hash + &label.unwrap_or_default()
+                }
+            };
+            let class = if !options.node_classes {
+                ""
+            } else if step.direction == NodeDirection::Root {
+                ":::root"
+            } else if node.is_leaf() {
+                ":::leaf"
+            } else {
+                ":::branch"
+            };
+            let node_mermaid = format!("Node_{current_node_hash}[{current_node_label}]{class}");
+
+            let node_connection_mermaid = if step.direction != NodeDirection::Root {
+                let parent_node_hash = hex::encode(&self.nodes[step.parent_node.unwrap()].hash);
+
+                format!("\nNode_{} --> Node_{}", parent_node_hash, current_node_hash)
+            } else {
+                String::new()
+            };
+
+            format!("{node_mermaid}{node_connection_mermaid}")
+        });
+
+        let mut diagram = format!("flowchart {}\n{}", options.direction, output.join("\n"));
+        if options.node_classes {
+            diagram.push_str("\nclassDef root fill:#f9d77e,stroke:#b8860b");
+            diagram.push_str("\nclassDef branch fill:#e8e8e8,stroke:#888888");
+            diagram.push_str("\nclassDef leaf fill:#cdeccd,stroke:#3c8d3c");
+        }
+        diagram
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util;
+
+    #[test]
+    fn it_can_configure_mermaid_diagram() {
+        let user_data = util::generate_random_user_data(4);
+        let tree: MerkleTree<_> =
+            MerkleTree::build("ProofOfReserve_Leaf", "ProofOfReserve_Branch", &user_data);
+
+        assert_eq!(
+            tree.display_mermaid_diagram_with_options(&MermaidOptions::default()),
+            tree.display_mermaid_diagram()
+        );
+
+        let root = tree.root().unwrap();
+        let diagram = tree.display_mermaid_diagram_with_options(&MermaidOptions {
+            direction: MermaidDirection::LeftRight,
+            hash_length: None,
+            ..MermaidOptions::default()
+        });
+        assert!(diagram.starts_with(&format!("flowchart LR\nNode_{root}[{root}]\n")));
+
+        let diagram = tree.display_mermaid_diagram_with_options(&MermaidOptions {
+            leaf_labels_only: true,
+            node_classes: true,
+            ..MermaidOptions::default()
+        });
+        assert!(diagram.contains(&format!("Node_{root}[\" \"]:::root\n")));
+        assert!(diagram.contains("[User ID: 1<br>Balance: 1000]:::leaf\n"));
+        assert_eq!(diagram.matches(":::branch").count(), 2);
+        assert!(diagram.ends_with("classDef leaf fill:#cdeccd,stroke:#3c8d3c"));
+
+        let left = tree
+            .node(tree.root_index().unwrap())
+            .unwrap()
+            .left()
+            .unwrap();
+        let left_hash = hex::encode(tree.node(left).unwrap().hash);
+        let diagram = tree.display_mermaid_diagram_with_options(&MermaidOptions {
+            subtree_root: Some(left),
+            ..MermaidOptions::default()
+        });
+        assert!(diagram.starts_with(&format!("flowchart TD\nNode_{left_hash}[")));
+        assert!(diagram.contains("User ID: 2"));
+        assert!(!diagram.contains("User ID: 3"));
+        assert_eq!(diagram.matches(" --> ").count(), 2);
+
+        let diagram = tree.display_mermaid_diagram_with_options(&MermaidOptions {
+            subtree_root: Some(usize::MAX),
+            ..MermaidOptions::default()
+        });
+        assert_eq!(diagram, "Tree is empty.");
+    }
+}
@@ -0,0 +1,433 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+use core::marker::PhantomData;
+use sha2::digest::Output;
+use sha2::Digest;
+
+use crate::multiproof::decode_hash;
+use crate::root::roots_match;
+use crate::{MerkleError, MerkleTreeData, Sha256, TreeOptions};
+
+/// An append-only Merkle Mountain Range.
+///
+/// The leaves form a list of perfect binary trees (mountains) of decreasing size, one per set
+/// bit of the leaf count. Appending a leaf only merges mountains of equal height, so the path
+/// from a leaf to the peak of its mountain never changes once written, and a proof only needs
+/// the other peaks refreshed as the range grows. The root bags the peaks from right to left:
+/// `H(p0, H(p1, ... H(pn-1, pn)))`.
+pub struct MerkleMountainRange<D: Digest = Sha256> {
+    options: TreeOptions,
+    levels: Vec<Vec<Output<D>>>,
+}
+
+impl<D: Digest> MerkleMountainRange<D> {
+    /// Creates an empty Merkle Mountain Range.
+    ///
+    /// # Arguments
+    ///
+    /// * `options`: The hashing configuration of the range. The padding policy is not used.
+    pub fn new(options: TreeOptions) -> Self {
+        MerkleMountainRange {
+            options,
+            levels: Vec::new(),
+        }
+    }
+
+    /// Returns the hashing configuration of the range.
+    pub fn options(&self) -> &TreeOptions {
+        &self.options
+    }
+
+    /// Returns the number of leaves appended so far.
+    pub fn leaf_count(&self) -> usize {
+        self.levels.first().map_or(0, Vec::len)
+    }
+
+    /// Appends a leaf.
+    ///
+    /// # Arguments
+    ///
+    /// * `leaf`: The user data of the leaf.
+    ///
+    /// # Returns
+    ///
+    /// The index of the new leaf.
+    pub fn push<T: MerkleTreeData>(&mut self, leaf: &T) -> usize {
+        let hash = self.options.hash_leaf_data::<D>(leaf);
+        self.push_leaf_hash(hash)
+    }
+
+    /// Appends a leaf hash that was computed elsewhere.
+    ///
+    /// # Arguments
+    ///
+    /// * `hash`: The hash of the leaf.
+    ///
+    /// # Returns
+    ///
+    /// The index of the new leaf.
+    pub fn push_leaf_hash(&mut self, hash: Output<D>) -> usize {
+        let index = self.leaf_count();
+        let mut hash = hash;
+        let mut level = 0;
+
+        loop {
+            if level == self.levels.len() {
+                self.levels.push(Vec::new());
+            }
+            self.levels[level].push(hash);
+
+            // A node at an odd position completes a mountain together with its left sibling.
+            let size = self.levels[level].len();
+            if size & 1 == 1 {
+                break;
+            }
+            hash = self
+                .options
+                .hash_branch::<D>(&self.levels[level][size - 2], &self.levels[level][size - 1]);
+            level += 1;
+        }
+
+        index
+    }
+
+    /// Returns the hashes of the peaks, from the highest mountain to the lowest.
+    pub fn peaks(&self) -> Vec<String> {
+        self.peaks_at(self.leaf_count())
+            .into_iter()
+            .map(hex::encode)
+            .collect()
+    }
+
+    /// Returns the root of the leaves appended so far.
+    ///
+    /// # Returns
+    ///
+    /// The hex encoded root, or `MerkleError::EmptyTree` if no leaf has been appended.
+    pub fn root(&self) -> Result<String, MerkleError> {
+        self.root_at(self.leaf_count())
+    }
+
+    /// Returns the root the range had when it held the given number of leaves.
+    ///
+    /// Proofs generated at that time still verify against this root.
+    ///
+    /// # Arguments
+    ///
+    /// * `leaf_count`: The number of leaves, no more than the current number.
+    ///
+    /// # Returns
+    ///
+    /// The hex encoded root, `MerkleError::EmptyTree` if `leaf_count` is 0 or
+    /// `MerkleError::InvalidRange` if it is larger than the current count.
+    pub fn root_at(&self, leaf_count: usize) -> Result<String, MerkleError> {
+        if leaf_count > self.leaf_count() {
+            return Err(MerkleError::InvalidRange);
+        }
+        (bag_peaks::<D>(&self.options, &self.peaks_at(leaf_count)))
+            .map(hex::encode)
+            .ok_or(MerkleError::EmptyTree)
+    }
+
+    /// Generates the proof for the leaf at the given index against the current root.
+    ///
+    /// # Arguments
+    ///
+    /// * `index`: The index of the leaf.
+    ///
+    /// # Returns
+    ///
+    /// The `MmrProof`, or `MerkleError::LeafNotFound` if the index is out of bounds.
+    pub fn proof(&self, index: usize) -> Result<MmrProof<D>, MerkleError> {
+        let leaf_count = self.leaf_count();
+        let (height, peak) = mountain_of(leaf_count, index).ok_or(MerkleError::LeafNotFound)?;
+
+        let siblings = (0..height)
+            .map(|level| hex::encode(&self.levels[level][(index >> level) ^ 1]))
+            .collect();
+        let mut peaks = self.peaks_at(leaf_count);
+        peaks.remove(peak);
+
+        Ok(MmrProof {
+            leaf_count,
+            index,
+            siblings,
+            peaks: peaks.into_iter().map(hex::encode).collect(),
+            digest: PhantomData,
+        })
+    }
+
+    /// Collects the peaks of the range as it was with the given number of leaves.
+    ///
+    /// # Arguments
+    ///
+    /// * `leaf_count`: The number of leaves, no more than the current number.
+    fn peaks_at(&self, leaf_count: usize) -> Vec<Output<D>> {
+        let mut peaks = Vec::new();
+        let mut start = 0;
+        for height in (0..usize::BITS as usize).rev() {
+            if leaf_count >> height & 1 == 1 {
+                peaks.push(self.levels[height][start >> height].clone());
+                start += 1 << height;
+            }
+        }
+        peaks
+    }
+}
+
+/// A proof for a leaf of a `MerkleMountainRange`.
+///
+/// Holds the siblings from the leaf up to the peak of its mountain, and the other peaks of
+/// the range from the highest mountain to the lowest.
+pub struct MmrProof<D = Sha256> {
+    pub leaf_count: usize,
+    pub index: usize,
+    pub siblings: Vec<String>,
+    pub peaks: Vec<String>,
+    digest: PhantomData<fn() -> D>,
+}
+
+impl<D> Clone for MmrProof<D> {
+    fn clone(&self) -> Self {
+        MmrProof {
+            leaf_count: self.leaf_count,
+            index: self.index,
+            siblings: self.siblings.clone(),
+            peaks: self.peaks.clone(),
+            digest: PhantomData,
+        }
+    }
+}
+
+impl<D> fmt::Debug for MmrProof<D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MmrProof")
+            .field("leaf_count", &self.leaf_count)
+            .field("index", &self.index)
+            .field("siblings", &self.siblings)
+            .field("peaks", &self.peaks)
+            .finish()
+    }
+}
+
+impl<D: Digest> MmrProof<D> {
+    /// Recomputes the root committed to by this proof for the given leaf.
+    ///
+    /// # Arguments
+    ///
+    /// * `options`: The options the range was built with.
+    /// * `leaf`: The user data of the leaf.
+    ///
+    /// # Returns
+    ///
+    /// The hex encoded root, or `None` if the proof is malformed.
+    pub fn compute_root_with_options<T: MerkleTreeData>(
+        &self,
+        options: &TreeOptions,
+        leaf: &T,
+    ) -> Option<String> {
+        let (height, peak) = mountain_of(self.leaf_count, self.index)?;
+        if self.siblings.len() != height
+            || self.peaks.len() != self.leaf_count.count_ones() as usize - 1
+        {
+            return None;
+        }
+
+        let mut hash = options.hash_leaf_data::<D>(leaf);
+        for (level, sibling) in self.siblings.iter().enumerate() {
+            let sibling = decode_hash::<D>(sibling)?;
+            hash = if self.index >> level & 1 == 1 {
+                options.hash_branch::<D>(&sibling, &hash)
+            } else {
+                options.hash_branch::<D>(&hash, &sibling)
+            };
+        }
+
+        let mut peaks = self
+            .peaks
+            .iter()
+            .map(|peak| decode_hash::<D>(peak))
+            .collect::<Option<Vec<_>>>()?;
+        peaks.insert(peak, hash);
+
+        bag_peaks::<D>(options, &peaks).map(hex::encode)
+    }
+}
+
+/// Verifies a leaf of a Merkle Mountain Range.
+///
+/// # Arguments
+///
+/// * `tag_leaf`: The tag used for hashing leaf nodes.
+/// * `tag_branch`: The tag used for hashing branch nodes.
+/// * `root`: The hex encoded root to verify against.
+/// * `leaf`: The user data of the leaf.
+/// * `proof`: The proof returned by `MerkleMountainRange::proof`.
+///
+/// # Returns
+///
+/// `true` if the proof reconstructs the given root, `false` otherwise.
+pub fn verify_mmr_proof<T, D>(
+    tag_leaf: &str,
+    tag_branch: &str,
+    root: &str,
+    leaf: &T,
+    proof: &MmrProof<D>,
+) -> bool
+where
+    T: MerkleTreeData,
+    D: Digest,
+{
+    verify_mmr_proof_with_options(
+        &TreeOptions::tagged(tag_leaf, tag_branch),
+        root,
+        leaf,
+        proof,
+    )
+}
+
+/// Verifies a leaf of a Merkle Mountain Range, using the hashing configuration the range was
+/// built with.
+///
+/// # Arguments
+///
+/// * `options`: The options the range was built with.
+/// * `root`: The hex encoded root to verify against.
+/// * `leaf`: The user data of the leaf.
+/// * `proof`: The proof returned by `MerkleMountainRange::proof`.
+///
+/// # Returns
+///
+/// `true` if the proof reconstructs the given root, `false` otherwise.
+pub fn verify_mmr_proof_with_options<T, D>(
+    options: &TreeOptions,
+    root: &str,
+    leaf: &T,
+    proof: &MmrProof<D>,
+) -> bool
+where
+    T: MerkleTreeData,
+    D: Digest,
+{
+    proof
+        .compute_root_with_options(options, leaf)
+        .is_some_and(|computed| roots_match(&computed, root))
+}
+
+/// Finds the mountain that holds the given leaf.
+///
+/// # Arguments
+///
+/// * `leaf_count`: The number of leaves in the range.
+/// * `index`: The index of the leaf.
+///
+/// # Returns
+///
+/// An `Option` containing the height of the mountain and its position among the peaks, or
+/// `None` if the index is out of bounds.
+fn mountain_of(leaf_count: usize, index: usize) -> Option<(usize, usize)> {
+    let mut start = 0;
+    let mut peak = 0;
+    for height in (0..usize::BITS as usize).rev() {
+        if leaf_count >> height & 1 == 1 {
+            start += 1 << height;
+            if index < start {
+                return Some((height, peak));
+            }
+            peak += 1;
+        }
+    }
+    None
+}
+
+/// Bags the peaks from right to left into a single root.
+///
+/// # Arguments
+///
+/// * `options`: The hashing configuration of the range.
+/// * `peaks`: The peaks from the highest mountain to the lowest.
+fn bag_peaks<D: Digest>(options: &TreeOptions, peaks: &[Output<D>]) -> Option<Output<D>> {
+    let (last, rest) = peaks.split_last()?;
+    Some(rest.iter().rev().fold(last.clone(), |acc, peak| {
+        options.hash_branch::<D>(peak, &acc)
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util;
+
+    #[test]
+    fn it_can_verify_mmr_proofs() {
+        let user_data = util::generate_random_user_data(11);
+        let mut mmr: MerkleMountainRange = MerkleMountainRange::new(TreeOptions::tagged(
+            "ProofOfReserve_Leaf",
+            "ProofOfReserve_Branch",
+        ));
+        assert_eq!(mmr.root(), Err(MerkleError::EmptyTree));
+
+        for (i, item) in user_data.iter().enumerate() {
+            assert_eq!(mmr.push(item), i);
+        }
+        assert_eq!(mmr.leaf_count(), 11);
+        // 11 = 8 + 2 + 1 leaves make three mountains.
+        assert_eq!(mmr.peaks().len(), 3);
+
+        let root = mmr.root().unwrap();
+        for (i, item) in user_data.iter().enumerate() {
+            let proof = mmr.proof(i).unwrap();
+            assert!(verify_mmr_proof_with_options(
+                mmr.options(),
+                &root,
+                item,
+                &proof
+            ));
+            assert!(!verify_mmr_proof_with_options(
+                mmr.options(),
+                &root,
+                &user_data[(i + 1) % 11],
+                &proof
+            ));
+        }
+        assert_eq!(mmr.proof(11).err(), Some(MerkleError::LeafNotFound));
+    }
+
+    #[test]
+    fn it_keeps_historical_mmr_proofs_valid() {
+        let user_data = util::generate_random_user_data(20);
+        let mut mmr: MerkleMountainRange = MerkleMountainRange::new(TreeOptions::tagged(
+            "ProofOfReserve_Leaf",
+            "ProofOfReserve_Branch",
+        ));
+        for item in &user_data[..6] {
+            mmr.push(item);
+        }
+        let old_root = mmr.root().unwrap();
+        let old_proof = mmr.proof(4).unwrap();
+
+        for item in &user_data[6..] {
+            mmr.push(item);
+        }
+        assert_ne!(mmr.root().unwrap(), old_root);
+        assert_eq!(mmr.root_at(6).unwrap(), old_root);
+        assert_eq!(mmr.root_at(21), Err(MerkleError::InvalidRange));
+        assert!(verify_mmr_proof_with_options(
+            mmr.options(),
+            &old_root,
+            &user_data[4],
+            &old_proof
+        ));
+
+        // The path within the mountain stays the same, only the peaks are refreshed.
+        let new_proof = mmr.proof(4).unwrap();
+        assert_eq!(new_proof.siblings[..1], old_proof.siblings[..]);
+        assert!(verify_mmr_proof_with_options(
+            mmr.options(),
+            &mmr.root().unwrap(),
+            &user_data[4],
+            &new_proof
+        ));
+    }
+}
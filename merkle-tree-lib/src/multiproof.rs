@@ -0,0 +1,418 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+use core::marker::PhantomData;
+use sha2::digest::Output;
+use sha2::Digest;
+
+use crate::root::roots_match;
+use crate::{MerkleError, MerkleTree, MerkleTreeData, NodeStore, Padding, Sha256, TreeOptions};
+
+/// A combined Merkle proof for several leaves of the same tree.
+///
+/// Hashes that can be computed from the proven leaves themselves are left out, so interior
+/// nodes shared by the paths of several leaves appear at most once. The sibling hashes are
+/// ordered level by level from the leaves up, and by position within each level.
+pub struct MultiProof<D = Sha256> {
+    /// The number of leaves in the tree the proof was generated from.
+    pub leaf_count: usize,
+    /// The positions of the proven leaves, sorted and without duplicates.
+    pub indices: Vec<usize>,
+    pub siblings: Vec<String>,
+    digest: PhantomData<fn() -> D>,
+}
+
+impl<D> Clone for MultiProof<D> {
+    fn clone(&self) -> Self {
+        Self::new(self.leaf_count, self.indices.clone(), self.siblings.clone())
+    }
+}
+
+impl<D> fmt::Debug for MultiProof<D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MultiProof")
+            .field("leaf_count", &self.leaf_count)
+            .field("indices", &self.indices)
+            .field("siblings", &self.siblings)
+            .finish()
+    }
+}
+
+impl<D> MultiProof<D> {
+    /// Creates a `MultiProof` from its parts.
+    ///
+    /// # Arguments
+    ///
+    /// * `leaf_count`: The number of leaves in the tree.
+    /// * `indices`: The sorted positions of the proven leaves.
+    /// * `siblings`: The hex encoded sibling hashes, in the order described on `MultiProof`.
+    pub fn new(leaf_count: usize, indices: Vec<usize>, siblings: Vec<String>) -> Self {
+        MultiProof {
+            leaf_count,
+            indices,
+            siblings,
+            digest: PhantomData,
+        }
+    }
+
+    /// Recomputes the Merkle root committed to by this proof for the given leaves, using the
+    /// hashing configuration the tree was built with.
+    ///
+    /// # Arguments
+    ///
+    /// * `options`: The options the tree was built with.
+    /// * `leaves`: The user data of the proven leaves, in the order of `indices`.
+    ///
+    /// # Returns
+    ///
+    /// The hex encoded root, or `MerkleError::InvalidProof` if the proof is malformed or does
+    /// not match the number of leaves.
+    pub fn compute_root_with_options<T>(
+        &self,
+        options: &TreeOptions,
+        leaves: &[T],
+    ) -> Result<String, MerkleError>
+    where
+        T: MerkleTreeData,
+        D: Digest,
+    {
+        let Some(&last) = self.indices.last() else {
+            return Err(MerkleError::InvalidProof);
+        };
+        if leaves.len() != self.indices.len()
+            || self.indices.windows(2).any(|pair| pair[0] >= pair[1])
+            || last >= self.leaf_count
+        {
+            return Err(MerkleError::InvalidProof);
+        }
+
+        let mut known: Vec<(usize, Output<D>)> = self
+            .indices
+            .iter()
+            .zip(leaves)
+            .map(|(&index, leaf)| (index, options.hash_leaf_data::<D>(leaf)))
+            .collect();
+        let mut siblings = self.siblings.iter();
+        let mut size = self.leaf_count;
+
+        while size > 1 {
+            let mut parents = Vec::with_capacity(known.len());
+            let mut i = 0;
+
+            while i < known.len() {
+                let position = known[i].0;
+                let sibling = position ^ 1;
+
+                let parent = if sibling >= size {
+                    let hash = &known[i].1;
+                    match options.padding {
+                        Padding::DuplicateLast => options.hash_branch::<D>(hash, hash),
+                        Padding::Promote => hash.clone(),
+                        Padding::PadWithEmpty => {
+                            options.hash_branch::<D>(hash, &options.hash_leaf::<D>(&[]))
+                        }
+                    }
+                } else if known.get(i + 1).is_some_and(|(next, _)| *next == sibling) {
+                    i += 1;
+                    options.hash_branch::<D>(&known[i - 1].1, &known[i].1)
+                } else {
+                    let sibling_hash = (siblings.next())
+                        .and_then(|sibling| decode_hash::<D>(sibling))
+                        .ok_or(MerkleError::InvalidProof)?;
+                    let hash = &known[i].1;
+                    if position & 1 == 0 {
+                        options.hash_branch::<D>(hash, &sibling_hash)
+                    } else {
+                        options.hash_branch::<D>(&sibling_hash, hash)
+                    }
+                };
+
+                parents.push((position >> 1, parent));
+                i += 1;
+            }
+
+            known = parents;
+            size = size.div_ceil(2);
+        }
+
+        if siblings.next().is_some() {
+            return Err(MerkleError::InvalidProof);
+        }
+
+        (known.first())
+            .map(|(_, hash)| hex::encode(hash))
+            .ok_or(MerkleError::InvalidProof)
+    }
+}
+
+/// Decodes a hex encoded hash, checking that it has the length of the digest output.
+///
+/// # Arguments
+///
+/// * `hash`: The hex encoded hash.
+pub(crate) fn decode_hash<D: Digest>(hash: &str) -> Option<Output<D>> {
+    match hex::decode(hash) {
+        Ok(bytes) if bytes.len() == <D as Digest>::output_size() => {
+            Some(Output::<D>::clone_from_slice(&bytes))
+        }
+        _ => None,
+    }
+}
+
+impl<T, D: Digest, S: NodeStore<T, D>> MerkleTree<T, D, S> {
+    /// Generates a single proof covering all the leaves at the given indices.
+    ///
+    /// # Arguments
+    ///
+    /// * `indices`: The positions of the leaves to prove, in any order.
+    ///
+    /// # Returns
+    ///
+    /// The `MultiProof`, `MerkleError::InvalidRange` if `indices` is empty or
+    /// `MerkleError::LeafNotFound` if any index is out of range.
+    pub fn multiproof(&self, indices: &[usize]) -> Result<MultiProof<D>, MerkleError> {
+        let mut indices = indices.to_vec();
+        indices.sort_unstable();
+        indices.dedup();
+
+        if *indices.last().ok_or(MerkleError::InvalidRange)? >= self.leaf_count {
+            return Err(MerkleError::LeafNotFound);
+        }
+
+        let level_sizes = self.level_sizes();
+        let mut siblings = Vec::new();
+        let mut known = indices.clone();
+
+        for (level, &size) in level_sizes.iter().enumerate().take(level_sizes.len() - 1) {
+            let mut parents = Vec::with_capacity(known.len());
+            let mut i = 0;
+
+            while i < known.len() {
+                let position = known[i];
+                let sibling = position ^ 1;
+
+                // An unpaired node is promoted or hashed with a value the verifier can derive.
+                if sibling < size {
+                    if known.get(i + 1) == Some(&sibling) {
+                        i += 1;
+                    } else {
+                        let node = self
+                            .node_at(level, sibling)
+                            .ok_or(MerkleError::LeafNotFound)?;
+                        siblings.push(hex::encode(&self.nodes[node].hash));
+                    }
+                }
+
+                parents.push(position >> 1);
+                i += 1;
+            }
+
+            known = parents;
+        }
+
+        Ok(MultiProof::new(self.leaf_count, indices, siblings))
+    }
+}
+
+/// Verifies that the given leaves are committed to by the given Merkle root.
+///
+/// # Arguments
+///
+/// * `tag_leaf`: The tag used for hashing leaf nodes.
+/// * `tag_branch`: The tag used for hashing branch nodes.
+/// * `root`: The hex encoded Merkle root to verify against.
+/// * `leaves`: The user data of the proven leaves, in the order of `proof.indices`.
+/// * `proof`: The proof returned by `MerkleTree::multiproof`.
+///
+/// # Returns
+///
+/// `true` if the proof reconstructs the given root, `false` otherwise.
+pub fn verify_multiproof<T, D>(
+    tag_leaf: &str,
+    tag_branch: &str,
+    root: &str,
+    leaves: &[T],
+    proof: &MultiProof<D>,
+) -> bool
+where
+    T: MerkleTreeData,
+    D: Digest,
+{
+    verify_multiproof_with_options(
+        &TreeOptions::tagged(tag_leaf, tag_branch),
+        root,
+        leaves,
+        proof,
+    )
+}
+
+/// Verifies that the given leaves are committed to by the given Merkle root, using the
+/// hashing configuration the tree was built with.
+///
+/// # Arguments
+///
+/// * `options`: The options the tree was built with.
+/// * `root`: The hex encoded Merkle root to verify against.
+/// * `leaves`: The user data of the proven leaves, in the order of `proof.indices`.
+/// * `proof`: The proof returned by `MerkleTree::multiproof`.
+///
+/// # Returns
+///
+/// `true` if the proof reconstructs the given root, `false` otherwise.
+pub fn verify_multiproof_with_options<T, D>(
+    options: &TreeOptions,
+    root: &str,
+    leaves: &[T],
+    proof: &MultiProof<D>,
+) -> bool
+where
+    T: MerkleTreeData,
+    D: Digest,
+{
+    proof
+        .compute_root_with_options(options, leaves)
+        .is_ok_and(|computed| roots_match(&computed, root))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util;
+    use alloc::vec;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(8, vec![0], 3)]
+    #[case(8, vec![3, 2], 2)]
+    #[case(8, vec![7, 0, 0], 4)]
+    #[case(8, (0..8).collect(), 0)]
+    #[case(5, vec![4], 1)]
+    #[case(1000, vec![1, 500, 999], 23)]
+    fn it_can_generate_multiproof(
+        #[case] leaf_count: usize,
+        #[case] indices: Vec<usize>,
+        #[case] expected_siblings: usize,
+    ) {
+        let user_data = util::generate_random_user_data(leaf_count);
+        let tree: MerkleTree<_> =
+            MerkleTree::build("ProofOfReserve_Leaf", "ProofOfReserve_Branch", &user_data);
+
+        let proof = tree.multiproof(&indices).unwrap();
+
+        let mut sorted = indices.clone();
+        sorted.sort();
+        sorted.dedup();
+        assert_eq!(proof.leaf_count, leaf_count);
+        assert_eq!(proof.indices, sorted);
+        assert_eq!(proof.siblings.len(), expected_siblings);
+    }
+
+    #[test]
+    fn it_can_generate_multiproof_matching_single_proof() {
+        let user_data = util::generate_random_user_data(8);
+        let tree: MerkleTree<_> =
+            MerkleTree::build("ProofOfReserve_Leaf", "ProofOfReserve_Branch", &user_data);
+
+        let (_node, path) = tree.proof_by_index(5).unwrap();
+        let proof = tree.multiproof(&[5]).unwrap();
+
+        let mut siblings = path.siblings.clone();
+        siblings.reverse();
+        assert_eq!(proof.siblings, siblings);
+
+        assert_eq!(tree.multiproof(&[]).unwrap_err(), MerkleError::InvalidRange);
+        assert_eq!(
+            tree.multiproof(&[8]).unwrap_err(),
+            MerkleError::LeafNotFound
+        );
+    }
+
+    #[rstest]
+    #[case(Padding::DuplicateLast, 8, vec![2, 3])]
+    #[case(Padding::DuplicateLast, 11, vec![0, 5, 10])]
+    #[case(Padding::DuplicateLast, 11, (0..11).collect())]
+    #[case(Padding::Promote, 13, vec![6, 7, 8])]
+    #[case(Padding::Promote, 13, vec![1, 12])]
+    #[case(Padding::Promote, 13, (0..13).collect())]
+    #[case(Padding::PadWithEmpty, 7, vec![3, 4])]
+    #[case(Padding::PadWithEmpty, 7, vec![0, 6])]
+    #[case(Padding::PadWithEmpty, 7, (0..7).collect())]
+    fn it_can_verify_multiproof(
+        #[case] padding: Padding,
+        #[case] leaf_count: usize,
+        #[case] indices: Vec<usize>,
+    ) {
+        let user_data = util::generate_random_user_data(leaf_count);
+        let options = TreeOptions::tagged("ProofOfReserve_Leaf", "ProofOfReserve_Branch")
+            .with_padding(padding);
+        let tree: MerkleTree<_> = MerkleTree::build_with_options(options.clone(), &user_data);
+        let root = tree.root().unwrap();
+
+        let proof = tree.multiproof(&indices).unwrap();
+        let leaves: Vec<_> = proof
+            .indices
+            .iter()
+            .map(|&i| user_data[i].clone())
+            .collect();
+        assert!(verify_multiproof_with_options(
+            &options, &root, &leaves, &proof
+        ));
+
+        let mut tampered = leaves.clone();
+        tampered[0].balance += 1;
+        assert!(!verify_multiproof_with_options(
+            &options, &root, &tampered, &proof
+        ));
+
+        assert!(!verify_multiproof_with_options(
+            &options,
+            &root,
+            &leaves[1..],
+            &proof
+        ));
+
+        let mut extended = proof.clone();
+        extended.siblings.push(root.clone());
+        assert!(!verify_multiproof_with_options(
+            &options, &root, &leaves, &extended
+        ));
+        assert_eq!(
+            extended.compute_root_with_options(&options, &leaves),
+            Err(MerkleError::InvalidProof)
+        );
+        assert_eq!(proof.compute_root_with_options(&options, &leaves), Ok(root));
+    }
+
+    #[test]
+    fn it_can_verify_multiproof_with_tags() {
+        let user_data = util::generate_random_user_data(100);
+        let tree: MerkleTree<_> =
+            MerkleTree::build("ProofOfReserve_Leaf", "ProofOfReserve_Branch", &user_data);
+        let root = tree.root().unwrap();
+
+        let proof = tree.multiproof(&[42, 3, 99, 4]).unwrap();
+        let leaves: Vec<_> = proof
+            .indices
+            .iter()
+            .map(|&i| user_data[i].clone())
+            .collect();
+
+        assert!(verify_multiproof(
+            "ProofOfReserve_Leaf",
+            "ProofOfReserve_Branch",
+            &root,
+            &leaves,
+            &proof
+        ));
+
+        let mut truncated = proof.clone();
+        truncated.siblings.pop();
+        assert!(!verify_multiproof(
+            "ProofOfReserve_Leaf",
+            "ProofOfReserve_Branch",
+            &root,
+            &leaves,
+            &truncated
+        ));
+    }
+}
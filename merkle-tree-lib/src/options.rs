@@ -0,0 +1,456 @@
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use sha2::digest::Output;
+use sha2::Digest;
+
+use crate::{tagged_hasher, MerkleTreeData};
+
+/// Selects how a tag is combined with the input in `HashMode::Tagged`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TagStyle {
+    /// `H(H(tag) || H(tag) || input)`, the tagged hash defined by BIP340.
+    #[default]
+    Bip340,
+    /// `H(tag || input)`, the raw tag bytes are prepended to the input.
+    Prefix,
+}
+
+impl TagStyle {
+    /// Calculates the tagged hash of the input with this style.
+    ///
+    /// # Arguments
+    ///
+    /// * `tag`: The tag string.
+    /// * `input`: The input byte slice.
+    pub fn hash<D: Digest>(&self, tag: &str, input: &[u8]) -> Output<D> {
+        let mut hasher = self.hasher::<D>(tag);
+        hasher.update(input);
+        hasher.finalize()
+    }
+
+    /// Creates a hasher that has already consumed the tag, so the input can be fed to it in
+    /// several parts.
+    ///
+    /// # Arguments
+    ///
+    /// * `tag`: The tag string.
+    fn hasher<D: Digest>(&self, tag: &str) -> D {
+        match self {
+            TagStyle::Bip340 => tagged_hasher::<D>(tag),
+            TagStyle::Prefix => {
+                let mut hasher = D::new();
+                hasher.update(tag.as_bytes());
+                hasher
+            }
+        }
+    }
+}
+
+/// Selects how leaf and branch hashes are computed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HashMode {
+    /// Leaves are hashed as `tagged_hash(tag_leaf, data)` and branches as
+    /// `tagged_hash(tag_branch, left || right)`, where the tagged hash is selected by
+    /// `TreeOptions::tag_style`.
+    #[default]
+    Tagged,
+    /// Leaves are hashed as `H(data)` and branches as `H(min(left, right) || max(left, right))`.
+    ///
+    /// Since the children are sorted before hashing, the directions in a proof are not needed
+    /// for verification. Combined with the `Keccak256` digest this matches the construction
+    /// verified by Solidity's `MerkleProof.verify`.
+    SortedPair,
+    /// Leaves are hashed as `H(H(data))` and branches as `H(H(left || right))`.
+    ///
+    /// Combined with the `Sha256` digest this is the construction of Bitcoin's block Merkle
+    /// trees, where the leaf hashes are the transaction ids.
+    DoubleHash,
+}
+
+/// Selects how a node without a sibling is handled when building the next level.
+///
+/// Each convention produces a different root for trees whose levels are not all even.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Padding {
+    /// The unpaired node is hashed with itself, as in Bitcoin's block Merkle trees.
+    #[default]
+    DuplicateLast,
+    /// The unpaired node is moved up to the next level unchanged, as in Certificate
+    /// Transparency (RFC 6962).
+    Promote,
+    /// The unpaired node is hashed with the hash of an empty leaf, `hash_leaf(&[])`.
+    PadWithEmpty,
+}
+
+/// Selects the root of a tree without leaves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EmptyRoot {
+    /// The tree has no root, so `MerkleTree::root` returns `MerkleError::EmptyTree`.
+    #[default]
+    Undefined,
+    /// The root is the hash of an empty leaf, `hash_leaf(&[])`, which depends on the leaf tag.
+    EmptyLeaf,
+    /// The root is the plain digest of the empty string, `H("")`, as defined by RFC 6962.
+    EmptyString,
+}
+
+/// The byte prepended to leaf inputs when domain separation is enabled.
+pub const LEAF_PREFIX: u8 = 0x00;
+
+/// The byte prepended to branch inputs when domain separation is enabled.
+pub const BRANCH_PREFIX: u8 = 0x01;
+
+/// The hashing configuration of a Merkle Tree.
+///
+/// A tree remembers the options it was built with, and the same options are needed to verify
+/// proofs produced by the tree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TreeOptions {
+    pub tag_leaf: String,
+    pub tag_branch: String,
+    pub hash_mode: HashMode,
+    pub tag_style: TagStyle,
+    /// Prepends `LEAF_PREFIX` to leaf inputs and `BRANCH_PREFIX` to branch inputs (RFC 6962
+    /// style), so a leaf can never be confused with a branch even if both use the same tag.
+    pub domain_separation: bool,
+    pub padding: Padding,
+    pub empty_root: EmptyRoot,
+}
+
+impl TreeOptions {
+    /// Creates options for the default tagged hash construction.
+    ///
+    /// # Arguments
+    ///
+    /// * `tag_leaf`: The tag used for hashing leaf nodes.
+    /// * `tag_branch`: The tag used for hashing branch nodes.
+    pub fn tagged(tag_leaf: &str, tag_branch: &str) -> Self {
+        TreeOptions {
+            tag_leaf: tag_leaf.to_string(),
+            tag_branch: tag_branch.to_string(),
+            hash_mode: HashMode::Tagged,
+            tag_style: TagStyle::Bip340,
+            domain_separation: false,
+            padding: Padding::DuplicateLast,
+            empty_root: EmptyRoot::Undefined,
+        }
+    }
+
+    /// Creates options for the untagged sorted pair construction.
+    pub fn sorted_pair() -> Self {
+        TreeOptions {
+            hash_mode: HashMode::SortedPair,
+            ..Self::tagged("", "")
+        }
+    }
+
+    /// Creates options for the untagged double hash construction.
+    pub fn double_hash() -> Self {
+        TreeOptions {
+            hash_mode: HashMode::DoubleHash,
+            ..Self::tagged("", "")
+        }
+    }
+
+    /// Creates options for the Certificate Transparency construction of RFC 6962.
+    ///
+    /// Leaves are hashed as `H(0x00 || data)` and branches as `H(0x01 || left || right)`.
+    /// Unpaired nodes are promoted, which splits the leaves at the largest power of two below
+    /// the leaf count as the RFC does, and the empty tree has the root `H("")`, so roots and
+    /// proofs match those of CT logs with the `Sha256` digest.
+    pub fn certificate_transparency() -> Self {
+        TreeOptions {
+            tag_style: TagStyle::Prefix,
+            domain_separation: true,
+            padding: Padding::Promote,
+            empty_root: EmptyRoot::EmptyString,
+            ..Self::tagged("", "")
+        }
+    }
+
+    /// Returns these options with the given tag style.
+    ///
+    /// # Arguments
+    ///
+    /// * `tag_style`: How tags are combined with the hashed input.
+    pub fn with_tag_style(mut self, tag_style: TagStyle) -> Self {
+        self.tag_style = tag_style;
+        self
+    }
+
+    /// Returns these options with leaf/branch domain separation enabled or disabled.
+    ///
+    /// # Arguments
+    ///
+    /// * `domain_separation`: Whether to prefix leaf and branch inputs.
+    pub fn with_domain_separation(mut self, domain_separation: bool) -> Self {
+        self.domain_separation = domain_separation;
+        self
+    }
+
+    /// Returns these options with the given padding policy for unpaired nodes.
+    ///
+    /// # Arguments
+    ///
+    /// * `padding`: How a node without a sibling is carried to the next level.
+    pub fn with_padding(mut self, padding: Padding) -> Self {
+        self.padding = padding;
+        self
+    }
+
+    /// Returns these options with the given root for trees without leaves.
+    ///
+    /// # Arguments
+    ///
+    /// * `empty_root`: How the root of an empty tree is defined.
+    pub fn with_empty_root(mut self, empty_root: EmptyRoot) -> Self {
+        self.empty_root = empty_root;
+        self
+    }
+
+    /// Calculates the root of a tree without leaves according to `empty_root`.
+    ///
+    /// # Returns
+    ///
+    /// The root hash, or `None` for `EmptyRoot::Undefined`.
+    pub fn empty_root_hash<D: Digest>(&self) -> Option<Output<D>> {
+        match self.empty_root {
+            EmptyRoot::Undefined => None,
+            EmptyRoot::EmptyLeaf => Some(self.hash_leaf::<D>(&[])),
+            EmptyRoot::EmptyString => Some(D::digest([])),
+        }
+    }
+
+    /// Calculates the hash of a leaf node from its serialized data.
+    ///
+    /// # Arguments
+    ///
+    /// * `input`: The serialized leaf data.
+    pub fn hash_leaf<D: Digest>(&self, input: &[u8]) -> Output<D> {
+        let mut hasher = self.hasher::<D>(&self.tag_leaf);
+        if self.domain_separation {
+            hasher.update([LEAF_PREFIX]);
+        }
+        hasher.update(input);
+        self.finalize(hasher)
+    }
+
+    /// Calculates the hash of a leaf node from its user data, streaming it into the hasher
+    /// with `MerkleTreeData::hash_into` instead of serializing it first.
+    ///
+    /// The hash is the same as `hash_leaf` of the serialized data.
+    ///
+    /// # Arguments
+    ///
+    /// * `leaf`: The user data of the leaf.
+    pub fn hash_leaf_data<D: Digest>(&self, leaf: &(impl MerkleTreeData + ?Sized)) -> Output<D> {
+        let mut hasher = self.hasher::<D>(&self.tag_leaf);
+        if self.domain_separation {
+            hasher.update([LEAF_PREFIX]);
+        }
+        leaf.hash_into(&mut hasher);
+        self.finalize(hasher)
+    }
+
+    /// Calculates the hash of a branch node from the hashes of its children.
+    ///
+    /// # Arguments
+    ///
+    /// * `left`: The hash of the left child node.
+    /// * `right`: The hash of the right child node.
+    pub fn hash_branch<D: Digest>(&self, left: &Output<D>, right: &Output<D>) -> Output<D> {
+        let (first, second) = match self.hash_mode {
+            HashMode::SortedPair if right < left => (right, left),
+            _ => (left, right),
+        };
+
+        self.hash_branch_parts::<D>(&[first, second])
+    }
+
+    /// Calculates the hash of a branch node with any number of children, as used by
+    /// `KaryMerkleTree`.
+    ///
+    /// The children are sorted first in `HashMode::SortedPair`, which for two children is the
+    /// same as `hash_branch`.
+    ///
+    /// # Arguments
+    ///
+    /// * `children`: The hashes of the child nodes, in order.
+    pub fn hash_children<D: Digest>(&self, children: &[&Output<D>]) -> Output<D> {
+        let mut parts: Vec<&[u8]> = children.iter().map(|child| child.as_slice()).collect();
+        if self.hash_mode == HashMode::SortedPair {
+            parts.sort();
+        }
+
+        self.hash_branch_parts::<D>(&parts)
+    }
+
+    /// Calculates the hash of a branch node from the concatenation of the parts, without
+    /// sorting them.
+    ///
+    /// # Arguments
+    ///
+    /// * `parts`: The input byte slices, in order.
+    pub(crate) fn hash_branch_parts<D: Digest>(&self, parts: &[&[u8]]) -> Output<D> {
+        let mut hasher = self.hasher::<D>(&self.tag_branch);
+        if self.domain_separation {
+            hasher.update([BRANCH_PREFIX]);
+        }
+        for part in parts {
+            hasher.update(part);
+        }
+        self.finalize(hasher)
+    }
+
+    /// Creates a hasher for the hash mode that has already consumed the tag, if any.
+    ///
+    /// # Arguments
+    ///
+    /// * `tag`: The tag used by `HashMode::Tagged`.
+    fn hasher<D: Digest>(&self, tag: &str) -> D {
+        match self.hash_mode {
+            HashMode::Tagged => self.tag_style.hasher::<D>(tag),
+            HashMode::SortedPair | HashMode::DoubleHash => D::new(),
+        }
+    }
+
+    /// Finishes a hash started with `hasher` according to the hash mode.
+    ///
+    /// # Arguments
+    ///
+    /// * `hasher`: The hasher that has consumed the whole input.
+    fn finalize<D: Digest>(&self, hasher: D) -> Output<D> {
+        let hash = hasher.finalize();
+        match self.hash_mode {
+            HashMode::DoubleHash => D::digest(hash),
+            HashMode::Tagged | HashMode::SortedPair => hash,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        util, KaryMerkleTree, MerkleError, MerkleTree, RootAccumulator, Salted, Sha256, SplitLeaf,
+    };
+    use alloc::format;
+    use alloc::vec;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(
+        TagStyle::Bip340,
+        "d2d838724571ff750eb7f498a667c32f522efae2b403eae6f678207ac6f978de"
+    )]
+    #[case(
+        TagStyle::Prefix,
+        "853f3a27d62fb748051212f9b1eb28fde39381ff7f28d0f230cb2c2b02b9cf53"
+    )]
+    fn it_can_hash_with_tag_style(#[case] tag_style: TagStyle, #[case] expected: &str) {
+        let actual = tag_style.hash::<Sha256>("Bitcoin_Transaction", "aaa".as_bytes());
+        assert_eq!(hex::encode(actual), expected);
+    }
+
+    #[test]
+    fn it_can_hash_leaves_by_streaming() {
+        #[derive(Clone, Debug, Default)]
+        struct Blob(Vec<u8>);
+
+        impl MerkleTreeData for Blob {
+            fn serialize(&self) -> Vec<u8> {
+                self.0.clone()
+            }
+
+            fn mermaid_node_label(&self) -> String {
+                format!("{} bytes", self.0.len())
+            }
+
+            fn hash_into(&self, hasher: &mut impl Digest) {
+                for piece in self.0.chunks(4096) {
+                    hasher.update(piece);
+                }
+            }
+        }
+
+        let blobs: Vec<Blob> = (0..5u32)
+            .map(|i| Blob((0..20_000 + i).map(|j| (i + j) as u8).collect()))
+            .collect();
+        for options in [
+            TreeOptions::tagged("Leaf", "Branch"),
+            TreeOptions::certificate_transparency(),
+            TreeOptions::double_hash(),
+        ] {
+            for blob in &blobs {
+                assert_eq!(
+                    options.hash_leaf_data::<Sha256>(blob),
+                    options.hash_leaf::<Sha256>(&blob.serialize())
+                );
+            }
+            let tree: MerkleTree<Blob> =
+                MerkleTree::build_from_iter_with_options(options.clone(), blobs.clone());
+            let bytes: MerkleTree<()> = MerkleTree::from_leaf_bytes_with_options(
+                options.clone(),
+                blobs.iter().map(|blob| blob.0.as_slice()),
+            );
+            assert_eq!(tree.root(), bytes.root());
+        }
+
+        let options = TreeOptions::tagged("Leaf", "Branch");
+        let salted = Salted::new(Blob(vec![1, 2, 3]), vec![4, 5]);
+        assert_eq!(
+            options.hash_leaf_data::<Sha256>(&salted),
+            options.hash_leaf::<Sha256>(&salted.serialize())
+        );
+        let split = SplitLeaf {
+            data: Blob(vec![6, 7]),
+            amount: 89,
+            blinding: vec![10],
+        };
+        assert_eq!(
+            options.hash_leaf_data::<Sha256>(&split),
+            options.hash_leaf::<Sha256>(&split.serialize())
+        );
+    }
+
+    #[rstest]
+    #[case(EmptyRoot::Undefined, None)]
+    #[case(
+        EmptyRoot::EmptyString,
+        Some("e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855")
+    )]
+    #[case(
+        EmptyRoot::EmptyLeaf,
+        Some("a610e7bbf5541b81ada1636b6f915412e0eeeff509acf727164ee81c4c1291d7")
+    )]
+    fn it_can_define_empty_root(#[case] empty_root: EmptyRoot, #[case] expected: Option<&str>) {
+        let options = TreeOptions::tagged("ProofOfReserve_Leaf", "ProofOfReserve_Branch")
+            .with_empty_root(empty_root);
+
+        let tree: MerkleTree<util::UserData> = MerkleTree::build_with_options(options.clone(), &[]);
+        let accumulator: RootAccumulator = RootAccumulator::new(options.clone());
+        let kary: KaryMerkleTree<util::UserData> =
+            KaryMerkleTree::build_with_options(options, 4, &[]);
+
+        assert_eq!(tree.root().ok().as_deref(), expected);
+        assert_eq!(accumulator.root().ok().as_deref(), expected);
+        assert_eq!(kary.root().ok().as_deref(), expected);
+        if expected.is_none() {
+            assert_eq!(tree.root(), Err(MerkleError::EmptyTree));
+            assert_eq!(kary.root(), Err(MerkleError::EmptyTree));
+        }
+    }
+
+    #[test]
+    fn it_can_define_empty_root_for_certificate_transparency() {
+        // The empty tree hash of RFC 6962 is SHA-256 of the empty string.
+        let tree: MerkleTree<()> = MerkleTree::from_leaf_bytes_with_options(
+            TreeOptions::certificate_transparency(),
+            core::iter::empty(),
+        );
+        assert_eq!(
+            tree.root().unwrap(),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+    }
+}
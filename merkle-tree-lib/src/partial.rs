@@ -0,0 +1,468 @@
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+use core::marker::PhantomData;
+use sha2::digest::Output;
+use sha2::Digest;
+
+use crate::multiproof::decode_hash;
+use crate::{MerkleError, MerkleTree, NodeStore, Padding, Sha256, TreeOptions};
+
+/// A partial Merkle Tree in the format of Bitcoin's `merkleblock` message (BIP37), proving
+/// that some leaves are included in a tree.
+///
+/// The tree is traversed depth first from the root. A flag bit is recorded for every visited
+/// node, set if a matched leaf is below it. The traversal descends into nodes with the flag
+/// set, and records the hash of every other node and of the matched leaves themselves.
+pub struct PartialMerkleTree<D = Sha256> {
+    /// The number of leaves in the tree, the transaction count of the block.
+    pub leaf_count: u32,
+    /// The hex encoded hashes, in the order they are visited.
+    pub hashes: Vec<String>,
+    /// The flag bits, in the order the nodes are visited.
+    pub flags: Vec<bool>,
+    digest: PhantomData<fn() -> D>,
+}
+
+impl<D> Clone for PartialMerkleTree<D> {
+    fn clone(&self) -> Self {
+        Self::new(self.leaf_count, self.hashes.clone(), self.flags.clone())
+    }
+}
+
+impl<D> fmt::Debug for PartialMerkleTree<D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PartialMerkleTree")
+            .field("leaf_count", &self.leaf_count)
+            .field("hashes", &self.hashes)
+            .field("flags", &self.flags)
+            .finish()
+    }
+}
+
+impl<D> PartialMerkleTree<D> {
+    /// Creates a `PartialMerkleTree` from its parts.
+    ///
+    /// # Arguments
+    ///
+    /// * `leaf_count`: The number of leaves in the tree.
+    /// * `hashes`: The hex encoded hashes, in the order described on `PartialMerkleTree`.
+    /// * `flags`: The flag bits, in the order described on `PartialMerkleTree`.
+    pub fn new(leaf_count: u32, hashes: Vec<String>, flags: Vec<bool>) -> Self {
+        PartialMerkleTree {
+            leaf_count,
+            hashes,
+            flags,
+            digest: PhantomData,
+        }
+    }
+}
+
+impl<D: Digest> PartialMerkleTree<D> {
+    /// Serializes the partial tree as in the `merkleblock` message after the block header: the
+    /// leaf count as a little endian `u32`, the hashes and the flag bits packed into bytes with
+    /// the first bit in the least significant bit, both preceded by their count as a Bitcoin
+    /// `CompactSize`.
+    ///
+    /// # Returns
+    ///
+    /// The serialized bytes, or `MerkleError::InvalidProof` if a hash is not valid hex of the
+    /// digest size.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, MerkleError> {
+        let mut bytes = self.leaf_count.to_le_bytes().to_vec();
+        write_compact_size(&mut bytes, self.hashes.len());
+        for hash in &self.hashes {
+            bytes.extend_from_slice(&decode_hash::<D>(hash).ok_or(MerkleError::InvalidProof)?);
+        }
+
+        let mut flags = vec![0u8; self.flags.len().div_ceil(8)];
+        for (bit, _) in self.flags.iter().enumerate().filter(|(_, flag)| **flag) {
+            flags[bit / 8] |= 1 << (bit % 8);
+        }
+        write_compact_size(&mut bytes, flags.len());
+        bytes.extend_from_slice(&flags);
+        Ok(bytes)
+    }
+
+    /// Parses a partial tree serialized by `to_bytes`.
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes`: The serialized partial tree.
+    ///
+    /// # Returns
+    ///
+    /// The partial tree, or `MerkleError::InvalidProof` if the bytes are truncated or have
+    /// trailing data. The flags have all bits of the last byte, as the count of bits is not
+    /// serialized.
+    pub fn from_bytes(mut bytes: &[u8]) -> Result<Self, MerkleError> {
+        let leaf_count = take(&mut bytes, 4)?;
+        let leaf_count =
+            u32::from_le_bytes([leaf_count[0], leaf_count[1], leaf_count[2], leaf_count[3]]);
+
+        let hash_count = read_compact_size(&mut bytes)?;
+        let hash_size = <D as Digest>::output_size();
+        if hash_count > bytes.len() / hash_size {
+            return Err(MerkleError::InvalidProof);
+        }
+        let hashes = (0..hash_count)
+            .map(|_| take(&mut bytes, hash_size).map(hex::encode))
+            .collect::<Result<_, _>>()?;
+
+        let flag_count = read_compact_size(&mut bytes)?;
+        let flags = take(&mut bytes, flag_count)?
+            .iter()
+            .flat_map(|byte| (0..8).map(move |bit| byte >> bit & 1 == 1))
+            .collect();
+        if !bytes.is_empty() {
+            return Err(MerkleError::InvalidProof);
+        }
+
+        Ok(Self::new(leaf_count, hashes, flags))
+    }
+
+    /// Recomputes the root of a Bitcoin block Merkle Tree, hashed as by
+    /// `TreeOptions::double_hash()`, and extracts the matched leaves. See
+    /// `extract_matches_with_options`.
+    pub fn extract_matches(&self) -> Result<(String, Vec<(usize, String)>), MerkleError> {
+        self.extract_matches_with_options(&TreeOptions::double_hash())
+    }
+
+    /// Recomputes the Merkle root committed to by this partial tree and extracts the matched
+    /// leaves, using the hashing configuration the tree was built with.
+    ///
+    /// As in Bitcoin Core, the partial tree is rejected if it does not use all hashes and all
+    /// bytes of flags, or if a node has two identical children, which would allow a leaf to
+    /// be duplicated without changing the root (CVE-2012-2459).
+    ///
+    /// # Arguments
+    ///
+    /// * `options`: The options the tree was built with.
+    ///
+    /// # Returns
+    ///
+    /// The hex encoded root and the positions and hex encoded hashes of the matched leaves,
+    /// `MerkleError::EmptyTree` if the partial tree has no leaves,
+    /// `MerkleError::UnsupportedPadding` if the options do not use `Padding::DuplicateLast` as
+    /// Bitcoin's trees do, or `MerkleError::InvalidProof` if the partial tree is malformed.
+    pub fn extract_matches_with_options(
+        &self,
+        options: &TreeOptions,
+    ) -> Result<(String, Vec<(usize, String)>), MerkleError> {
+        if options.padding != Padding::DuplicateLast {
+            return Err(MerkleError::UnsupportedPadding);
+        }
+        if self.leaf_count == 0 {
+            return Err(MerkleError::EmptyTree);
+        }
+        if self.hashes.len() > self.leaf_count as usize {
+            return Err(MerkleError::InvalidProof);
+        }
+
+        let hashes = self
+            .hashes
+            .iter()
+            .map(|hash| decode_hash::<D>(hash))
+            .collect::<Option<Vec<_>>>()
+            .ok_or(MerkleError::InvalidProof)?;
+        let mut extractor = Extractor::<D> {
+            options,
+            level_sizes: crate::level_sizes(self.leaf_count as usize),
+            hashes: hashes.into_iter(),
+            flags: self.flags.iter(),
+            matches: Vec::new(),
+        };
+        let root = extractor.extract(extractor.level_sizes.len() - 1, 0)?;
+
+        let flags_used = self.flags.len() - extractor.flags.len();
+        if extractor.hashes.next().is_some()
+            || flags_used.div_ceil(8) != self.flags.len().div_ceil(8)
+        {
+            return Err(MerkleError::InvalidProof);
+        }
+
+        Ok((hex::encode(root), extractor.matches))
+    }
+}
+
+/// The state of the depth first traversal of `PartialMerkleTree::extract_matches_with_options`.
+struct Extractor<'a, D: Digest> {
+    options: &'a TreeOptions,
+    level_sizes: Vec<usize>,
+    hashes: vec::IntoIter<Output<D>>,
+    flags: core::slice::Iter<'a, bool>,
+    matches: Vec<(usize, String)>,
+}
+
+impl<D: Digest> Extractor<'_, D> {
+    /// Computes the hash of a node, consuming its flag and hashes.
+    ///
+    /// # Arguments
+    ///
+    /// * `level`: The level of the node, 0 being the leaves.
+    /// * `position`: The position of the node within its level.
+    fn extract(&mut self, level: usize, position: usize) -> Result<Output<D>, MerkleError> {
+        let flag = *self.flags.next().ok_or(MerkleError::InvalidProof)?;
+        if level == 0 || !flag {
+            let hash = self.hashes.next().ok_or(MerkleError::InvalidProof)?;
+            if level == 0 && flag {
+                self.matches.push((position, hex::encode(&hash)));
+            }
+            return Ok(hash);
+        }
+
+        let left = self.extract(level - 1, position * 2)?;
+        let right = if position * 2 + 1 < self.level_sizes[level - 1] {
+            let right = self.extract(level - 1, position * 2 + 1)?;
+            if right == left {
+                return Err(MerkleError::InvalidProof);
+            }
+            right
+        } else {
+            left.clone()
+        };
+        Ok(self.options.hash_branch::<D>(&left, &right))
+    }
+}
+
+impl<T, D: Digest, S: NodeStore<T, D>> MerkleTree<T, D, S> {
+    /// Generates a partial Merkle Tree proving the leaves at the given positions, as Bitcoin
+    /// does for the `merkleblock` message.
+    ///
+    /// # Arguments
+    ///
+    /// * `leaf_indices`: The positions of the leaves to prove, in any order.
+    ///
+    /// # Returns
+    ///
+    /// The partial tree, `MerkleError::EmptyTree` if the tree has no leaves,
+    /// `MerkleError::LeafNotFound` if a position is out of bounds,
+    /// `MerkleError::UnsupportedPadding` if the tree was not built with
+    /// `Padding::DuplicateLast` as Bitcoin's trees are, or `MerkleError::InvalidRange` if the
+    /// tree has more leaves than the format can count.
+    pub fn to_partial_tree(
+        &self,
+        leaf_indices: &[usize],
+    ) -> Result<PartialMerkleTree<D>, MerkleError> {
+        if self.options.padding != Padding::DuplicateLast {
+            return Err(MerkleError::UnsupportedPadding);
+        }
+        if self.leaf_count == 0 {
+            return Err(MerkleError::EmptyTree);
+        }
+        let leaf_count = u32::try_from(self.leaf_count).map_err(|_| MerkleError::InvalidRange)?;
+
+        let mut matched = vec![false; self.leaf_count];
+        for &index in leaf_indices {
+            *matched.get_mut(index).ok_or(MerkleError::LeafNotFound)? = true;
+        }
+
+        let level_sizes = self.level_sizes();
+        let mut partial = PartialMerkleTree::new(leaf_count, Vec::new(), Vec::new());
+        let mut stack = vec![(level_sizes.len() - 1, 0)];
+
+        while let Some((level, position)) = stack.pop() {
+            let first = position << level;
+            let last = ((position + 1) << level).min(self.leaf_count);
+            let flag = matched[first..last].contains(&true);
+            partial.flags.push(flag);
+
+            if level == 0 || !flag {
+                let node = self
+                    .node_at(level, position)
+                    .ok_or(MerkleError::LeafNotFound)?;
+                partial.hashes.push(hex::encode(&self.nodes[node].hash));
+            } else {
+                if position * 2 + 1 < level_sizes[level - 1] {
+                    stack.push((level - 1, position * 2 + 1));
+                }
+                stack.push((level - 1, position * 2));
+            }
+        }
+
+        Ok(partial)
+    }
+}
+
+/// Appends a number as a Bitcoin `CompactSize`.
+fn write_compact_size(bytes: &mut Vec<u8>, value: usize) {
+    let value = value as u64;
+    match value {
+        0..=0xfc => bytes.push(value as u8),
+        0xfd..=0xffff => {
+            bytes.push(0xfd);
+            bytes.extend_from_slice(&(value as u16).to_le_bytes());
+        }
+        0x10000..=0xffff_ffff => {
+            bytes.push(0xfe);
+            bytes.extend_from_slice(&(value as u32).to_le_bytes());
+        }
+        _ => {
+            bytes.push(0xff);
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+}
+
+/// Reads a Bitcoin `CompactSize` from the front of the bytes.
+fn read_compact_size(bytes: &mut &[u8]) -> Result<usize, MerkleError> {
+    let size = match take(bytes, 1)?[0] {
+        0xfd => take(bytes, 2)?
+            .iter()
+            .rev()
+            .fold(0, |acc, byte| acc << 8 | *byte as u64),
+        0xfe => take(bytes, 4)?
+            .iter()
+            .rev()
+            .fold(0, |acc, byte| acc << 8 | *byte as u64),
+        0xff => take(bytes, 8)?
+            .iter()
+            .rev()
+            .fold(0, |acc, byte| acc << 8 | *byte as u64),
+        size => size as u64,
+    };
+    usize::try_from(size).map_err(|_| MerkleError::InvalidProof)
+}
+
+/// Splits the given number of bytes off the front of the bytes.
+fn take<'a>(bytes: &mut &'a [u8], len: usize) -> Result<&'a [u8], MerkleError> {
+    if bytes.len() < len {
+        return Err(MerkleError::InvalidProof);
+    }
+    let (head, tail) = bytes.split_at(len);
+    *bytes = tail;
+    Ok(head)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{util, RootAccumulator};
+    use rstest::rstest;
+
+    /// Converts a transaction id as displayed by Bitcoin tooling into its internal byte order.
+    fn txid_to_hash(txid: &str) -> Output<Sha256> {
+        let mut bytes = hex::decode(txid).unwrap();
+        bytes.reverse();
+        Output::<Sha256>::clone_from_slice(&bytes)
+    }
+
+    #[rstest]
+    #[case(
+        &[
+            "8c14f0db3df150123e6f3dbbf30f8b955a8249b62ac1d1ff16284aefa3d06d87",
+            "fff2525b8931402dd09222c50775608f75787bd2b87e56995a7bdd30f79702c4",
+            "6359f0868171b1d194cbee1af2f16ea598ae8fad666d9b012c8ed2b79a236ec4",
+            "e9a66845e05d5abc0ad04ec80f774a7e585c6e8db975962d069a522137b80c1d",
+        ],
+        "f3e94742aca4b5ef85488dc37c06c3282295ffec960994b2c0d5ac2a25a95766"
+    )]
+    #[case(
+        &[
+            "8c14f0db3df150123e6f3dbbf30f8b955a8249b62ac1d1ff16284aefa3d06d87",
+            "fff2525b8931402dd09222c50775608f75787bd2b87e56995a7bdd30f79702c4",
+            "6359f0868171b1d194cbee1af2f16ea598ae8fad666d9b012c8ed2b79a236ec4",
+        ],
+        "fa435470825de273081dcc706b25514c936fa6dc80ab965ce6970d68ddd0b553"
+    )]
+    fn it_can_reproduce_bitcoin_merkle_root(#[case] txids: &[&str], #[case] expected: &str) {
+        let hashes: Vec<_> = txids.iter().map(|txid| txid_to_hash(txid)).collect();
+
+        let tree: MerkleTree<()> =
+            MerkleTree::from_leaf_hashes_with_options(TreeOptions::double_hash(), &hashes);
+
+        let mut root = hex::decode(tree.root().unwrap()).unwrap();
+        root.reverse();
+        assert_eq!(hex::encode(root), expected);
+
+        let mut accumulator: RootAccumulator = RootAccumulator::new(TreeOptions::double_hash());
+        for hash in hashes {
+            accumulator.push_leaf_hash(hash);
+        }
+        assert_eq!(accumulator.root(), tree.root());
+    }
+
+    #[test]
+    fn it_can_export_a_bitcoin_partial_tree() {
+        let hashes: Vec<_> = [
+            "8c14f0db3df150123e6f3dbbf30f8b955a8249b62ac1d1ff16284aefa3d06d87",
+            "fff2525b8931402dd09222c50775608f75787bd2b87e56995a7bdd30f79702c4",
+            "6359f0868171b1d194cbee1af2f16ea598ae8fad666d9b012c8ed2b79a236ec4",
+            "e9a66845e05d5abc0ad04ec80f774a7e585c6e8db975962d069a522137b80c1d",
+        ]
+        .iter()
+        .map(|txid| txid_to_hash(txid))
+        .collect();
+        let tree: MerkleTree<()> =
+            MerkleTree::from_leaf_hashes_with_options(TreeOptions::double_hash(), &hashes);
+
+        let partial = tree.to_partial_tree(&[1]).unwrap();
+        assert_eq!(partial.flags, [true, true, false, true, false]);
+        let bytes = partial.to_bytes().unwrap();
+        assert_eq!(bytes.len(), 4 + 1 + 3 * 32 + 1 + 1);
+        assert_eq!(&bytes[..5], &[4, 0, 0, 0, 3]);
+        assert_eq!(&bytes[bytes.len() - 2..], &[1, 0x0b]);
+
+        let partial = PartialMerkleTree::<Sha256>::from_bytes(&bytes).unwrap();
+        let (root, matches) = partial.extract_matches().unwrap();
+        assert_eq!(root, tree.root().unwrap());
+        assert_eq!(matches, [(1, hex::encode(hashes[1]))]);
+
+        let mut duplicated = partial.clone();
+        duplicated.hashes[0] = duplicated.hashes[1].clone();
+        assert_eq!(
+            duplicated.extract_matches().unwrap_err(),
+            MerkleError::InvalidProof
+        );
+        let mut extra = partial.clone();
+        extra.hashes.push(extra.hashes[0].clone());
+        assert_eq!(
+            extra.extract_matches().unwrap_err(),
+            MerkleError::InvalidProof
+        );
+        for padding in [Padding::Promote, Padding::PadWithEmpty] {
+            let options = TreeOptions::double_hash().with_padding(padding);
+            assert_eq!(
+                partial.extract_matches_with_options(&options).unwrap_err(),
+                MerkleError::UnsupportedPadding
+            );
+        }
+        let empty = PartialMerkleTree::<Sha256>::new(0, Vec::new(), Vec::new());
+        assert_eq!(empty.extract_matches().unwrap_err(), MerkleError::EmptyTree);
+        assert!(PartialMerkleTree::<Sha256>::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+    }
+
+    #[rstest]
+    #[case(1, &[0])]
+    #[case(7, &[6])]
+    #[case(11, &[0, 3, 4, 10])]
+    #[case(16, &[])]
+    fn it_can_extract_matches_from_partial_trees(
+        #[case] leaf_count: usize,
+        #[case] indices: &[usize],
+    ) {
+        let user_data = util::generate_random_user_data(leaf_count);
+        let tree: MerkleTree<_> =
+            MerkleTree::build_with_options(TreeOptions::double_hash(), &user_data);
+
+        let partial = tree.to_partial_tree(indices).unwrap();
+        let bytes = partial.to_bytes().unwrap();
+        let (root, matches) = PartialMerkleTree::<Sha256>::from_bytes(&bytes)
+            .unwrap()
+            .extract_matches()
+            .unwrap();
+
+        assert_eq!(root, tree.root().unwrap());
+        let matched: Vec<_> = matches.iter().map(|(index, _)| *index).collect();
+        assert_eq!(matched, indices);
+        for (index, hash) in matches {
+            let (node, _path) = tree.proof_by_index(index).unwrap();
+            assert_eq!(hash, hex::encode(node.hash));
+        }
+        assert_eq!(
+            tree.to_partial_tree(&[leaf_count]).unwrap_err(),
+            MerkleError::LeafNotFound
+        );
+    }
+}
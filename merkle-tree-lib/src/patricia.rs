@@ -0,0 +1,752 @@
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+use core::marker::PhantomData;
+use core::mem;
+use sha2::Digest;
+
+use crate::constant_time_eq;
+use crate::rlp::{self, Rlp};
+
+/// An Ethereum-style Merkle Patricia trie.
+///
+/// Keys are split into 4-bit nibbles that select the children of 16-way branch nodes, with
+/// leaf and extension nodes holding runs of nibbles that no other key shares. Nodes are RLP
+/// encoded, and a node is referenced by the hash of its encoding unless the encoding is shorter
+/// than 32 bytes, in which case it is embedded in its parent. With `Keccak256` as the digest,
+/// the roots and proofs are the ones of Ethereum's state and storage tries; keys of those tries
+/// are the Keccak-256 hashes of the addresses and slots.
+pub struct PatriciaTrie<D: Digest> {
+    root: Node,
+    digest: PhantomData<fn() -> D>,
+}
+
+#[derive(Default)]
+enum Node {
+    #[default]
+    Empty,
+    Leaf {
+        path: Vec<u8>,
+        value: Vec<u8>,
+    },
+    Extension {
+        path: Vec<u8>,
+        child: Box<Node>,
+    },
+    Branch {
+        children: Vec<Node>,
+        value: Option<Vec<u8>>,
+    },
+}
+
+impl<D: Digest> Default for PatriciaTrie<D> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<D: Digest> PatriciaTrie<D> {
+    /// Creates an empty trie.
+    pub fn new() -> Self {
+        PatriciaTrie {
+            root: Node::Empty,
+            digest: PhantomData,
+        }
+    }
+
+    /// Returns the hash of the root node of the trie.
+    pub fn root(&self) -> String {
+        hex::encode(D::digest(self.root.encode::<D>()))
+    }
+
+    /// Returns the value stored at the given key.
+    ///
+    /// # Arguments
+    ///
+    /// * `key`: The key to look up.
+    pub fn get(&self, key: &[u8]) -> Option<&[u8]> {
+        self.root.get(&to_nibbles(key))
+    }
+
+    /// Inserts or updates the value at the given key.
+    ///
+    /// As in Ethereum, storing an empty value removes the key.
+    ///
+    /// # Arguments
+    ///
+    /// * `key`: The key of the value.
+    /// * `value`: The value to store.
+    ///
+    /// # Returns
+    ///
+    /// The value previously stored at the key, if any.
+    pub fn insert(&mut self, key: &[u8], value: Vec<u8>) -> Option<Vec<u8>> {
+        if value.is_empty() {
+            return self.remove(key);
+        }
+        let (root, old) = mem::take(&mut self.root).insert(&to_nibbles(key), value);
+        self.root = root;
+        old
+    }
+
+    /// Removes the value at the given key, merging the nodes that are left with a single child.
+    ///
+    /// # Arguments
+    ///
+    /// * `key`: The key of the value.
+    ///
+    /// # Returns
+    ///
+    /// The value that was stored at the key, if any.
+    pub fn remove(&mut self, key: &[u8]) -> Option<Vec<u8>> {
+        let (root, old) = mem::take(&mut self.root).remove(&to_nibbles(key));
+        self.root = root;
+        old
+    }
+
+    /// Generates the proof for the given key.
+    ///
+    /// The proof holds the encoded nodes along the path of the key that are referenced by hash,
+    /// starting with the root, in the same form as the `accountProof` and `storageProof` of
+    /// Ethereum's `eth_getProof`. It shows the value of the key, or that the key is absent.
+    ///
+    /// # Arguments
+    ///
+    /// * `key`: The key to prove.
+    pub fn proof(&self, key: &[u8]) -> PatriciaProof<D> {
+        let nibbles = to_nibbles(key);
+        let mut path = nibbles.as_slice();
+        let mut node = &self.root;
+        let mut nodes = vec![hex::encode(node.encode::<D>())];
+
+        loop {
+            node = match node {
+                Node::Extension {
+                    path: prefix,
+                    child,
+                } if path.starts_with(prefix) => {
+                    path = &path[prefix.len()..];
+                    child
+                }
+                Node::Branch { children, .. } if !path.is_empty() => {
+                    let child = &children[usize::from(path[0])];
+                    path = &path[1..];
+                    child
+                }
+                _ => break,
+            };
+
+            let encoded = node.encode::<D>();
+            if encoded.len() >= 32 {
+                nodes.push(hex::encode(encoded));
+            }
+        }
+
+        PatriciaProof::new(nodes)
+    }
+}
+
+impl Node {
+    /// Creates a branch node without children or value.
+    fn empty_branch() -> Self {
+        Node::Branch {
+            children: (0..16).map(|_| Node::Empty).collect(),
+            value: None,
+        }
+    }
+
+    /// Prefixes a node with a run of nibbles, merging it into the node if it is a leaf or an
+    /// extension.
+    ///
+    /// # Arguments
+    ///
+    /// * `prefix`: The nibbles before the node.
+    /// * `node`: The node.
+    fn with_prefix(mut prefix: Vec<u8>, node: Node) -> Self {
+        if prefix.is_empty() {
+            return node;
+        }
+        match node {
+            Node::Empty => Node::Empty,
+            Node::Leaf { path, value } => {
+                prefix.extend_from_slice(&path);
+                Node::Leaf {
+                    path: prefix,
+                    value,
+                }
+            }
+            Node::Extension { path, child } => {
+                prefix.extend_from_slice(&path);
+                Node::Extension {
+                    path: prefix,
+                    child,
+                }
+            }
+            branch => Node::Extension {
+                path: prefix,
+                child: Box::new(branch),
+            },
+        }
+    }
+
+    /// Stores a value in a branch node, either as its own value or in a new leaf.
+    ///
+    /// # Arguments
+    ///
+    /// * `path`: The nibbles of the key after the branch.
+    /// * `new_value`: The value to store.
+    fn put(&mut self, path: &[u8], new_value: Vec<u8>) {
+        let Node::Branch { children, value } = self else {
+            unreachable!("values are only put into branches");
+        };
+        match path.split_first() {
+            None => *value = Some(new_value),
+            Some((first, rest)) => {
+                children[usize::from(*first)] = Node::Leaf {
+                    path: rest.to_vec(),
+                    value: new_value,
+                };
+            }
+        }
+    }
+
+    /// Returns the value at the given path below this node.
+    fn get(&self, path: &[u8]) -> Option<&[u8]> {
+        match self {
+            Node::Empty => None,
+            Node::Leaf { path: leaf, value } => (leaf == path).then_some(value.as_slice()),
+            Node::Extension {
+                path: prefix,
+                child,
+            } => child.get(path.strip_prefix(prefix.as_slice())?),
+            Node::Branch { children, value } => match path.split_first() {
+                None => value.as_deref(),
+                Some((first, rest)) => children[usize::from(*first)].get(rest),
+            },
+        }
+    }
+
+    /// Inserts a value at the given path below this node.
+    ///
+    /// # Returns
+    ///
+    /// The new node and the value previously stored at the path, if any.
+    fn insert(self, path: &[u8], value: Vec<u8>) -> (Node, Option<Vec<u8>>) {
+        match self {
+            Node::Empty => (
+                Node::Leaf {
+                    path: path.to_vec(),
+                    value,
+                },
+                None,
+            ),
+            Node::Leaf {
+                path: leaf,
+                value: old,
+            } if leaf == path => (Node::Leaf { path: leaf, value }, Some(old)),
+            Node::Leaf {
+                path: leaf,
+                value: old,
+            } => {
+                let common = common_prefix(&leaf, path);
+                let mut branch = Node::empty_branch();
+                branch.put(&leaf[common..], old);
+                branch.put(&path[common..], value);
+                (Node::with_prefix(path[..common].to_vec(), branch), None)
+            }
+            Node::Extension {
+                path: prefix,
+                child,
+            } => {
+                let common = common_prefix(&prefix, path);
+                if common == prefix.len() {
+                    let (child, old) = child.insert(&path[common..], value);
+                    return (Node::with_prefix(prefix, child), old);
+                }
+
+                let mut branch = Node::empty_branch();
+                if let Node::Branch { children, .. } = &mut branch {
+                    children[usize::from(prefix[common])] =
+                        Node::with_prefix(prefix[common + 1..].to_vec(), *child);
+                }
+                branch.put(&path[common..], value);
+                (Node::with_prefix(path[..common].to_vec(), branch), None)
+            }
+            Node::Branch {
+                mut children,
+                value: own,
+            } => match path.split_first() {
+                None => (
+                    Node::Branch {
+                        children,
+                        value: Some(value),
+                    },
+                    own,
+                ),
+                Some((first, rest)) => {
+                    let index = usize::from(*first);
+                    let (child, old) = mem::take(&mut children[index]).insert(rest, value);
+                    children[index] = child;
+                    (
+                        Node::Branch {
+                            children,
+                            value: own,
+                        },
+                        old,
+                    )
+                }
+            },
+        }
+    }
+
+    /// Removes the value at the given path below this node.
+    ///
+    /// # Returns
+    ///
+    /// The new node and the value that was stored at the path, if any.
+    fn remove(self, path: &[u8]) -> (Node, Option<Vec<u8>>) {
+        match self {
+            Node::Leaf { path: leaf, value } if leaf == path => (Node::Empty, Some(value)),
+            Node::Extension {
+                path: prefix,
+                child,
+            } if path.starts_with(&prefix) => {
+                let (child, old) = child.remove(&path[prefix.len()..]);
+                (Node::with_prefix(prefix, child), old)
+            }
+            Node::Branch {
+                mut children,
+                mut value,
+            } => {
+                let old = match path.split_first() {
+                    None => value.take(),
+                    Some((first, rest)) => {
+                        let index = usize::from(*first);
+                        let (child, old) = mem::take(&mut children[index]).remove(rest);
+                        children[index] = child;
+                        old
+                    }
+                };
+
+                // A branch that is left with a single child or value is merged into it.
+                let occupied: Vec<usize> = children
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, child)| !matches!(child, Node::Empty))
+                    .map(|(index, _)| index)
+                    .collect();
+                let node = match (occupied.as_slice(), value) {
+                    ([], None) => Node::Empty,
+                    ([], Some(value)) => Node::Leaf {
+                        path: Vec::new(),
+                        value,
+                    },
+                    (&[index], None) => {
+                        let child = mem::take(&mut children[index]);
+                        Node::with_prefix(vec![index as u8], child)
+                    }
+                    (_, value) => Node::Branch { children, value },
+                };
+                (node, old)
+            }
+            node => (node, None),
+        }
+    }
+
+    /// Encodes this node with RLP.
+    fn encode<D: Digest>(&self) -> Vec<u8> {
+        match self {
+            Node::Empty => rlp::encode_bytes(&[]),
+            Node::Leaf { path, value } => rlp::encode_list(&[
+                rlp::encode_bytes(&encode_path(path, true)),
+                rlp::encode_bytes(value),
+            ]),
+            Node::Extension { path, child } => rlp::encode_list(&[
+                rlp::encode_bytes(&encode_path(path, false)),
+                child.reference::<D>(),
+            ]),
+            Node::Branch { children, value } => {
+                let mut items: Vec<Vec<u8>> = children.iter().map(Node::reference::<D>).collect();
+                items.push(rlp::encode_bytes(value.as_deref().unwrap_or_default()));
+                rlp::encode_list(&items)
+            }
+        }
+    }
+
+    /// Encodes the reference to this node from its parent: the node itself if its encoding is
+    /// shorter than 32 bytes, the hash of the encoding otherwise.
+    fn reference<D: Digest>(&self) -> Vec<u8> {
+        let encoded = self.encode::<D>();
+        if encoded.len() < 32 {
+            encoded
+        } else {
+            rlp::encode_bytes(&D::digest(&encoded))
+        }
+    }
+}
+
+/// A proof for a key of a `PatriciaTrie`.
+///
+/// Holds the hex encoded nodes along the path of the key, starting with the root. A `0x`
+/// prefix is accepted, so proofs returned by `eth_getProof` can be used as they are.
+pub struct PatriciaProof<D> {
+    pub nodes: Vec<String>,
+    digest: PhantomData<fn() -> D>,
+}
+
+impl<D> Clone for PatriciaProof<D> {
+    fn clone(&self) -> Self {
+        Self::new(self.nodes.clone())
+    }
+}
+
+impl<D> fmt::Debug for PatriciaProof<D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PatriciaProof")
+            .field("nodes", &self.nodes)
+            .finish()
+    }
+}
+
+impl<D> PatriciaProof<D> {
+    /// Creates a `PatriciaProof` from hex encoded nodes.
+    ///
+    /// # Arguments
+    ///
+    /// * `nodes`: The hex encoded nodes, starting with the root.
+    pub fn new(nodes: Vec<String>) -> Self {
+        PatriciaProof {
+            nodes,
+            digest: PhantomData,
+        }
+    }
+
+    /// Looks up a key in the trie with the given root by following the nodes of this proof.
+    ///
+    /// # Arguments
+    ///
+    /// * `root`: The hex encoded root of the trie.
+    /// * `key`: The key to look up.
+    ///
+    /// # Returns
+    ///
+    /// `Some(Some(value))` if the proof shows the value of the key, `Some(None)` if it shows
+    /// that the key is absent, and `None` if the proof does not match the root or is malformed.
+    pub fn lookup(&self, root: &str, key: &[u8]) -> Option<Option<Vec<u8>>>
+    where
+        D: Digest,
+    {
+        let root = decode_hex(root)?;
+        let nodes = self
+            .nodes
+            .iter()
+            .map(|node| decode_hex(node))
+            .collect::<Option<Vec<_>>>()?;
+        let mut nodes = nodes.iter();
+
+        let nibbles = to_nibbles(key);
+        let mut path = nibbles.as_slice();
+        let mut reference = Reference::Hash(&root);
+
+        loop {
+            let node = match reference {
+                Reference::Hash(hash) => {
+                    let encoded = nodes.next()?;
+                    if !constant_time_eq(&D::digest(encoded), hash) {
+                        return None;
+                    }
+                    rlp::decode(encoded)?
+                }
+                Reference::Embedded(node) => node,
+            };
+
+            let items = match node {
+                Rlp::List(items) => items,
+                // Only the root of an empty trie is an empty string.
+                Rlp::Bytes([]) => return Some(None),
+                Rlp::Bytes(_) => return None,
+            };
+
+            let child = match items.len() {
+                17 => match path.split_first() {
+                    None => {
+                        let Rlp::Bytes(value) = &items[16] else {
+                            return None;
+                        };
+                        return Some((!value.is_empty()).then(|| value.to_vec()));
+                    }
+                    Some((first, rest)) => {
+                        path = rest;
+                        items.into_iter().nth(usize::from(*first))?
+                    }
+                },
+                2 => {
+                    let mut items = items.into_iter();
+                    let (Some(Rlp::Bytes(encoded_path)), Some(second)) =
+                        (items.next(), items.next())
+                    else {
+                        return None;
+                    };
+                    let (node_path, is_leaf) = decode_path(encoded_path)?;
+
+                    if is_leaf {
+                        let Rlp::Bytes(value) = second else {
+                            return None;
+                        };
+                        return Some((path == node_path.as_slice()).then(|| value.to_vec()));
+                    }
+                    match path.strip_prefix(node_path.as_slice()) {
+                        Some(rest) => path = rest,
+                        None => return Some(None),
+                    }
+                    second
+                }
+                _ => return None,
+            };
+
+            reference = match child {
+                Rlp::Bytes([]) => return Some(None),
+                Rlp::Bytes(hash) if hash.len() == <D as Digest>::output_size() => {
+                    Reference::Hash(hash)
+                }
+                Rlp::Bytes(_) => return None,
+                node => Reference::Embedded(node),
+            };
+        }
+    }
+}
+
+/// A reference from a node to its child, as seen by a verifier.
+enum Reference<'a> {
+    Hash(&'a [u8]),
+    Embedded(Rlp<'a>),
+}
+
+/// Verifies the value of a key in a Merkle Patricia trie, or that the key is absent.
+///
+/// # Arguments
+///
+/// * `root`: The hex encoded root to verify against.
+/// * `key`: The key to verify.
+/// * `value`: The claimed value of the key, or `None` to verify that the key is absent.
+/// * `proof`: The proof returned by `PatriciaTrie::proof` or `eth_getProof`.
+///
+/// # Returns
+///
+/// `true` if the proof shows the claimed value, `false` otherwise.
+pub fn verify_patricia_proof<D: Digest>(
+    root: &str,
+    key: &[u8],
+    value: Option<&[u8]>,
+    proof: &PatriciaProof<D>,
+) -> bool {
+    proof
+        .lookup(root, key)
+        .is_some_and(|found| found.as_deref() == value)
+}
+
+/// Splits a key into nibbles, high nibble first.
+fn to_nibbles(key: &[u8]) -> Vec<u8> {
+    key.iter()
+        .flat_map(|byte| [byte >> 4, byte & 0x0f])
+        .collect()
+}
+
+/// Returns the length of the common prefix of two runs of nibbles.
+fn common_prefix(a: &[u8], b: &[u8]) -> usize {
+    a.iter().zip(b).take_while(|(a, b)| a == b).count()
+}
+
+/// Packs a run of nibbles into bytes with the hex-prefix encoding, whose first nibble flags
+/// a leaf (2) or an extension (0), plus 1 if the number of nibbles is odd.
+///
+/// # Arguments
+///
+/// * `path`: The nibbles.
+/// * `is_leaf`: Whether the path belongs to a leaf node.
+fn encode_path(path: &[u8], is_leaf: bool) -> Vec<u8> {
+    let odd = path.len() & 1;
+    let flag = if is_leaf { 2 } else { 0 } + odd as u8;
+
+    let mut encoded = Vec::with_capacity(path.len() / 2 + 1);
+    encoded.push(flag << 4 | if odd == 1 { path[0] } else { 0 });
+    for pair in path[odd..].chunks(2) {
+        encoded.push(pair[0] << 4 | pair[1]);
+    }
+    encoded
+}
+
+/// Unpacks a hex-prefix encoded run of nibbles.
+///
+/// # Returns
+///
+/// The nibbles and whether they belong to a leaf node, or `None` if the encoding is invalid.
+fn decode_path(encoded: &[u8]) -> Option<(Vec<u8>, bool)> {
+    let (first, rest) = encoded.split_first()?;
+    let flag = first >> 4;
+    if flag > 3 || (flag & 1 == 0 && first & 0x0f != 0) {
+        return None;
+    }
+
+    let mut path = Vec::with_capacity(rest.len() * 2 + 1);
+    if flag & 1 == 1 {
+        path.push(first & 0x0f);
+    }
+    path.extend(to_nibbles(rest));
+    Some((path, flag & 2 != 0))
+}
+
+/// Decodes a hex string with an optional `0x` prefix.
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    hex::decode(hex.strip_prefix("0x").unwrap_or(hex)).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "keccak")]
+    use crate::Keccak256;
+    use crate::Sha256;
+    use alloc::format;
+    use rstest::rstest;
+
+    fn generate_patricia_trie<D: Digest>(entries: &[(&str, &str)]) -> PatriciaTrie<D> {
+        let mut trie = PatriciaTrie::new();
+        for (key, value) in entries {
+            trie.insert(key.as_bytes(), value.as_bytes().to_vec());
+        }
+        trie
+    }
+
+    #[rstest]
+    #[case(&[], "76be8b528d0075f7aae98d6fa57a6d3c83ae480a8469e668d7b0af968995ac71")]
+    #[case(
+        &[("doe", "reindeer"), ("dog", "puppy"), ("dogglesworth", "cat")],
+        "8da4fc0db7d0a5fdf2b6658e36fc414489866d0fe6473cee4a55cd9776cba39a"
+    )]
+    #[case(
+        &[("do", "verb"), ("horse", "stallion"), ("doge", "coin"), ("dog", "puppy")],
+        "637e1cfa74b164c4dbf019aec31632a46716d9d7cfba73d3fe7a7136a24c5c1a"
+    )]
+    fn it_can_compute_patricia_root(#[case] entries: &[(&str, &str)], #[case] expected: &str) {
+        let trie = generate_patricia_trie::<Sha256>(entries);
+        assert_eq!(trie.root(), expected);
+
+        let mut reversed = entries.to_vec();
+        reversed.reverse();
+        assert_eq!(generate_patricia_trie::<Sha256>(&reversed).root(), expected);
+    }
+
+    #[cfg(feature = "keccak")]
+    #[rstest]
+    #[case(&[], "56e81f171bcc55a6ff8345e692c0f86e5b48e01b996cadc001622fb5e363b421")]
+    #[case(
+        &[("doe", "reindeer"), ("dog", "puppy"), ("dogglesworth", "cat")],
+        "8aad789dff2f538bca5d8ea56e8abe10f4c7ba3a5dea95fea4cd6e7c3a1168d3"
+    )]
+    #[case(
+        &[("do", "verb"), ("horse", "stallion"), ("doge", "coin"), ("dog", "puppy")],
+        "5991bb8c6514148a29db676a14ac506cd2cd5775ace63c30a4fe457715e9ac84"
+    )]
+    fn it_can_compute_ethereum_patricia_root(
+        #[case] entries: &[(&str, &str)],
+        #[case] expected: &str,
+    ) {
+        let trie = generate_patricia_trie::<Keccak256>(entries);
+        assert_eq!(trie.root(), expected);
+    }
+
+    #[test]
+    fn it_can_update_patricia_trie() {
+        let keys: Vec<Vec<u8>> = (0u32..60)
+            .map(|i| Sha256::digest(i.to_be_bytes())[..(i as usize % 4) + 2].to_vec())
+            .collect();
+        let mut trie: PatriciaTrie<Sha256> = PatriciaTrie::new();
+        for (i, key) in keys.iter().enumerate() {
+            trie.insert(key, format!("value {i}").into_bytes());
+        }
+        for (i, key) in keys.iter().enumerate() {
+            assert_eq!(trie.get(key).unwrap(), format!("value {i}").as_bytes());
+        }
+        assert_eq!(
+            trie.root(),
+            "b0170a28b052783d470865adc7fb13934101ba4c5bad55d7f36db771406c10b6"
+        );
+
+        // Removing keys merges the nodes back into the shape of a trie built without them.
+        let mut expected: PatriciaTrie<Sha256> = PatriciaTrie::new();
+        for (i, key) in keys.iter().enumerate() {
+            if i % 3 == 0 {
+                assert!(trie.remove(key).is_some());
+                assert!(trie.get(key).is_none());
+            } else {
+                expected.insert(key, format!("value {i}").into_bytes());
+            }
+        }
+        assert_eq!(trie.root(), expected.root());
+        assert_eq!(
+            trie.root(),
+            "183168129bde4eb79745474ab7d43fa673b4559b37b9578f34c62279782d8d8f"
+        );
+
+        // An empty value removes the key, as in Ethereum.
+        assert_eq!(
+            trie.insert(&keys[1], Vec::new()).unwrap(),
+            b"value 1".to_vec()
+        );
+        assert!(trie.get(&keys[1]).is_none());
+        assert_ne!(trie.root(), expected.root());
+    }
+
+    #[test]
+    fn it_can_verify_patricia_proofs() {
+        let entries = [
+            ("do", "verb"),
+            ("horse", "stallion"),
+            ("doge", "coin"),
+            ("dog", "puppy"),
+        ];
+        let trie = generate_patricia_trie::<Sha256>(&entries);
+        let root = trie.root();
+
+        for (key, value) in entries {
+            let proof = trie.proof(key.as_bytes());
+            assert!(verify_patricia_proof(
+                &root,
+                key.as_bytes(),
+                Some(value.as_bytes()),
+                &proof
+            ));
+            assert!(!verify_patricia_proof(
+                &root,
+                key.as_bytes(),
+                Some(b"other"),
+                &proof
+            ));
+            assert!(!verify_patricia_proof(&root, key.as_bytes(), None, &proof));
+        }
+
+        for key in ["d", "dogs", "horses", "cat", ""] {
+            let proof = trie.proof(key.as_bytes());
+            assert!(verify_patricia_proof(&root, key.as_bytes(), None, &proof));
+        }
+
+        // Proofs in the form returned by eth_getProof.
+        let proof = trie.proof(b"doge");
+        let prefixed = PatriciaProof::<Sha256>::new(
+            proof.nodes.iter().map(|node| format!("0x{node}")).collect(),
+        );
+        assert!(verify_patricia_proof(
+            &format!("0x{root}"),
+            b"doge",
+            Some(b"coin"),
+            &prefixed
+        ));
+
+        let mut tampered = proof.clone();
+        tampered.nodes[0].replace_range(..2, "00");
+        assert!(tampered.lookup(&root, b"doge").is_none());
+    }
+}
@@ -0,0 +1,113 @@
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// An item of the Recursive Length Prefix encoding used by Ethereum, borrowing its byte
+/// strings from the decoded input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Rlp<'a> {
+    Bytes(&'a [u8]),
+    List(Vec<Rlp<'a>>),
+}
+
+/// Encodes a byte string.
+///
+/// # Arguments
+///
+/// * `bytes`: The byte string to encode.
+pub(crate) fn encode_bytes(bytes: &[u8]) -> Vec<u8> {
+    if bytes.len() == 1 && bytes[0] < 0x80 {
+        return bytes.to_vec();
+    }
+    let mut encoded = header(0x80, bytes.len());
+    encoded.extend_from_slice(bytes);
+    encoded
+}
+
+/// Encodes a list of items that are already encoded.
+///
+/// # Arguments
+///
+/// * `items`: The encoded items of the list.
+pub(crate) fn encode_list(items: &[Vec<u8>]) -> Vec<u8> {
+    let payload: usize = items.iter().map(Vec::len).sum();
+    let mut encoded = header(0xc0, payload);
+    for item in items {
+        encoded.extend_from_slice(item);
+    }
+    encoded
+}
+
+/// Decodes a single item that spans the whole input.
+///
+/// # Arguments
+///
+/// * `input`: The encoded item.
+///
+/// # Returns
+///
+/// The decoded item, or `None` if the input is malformed or has trailing bytes.
+pub(crate) fn decode(input: &[u8]) -> Option<Rlp<'_>> {
+    let (item, rest) = decode_item(input)?;
+    rest.is_empty().then_some(item)
+}
+
+/// Creates the header of a byte string (`offset` 0x80) or a list (`offset` 0xc0).
+fn header(offset: u8, len: usize) -> Vec<u8> {
+    if len <= 55 {
+        return vec![offset + len as u8];
+    }
+    let len_bytes = len.to_be_bytes();
+    let skip = len_bytes.iter().take_while(|byte| **byte == 0).count();
+    let mut header = vec![offset + 55 + (len_bytes.len() - skip) as u8];
+    header.extend_from_slice(&len_bytes[skip..]);
+    header
+}
+
+/// Decodes the item at the start of the input.
+///
+/// # Returns
+///
+/// The decoded item and the bytes after it.
+fn decode_item(input: &[u8]) -> Option<(Rlp<'_>, &[u8])> {
+    let (&prefix, rest) = input.split_first()?;
+    let (is_list, len, rest) = match prefix {
+        0x00..=0x7f => return Some((Rlp::Bytes(&input[..1]), rest)),
+        0x80..=0xb7 => (false, usize::from(prefix - 0x80), rest),
+        0xb8..=0xbf => {
+            let (len, rest) = decode_len(rest, usize::from(prefix - 0xb7))?;
+            (false, len, rest)
+        }
+        0xc0..=0xf7 => (true, usize::from(prefix - 0xc0), rest),
+        0xf8..=0xff => {
+            let (len, rest) = decode_len(rest, usize::from(prefix - 0xf7))?;
+            (true, len, rest)
+        }
+    };
+
+    let payload = rest.get(..len)?;
+    let rest = &rest[len..];
+    if !is_list {
+        return Some((Rlp::Bytes(payload), rest));
+    }
+
+    let mut items = Vec::new();
+    let mut payload = payload;
+    while !payload.is_empty() {
+        let (item, remaining) = decode_item(payload)?;
+        items.push(item);
+        payload = remaining;
+    }
+    Some((Rlp::List(items), rest))
+}
+
+/// Decodes a big-endian length of the given number of bytes.
+fn decode_len(input: &[u8], size: usize) -> Option<(usize, &[u8])> {
+    if size > core::mem::size_of::<usize>() {
+        return None;
+    }
+    let bytes = input.get(..size)?;
+    let len = bytes
+        .iter()
+        .fold(0usize, |len, byte| len << 8 | usize::from(*byte));
+    Some((len, &input[size..]))
+}
@@ -0,0 +1,221 @@
+use core::fmt;
+use core::hash::{Hash, Hasher};
+use core::str::FromStr;
+use sha2::Digest;
+
+use crate::{MerkleError, MerkleTree, NodeStore};
+
+/// A 32-byte Merkle root, displayed and parsed as lowercase hex.
+///
+/// Comparing two `MerkleRoot`s compares the bytes in constant time, so roots can be compared
+/// regardless of the case of the hex string or an `0x` prefix they were parsed from, and
+/// without leaking how many leading bytes match.
+#[derive(Debug, Clone, Copy, Eq, PartialOrd, Ord, Default)]
+pub struct MerkleRoot(pub [u8; 32]);
+
+impl PartialEq for MerkleRoot {
+    fn eq(&self, other: &Self) -> bool {
+        constant_time_eq(&self.0, &other.0)
+    }
+}
+
+impl Hash for MerkleRoot {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.hash(state);
+    }
+}
+
+impl MerkleRoot {
+    /// Returns the bytes of the root.
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+}
+
+impl From<[u8; 32]> for MerkleRoot {
+    fn from(bytes: [u8; 32]) -> Self {
+        MerkleRoot(bytes)
+    }
+}
+
+impl fmt::Display for MerkleRoot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for byte in self.0 {
+            write!(f, "{byte:02x}")?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for MerkleRoot {
+    type Err = hex::FromHexError;
+
+    /// Parses a root from 64 hex digits of either case, optionally prefixed with `0x`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.strip_prefix("0x").unwrap_or(s);
+        let mut bytes = [0; 32];
+        hex::decode_to_slice(s, &mut bytes)?;
+        Ok(MerkleRoot(bytes))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for MerkleRoot {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&hex::encode(self.0))
+    }
+}
+
+impl<T, D: Digest, S: NodeStore<T, D>> MerkleTree<T, D, S> {
+    /// Returns the root of the tree as a `MerkleRoot`.
+    ///
+    /// # Returns
+    ///
+    /// The root, `MerkleError::EmptyTree` if the tree has no leaves or
+    /// `MerkleError::UnsupportedDigest` if the digest does not produce 32-byte hashes.
+    pub fn root_bytes(&self) -> Result<MerkleRoot, MerkleError> {
+        let hash = self.root_hash()?;
+        <[u8; 32]>::try_from(hash.as_slice())
+            .map(MerkleRoot)
+            .map_err(|_| MerkleError::UnsupportedDigest)
+    }
+}
+
+/// Two trees are equal if they have the same root, compared in constant time, regardless of
+/// how they were built or stored. Empty trees without a root are equal to each other.
+impl<T, D: Digest, S: NodeStore<T, D>> PartialEq for MerkleTree<T, D, S> {
+    fn eq(&self, other: &Self) -> bool {
+        match (self.root_hash(), other.root_hash()) {
+            (Ok(a), Ok(b)) => constant_time_eq(&a, &b),
+            (a, b) => a.is_err() && b.is_err(),
+        }
+    }
+}
+
+impl<T, D: Digest, S: NodeStore<T, D>> Eq for MerkleTree<T, D, S> {}
+
+impl<T, D: Digest, S: NodeStore<T, D>> Hash for MerkleTree<T, D, S> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.root_hash().ok().hash(state);
+    }
+}
+
+impl<T, D: Digest, S: NodeStore<T, D>> fmt::Debug for MerkleTree<T, D, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MerkleTree")
+            .field("root", &self.root().ok())
+            .field("leaf_count", &self.leaf_count())
+            .finish()
+    }
+}
+
+/// Compares two byte strings in constant time, taking as long for a mismatch in the first
+/// byte as in the last one, so that a verifier does not leak through its response time how
+/// close a forged hash came to the expected one.
+///
+/// All verification functions of this crate compare hashes and roots with it. Only the
+/// lengths are compared in variable time, as they are public.
+///
+/// # Arguments
+///
+/// * `a`: The first byte string, such as a computed hash.
+/// * `b`: The second byte string, such as the expected hash.
+///
+/// # Returns
+///
+/// `true` if the byte strings are equal, `false` otherwise.
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    let diff = a.iter().zip(b).fold(0u8, |diff, (a, b)| diff | (a ^ b));
+    core::hint::black_box(diff) == 0
+}
+
+/// Compares a hash with a hex encoded root of either case in constant time.
+///
+/// # Returns
+///
+/// `true` if `root` is the hex encoding of `hash`, `false` otherwise.
+pub(crate) fn hash_matches(hash: &[u8], root: &str) -> bool {
+    hex::decode(root).is_ok_and(|root| constant_time_eq(hash, &root))
+}
+
+/// Compares two hex encoded roots of either case in constant time.
+///
+/// # Returns
+///
+/// `true` if both are the hex encodings of the same bytes, `false` otherwise.
+pub(crate) fn roots_match(a: &str, b: &str) -> bool {
+    hex::decode(a).is_ok_and(|a| hash_matches(&a, b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{util, MerkleTreeBuilder};
+    use alloc::format;
+    use alloc::string::ToString;
+    use rstest::rstest;
+
+    #[test]
+    fn it_can_parse_and_display_merkle_root() {
+        let user_data = util::generate_random_user_data(5);
+        let tree: MerkleTree<_> =
+            MerkleTree::build("ProofOfReserve_Leaf", "ProofOfReserve_Branch", &user_data);
+
+        let root = tree.root_bytes().unwrap();
+        let hex_root = tree.root().unwrap();
+        assert_eq!(root.to_string(), hex_root);
+        assert_eq!(
+            root.as_bytes().as_slice(),
+            tree.root_hash().unwrap().as_slice()
+        );
+
+        assert_eq!(hex_root.parse::<MerkleRoot>(), Ok(root));
+        assert_eq!(hex_root.to_uppercase().parse::<MerkleRoot>(), Ok(root));
+        assert_eq!(format!("0x{hex_root}").parse::<MerkleRoot>(), Ok(root));
+        assert!(hex_root[2..].parse::<MerkleRoot>().is_err());
+        assert!(hex_root.replace('a', "g").parse::<MerkleRoot>().is_err());
+
+        let empty: MerkleTree<util::UserData> = MerkleTree::build("", "", &[]);
+        assert_eq!(empty.root_bytes(), Err(MerkleError::EmptyTree));
+    }
+
+    #[rstest]
+    #[case(b"", b"", true)]
+    #[case(b"abc", b"abc", true)]
+    #[case(b"abc", b"abd", false)]
+    #[case(b"abc", b"xbc", false)]
+    #[case(b"abc", b"abcd", false)]
+    fn it_can_compare_in_constant_time(#[case] a: &[u8], #[case] b: &[u8], #[case] equal: bool) {
+        assert_eq!(constant_time_eq(a, b), equal);
+        assert_eq!(constant_time_eq(b, a), equal);
+    }
+
+    #[test]
+    fn it_can_compare_trees_by_root() {
+        let user_data = util::generate_random_user_data(6);
+        let tree: MerkleTree<_> = MerkleTree::build("Leaf", "Branch", &user_data);
+        let rebuilt: MerkleTree<_> = MerkleTreeBuilder::new()
+            .with_tags("Leaf", "Branch")
+            .build(&user_data);
+        let other: MerkleTree<_> = MerkleTree::build("Leaf", "Branch", &user_data[1..]);
+        assert_eq!(tree, rebuilt);
+        assert_ne!(tree, other);
+
+        let empty: MerkleTree<util::UserData> = MerkleTree::build("Leaf", "Branch", &[]);
+        assert_eq!(empty, MerkleTree::build("Leaf", "Branch", &[]));
+        assert_ne!(empty, tree);
+
+        #[cfg(feature = "std")]
+        {
+            let trees: std::collections::HashSet<_> =
+                [tree, rebuilt, other, empty].into_iter().collect();
+            assert_eq!(trees.len(), 3);
+        }
+
+        let debug = format!("{:?}", MerkleTree::<_>::build("Leaf", "Branch", &user_data));
+        assert!(debug.contains("leaf_count: 6"));
+    }
+}
@@ -0,0 +1,144 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use sha2::Digest;
+
+use crate::{MerkleSumData, MerkleTreeData, MerkleTreeKey};
+
+/// User data with a salt that is hashed into its leaf.
+///
+/// Without a salt, a leaf hash over a small id/balance space can be brute-forced by anyone
+/// who sees it as a sibling hash in a proof. The salt must be handed to the user together
+/// with the proof, which happens naturally since it is part of the leaf's user data.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Salted<T> {
+    pub data: T,
+    pub salt: Vec<u8>,
+}
+
+impl<T> Salted<T> {
+    /// Attaches a salt to user data.
+    ///
+    /// # Arguments
+    ///
+    /// * `data`: The user data.
+    /// * `salt`: A random salt, unique per leaf.
+    pub fn new(data: T, salt: Vec<u8>) -> Self {
+        Salted { data, salt }
+    }
+}
+
+impl<T: MerkleTreeData> MerkleTreeData for Salted<T> {
+    fn serialize(&self) -> Vec<u8> {
+        let mut bytes = self.data.serialize();
+        bytes.extend_from_slice(&self.salt);
+        bytes
+    }
+
+    fn hash_into(&self, hasher: &mut impl Digest) {
+        self.data.hash_into(hasher);
+        hasher.update(&self.salt);
+    }
+
+    fn mermaid_node_label(&self) -> String {
+        self.data.mermaid_node_label()
+    }
+}
+
+impl<B, T: MerkleSumData<B>> MerkleSumData<B> for Salted<T> {
+    fn balance(&self) -> B {
+        self.data.balance()
+    }
+}
+
+impl<T: MerkleTreeKey> MerkleTreeKey for Salted<T> {
+    type Key = T::Key;
+
+    fn key(&self) -> Self::Key {
+        self.data.key()
+    }
+}
+
+/// Salts every leaf with a salt derived from a secret seed.
+///
+/// The salt of the leaf at position `i` is `H(seed || i)` with the digest, so the same seed
+/// reproduces the same salts without storing them.
+///
+/// # Arguments
+///
+/// * `input`: The user data to salt.
+/// * `seed`: A secret seed.
+pub fn salt_with_seed<T, D>(input: &[T], seed: &[u8]) -> Vec<Salted<T>>
+where
+    T: Clone,
+    D: Digest,
+{
+    input
+        .iter()
+        .enumerate()
+        .map(|(index, data)| {
+            let mut hasher = D::new();
+            hasher.update(seed);
+            hasher.update((index as u64).to_be_bytes());
+            Salted::new(data.clone(), hasher.finalize().to_vec())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "std")]
+    use crate::IndexedMerkleTree;
+    use crate::{util, verify_proof, MerkleTree, Sha256};
+    use alloc::vec;
+
+    #[test]
+    fn it_can_verify_proof_with_salted_leaves() {
+        let user_data = util::generate_random_user_data(6);
+        let salted = salt_with_seed::<_, Sha256>(&user_data, b"seed");
+
+        let plain: MerkleTree<_> =
+            MerkleTree::build("ProofOfReserve_Leaf", "ProofOfReserve_Branch", &user_data);
+        let tree: MerkleTree<_> =
+            MerkleTree::build("ProofOfReserve_Leaf", "ProofOfReserve_Branch", &salted);
+        let root = tree.root().unwrap();
+        assert_ne!(tree.root(), plain.root());
+
+        let (node, path) = tree.proof_by_index(2).unwrap();
+        let leaf = node.user_data.as_ref().unwrap();
+        assert_eq!(leaf.salt, salted[2].salt);
+        assert_eq!(leaf.salt.len(), 32);
+        assert!(verify_proof(
+            "ProofOfReserve_Leaf",
+            "ProofOfReserve_Branch",
+            &root,
+            leaf,
+            &path
+        ));
+
+        let unsalted = Salted::new(leaf.data.clone(), vec![]);
+        assert!(!verify_proof(
+            "ProofOfReserve_Leaf",
+            "ProofOfReserve_Branch",
+            &root,
+            &unsalted,
+            &path
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn it_can_generate_proof_for_key_with_salted_leaves() {
+        let user_data = util::generate_random_user_data(6);
+        let salted = salt_with_seed::<_, Sha256>(&user_data, b"seed");
+
+        let tree: IndexedMerkleTree<_> =
+            IndexedMerkleTree::build("ProofOfReserve_Leaf", "ProofOfReserve_Branch", &salted)
+                .unwrap();
+
+        let (node, _path) = tree.proof_for_key(&4).unwrap();
+        let leaf = node.user_data.as_ref().unwrap();
+        assert_eq!(leaf.data.id, 4);
+        assert_eq!(leaf.salt, salted[3].salt);
+    }
+}
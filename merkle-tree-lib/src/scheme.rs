@@ -0,0 +1,269 @@
+use alloc::string::String;
+use core::fmt;
+use core::marker::PhantomData;
+use core::ops::Deref;
+use sha2::Digest;
+
+use crate::{
+    verify_proof_with_options, MerkleError, MerkleNode, MerkleTree, MerkleTreeData, Sha256,
+    TraversePath, TreeOptions,
+};
+
+/// A tag scheme carried by a marker type, so that trees, roots and proofs of different
+/// schemes are different types.
+///
+/// A proof of a `TaggedTree<T, ProofOfReserveScheme>` can only be verified against a root of
+/// the same scheme, so mixing up the tags of two applications is a compile error rather than
+/// a proof that silently fails or, worse, a proof accepted under the wrong scheme.
+pub trait TagScheme {
+    /// The tag used for hashing leaf nodes.
+    const TAG_LEAF: &'static str;
+    /// The tag used for hashing branch nodes.
+    const TAG_BRANCH: &'static str;
+
+    /// Returns the hashing configuration of the scheme.
+    ///
+    /// Defaults to `TreeOptions::tagged` with the tags of the scheme. Override it to change
+    /// other options, such as the padding.
+    fn options() -> TreeOptions {
+        TreeOptions::tagged(Self::TAG_LEAF, Self::TAG_BRANCH)
+    }
+}
+
+/// The tag scheme of the proof of reserve app.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct ProofOfReserveScheme;
+
+impl TagScheme for ProofOfReserveScheme {
+    const TAG_LEAF: &'static str = "ProofOfReserve_Leaf";
+    const TAG_BRANCH: &'static str = "ProofOfReserve_Branch";
+}
+
+/// A `MerkleTree` built under the tag scheme `Sc`.
+///
+/// Dereferences to the underlying `MerkleTree`, so all of its methods are available, although
+/// those return untagged roots and proofs.
+pub struct TaggedTree<T, Sc, D: Digest = Sha256> {
+    tree: MerkleTree<T, D>,
+    scheme: PhantomData<fn() -> Sc>,
+}
+
+/// A hex encoded root of a tree built under the tag scheme `Sc`.
+pub struct TaggedRoot<Sc> {
+    hex: String,
+    scheme: PhantomData<fn() -> Sc>,
+}
+
+/// A proof of a leaf of a tree built under the tag scheme `Sc`.
+pub struct TaggedProof<Sc, D = Sha256> {
+    path: TraversePath<D>,
+    scheme: PhantomData<fn() -> Sc>,
+}
+
+/// A leaf node with its proof.
+type LeafProof<'a, T, Sc, D> = (&'a MerkleNode<T, D>, TaggedProof<Sc, D>);
+
+impl<T, Sc, D> TaggedTree<T, Sc, D>
+where
+    T: Clone + fmt::Debug + MerkleTreeData + Default,
+    Sc: TagScheme,
+    D: Digest,
+{
+    /// Builds a Merkle Tree from the given user data under the tag scheme.
+    ///
+    /// # Arguments
+    ///
+    /// * `input`: The user data to commit to, in leaf order.
+    pub fn build(input: &[T]) -> Self {
+        TaggedTree {
+            tree: MerkleTree::build_with_options(Sc::options(), input),
+            scheme: PhantomData,
+        }
+    }
+
+    /// Generates the proof of the first leaf whose user data matches the predicate.
+    ///
+    /// # Arguments
+    ///
+    /// * `predicate`: A function that returns `true` for the user data to prove.
+    ///
+    /// # Returns
+    ///
+    /// A tuple of `(&MerkleNode, TaggedProof)`, `MerkleError::EmptyTree` if the tree has no
+    /// leaves and `MerkleError::LeafNotFound` otherwise.
+    pub fn search_with_proof<F>(&self, predicate: F) -> Result<LeafProof<'_, T, Sc, D>, MerkleError>
+    where
+        F: Fn(&T) -> bool,
+    {
+        let (node, path) = self.tree.search_with_path(predicate)?;
+        Ok((node, TaggedProof::new(path)))
+    }
+}
+
+impl<T, Sc, D: Digest> TaggedTree<T, Sc, D> {
+    /// Returns the root of the tree, or `MerkleError::EmptyTree` if the tree has no leaves.
+    pub fn tagged_root(&self) -> Result<TaggedRoot<Sc>, MerkleError> {
+        self.tree.root().map(TaggedRoot::new)
+    }
+
+    /// Generates the proof of the leaf at the given index.
+    ///
+    /// # Arguments
+    ///
+    /// * `index`: The index of the leaf, in leaf order.
+    ///
+    /// # Returns
+    ///
+    /// A tuple of `(&MerkleNode, TaggedProof)`, or `MerkleError::LeafNotFound` if the index
+    /// is out of range.
+    pub fn tagged_proof(&self, index: usize) -> Result<LeafProof<'_, T, Sc, D>, MerkleError> {
+        let (node, path) = self.tree.proof_by_index(index)?;
+        Ok((node, TaggedProof::new(path)))
+    }
+
+    /// Returns the underlying untagged tree.
+    pub fn into_inner(self) -> MerkleTree<T, D> {
+        self.tree
+    }
+}
+
+impl<T, Sc, D: Digest> Deref for TaggedTree<T, Sc, D> {
+    type Target = MerkleTree<T, D>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.tree
+    }
+}
+
+impl<Sc> TaggedRoot<Sc> {
+    /// Declares a hex encoded root to be a root of the tag scheme, such as one published by
+    /// the application that uses the scheme.
+    ///
+    /// # Arguments
+    ///
+    /// * `hex`: The hex encoded root.
+    pub fn new(hex: impl Into<String>) -> Self {
+        TaggedRoot {
+            hex: hex.into(),
+            scheme: PhantomData,
+        }
+    }
+
+    /// Returns the hex encoded root.
+    pub fn as_str(&self) -> &str {
+        &self.hex
+    }
+}
+
+impl<Sc> Clone for TaggedRoot<Sc> {
+    fn clone(&self) -> Self {
+        TaggedRoot::new(self.hex.clone())
+    }
+}
+
+impl<Sc> fmt::Debug for TaggedRoot<Sc> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("TaggedRoot").field(&self.hex).finish()
+    }
+}
+
+impl<Sc> fmt::Display for TaggedRoot<Sc> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.hex)
+    }
+}
+
+impl<Sc> PartialEq for TaggedRoot<Sc> {
+    fn eq(&self, other: &Self) -> bool {
+        crate::root::roots_match(&self.hex, &other.hex)
+    }
+}
+
+impl<Sc, D> TaggedProof<Sc, D> {
+    /// Declares a path to be a proof of the tag scheme, such as one received from the
+    /// application that uses the scheme.
+    ///
+    /// # Arguments
+    ///
+    /// * `path`: The path from the root down to the leaf.
+    pub fn new(path: TraversePath<D>) -> Self {
+        TaggedProof {
+            path,
+            scheme: PhantomData,
+        }
+    }
+
+    /// Returns the path from the root down to the leaf.
+    pub fn path(&self) -> &TraversePath<D> {
+        &self.path
+    }
+}
+
+impl<Sc: TagScheme, D: Digest> TaggedProof<Sc, D> {
+    /// Verifies that a leaf is included in the tree with the given root.
+    ///
+    /// # Arguments
+    ///
+    /// * `root`: The root of a tree of the same tag scheme.
+    /// * `leaf_data`: The user data that is claimed to be included in the tree.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the recomputed root matches `root`, `false` otherwise.
+    pub fn verify<T: MerkleTreeData>(&self, root: &TaggedRoot<Sc>, leaf_data: &T) -> bool {
+        verify_proof_with_options(&Sc::options(), &root.hex, leaf_data, &self.path)
+    }
+}
+
+impl<Sc, D> Clone for TaggedProof<Sc, D> {
+    fn clone(&self) -> Self {
+        TaggedProof::new(self.path.clone())
+    }
+}
+
+impl<Sc, D> fmt::Debug for TaggedProof<Sc, D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("TaggedProof").field(&self.path).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util;
+
+    #[test]
+    fn it_can_verify_proofs_of_a_tag_scheme() {
+        struct OtherScheme;
+
+        impl TagScheme for OtherScheme {
+            const TAG_LEAF: &'static str = "Other_Leaf";
+            const TAG_BRANCH: &'static str = "Other_Branch";
+        }
+
+        let user_data = util::generate_random_user_data(5);
+        let tree: TaggedTree<_, ProofOfReserveScheme> = TaggedTree::build(&user_data);
+        let untagged: MerkleTree<_> =
+            MerkleTree::build("ProofOfReserve_Leaf", "ProofOfReserve_Branch", &user_data);
+        let root = tree.tagged_root().unwrap();
+        assert_eq!(root.as_str(), untagged.root().unwrap());
+        assert_eq!(tree.leaf_count(), 5);
+
+        let (node, proof) = tree.tagged_proof(2).unwrap();
+        let leaf = node.user_data.as_ref().unwrap();
+        assert!(proof.verify(&root, leaf));
+        assert!(!proof.verify(&root, &user_data[3]));
+
+        let (_, found) = tree.search_with_proof(|user| user.id == 3).unwrap();
+        assert_eq!(found.path().to_vec(), proof.path().to_vec());
+
+        // A proof of another scheme only verifies against roots of that scheme.
+        let other: TaggedTree<_, OtherScheme> = TaggedTree::build(&user_data);
+        let other_root = other.tagged_root().unwrap();
+        assert_ne!(other_root.as_str(), root.as_str());
+        let (_, other_proof) = other.tagged_proof(2).unwrap();
+        assert!(other_proof.verify(&other_root, leaf));
+        let forged: TaggedRoot<OtherScheme> = TaggedRoot::new(root.as_str());
+        assert!(!other_proof.verify(&forged, leaf));
+    }
+}
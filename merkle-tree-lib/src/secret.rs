@@ -0,0 +1,134 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use sha2::Digest;
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::util::UserData;
+use crate::{
+    Keyed, LeafEnvelope, MerkleNode, MerkleSumData, MerkleTree, MerkleTreeData, MerkleTreeKey,
+    Salted,
+};
+
+impl<B: Zeroize> Zeroize for UserData<B> {
+    fn zeroize(&mut self) {
+        self.id.zeroize();
+        self.balance.zeroize();
+    }
+}
+
+impl<T: Zeroize> Zeroize for Salted<T> {
+    fn zeroize(&mut self) {
+        self.data.zeroize();
+        self.salt.zeroize();
+    }
+}
+
+impl<T: Zeroize> Zeroize for Keyed<T> {
+    fn zeroize(&mut self) {
+        self.data.zeroize();
+        self.mac.zeroize();
+    }
+}
+
+impl<T: Zeroize> Zeroize for LeafEnvelope<T> {
+    fn zeroize(&mut self) {
+        self.schema_version.zeroize();
+        self.timestamp.zeroize();
+        self.data.zeroize();
+    }
+}
+
+/// Wipes the hash and the user data of the node and unlinks it.
+impl<T: Zeroize, D: Digest> Zeroize for MerkleNode<T, D> {
+    fn zeroize(&mut self) {
+        self.hash.as_mut_slice().zeroize();
+        self.left.zeroize();
+        self.right.zeroize();
+        self.user_data.zeroize();
+    }
+}
+
+/// Wipes all nodes of the tree, leaving an empty tree. Wrap the tree in `zeroize::Zeroizing`
+/// to wipe it when it is dropped.
+///
+/// Earlier versions of the tree are invalidated.
+impl<T: Zeroize, D: Digest> Zeroize for MerkleTree<T, D> {
+    fn zeroize(&mut self) {
+        self.nodes.zeroize();
+        self.root = None;
+        self.leaf_count = 0;
+        self.empty_leaf = None;
+        self.generation += 1;
+        self.pinned = 0;
+    }
+}
+
+/// Lets leaves wipe their user data when they are dropped, as in
+/// `MerkleTree<Zeroizing<UserData<u64>>>`.
+impl<T: MerkleTreeData + Zeroize> MerkleTreeData for Zeroizing<T> {
+    fn serialize(&self) -> Vec<u8> {
+        (**self).serialize()
+    }
+
+    fn hash_into(&self, hasher: &mut impl Digest) {
+        (**self).hash_into(hasher);
+    }
+
+    fn mermaid_node_label(&self) -> String {
+        (**self).mermaid_node_label()
+    }
+
+    fn dot_node_label(&self) -> String {
+        (**self).dot_node_label()
+    }
+}
+
+impl<B, T: MerkleSumData<B> + Zeroize> MerkleSumData<B> for Zeroizing<T> {
+    fn balance(&self) -> B {
+        (**self).balance()
+    }
+}
+
+impl<T: MerkleTreeKey + Zeroize> MerkleTreeKey for Zeroizing<T> {
+    type Key = T::Key;
+
+    fn key(&self) -> Self::Key {
+        (**self).key()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{util, verify_proof, Decimal, MerkleError};
+
+    #[test]
+    fn it_can_zeroize_leaf_data() {
+        use zeroize::{Zeroize, Zeroizing};
+
+        let user_data = util::generate_random_user_data(5);
+        let plain: MerkleTree<_> = MerkleTree::build("Leaf", "Branch", &user_data);
+        let wrapped: Vec<_> = user_data.iter().cloned().map(Zeroizing::new).collect();
+        let mut tree: MerkleTree<_> = MerkleTree::build("Leaf", "Branch", &wrapped);
+        let root = tree.root().unwrap();
+        assert_eq!(tree.root(), plain.root());
+        let (node, path) = tree.proof_by_index(2).unwrap();
+        let leaf = node.user_data.as_ref().unwrap();
+        assert!(verify_proof("Leaf", "Branch", &root, leaf, &path));
+
+        tree.zeroize();
+        assert_eq!(tree.root(), Err(MerkleError::EmptyTree));
+        assert_eq!(tree.leaf_count(), 0);
+
+        let mut user = Salted::new(
+            util::UserData {
+                id: 1,
+                balance: "12.34".parse::<Decimal<2>>().unwrap(),
+            },
+            vec![1, 2, 3],
+        );
+        user.zeroize();
+        assert_eq!((user.data.id, user.data.balance.units()), (0, 0));
+        assert!(user.salt.is_empty());
+    }
+}
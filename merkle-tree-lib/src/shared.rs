@@ -0,0 +1,196 @@
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt;
+use core::ops::Deref;
+use sha2::Digest;
+
+use crate::{
+    KaryMerkleTree, MerkleError, MerkleNode, MerkleTree, MerkleTreeData, NodeStore, Sha256,
+    TraversePath, TreeOptions,
+};
+
+/// A leaf node with its proof, detached from the tree.
+type OwnedProof<T, D> = (MerkleNode<T, D>, TraversePath<D>);
+
+/// A cheaply cloneable handle to a `MerkleTree` shared between threads, such as the workers
+/// of a web server.
+///
+/// A `MerkleTree` over `Send + Sync` user data is `Send + Sync` itself, which is checked at
+/// compile time, so clones of the handle can be moved to other threads. Dereferences to the
+/// underlying `MerkleTree`, so all of its read methods are available. The `owned_` methods
+/// return proofs that do not borrow the tree, so they can outlive the handle they were
+/// generated from, such as in a response built after the handle was dropped.
+pub struct SharedMerkleTree<T, D: Digest = Sha256, S = Vec<MerkleNode<T, D>>> {
+    tree: Arc<MerkleTree<T, D, S>>,
+}
+
+impl<T, D: Digest, S> SharedMerkleTree<T, D, S> {
+    /// Shares a tree.
+    ///
+    /// # Arguments
+    ///
+    /// * `tree`: The tree to share.
+    pub fn new(tree: MerkleTree<T, D, S>) -> Self {
+        SharedMerkleTree {
+            tree: Arc::new(tree),
+        }
+    }
+
+    /// Returns the tree, if this is the only handle to it, or the handle otherwise.
+    pub fn try_into_inner(self) -> Result<MerkleTree<T, D, S>, Self> {
+        Arc::try_unwrap(self.tree).map_err(|tree| SharedMerkleTree { tree })
+    }
+}
+
+impl<T, D: Digest, S: NodeStore<T, D>> SharedMerkleTree<T, D, S>
+where
+    MerkleNode<T, D>: Clone,
+{
+    /// Generates the proof for the leaf at the given index, cloning the leaf node.
+    ///
+    /// # Arguments
+    ///
+    /// * `index`: The position of the leaf in the input the tree was built from.
+    ///
+    /// # Returns
+    ///
+    /// A tuple of `(MerkleNode, TraversePath)`, or `MerkleError::LeafNotFound` if the index
+    /// is out of range.
+    pub fn owned_proof_by_index(&self, index: usize) -> Result<OwnedProof<T, D>, MerkleError> {
+        let (node, path) = self.tree.proof_by_index(index)?;
+        Ok((node.clone(), path))
+    }
+}
+
+impl<T, D> SharedMerkleTree<T, D>
+where
+    T: Clone + fmt::Debug + MerkleTreeData + Default,
+    D: Digest,
+    MerkleNode<T, D>: Clone,
+{
+    /// Builds a Merkle Tree from the given user data and shares it.
+    ///
+    /// # Arguments
+    ///
+    /// * `options`: The hashing configuration of the tree.
+    /// * `input`: The user data to commit to.
+    pub fn build_with_options(options: TreeOptions, input: &[T]) -> Self {
+        Self::new(MerkleTree::build_with_options(options, input))
+    }
+
+    /// Searches for a user with the given predicate, cloning the leaf node.
+    ///
+    /// # Arguments
+    ///
+    /// * `predicate`: A function that returns `true` for the user data to prove.
+    ///
+    /// # Returns
+    ///
+    /// A tuple of `(MerkleNode, TraversePath)` if a matching user is found,
+    /// `MerkleError::EmptyTree` if the tree has no leaves and `MerkleError::LeafNotFound`
+    /// otherwise.
+    pub fn owned_search_with_path<F>(&self, predicate: F) -> Result<OwnedProof<T, D>, MerkleError>
+    where
+        F: Fn(&T) -> bool,
+    {
+        let (node, path) = self.tree.search_with_path(predicate)?;
+        Ok((node.clone(), path))
+    }
+}
+
+impl<T, D: Digest, S> Clone for SharedMerkleTree<T, D, S> {
+    fn clone(&self) -> Self {
+        SharedMerkleTree {
+            tree: Arc::clone(&self.tree),
+        }
+    }
+}
+
+impl<T, D: Digest, S> Deref for SharedMerkleTree<T, D, S> {
+    type Target = MerkleTree<T, D, S>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.tree
+    }
+}
+
+impl<T, D: Digest, S> From<MerkleTree<T, D, S>> for SharedMerkleTree<T, D, S> {
+    fn from(tree: MerkleTree<T, D, S>) -> Self {
+        Self::new(tree)
+    }
+}
+
+impl<T, D: Digest, S> From<Arc<MerkleTree<T, D, S>>> for SharedMerkleTree<T, D, S> {
+    fn from(tree: Arc<MerkleTree<T, D, S>>) -> Self {
+        SharedMerkleTree { tree }
+    }
+}
+
+impl<T, D: Digest, S: NodeStore<T, D>> fmt::Debug for SharedMerkleTree<T, D, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("SharedMerkleTree").field(&self.tree).finish()
+    }
+}
+
+/// Fails to compile if a tree or a proof over `Send + Sync` user data is not `Send + Sync`
+/// itself, so that sharing trees between threads stays guaranteed as their fields change.
+const _: () = {
+    fn assert_send_sync<X: Send + Sync>() {}
+
+    #[allow(dead_code)]
+    fn assert_trees<T: Send + Sync, D: Digest>() {
+        assert_send_sync::<MerkleNode<T, D>>();
+        assert_send_sync::<MerkleTree<T, D>>();
+        assert_send_sync::<SharedMerkleTree<T, D>>();
+        assert_send_sync::<KaryMerkleTree<T, D>>();
+        assert_send_sync::<TraversePath<D>>();
+        assert_send_sync::<TreeOptions>();
+    }
+
+    #[cfg(feature = "std")]
+    #[allow(dead_code)]
+    fn assert_indexed_tree<T, D>()
+    where
+        T: crate::MerkleTreeKey + Send + Sync,
+        T::Key: Send + Sync,
+        D: Digest,
+    {
+        assert_send_sync::<crate::IndexedMerkleTree<T, D>>();
+    }
+};
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::{util, verify_proof};
+
+    #[test]
+    fn it_can_share_trees_between_threads() {
+        let user_data = util::generate_random_user_data(8);
+        let options = TreeOptions::tagged("Leaf", "Branch");
+        let shared = SharedMerkleTree::<_>::build_with_options(options, &user_data);
+        let root = shared.root().unwrap();
+
+        let handles: Vec<_> = (0..4)
+            .map(|thread| {
+                let shared = shared.clone();
+                std::thread::spawn(move || {
+                    let (node, path) = shared.owned_proof_by_index(thread * 2).unwrap();
+                    (node.user_data.unwrap(), path)
+                })
+            })
+            .collect();
+        for (thread, handle) in handles.into_iter().enumerate() {
+            let (leaf, path) = handle.join().unwrap();
+            assert_eq!(leaf.id, user_data[thread * 2].id);
+            assert!(verify_proof("Leaf", "Branch", &root, &leaf, &path));
+        }
+
+        let (node, _) = shared
+            .owned_search_with_path(|user| user.id == user_data[5].id)
+            .unwrap();
+        assert_eq!(node.user_data.unwrap().id, user_data[5].id);
+        let shared = shared.try_into_inner().map_err(drop).unwrap();
+        assert_eq!(shared.root().unwrap(), root);
+    }
+}
@@ -0,0 +1,66 @@
+use sha2::Digest;
+
+/// Shuffles the leaves in place with a permutation derived from a seed.
+///
+/// Building the tree from the shuffled leaves hides the signup order of the users, while
+/// anyone who is given the seed can reproduce the order. The permutation is a Fisher-Yates
+/// shuffle: for `i` from `len - 1` down to 1, the element at `i` is swapped with the one at
+/// a uniformly drawn `j` in `0..=i`. The `k`-th random number is the first 8 bytes of
+/// `H(seed || k)` as a big-endian `u64`, with `k` as a big-endian `u64` counting from 0, and
+/// numbers that would bias `j` are skipped.
+///
+/// # Arguments
+///
+/// * `items`: The leaves to shuffle.
+/// * `seed`: The seed of the permutation.
+pub fn shuffle_with_seed<T, D: Digest>(items: &mut [T], seed: &[u8]) {
+    let mut counter: u64 = 0;
+    let mut next = || {
+        let mut hasher = D::new();
+        hasher.update(seed);
+        hasher.update(counter.to_be_bytes());
+        counter += 1;
+
+        let hash = hasher.finalize();
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(&hash[..8]);
+        u64::from_be_bytes(bytes)
+    };
+
+    for i in (1..items.len()).rev() {
+        let bound = i as u64 + 1;
+        let limit = u64::MAX - u64::MAX % bound;
+        let j = loop {
+            let random = next();
+            if random < limit {
+                break random % bound;
+            }
+        };
+        items.swap(i, j as usize);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{util, MerkleTree, Sha256};
+    use alloc::vec::Vec;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(b"seed", [10, 8, 6, 7, 2, 5, 9, 1, 3, 4])]
+    #[case(b"other", [9, 5, 8, 7, 2, 10, 3, 6, 1, 4])]
+    fn it_can_shuffle_with_seed(#[case] seed: &[u8], #[case] expected: [u32; 10]) {
+        let mut user_data = util::generate_random_user_data(10);
+
+        shuffle_with_seed::<_, Sha256>(&mut user_data, seed);
+
+        let ids: Vec<u32> = user_data.iter().map(|data| data.id).collect();
+        assert_eq!(ids, expected);
+
+        let tree: MerkleTree<_> =
+            MerkleTree::build("ProofOfReserve_Leaf", "ProofOfReserve_Branch", &user_data);
+        let (node, _path) = tree.proof_by_index(0).unwrap();
+        assert_eq!(node.user_data.as_ref().unwrap().id, expected[0]);
+    }
+}
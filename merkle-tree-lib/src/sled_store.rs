@@ -0,0 +1,473 @@
+use core::marker::PhantomData;
+use core::ops::Index;
+use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use sha2::digest::Output;
+use sha2::Digest;
+use std::sync::OnceLock;
+
+use crate::{MerkleNode, NodeStore, Sha256, TreeMetadata};
+
+/// The number of nodes written to and read from the database as one value.
+const PAGE_SIZE: usize = 4096;
+
+/// The default number of pages kept in memory after being read from the database.
+const DEFAULT_CACHED_PAGES: usize = 1024;
+
+/// Marks a missing child or missing user data in an encoded node.
+const NONE: u64 = u64::MAX;
+
+/// The key of the number of nodes written by the last `flush`. Page keys are 8 bytes long,
+/// so the shorter keys cannot collide with them.
+const LEN_KEY: &[u8] = b"len";
+
+/// The key of the nodes of the last page that was not full at the last `flush`.
+const TAIL_KEY: &[u8] = b"tail";
+
+/// The key of the metadata of the tree saved by the last `flush`.
+const METADATA_KEY: &[u8] = b"metadata";
+
+/// A `NodeStore` backed by a sled database, so trees larger than memory can be built and
+/// queried from disk.
+///
+/// Nodes are grouped into pages of 4096 nodes. Appended nodes are kept in memory until their
+/// page is full, and then written to the database in a single insert. `flush` also writes the
+/// last page that is not full yet and the metadata of the tree, so the tree can be reattached
+/// with `open` and `MerkleTree::from_store` after a restart. User data is encoded as JSON.
+///
+/// Pages are read back on first access and cached. Every read marks its page as used, and once
+/// more pages than the limit are cached the least recently used ones are evicted. Nodes are
+/// borrowed from the cache, so pages are only evicted while the store is borrowed mutably:
+/// on `push` or `MerkleTree::release_cache`, which a workload that only queries the tree calls
+/// to bound the cache.
+///
+/// Database errors cannot be reported through `NodeStore`, so they panic.
+pub struct SledNodeStore<T, D: Digest = Sha256> {
+    tree: sled::Tree,
+    pages: Vec<CachedPage<T, D>>,
+    tail: Vec<MerkleNode<T, D>>,
+    cached_pages: AtomicUsize,
+    max_cached_pages: usize,
+    clock: AtomicU64,
+    digest: PhantomData<fn() -> D>,
+}
+
+/// A page of nodes written to the database, with its nodes once they are read back.
+struct CachedPage<T, D: Digest> {
+    nodes: OnceLock<Vec<MerkleNode<T, D>>>,
+    last_used: AtomicU64,
+}
+
+impl<T, D: Digest> CachedPage<T, D> {
+    fn new() -> Self {
+        CachedPage {
+            nodes: OnceLock::new(),
+            last_used: AtomicU64::new(0),
+        }
+    }
+}
+
+impl<T, D> SledNodeStore<T, D>
+where
+    T: Serialize + DeserializeOwned,
+    D: Digest,
+{
+    /// Creates a store in the given sled tree, removing anything the tree contains.
+    ///
+    /// # Arguments
+    ///
+    /// * `tree`: The sled tree to keep the nodes in, such as `sled::Db::open_tree`.
+    pub fn new(tree: sled::Tree) -> sled::Result<Self> {
+        tree.clear()?;
+        Self::open(tree)
+    }
+
+    /// Opens a store in the given sled tree, keeping the nodes written up to the last
+    /// `flush`. The tree is reattached with `MerkleTree::from_store` and the saved `metadata`.
+    ///
+    /// # Arguments
+    ///
+    /// * `tree`: The sled tree the nodes were kept in, such as `sled::Db::open_tree`.
+    pub fn open(tree: sled::Tree) -> sled::Result<Self> {
+        let len = tree
+            .get(LEN_KEY)?
+            .map_or(0, |bytes| take_u64(&mut &bytes[..]) as usize);
+        let tail = match tree.get(TAIL_KEY)? {
+            Some(bytes) => decode_page(&bytes, len % PAGE_SIZE),
+            None => Vec::new(),
+        };
+
+        let mut store = SledNodeStore {
+            tree,
+            pages: (0..len / PAGE_SIZE).map(|_| CachedPage::new()).collect(),
+            tail,
+            cached_pages: AtomicUsize::new(0),
+            max_cached_pages: DEFAULT_CACHED_PAGES,
+            clock: AtomicU64::new(0),
+            digest: PhantomData,
+        };
+        store.tail.reserve(PAGE_SIZE - store.tail.len());
+        Ok(store)
+    }
+
+    /// Sets the number of pages of 4096 nodes kept in memory after being read.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_cached_pages`: The maximum number of cached pages, 1024 by default.
+    pub fn with_max_cached_pages(mut self, max_cached_pages: usize) -> Self {
+        self.max_cached_pages = max_cached_pages;
+        self
+    }
+
+    /// Writes the last page that is not full yet and the metadata of the tree, and waits
+    /// until all nodes are durable.
+    ///
+    /// The store can be opened again with `open` from then on, until `take_nodes` clears it
+    /// when the tree is rebuilt.
+    ///
+    /// # Arguments
+    ///
+    /// * `metadata`: The metadata of the tree, as returned by `MerkleTree::metadata`.
+    pub fn flush(&self, metadata: TreeMetadata) -> sled::Result<()> {
+        let mut batch = sled::Batch::default();
+        batch.insert(LEN_KEY, &(self.len() as u64).to_be_bytes());
+        batch.insert(TAIL_KEY, encode_page(&self.tail));
+        batch.insert(METADATA_KEY, encode_metadata(&metadata));
+        self.tree.apply_batch(batch)?;
+        self.tree.flush()?;
+        Ok(())
+    }
+
+    /// Returns the metadata of the tree saved by the last `flush`, or `None` if the store was
+    /// never flushed.
+    pub fn metadata(&self) -> sled::Result<Option<TreeMetadata>> {
+        Ok(self
+            .tree
+            .get(METADATA_KEY)?
+            .map(|bytes| decode_metadata(&bytes)))
+    }
+
+    /// Reads a page from the database.
+    ///
+    /// # Arguments
+    ///
+    /// * `page`: The position of the page.
+    fn load_page(&self, page: usize) -> Vec<MerkleNode<T, D>> {
+        let bytes = self
+            .tree
+            .get((page as u64).to_be_bytes())
+            .expect("failed to read a node page")
+            .expect("a written node page is missing");
+        self.cached_pages.fetch_add(1, Ordering::Relaxed);
+        decode_page(&bytes, PAGE_SIZE)
+    }
+}
+
+impl<T, D> Index<usize> for SledNodeStore<T, D>
+where
+    T: Serialize + DeserializeOwned,
+    D: Digest,
+{
+    type Output = MerkleNode<T, D>;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        let page = index / PAGE_SIZE;
+        if page == self.pages.len() {
+            return &self.tail[index % PAGE_SIZE];
+        }
+        let cached = &self.pages[page];
+        let now = self.clock.fetch_add(1, Ordering::Relaxed);
+        cached.last_used.store(now, Ordering::Relaxed);
+        &cached.nodes.get_or_init(|| self.load_page(page))[index % PAGE_SIZE]
+    }
+}
+
+impl<T, D> NodeStore<T, D> for SledNodeStore<T, D>
+where
+    T: Serialize + DeserializeOwned,
+    D: Digest,
+{
+    fn len(&self) -> usize {
+        self.pages.len() * PAGE_SIZE + self.tail.len()
+    }
+
+    fn push(&mut self, node: MerkleNode<T, D>) {
+        self.release_cache();
+
+        self.tail.push(node);
+        if self.tail.len() == PAGE_SIZE {
+            let key = (self.pages.len() as u64).to_be_bytes();
+            self.tree
+                .insert(key, encode_page(&self.tail))
+                .expect("failed to write a node page");
+            self.tail.clear();
+            self.pages.push(CachedPage::new());
+        }
+    }
+
+    /// Only the nodes of the last page, which is not written to the database yet, can be
+    /// overwritten.
+    fn get_mut(&mut self, index: usize) -> Option<&mut MerkleNode<T, D>> {
+        let start = self.pages.len() * PAGE_SIZE;
+        self.tail.get_mut(index.checked_sub(start)?)
+    }
+
+    fn take_nodes(&mut self) -> Vec<MerkleNode<T, D>> {
+        let mut nodes = Vec::with_capacity(self.len());
+        for page in 0..self.pages.len() {
+            nodes.extend(match self.pages[page].nodes.take() {
+                Some(nodes) => nodes,
+                None => self.load_page(page),
+            });
+        }
+        nodes.append(&mut self.tail);
+
+        self.pages.clear();
+        *self.cached_pages.get_mut() = 0;
+        self.tree.clear().expect("failed to clear the node pages");
+        nodes
+    }
+
+    /// Evicts the least recently used pages until no more than the limit are cached. No node
+    /// can be borrowed while the store is mutably borrowed, so the pages can be dropped here.
+    fn release_cache(&mut self) {
+        let excess = self
+            .cached_pages
+            .get_mut()
+            .saturating_sub(self.max_cached_pages);
+        if excess == 0 {
+            return;
+        }
+
+        let mut cached: Vec<(u64, usize)> = (self.pages.iter().enumerate())
+            .filter(|(_, page)| page.nodes.get().is_some())
+            .map(|(i, page)| (page.last_used.load(Ordering::Relaxed), i))
+            .collect();
+        cached.sort_unstable();
+        for (_, page) in cached.into_iter().take(excess) {
+            self.pages[page].nodes.take();
+        }
+        *self.cached_pages.get_mut() -= excess;
+    }
+}
+
+/// Encodes a page of nodes as the hash, the children and the JSON encoded user data of each
+/// node, with lengths and missing values as big-endian `u64`s.
+///
+/// # Arguments
+///
+/// * `nodes`: The nodes of the page.
+fn encode_page<T: Serialize, D: Digest>(nodes: &[MerkleNode<T, D>]) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    for node in nodes {
+        bytes.extend_from_slice(&node.hash);
+        for child in [node.left, node.right] {
+            bytes.extend_from_slice(&encode_position(child));
+        }
+        match node.user_data.as_ref() {
+            Some(user_data) => {
+                let json = serde_json::to_vec(user_data).expect("failed to encode user data");
+                bytes.extend_from_slice(&(json.len() as u64).to_be_bytes());
+                bytes.extend_from_slice(&json);
+            }
+            None => bytes.extend_from_slice(&NONE.to_be_bytes()),
+        }
+    }
+    bytes
+}
+
+/// Decodes a page of nodes encoded by `encode_page`.
+///
+/// # Arguments
+///
+/// * `bytes`: The encoded page.
+/// * `count`: The number of nodes in the page.
+fn decode_page<T: DeserializeOwned, D: Digest>(
+    mut bytes: &[u8],
+    count: usize,
+) -> Vec<MerkleNode<T, D>> {
+    let mut nodes = Vec::with_capacity(count);
+    for _ in 0..count {
+        let hash = Output::<D>::clone_from_slice(take(&mut bytes, <D as Digest>::output_size()));
+        let left = decode_position(take_u64(&mut bytes));
+        let right = decode_position(take_u64(&mut bytes));
+        let user_data = match take_u64(&mut bytes) {
+            NONE => None,
+            len => Some(
+                serde_json::from_slice(take(&mut bytes, len as usize))
+                    .expect("failed to decode user data"),
+            ),
+        };
+        nodes.push(MerkleNode {
+            hash,
+            left,
+            right,
+            user_data,
+        });
+    }
+    nodes
+}
+
+/// Encodes the metadata of a tree as its root, leaf count and empty leaf, each as a
+/// big-endian `u64`.
+///
+/// # Arguments
+///
+/// * `metadata`: The metadata of the tree.
+fn encode_metadata(metadata: &TreeMetadata) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(24);
+    bytes.extend_from_slice(&encode_position(metadata.root));
+    bytes.extend_from_slice(&(metadata.leaf_count as u64).to_be_bytes());
+    bytes.extend_from_slice(&encode_position(metadata.empty_leaf));
+    bytes
+}
+
+/// Decodes the metadata of a tree encoded by `encode_metadata`.
+///
+/// # Arguments
+///
+/// * `bytes`: The encoded metadata.
+fn decode_metadata(mut bytes: &[u8]) -> TreeMetadata {
+    TreeMetadata {
+        root: decode_position(take_u64(&mut bytes)),
+        leaf_count: take_u64(&mut bytes) as usize,
+        empty_leaf: decode_position(take_u64(&mut bytes)),
+    }
+}
+
+/// Encodes the position of a node, or `NONE` for a missing one, as a big-endian `u64`.
+fn encode_position(position: Option<usize>) -> [u8; 8] {
+    position
+        .map_or(NONE, |position| position as u64)
+        .to_be_bytes()
+}
+
+/// Decodes a position encoded by `encode_position`.
+fn decode_position(value: u64) -> Option<usize> {
+    (value != NONE).then_some(value as usize)
+}
+
+/// Splits the given number of bytes off the front of an encoded page.
+fn take<'a>(bytes: &mut &'a [u8], len: usize) -> &'a [u8] {
+    let (head, tail) = bytes.split_at(len);
+    *bytes = tail;
+    head
+}
+
+/// Splits a big-endian `u64` off the front of an encoded page.
+fn take_u64(bytes: &mut &[u8]) -> u64 {
+    u64::from_be_bytes(take(bytes, 8).try_into().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{util, verify_proof_with_options, MerkleTree, TreeOptions};
+
+    #[test]
+    fn it_can_keep_nodes_in_sled() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let store = SledNodeStore::<_, Sha256>::new(db.open_tree("nodes").unwrap())
+            .unwrap()
+            .with_max_cached_pages(8);
+
+        let user_data = util::generate_random_user_data(10000);
+        let options = TreeOptions::tagged("ProofOfReserve_Leaf", "ProofOfReserve_Branch");
+        let expected: MerkleTree<_> = MerkleTree::build_with_options(options.clone(), &user_data);
+
+        let mut tree = MerkleTree::with_store(options.clone(), store).unwrap();
+        for item in &user_data {
+            tree.push(item.clone());
+        }
+        tree.store().flush(tree.metadata()).unwrap();
+        assert_eq!(tree.root(), expected.root());
+
+        let root = tree.root().unwrap();
+        for index in [0, 4095, 4096, 9999] {
+            let (_node, path) = tree.proof_by_index(index).unwrap();
+            assert!(verify_proof_with_options(
+                &options,
+                &root,
+                &user_data[index],
+                &path
+            ));
+        }
+
+        tree.compact();
+        assert_eq!(tree.root(), expected.root());
+        assert_eq!(tree.into_leaves().count(), 10000);
+    }
+
+    #[test]
+    fn it_can_reopen_sled_store() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let store = SledNodeStore::<_, Sha256>::new(db.open_tree("nodes").unwrap()).unwrap();
+
+        let user_data = util::generate_random_user_data(5000);
+        let options = TreeOptions::tagged("ProofOfReserve_Leaf", "ProofOfReserve_Branch");
+        let mut tree = MerkleTree::with_store(options.clone(), store).unwrap();
+        for item in &user_data[..4500] {
+            tree.push(item.clone());
+        }
+        tree.store().flush(tree.metadata()).unwrap();
+        let metadata = tree.metadata();
+        drop(tree);
+
+        let store =
+            SledNodeStore::<util::UserData, Sha256>::open(db.open_tree("nodes").unwrap()).unwrap();
+        assert_eq!(store.metadata().unwrap(), Some(metadata));
+        let mut tree = MerkleTree::from_store(options.clone(), store, metadata).unwrap();
+        let expected: MerkleTree<_> =
+            MerkleTree::build_with_options(options.clone(), &user_data[..4500]);
+        assert_eq!(tree.root(), expected.root());
+
+        for item in &user_data[4500..] {
+            tree.push(item.clone());
+        }
+        let expected: MerkleTree<_> = MerkleTree::build_with_options(options.clone(), &user_data);
+        assert_eq!(tree.root(), expected.root());
+        let (node, _path) = tree.proof_by_index(4499).unwrap();
+        assert_eq!(node.user_data.as_ref().unwrap().id, user_data[4499].id);
+
+        let empty =
+            SledNodeStore::<util::UserData, Sha256>::open(db.open_tree("empty").unwrap()).unwrap();
+        assert!(empty.is_empty());
+        assert_eq!(empty.metadata().unwrap(), None);
+    }
+
+    #[test]
+    fn it_evicts_least_recently_used_pages() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let store = SledNodeStore::<_, Sha256>::new(db.open_tree("nodes").unwrap())
+            .unwrap()
+            .with_max_cached_pages(2);
+
+        let user_data = util::generate_random_user_data(5 * PAGE_SIZE);
+        let options = TreeOptions::tagged("ProofOfReserve_Leaf", "ProofOfReserve_Branch");
+        let mut tree = MerkleTree::with_store(options, store).unwrap();
+        for item in &user_data {
+            tree.push(item.clone());
+        }
+        tree.release_cache();
+
+        for index in (0..user_data.len()).step_by(PAGE_SIZE) {
+            tree.proof_by_index(index).unwrap();
+        }
+        let cached = |tree: &MerkleTree<_, Sha256, SledNodeStore<util::UserData>>| {
+            tree.store().cached_pages.load(Ordering::Relaxed)
+        };
+        assert!(cached(&tree) > 2);
+
+        // Reading the first leaf again keeps its page in the cache.
+        tree.leaf(0).unwrap();
+        tree.release_cache();
+        assert_eq!(cached(&tree), 2);
+        assert!(tree.store().pages[0].nodes.get().is_some());
+        assert_eq!(
+            tree.leaf(0).unwrap().user_data.as_ref().unwrap().id,
+            user_data[0].id
+        );
+    }
+}
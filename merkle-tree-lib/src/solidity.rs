@@ -0,0 +1,221 @@
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::Write;
+use sha2::Digest;
+
+use crate::{HashMode, MerkleError, MerkleTree, MerkleTreeData, NodeStore, TagStyle, TreeOptions};
+
+/// The hex encoded SHA-256 digest of the empty string.
+const SHA256_EMPTY: &str = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+
+/// The hex encoded Keccak-256 digest of the empty string.
+const KECCAK256_EMPTY: &str = "c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470";
+
+impl<T, D, S> MerkleTree<T, D, S>
+where
+    T: MerkleTreeData,
+    D: Digest,
+    S: NodeStore<T, D>,
+{
+    /// Generates the source of a Solidity contract that verifies proofs of this tree against
+    /// its current root. See `solidity_verifier`.
+    ///
+    /// # Arguments
+    ///
+    /// * `contract_name`: The name of the contract, which must be a valid Solidity identifier.
+    ///
+    /// # Returns
+    ///
+    /// The source of the contract, `MerkleError::EmptyTree` if the tree has no root or
+    /// `MerkleError::UnsupportedDigest` if the digest has no Solidity builtin.
+    pub fn to_solidity_verifier(&self, contract_name: &str) -> Result<String, MerkleError> {
+        solidity_verifier::<D>(&self.options, &self.root()?, contract_name)
+    }
+}
+
+/// Generates the source of a Solidity contract that verifies proofs against a root.
+///
+/// The hashing of leaves and branches is derived from `options`, so the contract hashes
+/// exactly as `verify_proof_with_options` does. Only the `Sha256` and `Keccak256` digests are
+/// supported, since they are the ones Solidity provides as `sha256` and `keccak256`.
+///
+/// The contract has a `verify(bytes leaf, bytes32[] siblings, uint8[] directions)` function,
+/// which takes the serialized leaf data from `MerkleTreeData::serialize` and the steps of
+/// `TraversePath::to_vec` from the root down.
+///
+/// # Arguments
+///
+/// * `options`: The options the tree was built with.
+/// * `root`: The hex encoded Merkle root the contract verifies against.
+/// * `contract_name`: The name of the contract, which must be a valid Solidity identifier.
+///
+/// # Returns
+///
+/// The source of the contract, or `MerkleError::UnsupportedDigest` if the digest has no
+/// Solidity builtin.
+pub fn solidity_verifier<D: Digest>(
+    options: &TreeOptions,
+    root: &str,
+    contract_name: &str,
+) -> Result<String, MerkleError> {
+    let hash_function = match hex::encode(D::digest([])).as_str() {
+        SHA256_EMPTY => "sha256",
+        KECCAK256_EMPTY => "keccak256",
+        _ => return Err(MerkleError::UnsupportedDigest),
+    };
+
+    let mut output = String::from("// SPDX-License-Identifier: MIT\npragma solidity ^0.8.0;\n\n");
+    let _ = writeln!(output, "contract {contract_name} {{");
+    let _ = writeln!(output, "    bytes32 public constant ROOT = 0x{root};");
+    if options.hash_mode == HashMode::Tagged {
+        write_tag_constant::<D>(&mut output, options, "TAG_LEAF", &options.tag_leaf);
+        write_tag_constant::<D>(&mut output, options, "TAG_BRANCH", &options.tag_branch);
+    }
+
+    let hash_leaf = hash_expression(options, hash_function, "TAG_LEAF", "0x00", "data");
+    let _ = write!(
+        output,
+        "
+    function hashLeaf(bytes memory data) internal pure returns (bytes32) {{
+        return {hash_leaf};
+    }}
+
+    function hashBranch(bytes32 left, bytes32 right) internal pure returns (bytes32) {{
+"
+    );
+    if options.hash_mode == HashMode::SortedPair {
+        output.push_str("        if (right < left) (left, right) = (right, left);\n");
+    }
+    let hash_branch = hash_expression(options, hash_function, "TAG_BRANCH", "0x01", "left, right");
+    let _ = writeln!(output, "        return {hash_branch};\n    }}");
+
+    output.push_str(
+        "
+    /// Verifies a proof with the steps from the root down, 0 for left and 1 for right.
+    function verify(bytes calldata leaf, bytes32[] calldata siblings, uint8[] calldata directions)
+        external
+        pure
+        returns (bool)
+    {
+        if (siblings.length != directions.length) return false;
+        bytes32 hash = hashLeaf(leaf);
+        for (uint256 i = siblings.length; i > 0; i--) {
+            if (directions[i - 1] == 0) hash = hashBranch(hash, siblings[i - 1]);
+            else if (directions[i - 1] == 1) hash = hashBranch(siblings[i - 1], hash);
+            else return false;
+        }
+        return hash == ROOT;
+    }
+}
+",
+    );
+    Ok(output)
+}
+
+/// Declares the constant a tag is hashed with in `HashMode::Tagged`.
+///
+/// # Arguments
+///
+/// * `output`: The contract source to append to.
+/// * `options`: The options the tree was built with.
+/// * `name`: The name of the constant.
+/// * `tag`: The tag string.
+fn write_tag_constant<D: Digest>(
+    output: &mut String,
+    options: &TreeOptions,
+    name: &str,
+    tag: &str,
+) {
+    let _ = match options.tag_style {
+        TagStyle::Bip340 => writeln!(
+            output,
+            "    bytes32 internal constant {name} = 0x{};",
+            hex::encode(D::digest(tag.as_bytes()))
+        ),
+        TagStyle::Prefix => writeln!(
+            output,
+            "    bytes internal constant {name} = hex\"{}\";",
+            hex::encode(tag.as_bytes())
+        ),
+    };
+}
+
+/// Builds the Solidity expression hashing the given input like `TreeOptions::hash_leaf` and
+/// `TreeOptions::hash_branch` do.
+///
+/// # Arguments
+///
+/// * `options`: The options the tree was built with.
+/// * `hash_function`: The Solidity builtin of the digest.
+/// * `tag`: The name of the tag constant.
+/// * `prefix`: The domain separation prefix as a Solidity literal.
+/// * `input`: The comma separated input expressions.
+fn hash_expression(
+    options: &TreeOptions,
+    hash_function: &str,
+    tag: &str,
+    prefix: &str,
+    input: &str,
+) -> String {
+    let mut parts: Vec<String> = Vec::new();
+    match (options.hash_mode, options.tag_style) {
+        (HashMode::Tagged, TagStyle::Bip340) => parts.extend([tag.into(), tag.into()]),
+        (HashMode::Tagged, TagStyle::Prefix) => parts.push(tag.into()),
+        (HashMode::SortedPair | HashMode::DoubleHash, _) => {}
+    }
+    if options.domain_separation {
+        parts.push(format!("bytes1({prefix})"));
+    }
+    parts.push(input.into());
+
+    let hash = format!("{hash_function}(abi.encodePacked({}))", parts.join(", "));
+    match options.hash_mode {
+        HashMode::DoubleHash => format!("{hash_function}(abi.encodePacked({hash}))"),
+        HashMode::Tagged | HashMode::SortedPair => hash,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{util, Sha256, Sha512};
+
+    #[test]
+    fn it_can_generate_a_solidity_verifier() {
+        let user_data = util::generate_random_user_data(5);
+        let tree: MerkleTree<_> =
+            MerkleTree::build("ProofOfReserve_Leaf", "ProofOfReserve_Branch", &user_data);
+        let contract = tree.to_solidity_verifier("ReserveVerifier").unwrap();
+
+        assert!(contract.contains("contract ReserveVerifier {"));
+        assert!(contract.contains(&format!(
+            "bytes32 public constant ROOT = 0x{};",
+            tree.root().unwrap()
+        )));
+        assert!(contract.contains(&format!(
+            "bytes32 internal constant TAG_LEAF = 0x{};",
+            hex::encode(Sha256::digest("ProofOfReserve_Leaf"))
+        )));
+        assert!(contract.contains("return sha256(abi.encodePacked(TAG_LEAF, TAG_LEAF, data));"));
+        assert!(contract
+            .contains("return sha256(abi.encodePacked(TAG_BRANCH, TAG_BRANCH, left, right));"));
+
+        let tree: MerkleTree<_> =
+            MerkleTree::build_with_options(TreeOptions::certificate_transparency(), &user_data);
+        let contract = tree.to_solidity_verifier("LogVerifier").unwrap();
+        assert!(contract.contains("bytes internal constant TAG_LEAF = hex\"\";"));
+        assert!(contract.contains("return sha256(abi.encodePacked(TAG_LEAF, bytes1(0x00), data));"));
+
+        let contract =
+            solidity_verifier::<Sha256>(&TreeOptions::sorted_pair(), &"00".repeat(32), "Allowlist")
+                .unwrap();
+        assert!(!contract.contains("TAG_LEAF"));
+        assert!(contract.contains("if (right < left) (left, right) = (right, left);"));
+
+        assert_eq!(
+            solidity_verifier::<Sha512>(&TreeOptions::sorted_pair(), &"00".repeat(64), "Allowlist"),
+            Err(MerkleError::UnsupportedDigest)
+        );
+    }
+}
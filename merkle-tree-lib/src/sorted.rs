@@ -0,0 +1,343 @@
+use alloc::vec::Vec;
+use core::fmt;
+use core::ops::Deref;
+use sha2::Digest;
+
+use crate::{
+    verify_proof_with_options, MerkleError, MerkleNode, MerkleTree, MerkleTreeData, MerkleTreeKey,
+    Sha256, TraversePath, TreeOptions,
+};
+
+/// A Merkle Tree whose leaves are sorted by key, so that the absence of a key can be proven.
+///
+/// Dereferences to the underlying `MerkleTree`, so all of its methods are available.
+pub struct SortedMerkleTree<T: MerkleTreeKey, D: Digest = Sha256> {
+    tree: MerkleTree<T, D>,
+    keys: Vec<T::Key>,
+}
+
+impl<T, D> SortedMerkleTree<T, D>
+where
+    T: Clone + fmt::Debug + MerkleTreeData + MerkleTreeKey + Default,
+    T::Key: Ord,
+    D: Digest,
+{
+    /// Builds the sorted Merkle Tree from the given input data.
+    ///
+    /// # Arguments
+    ///
+    /// * `tag_leaf`: The tag used for hashing leaf nodes.
+    /// * `tag_branch`: The tag used for hashing branch nodes.
+    /// * `input`: The user data to commit to, in any order.
+    pub fn build(tag_leaf: &str, tag_branch: &str, input: &[T]) -> Self {
+        Self::build_with_options(TreeOptions::tagged(tag_leaf, tag_branch), input)
+    }
+
+    /// Builds the sorted Merkle Tree from the given input data with the given options.
+    ///
+    /// # Arguments
+    ///
+    /// * `options`: The hashing configuration of the tree.
+    /// * `input`: The user data to commit to, in any order.
+    pub fn build_with_options(options: TreeOptions, input: &[T]) -> Self {
+        let mut sorted = input.to_vec();
+        sorted.sort_by_cached_key(|data| data.key());
+
+        SortedMerkleTree {
+            keys: sorted.iter().map(|data| data.key()).collect(),
+            tree: MerkleTree::build_from_iter_with_options(options, sorted),
+        }
+    }
+}
+
+impl<T, D> SortedMerkleTree<T, D>
+where
+    T: Clone + MerkleTreeKey,
+    T::Key: Ord,
+    D: Digest,
+{
+    /// Generates the proof for the leaf with the given key in O(log n) time.
+    ///
+    /// # Arguments
+    ///
+    /// * `key`: The key of the leaf.
+    ///
+    /// # Returns
+    ///
+    /// A tuple of `(&MerkleNode, TraversePath)`, or `MerkleError::LeafNotFound` if no leaf
+    /// has the key.
+    pub fn proof_for_key(
+        &self,
+        key: &T::Key,
+    ) -> Result<(&MerkleNode<T, D>, TraversePath<D>), MerkleError> {
+        let index = self
+            .keys
+            .binary_search(key)
+            .map_err(|_| MerkleError::LeafNotFound)?;
+        self.tree.proof_by_index(index)
+    }
+
+    /// Proves that no leaf has the given key.
+    ///
+    /// The proof consists of the leaves right before and after where the key would be, with
+    /// their proofs. One of them is missing if the key would be before the first or after the
+    /// last leaf.
+    ///
+    /// # Arguments
+    ///
+    /// * `key`: The key that is absent from the tree.
+    ///
+    /// # Returns
+    ///
+    /// The `AbsenceProof`, `MerkleError::EmptyTree` if the tree has no leaves to prove the
+    /// absence with, or `MerkleError::DuplicateKey` if a leaf has the key.
+    pub fn prove_absence(&self, key: &T::Key) -> Result<AbsenceProof<T, D>, MerkleError> {
+        if self.keys.is_empty() {
+            return Err(MerkleError::EmptyTree);
+        }
+        let index = match self.keys.binary_search(key) {
+            Ok(_) => return Err(MerkleError::DuplicateKey),
+            Err(index) => index,
+        };
+
+        let neighbor = |index: usize| {
+            let (node, path) = self.tree.proof_by_index(index)?;
+            let user_data = node.user_data.clone().ok_or(MerkleError::LeafNotFound)?;
+            Ok((user_data, path))
+        };
+
+        Ok(AbsenceProof {
+            left: index.checked_sub(1).map(neighbor).transpose()?,
+            right: (index < self.keys.len())
+                .then(|| neighbor(index))
+                .transpose()?,
+        })
+    }
+}
+
+impl<T: MerkleTreeKey, D: Digest> Deref for SortedMerkleTree<T, D> {
+    type Target = MerkleTree<T, D>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.tree
+    }
+}
+
+/// A proof that a key is not in a `SortedMerkleTree`.
+///
+/// Holds the adjacent leaves whose keys surround the absent key, with their proofs. `left`
+/// is `None` if the key is before the first leaf, and `right` is `None` if it is after the
+/// last leaf.
+pub struct AbsenceProof<T, D = Sha256> {
+    pub left: Option<(T, TraversePath<D>)>,
+    pub right: Option<(T, TraversePath<D>)>,
+}
+
+impl<T: Clone, D> Clone for AbsenceProof<T, D> {
+    fn clone(&self) -> Self {
+        AbsenceProof {
+            left: self.left.clone(),
+            right: self.right.clone(),
+        }
+    }
+}
+
+impl<T: fmt::Debug, D> fmt::Debug for AbsenceProof<T, D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AbsenceProof")
+            .field("left", &self.left)
+            .field("right", &self.right)
+            .finish()
+    }
+}
+
+/// Verifies that the given key is not in a sorted Merkle Tree.
+///
+/// # Arguments
+///
+/// * `tag_leaf`: The tag used for hashing leaf nodes.
+/// * `tag_branch`: The tag used for hashing branch nodes.
+/// * `root`: The hex encoded Merkle root to verify against.
+/// * `leaf_count`: The number of leaves in the tree.
+/// * `key`: The key that is claimed to be absent.
+/// * `proof`: The proof returned by `SortedMerkleTree::prove_absence`.
+///
+/// # Returns
+///
+/// `true` if the proof shows that the key is absent, `false` otherwise.
+pub fn verify_absence_proof<T, D>(
+    tag_leaf: &str,
+    tag_branch: &str,
+    root: &str,
+    leaf_count: usize,
+    key: &T::Key,
+    proof: &AbsenceProof<T, D>,
+) -> bool
+where
+    T: MerkleTreeData + MerkleTreeKey,
+    T::Key: Ord,
+    D: Digest,
+{
+    verify_absence_proof_with_options(
+        &TreeOptions::tagged(tag_leaf, tag_branch),
+        root,
+        leaf_count,
+        key,
+        proof,
+    )
+}
+
+/// Verifies that the given key is not in a sorted Merkle Tree, using the hashing
+/// configuration the tree was built with.
+///
+/// Both neighbors must be included in the tree, surround the key, and be adjacent leaves,
+/// or be the first or last leaf if the other one is missing.
+///
+/// # Arguments
+///
+/// * `options`: The options the tree was built with.
+/// * `root`: The hex encoded Merkle root to verify against.
+/// * `leaf_count`: The number of leaves in the tree.
+/// * `key`: The key that is claimed to be absent.
+/// * `proof`: The proof returned by `SortedMerkleTree::prove_absence`.
+///
+/// # Returns
+///
+/// `true` if the proof shows that the key is absent, `false` otherwise.
+pub fn verify_absence_proof_with_options<T, D>(
+    options: &TreeOptions,
+    root: &str,
+    leaf_count: usize,
+    key: &T::Key,
+    proof: &AbsenceProof<T, D>,
+) -> bool
+where
+    T: MerkleTreeData + MerkleTreeKey,
+    T::Key: Ord,
+    D: Digest,
+{
+    let neighbor_index = |(leaf, path): &(T, TraversePath<D>)| {
+        if verify_proof_with_options(options, root, leaf, path) {
+            path.leaf_index(leaf_count, options.padding).ok()
+        } else {
+            None
+        }
+    };
+
+    let left = match &proof.left {
+        Some(left) if left.0.key() < *key => Some(neighbor_index(left)),
+        Some(_) => return false,
+        None => None,
+    };
+    let right = match &proof.right {
+        Some(right) if *key < right.0.key() => Some(neighbor_index(right)),
+        Some(_) => return false,
+        None => None,
+    };
+
+    match (left, right) {
+        (Some(Some(left)), Some(Some(right))) => left + 1 == right,
+        (Some(Some(left)), None) => left + 1 == leaf_count,
+        (None, Some(Some(right))) => right == 0,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util;
+    use alloc::string::String;
+    use rstest::rstest;
+
+    fn generate_sorted_tree() -> (SortedMerkleTree<util::UserData>, String) {
+        let mut user_data: Vec<_> = (1..=15)
+            .map(|i| util::UserData {
+                id: i * 2,
+                balance: i,
+            })
+            .collect();
+        user_data.reverse();
+
+        let tree: SortedMerkleTree<_> =
+            SortedMerkleTree::build("ProofOfReserve_Leaf", "ProofOfReserve_Branch", &user_data);
+        let root = tree.root().unwrap();
+        (tree, root)
+    }
+
+    #[rstest]
+    #[case(1, None, Some(2))]
+    #[case(5, Some(4), Some(6))]
+    #[case(13, Some(12), Some(14))]
+    #[case(31, Some(30), None)]
+    fn it_can_verify_absence_proof(
+        #[case] key: u32,
+        #[case] left: Option<u32>,
+        #[case] right: Option<u32>,
+    ) {
+        let (tree, root) = generate_sorted_tree();
+
+        let proof = tree.prove_absence(&key).unwrap();
+        assert_eq!(proof.left.as_ref().map(|(leaf, _)| leaf.id), left);
+        assert_eq!(proof.right.as_ref().map(|(leaf, _)| leaf.id), right);
+
+        assert!(verify_absence_proof(
+            "ProofOfReserve_Leaf",
+            "ProofOfReserve_Branch",
+            &root,
+            15,
+            &key,
+            &proof
+        ));
+        assert!(!verify_absence_proof(
+            "ProofOfReserve_Leaf",
+            "ProofOfReserve_Branch",
+            &root,
+            40,
+            &key,
+            &proof
+        ));
+    }
+
+    #[test]
+    fn it_cannot_verify_absence_of_present_key() {
+        let (tree, root) = generate_sorted_tree();
+
+        assert_eq!(
+            tree.prove_absence(&6).err(),
+            Some(MerkleError::DuplicateKey)
+        );
+        let empty: SortedMerkleTree<util::UserData> =
+            SortedMerkleTree::build("ProofOfReserve_Leaf", "ProofOfReserve_Branch", &[]);
+        assert_eq!(empty.prove_absence(&6).err(), Some(MerkleError::EmptyTree));
+        let (node, path) = tree.proof_for_key(&6).unwrap();
+        assert_eq!(node.user_data.as_ref().unwrap().balance, 3);
+
+        // Skipping over the leaf of key 6 makes the neighbors non-adjacent.
+        let left = tree.prove_absence(&5).unwrap().left;
+        let right = tree.prove_absence(&7).unwrap().right;
+        let forged = AbsenceProof { left, right };
+        assert!(!verify_absence_proof(
+            "ProofOfReserve_Leaf",
+            "ProofOfReserve_Branch",
+            &root,
+            15,
+            &6,
+            &forged
+        ));
+
+        // Claiming a leaf in the middle is the last one.
+        let forged = AbsenceProof {
+            left: Some((node.user_data.clone().unwrap(), path)),
+            right: None,
+        };
+        assert!(!verify_absence_proof(
+            "ProofOfReserve_Leaf",
+            "ProofOfReserve_Branch",
+            &root,
+            15,
+            &7,
+            &forged
+        ));
+    }
+}
@@ -0,0 +1,513 @@
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+use core::marker::PhantomData;
+use sha2::digest::Output;
+use sha2::Digest;
+
+use crate::root::roots_match;
+use crate::{wipe, MerkleTreeData, Sha256, TreeOptions};
+
+/// The number of levels below the root of a `SparseMerkleTree`, one per bit of the key.
+pub const SPARSE_DEPTH: usize = 256;
+
+/// A sparse Merkle tree over 256-bit keys.
+///
+/// Every possible key has a leaf, at the position given by the bits of the key from the
+/// most significant one down. Empty leaves hash to `hash_leaf(&[])`, and the hashes of
+/// empty subtrees of each height are precomputed, so only the nodes above non-empty leaves
+/// are stored. A non-empty leaf hashes to `hash_leaf(key || value)`.
+pub struct SparseMerkleTree<T, D: Digest = Sha256> {
+    options: TreeOptions,
+    defaults: Vec<Output<D>>,
+    nodes: BTreeMap<(usize, [u8; 32]), Output<D>>,
+    leaves: BTreeMap<[u8; 32], T>,
+}
+
+impl<T: MerkleTreeData, D: Digest> SparseMerkleTree<T, D> {
+    /// Creates an empty sparse Merkle tree.
+    ///
+    /// # Arguments
+    ///
+    /// * `tag_leaf`: The tag used for hashing leaf nodes.
+    /// * `tag_branch`: The tag used for hashing branch nodes.
+    pub fn new(tag_leaf: &str, tag_branch: &str) -> Self {
+        Self::with_options(TreeOptions::tagged(tag_leaf, tag_branch))
+    }
+
+    /// Creates an empty sparse Merkle tree with the given hashing configuration.
+    ///
+    /// # Arguments
+    ///
+    /// * `options`: The hashing configuration of the tree. The padding policy is not used.
+    pub fn with_options(options: TreeOptions) -> Self {
+        SparseMerkleTree {
+            defaults: empty_subtree_hashes::<D>(&options),
+            options,
+            nodes: BTreeMap::new(),
+            leaves: BTreeMap::new(),
+        }
+    }
+
+    /// Returns the hashing configuration of the tree.
+    pub fn options(&self) -> &TreeOptions {
+        &self.options
+    }
+
+    /// Returns the hash of the root node of the tree.
+    pub fn root(&self) -> String {
+        hex::encode(self.node(SPARSE_DEPTH, &[0; 32]))
+    }
+
+    /// Returns the number of non-empty leaves.
+    pub fn len(&self) -> usize {
+        self.leaves.len()
+    }
+
+    /// Returns `true` if all leaves are empty.
+    pub fn is_empty(&self) -> bool {
+        self.leaves.is_empty()
+    }
+
+    /// Returns the value stored at the given key.
+    ///
+    /// # Arguments
+    ///
+    /// * `key`: The key of the leaf.
+    pub fn get(&self, key: &[u8; 32]) -> Option<&T> {
+        self.leaves.get(key)
+    }
+
+    /// Inserts or updates the value at the given key and rehashes its path to the root.
+    ///
+    /// # Arguments
+    ///
+    /// * `key`: The key of the leaf.
+    /// * `value`: The value to store.
+    ///
+    /// # Returns
+    ///
+    /// The value previously stored at the key, if any.
+    pub fn insert(&mut self, key: [u8; 32], value: T) -> Option<T> {
+        let old = self.leaves.insert(key, value);
+        self.update_path(&key);
+        old
+    }
+
+    /// Empties the leaf at the given key and rehashes its path to the root.
+    ///
+    /// # Arguments
+    ///
+    /// * `key`: The key of the leaf.
+    ///
+    /// # Returns
+    ///
+    /// The value that was stored at the key, if any.
+    pub fn remove(&mut self, key: &[u8; 32]) -> Option<T> {
+        let old = self.leaves.remove(key)?;
+        self.update_path(key);
+        Some(old)
+    }
+
+    /// Generates the proof for the leaf at the given key.
+    ///
+    /// The same proof shows the value of a non-empty leaf, or that the leaf is empty.
+    ///
+    /// # Arguments
+    ///
+    /// * `key`: The key of the leaf.
+    pub fn proof(&self, key: &[u8; 32]) -> SparseProof<D> {
+        let mut siblings: Vec<Option<String>> = (0..SPARSE_DEPTH)
+            .map(|height| {
+                let sibling = flip_bit(&clear_low_bits(key, height), SPARSE_DEPTH - 1 - height);
+                self.nodes.get(&(height, sibling)).map(hex::encode)
+            })
+            .collect();
+        siblings.reverse();
+        SparseProof::new(siblings)
+    }
+
+    /// Returns the hash of the node at the given height covering the given key prefix.
+    ///
+    /// # Arguments
+    ///
+    /// * `height`: The height of the node, 0 being the leaves.
+    /// * `id`: The key prefix of the node, with the low `height` bits cleared.
+    fn node(&self, height: usize, id: &[u8; 32]) -> &Output<D> {
+        self.nodes
+            .get(&(height, *id))
+            .unwrap_or(&self.defaults[height])
+    }
+
+    /// Stores the hash of a node, or forgets it if it is the hash of an empty subtree.
+    ///
+    /// # Arguments
+    ///
+    /// * `height`: The height of the node, 0 being the leaves.
+    /// * `id`: The key prefix of the node, with the low `height` bits cleared.
+    /// * `hash`: The hash of the node.
+    fn set_node(&mut self, height: usize, id: [u8; 32], hash: Output<D>) {
+        if hash == self.defaults[height] {
+            self.nodes.remove(&(height, id));
+        } else {
+            self.nodes.insert((height, id), hash);
+        }
+    }
+
+    /// Recomputes the hashes of the leaf at the given key and all its ancestors.
+    ///
+    /// # Arguments
+    ///
+    /// * `key`: The key of the leaf.
+    fn update_path(&mut self, key: &[u8; 32]) {
+        let mut hash = match self.leaves.get(key) {
+            Some(value) => sparse_leaf_hash::<T, D>(&self.options, key, value),
+            None => self.defaults[0].clone(),
+        };
+        self.set_node(0, *key, hash.clone());
+
+        for height in 0..SPARSE_DEPTH {
+            let bit = SPARSE_DEPTH - 1 - height;
+            let sibling_id = flip_bit(&clear_low_bits(key, height), bit);
+            let sibling = self.node(height, &sibling_id);
+
+            hash = if get_bit(key, bit) {
+                self.options.hash_branch::<D>(sibling, &hash)
+            } else {
+                self.options.hash_branch::<D>(&hash, sibling)
+            };
+            self.set_node(height + 1, clear_low_bits(key, height + 1), hash.clone());
+        }
+    }
+}
+
+/// A proof for a leaf of a `SparseMerkleTree`.
+///
+/// Holds the sibling hashes ordered from the root down to the leaf, with `None` for the
+/// siblings that are empty subtrees.
+pub struct SparseProof<D = Sha256> {
+    pub siblings: Vec<Option<String>>,
+    digest: PhantomData<fn() -> D>,
+}
+
+impl<D> Clone for SparseProof<D> {
+    fn clone(&self) -> Self {
+        Self::new(self.siblings.clone())
+    }
+}
+
+impl<D> fmt::Debug for SparseProof<D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SparseProof")
+            .field("siblings", &self.siblings)
+            .finish()
+    }
+}
+
+impl<D> SparseProof<D> {
+    /// Creates a `SparseProof` from sibling hashes ordered from the root down.
+    ///
+    /// # Arguments
+    ///
+    /// * `siblings`: The hex encoded sibling hashes, `None` for empty subtrees.
+    pub fn new(siblings: Vec<Option<String>>) -> Self {
+        SparseProof {
+            siblings,
+            digest: PhantomData,
+        }
+    }
+
+    /// Recomputes the root committed to by this proof for the given key and value.
+    ///
+    /// # Arguments
+    ///
+    /// * `options`: The options the tree was built with.
+    /// * `key`: The key of the leaf.
+    /// * `value`: The value of the leaf, or `None` to prove that the leaf is empty.
+    ///
+    /// # Returns
+    ///
+    /// The hex encoded root, or `None` if the proof is malformed.
+    pub fn compute_root_with_options<T>(
+        &self,
+        options: &TreeOptions,
+        key: &[u8; 32],
+        value: Option<&T>,
+    ) -> Option<String>
+    where
+        T: MerkleTreeData,
+        D: Digest,
+    {
+        if self.siblings.len() != SPARSE_DEPTH {
+            return None;
+        }
+
+        let mut empty = options.hash_leaf::<D>(&[]);
+        let mut hash = match value {
+            Some(value) => sparse_leaf_hash::<T, D>(options, key, value),
+            None => empty.clone(),
+        };
+
+        for (height, sibling) in self.siblings.iter().rev().enumerate() {
+            let sibling = match sibling {
+                Some(sibling) => match hex::decode(sibling) {
+                    Ok(bytes) if bytes.len() == hash.len() => Output::<D>::clone_from_slice(&bytes),
+                    _ => return None,
+                },
+                None => empty.clone(),
+            };
+
+            hash = if get_bit(key, SPARSE_DEPTH - 1 - height) {
+                options.hash_branch::<D>(&sibling, &hash)
+            } else {
+                options.hash_branch::<D>(&hash, &sibling)
+            };
+            empty = options.hash_branch::<D>(&empty, &empty);
+        }
+
+        Some(hex::encode(hash))
+    }
+}
+
+/// Verifies the value of a leaf of a sparse Merkle tree, or that the leaf is empty.
+///
+/// # Arguments
+///
+/// * `tag_leaf`: The tag used for hashing leaf nodes.
+/// * `tag_branch`: The tag used for hashing branch nodes.
+/// * `root`: The hex encoded root to verify against.
+/// * `key`: The key of the leaf.
+/// * `value`: The claimed value of the leaf, or `None` to verify that the leaf is empty.
+/// * `proof`: The proof returned by `SparseMerkleTree::proof`.
+///
+/// # Returns
+///
+/// `true` if the proof reconstructs the given root, `false` otherwise.
+pub fn verify_sparse_proof<T, D>(
+    tag_leaf: &str,
+    tag_branch: &str,
+    root: &str,
+    key: &[u8; 32],
+    value: Option<&T>,
+    proof: &SparseProof<D>,
+) -> bool
+where
+    T: MerkleTreeData,
+    D: Digest,
+{
+    verify_sparse_proof_with_options(
+        &TreeOptions::tagged(tag_leaf, tag_branch),
+        root,
+        key,
+        value,
+        proof,
+    )
+}
+
+/// Verifies the value of a leaf of a sparse Merkle tree, or that the leaf is empty, using
+/// the hashing configuration the tree was built with.
+///
+/// # Arguments
+///
+/// * `options`: The options the tree was built with.
+/// * `root`: The hex encoded root to verify against.
+/// * `key`: The key of the leaf.
+/// * `value`: The claimed value of the leaf, or `None` to verify that the leaf is empty.
+/// * `proof`: The proof returned by `SparseMerkleTree::proof`.
+///
+/// # Returns
+///
+/// `true` if the proof reconstructs the given root, `false` otherwise.
+pub fn verify_sparse_proof_with_options<T, D>(
+    options: &TreeOptions,
+    root: &str,
+    key: &[u8; 32],
+    value: Option<&T>,
+    proof: &SparseProof<D>,
+) -> bool
+where
+    T: MerkleTreeData,
+    D: Digest,
+{
+    proof
+        .compute_root_with_options(options, key, value)
+        .is_some_and(|computed| roots_match(&computed, root))
+}
+
+/// Calculates the hashes of empty subtrees of each height, from an empty leaf up to the
+/// root of an empty tree.
+///
+/// # Arguments
+///
+/// * `options`: The hashing configuration of the tree.
+fn empty_subtree_hashes<D: Digest>(options: &TreeOptions) -> Vec<Output<D>> {
+    let mut defaults = Vec::with_capacity(SPARSE_DEPTH + 1);
+    defaults.push(options.hash_leaf::<D>(&[]));
+    for height in 0..SPARSE_DEPTH {
+        defaults.push(options.hash_branch::<D>(&defaults[height], &defaults[height]));
+    }
+    defaults
+}
+
+/// Calculates the hash of a non-empty leaf, binding the value to its key.
+///
+/// # Arguments
+///
+/// * `options`: The hashing configuration of the tree.
+/// * `key`: The key of the leaf.
+/// * `value`: The value of the leaf.
+fn sparse_leaf_hash<T: MerkleTreeData, D: Digest>(
+    options: &TreeOptions,
+    key: &[u8; 32],
+    value: &T,
+) -> Output<D> {
+    let mut input = key.to_vec();
+    let mut bytes = value.serialize();
+    input.extend_from_slice(&bytes);
+    let hash = options.hash_leaf::<D>(&input);
+    wipe(&mut bytes);
+    wipe(&mut input);
+    hash
+}
+
+/// Returns whether the bit at the given index, counted from the most significant bit, is set.
+pub(crate) fn get_bit(key: &[u8; 32], bit: usize) -> bool {
+    key[bit / 8] & (0x80 >> (bit % 8)) != 0
+}
+
+/// Returns the key with the bit at the given index, counted from the most significant bit,
+/// flipped.
+fn flip_bit(key: &[u8; 32], bit: usize) -> [u8; 32] {
+    let mut key = *key;
+    key[bit / 8] ^= 0x80 >> (bit % 8);
+    key
+}
+
+/// Returns the key with the given number of least significant bits cleared.
+fn clear_low_bits(key: &[u8; 32], count: usize) -> [u8; 32] {
+    let mut key = *key;
+    for bit in SPARSE_DEPTH - count..SPARSE_DEPTH {
+        key[bit / 8] &= !(0x80 >> (bit % 8));
+    }
+    key
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util;
+
+    fn sparse_key(n: u8) -> [u8; 32] {
+        let mut key = [0u8; 32];
+        key[0] = n.wrapping_mul(37);
+        key[31] = n;
+        key
+    }
+
+    #[test]
+    fn it_can_insert_into_sparse_tree() {
+        let user_data = util::generate_random_user_data(4);
+        let mut tree: SparseMerkleTree<util::UserData> =
+            SparseMerkleTree::new("ProofOfReserve_Leaf", "ProofOfReserve_Branch");
+        let empty_root = tree.root();
+
+        for (i, data) in user_data.iter().enumerate() {
+            assert!(tree.insert(sparse_key(i as u8), data.clone()).is_none());
+        }
+        assert_eq!(tree.len(), 4);
+        assert_ne!(tree.root(), empty_root);
+        assert_eq!(tree.get(&sparse_key(2)).unwrap().id, user_data[2].id);
+
+        // The root does not depend on the insertion order.
+        let mut reversed: SparseMerkleTree<util::UserData> =
+            SparseMerkleTree::new("ProofOfReserve_Leaf", "ProofOfReserve_Branch");
+        for (i, data) in user_data.iter().enumerate().rev() {
+            reversed.insert(sparse_key(i as u8), data.clone());
+        }
+        assert_eq!(reversed.root(), tree.root());
+
+        // Updating and restoring a value restores the root.
+        let root = tree.root();
+        let updated = util::UserData { id: 99, balance: 1 };
+        let old = tree.insert(sparse_key(1), updated).unwrap();
+        assert_ne!(tree.root(), root);
+        tree.insert(sparse_key(1), old);
+        assert_eq!(tree.root(), root);
+
+        // Removing every leaf gives back the empty root.
+        for i in 0..4 {
+            assert!(tree.remove(&sparse_key(i)).is_some());
+        }
+        assert!(tree.remove(&sparse_key(0)).is_none());
+        assert!(tree.is_empty());
+        assert_eq!(tree.root(), empty_root);
+    }
+
+    #[test]
+    fn it_can_verify_sparse_proofs() {
+        let user_data = util::generate_random_user_data(5);
+        let mut tree: SparseMerkleTree<util::UserData> =
+            SparseMerkleTree::new("ProofOfReserve_Leaf", "ProofOfReserve_Branch");
+        for (i, data) in user_data.iter().enumerate() {
+            tree.insert(sparse_key(i as u8), data.clone());
+        }
+        let root = tree.root();
+
+        let proof = tree.proof(&sparse_key(3));
+        assert_eq!(proof.siblings.len(), SPARSE_DEPTH);
+        assert!(verify_sparse_proof(
+            "ProofOfReserve_Leaf",
+            "ProofOfReserve_Branch",
+            &root,
+            &sparse_key(3),
+            Some(&user_data[3]),
+            &proof
+        ));
+        assert!(!verify_sparse_proof(
+            "ProofOfReserve_Leaf",
+            "ProofOfReserve_Branch",
+            &root,
+            &sparse_key(3),
+            Some(&user_data[2]),
+            &proof
+        ));
+        assert!(!verify_sparse_proof::<util::UserData, _>(
+            "ProofOfReserve_Leaf",
+            "ProofOfReserve_Branch",
+            &root,
+            &sparse_key(3),
+            None,
+            &proof
+        ));
+
+        let absent = tree.proof(&sparse_key(42));
+        assert!(verify_sparse_proof::<util::UserData, _>(
+            "ProofOfReserve_Leaf",
+            "ProofOfReserve_Branch",
+            &root,
+            &sparse_key(42),
+            None,
+            &absent
+        ));
+        assert!(!verify_sparse_proof(
+            "ProofOfReserve_Leaf",
+            "ProofOfReserve_Branch",
+            &root,
+            &sparse_key(42),
+            Some(&user_data[0]),
+            &absent
+        ));
+
+        let mut truncated = absent.clone();
+        truncated.siblings.pop();
+        assert!(!verify_sparse_proof::<util::UserData, _>(
+            "ProofOfReserve_Leaf",
+            "ProofOfReserve_Branch",
+            &root,
+            &sparse_key(42),
+            None,
+            &truncated
+        ));
+    }
+}
@@ -0,0 +1,279 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use sha2::digest::Output;
+use sha2::Digest;
+
+use crate::{verify_sum_proof_with_options, MerkleSumData, MerkleTreeData, SumProof, TreeOptions};
+
+/// One part of a user's balance, as a leaf of a `SumMerkleTree`.
+///
+/// The blinding factor is hashed into the leaf so that the amount of a split can not be
+/// recovered by brute-forcing the leaf hash.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SplitLeaf<T> {
+    pub data: T,
+    pub amount: u64,
+    pub blinding: Vec<u8>,
+}
+
+impl<T: MerkleTreeData> MerkleTreeData for SplitLeaf<T> {
+    fn serialize(&self) -> Vec<u8> {
+        let mut bytes = self.data.serialize();
+        bytes.extend_from_slice(&self.amount.to_be_bytes());
+        bytes.extend_from_slice(&self.blinding);
+        bytes
+    }
+
+    fn hash_into(&self, hasher: &mut impl Digest) {
+        self.data.hash_into(hasher);
+        hasher.update(self.amount.to_be_bytes());
+        hasher.update(&self.blinding);
+    }
+
+    fn mermaid_node_label(&self) -> String {
+        self.data.mermaid_node_label()
+    }
+}
+
+impl<T: MerkleTreeData> MerkleSumData for SplitLeaf<T> {
+    fn balance(&self) -> u64 {
+        self.amount
+    }
+}
+
+/// Splits every user's balance into several blinded leaves, as in the Provisions protocol.
+///
+/// The amounts and blinding factors are derived from `seed` with the digest, so the same
+/// seed reproduces the same leaves. The split amounts of a user sum up to their balance.
+/// The leaves are ordered by split first and user second, so a user's splits are spread
+/// across the tree and the neighbours of a leaf belong to other users.
+///
+/// # Arguments
+///
+/// * `input`: The user data to split.
+/// * `splits`: The number of leaves per user, at least 1.
+/// * `seed`: A secret seed for the amounts and blinding factors.
+///
+/// # Returns
+///
+/// The leaves to build a `SumMerkleTree` from.
+pub fn split_balances<T, D>(input: &[T], splits: usize, seed: &[u8]) -> Vec<SplitLeaf<T>>
+where
+    T: Clone + MerkleSumData,
+    D: Digest,
+{
+    let splits = splits.max(1);
+    let mut amounts: Vec<u64> = Vec::with_capacity(input.len() * splits);
+
+    for (user, data) in input.iter().enumerate() {
+        let weights: Vec<u128> = (0..splits)
+            .map(|part| {
+                let hash = derive::<D>(seed, b"amount", user, part);
+                u128::from(u32::from_be_bytes([hash[0], hash[1], hash[2], hash[3]])) + 1
+            })
+            .collect();
+        let total_weight: u128 = weights.iter().sum();
+
+        let balance = data.balance();
+        let mut remaining = balance;
+        for weight in &weights[..splits - 1] {
+            let amount = (u128::from(balance) * weight / total_weight) as u64;
+            remaining -= amount;
+            amounts.push(amount);
+        }
+        amounts.push(remaining);
+    }
+
+    let mut leaves = Vec::with_capacity(input.len() * splits);
+    for part in 0..splits {
+        for (user, data) in input.iter().enumerate() {
+            leaves.push(SplitLeaf {
+                data: data.clone(),
+                amount: amounts[user * splits + part],
+                blinding: derive::<D>(seed, b"blinding", user, part).to_vec(),
+            });
+        }
+    }
+    leaves
+}
+
+/// Derives a pseudo-random hash for a split of a user from the seed.
+///
+/// # Arguments
+///
+/// * `seed`: The secret seed.
+/// * `label`: What the hash is used for.
+/// * `user`: The position of the user in the input.
+/// * `part`: The number of the split.
+fn derive<D: Digest>(seed: &[u8], label: &[u8], user: usize, part: usize) -> Output<D> {
+    let mut hasher = D::new();
+    hasher.update(seed);
+    hasher.update(label);
+    hasher.update((user as u64).to_be_bytes());
+    hasher.update((part as u64).to_be_bytes());
+    hasher.finalize()
+}
+
+/// Verifies all the splits of a user and checks that they add up to the user's balance.
+///
+/// # Arguments
+///
+/// * `tag_leaf`: The tag used for hashing leaf nodes.
+/// * `tag_branch`: The tag used for hashing branch nodes.
+/// * `root`: The hex encoded root to verify against.
+/// * `total`: The published total of all balances.
+/// * `balance`: The balance the user expects to be included.
+/// * `proofs`: The user's splits with their proofs.
+///
+/// # Returns
+///
+/// `true` if every split is included in the tree and the splits sum up to `balance`,
+/// `false` otherwise.
+pub fn verify_split_proofs<T, D>(
+    tag_leaf: &str,
+    tag_branch: &str,
+    root: &str,
+    total: u64,
+    balance: u64,
+    proofs: &[(SplitLeaf<T>, SumProof<D>)],
+) -> bool
+where
+    T: MerkleTreeData,
+    D: Digest,
+{
+    verify_split_proofs_with_options(
+        &TreeOptions::tagged(tag_leaf, tag_branch),
+        root,
+        total,
+        balance,
+        proofs,
+    )
+}
+
+/// Verifies all the splits of a user and checks that they add up to the user's balance,
+/// using the hashing configuration the tree was built with.
+///
+/// # Arguments
+///
+/// * `options`: The options the tree was built with.
+/// * `root`: The hex encoded root to verify against.
+/// * `total`: The published total of all balances.
+/// * `balance`: The balance the user expects to be included.
+/// * `proofs`: The user's splits with their proofs.
+///
+/// # Returns
+///
+/// `true` if every split is included in the tree and the splits sum up to `balance`,
+/// `false` otherwise.
+pub fn verify_split_proofs_with_options<T, D>(
+    options: &TreeOptions,
+    root: &str,
+    total: u64,
+    balance: u64,
+    proofs: &[(SplitLeaf<T>, SumProof<D>)],
+) -> bool
+where
+    T: MerkleTreeData,
+    D: Digest,
+{
+    let mut sum: u64 = 0;
+    let mut blindings: Vec<&[u8]> = Vec::with_capacity(proofs.len());
+
+    for (leaf, proof) in proofs {
+        // The same split must not be counted twice.
+        if blindings.contains(&leaf.blinding.as_slice()) {
+            return false;
+        }
+        blindings.push(&leaf.blinding);
+
+        if !verify_sum_proof_with_options(options, root, total, leaf, proof) {
+            return false;
+        }
+
+        sum = match sum.checked_add(leaf.amount) {
+            Some(sum) => sum,
+            None => return false,
+        };
+    }
+
+    sum == balance
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{util, Sha256, SumMerkleTree};
+    use alloc::vec;
+
+    #[test]
+    fn it_can_split_balances() {
+        let user_data = util::generate_random_user_data(6);
+
+        let splits = |seed: &[u8]| -> Vec<(u64, Vec<u8>)> {
+            split_balances::<_, Sha256>(&user_data, 3, seed)
+                .into_iter()
+                .map(|leaf| (leaf.amount, leaf.blinding))
+                .collect()
+        };
+
+        let leaves = split_balances::<_, Sha256>(&user_data, 3, b"seed");
+        assert_eq!(leaves.len(), 18);
+        assert_eq!(splits(b"seed"), splits(b"seed"));
+        assert_ne!(splits(b"seed"), splits(b"other seed"));
+
+        for (user, data) in user_data.iter().enumerate() {
+            let amounts: Vec<u64> = (0..3).map(|part| leaves[part * 6 + user].amount).collect();
+            assert!(leaves[user..]
+                .iter()
+                .step_by(6)
+                .all(|leaf| leaf.data.id == data.id));
+            assert_eq!(amounts.iter().sum::<u64>(), data.balance as u64);
+        }
+    }
+
+    #[test]
+    fn it_can_verify_split_proofs() {
+        let user_data = util::generate_random_user_data(6);
+        let leaves = split_balances::<_, Sha256>(&user_data, 3, b"seed");
+
+        let tree: SumMerkleTree<_> =
+            SumMerkleTree::build("ProofOfReserve_Leaf", "ProofOfReserve_Branch", &leaves).unwrap();
+        let root = tree.root().unwrap();
+        let total = tree.total().unwrap();
+        assert_eq!(total, 21000);
+
+        let proofs: Vec<_> = (0..3)
+            .map(|part| {
+                let (leaf, proof) = tree.proof_by_index(part * 6 + 4).unwrap();
+                (leaf.clone(), proof)
+            })
+            .collect();
+
+        assert!(verify_split_proofs(
+            "ProofOfReserve_Leaf",
+            "ProofOfReserve_Branch",
+            &root,
+            total,
+            5000,
+            &proofs
+        ));
+        assert!(!verify_split_proofs(
+            "ProofOfReserve_Leaf",
+            "ProofOfReserve_Branch",
+            &root,
+            total,
+            5000,
+            &proofs[1..]
+        ));
+
+        let duplicated = vec![proofs[0].clone(), proofs[0].clone(), proofs[1].clone()];
+        assert!(!verify_split_proofs(
+            "ProofOfReserve_Leaf",
+            "ProofOfReserve_Branch",
+            &root,
+            total,
+            proofs[0].0.amount * 2 + proofs[1].0.amount,
+            &duplicated
+        ));
+    }
+}
@@ -0,0 +1,70 @@
+use core::mem;
+use sha2::Digest;
+
+use crate::{MerkleTree, NodeStore};
+
+/// Statistics about the shape and size of a `MerkleTree`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TreeStats {
+    /// The number of levels above the leaves, which is the number of steps in the longest
+    /// proof.
+    pub depth: usize,
+    /// The number of leaves in the tree.
+    pub leaf_count: usize,
+    /// The number of nodes stored by the tree, including branch nodes and padding leaves.
+    pub node_count: usize,
+    /// The approximate number of bytes used by the tree.
+    ///
+    /// Heap memory owned by the user data, such as the contents of a `String`, is not
+    /// included.
+    pub memory_bytes: usize,
+}
+
+impl<T, D: Digest, S: NodeStore<T, D>> MerkleTree<T, D, S> {
+    /// Returns statistics about the shape and size of the tree.
+    pub fn stats(&self) -> TreeStats {
+        TreeStats {
+            depth: self.level_sizes().len() - 1,
+            leaf_count: self.leaf_count,
+            node_count: self.nodes.len(),
+            memory_bytes: mem::size_of::<Self>()
+                + self.nodes.memory_bytes()
+                + self.options.tag_leaf.capacity()
+                + self.options.tag_branch.capacity(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{util, MerkleNode, Padding, TreeOptions};
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(Padding::DuplicateLast, 0, 0, 0)]
+    #[case(Padding::DuplicateLast, 1, 0, 1)]
+    #[case(Padding::DuplicateLast, 5, 3, 11)]
+    #[case(Padding::Promote, 5, 3, 9)]
+    #[case(Padding::PadWithEmpty, 5, 3, 12)]
+    fn it_can_report_stats(
+        #[case] padding: Padding,
+        #[case] leaf_count: usize,
+        #[case] depth: usize,
+        #[case] node_count: usize,
+    ) {
+        let user_data = util::generate_random_user_data(leaf_count);
+        let options = TreeOptions::tagged("ProofOfReserve_Leaf", "ProofOfReserve_Branch")
+            .with_padding(padding);
+
+        let tree: MerkleTree<_> = MerkleTree::build_with_options(options, &user_data);
+        let stats = tree.stats();
+
+        assert_eq!(stats.depth, depth);
+        assert_eq!(stats.leaf_count, leaf_count);
+        assert_eq!(stats.node_count, node_count);
+        assert!(
+            stats.memory_bytes >= node_count * core::mem::size_of::<MerkleNode<util::UserData>>()
+        );
+    }
+}
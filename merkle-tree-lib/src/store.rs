@@ -0,0 +1,242 @@
+use alloc::vec::Vec;
+use core::mem;
+use core::ops::Index;
+use sha2::Digest;
+
+use crate::MerkleNode;
+
+/// What a `MerkleTree` keeps besides its nodes, returned by `MerkleTree::metadata`.
+///
+/// A store backed by external storage persists it together with the nodes, so the tree can be
+/// reattached to the store with `MerkleTree::from_store` after a restart.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TreeMetadata {
+    /// The position of the root node, or `None` for an empty tree.
+    pub root: Option<usize>,
+    /// The number of leaves.
+    pub leaf_count: usize,
+    /// The position of the empty leaf that pads levels with `Padding::PadWithEmpty`.
+    pub empty_leaf: Option<usize>,
+}
+
+/// The storage of the nodes of a `MerkleTree`, addressed by the position they were pushed at.
+///
+/// A tree appends nodes while it is in use, and overwrites through `get_mut` only the nodes
+/// that no version handed out by `MerkleTree::version` can reach. The nodes are handed back
+/// with `take_nodes` when `MerkleTree::remove` or `MerkleTree::compact` rebuild the tree.
+/// `Vec<MerkleNode>` is the in-memory store used by default; a store backed by external
+/// storage hands out references through `Index`, for example from a cache of loaded nodes.
+pub trait NodeStore<T, D: Digest>: Index<usize, Output = MerkleNode<T, D>> {
+    /// Returns the number of nodes in the store.
+    fn len(&self) -> usize;
+
+    /// Returns `true` if the store holds no nodes.
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Appends a node, which is addressed by the previous `len` from then on.
+    ///
+    /// # Arguments
+    ///
+    /// * `node`: The node to store.
+    fn push(&mut self, node: MerkleNode<T, D>);
+
+    /// Removes all nodes from the store.
+    ///
+    /// # Returns
+    ///
+    /// The nodes, in the order they were pushed.
+    fn take_nodes(&mut self) -> Vec<MerkleNode<T, D>>;
+
+    /// Returns the node at the given position, or `None` if it is out of range.
+    ///
+    /// # Arguments
+    ///
+    /// * `index`: The position of the node.
+    fn get(&self, index: usize) -> Option<&MerkleNode<T, D>> {
+        (index < self.len()).then(|| &self[index])
+    }
+
+    /// Returns the node at the given position for overwriting it in place, or `None` if it is
+    /// out of range or the store cannot overwrite it, in which case the tree appends the
+    /// changed node instead.
+    ///
+    /// Defaults to `None`, for stores that only append to external storage.
+    fn get_mut(&mut self, _index: usize) -> Option<&mut MerkleNode<T, D>> {
+        None
+    }
+
+    /// Releases the memory the store keeps to speed up reads, such as a cache of loaded
+    /// nodes. Nodes are borrowed from the store through shared references, so such a cache can
+    /// only shrink while the store is borrowed mutably.
+    ///
+    /// Defaults to doing nothing, for stores that keep no such memory.
+    fn release_cache(&mut self) {}
+
+    /// Returns the approximate number of bytes of memory used by the store.
+    fn memory_bytes(&self) -> usize {
+        self.len() * mem::size_of::<MerkleNode<T, D>>()
+    }
+}
+
+impl<T, D: Digest> NodeStore<T, D> for Vec<MerkleNode<T, D>> {
+    fn len(&self) -> usize {
+        Vec::len(self)
+    }
+
+    fn push(&mut self, node: MerkleNode<T, D>) {
+        Vec::push(self, node);
+    }
+
+    fn take_nodes(&mut self) -> Vec<MerkleNode<T, D>> {
+        mem::take(self)
+    }
+
+    fn get(&self, index: usize) -> Option<&MerkleNode<T, D>> {
+        <[_]>::get(self, index)
+    }
+
+    fn get_mut(&mut self, index: usize) -> Option<&mut MerkleNode<T, D>> {
+        <[_]>::get_mut(self, index)
+    }
+
+    fn memory_bytes(&self) -> usize {
+        self.capacity() * mem::size_of::<MerkleNode<T, D>>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        util, verify_proof_with_options, MerkleError, MerkleTree, Padding, Sha256, TreeOptions,
+    };
+
+    #[test]
+    fn it_can_keep_nodes_in_custom_store() {
+        /// A store that keeps its nodes in fixed-size pages, as a store that loads pages from
+        /// external storage would.
+        #[derive(Default)]
+        struct PagedStore {
+            pages: Vec<Vec<MerkleNode<util::UserData>>>,
+            len: usize,
+        }
+
+        const PAGE_SIZE: usize = 4;
+
+        impl core::ops::Index<usize> for PagedStore {
+            type Output = MerkleNode<util::UserData>;
+
+            fn index(&self, index: usize) -> &Self::Output {
+                &self.pages[index / PAGE_SIZE][index % PAGE_SIZE]
+            }
+        }
+
+        impl NodeStore<util::UserData, Sha256> for PagedStore {
+            fn len(&self) -> usize {
+                self.len
+            }
+
+            fn push(&mut self, node: MerkleNode<util::UserData>) {
+                if self.pages.last().is_none_or(|page| page.len() == PAGE_SIZE) {
+                    self.pages.push(Vec::with_capacity(PAGE_SIZE));
+                }
+                self.pages.last_mut().unwrap().push(node);
+                self.len += 1;
+            }
+
+            fn take_nodes(&mut self) -> Vec<MerkleNode<util::UserData>> {
+                self.len = 0;
+                core::mem::take(&mut self.pages)
+                    .into_iter()
+                    .flatten()
+                    .collect()
+            }
+        }
+
+        let user_data = util::generate_random_user_data(9);
+        let options = TreeOptions::tagged("ProofOfReserve_Leaf", "ProofOfReserve_Branch");
+        let expected: MerkleTree<_> = MerkleTree::build_with_options(options.clone(), &user_data);
+
+        let mut tree = MerkleTree::with_store(options.clone(), PagedStore::default()).unwrap();
+        for item in &user_data {
+            tree.push(item.clone());
+        }
+        assert_eq!(tree.root(), expected.root());
+        assert_eq!(tree.store().len(), tree.stats().node_count);
+
+        let built: MerkleTree<_, Sha256, PagedStore> =
+            MerkleTree::build_with_options(options.clone(), &user_data);
+        assert_eq!(built.root(), expected.root());
+        assert_eq!(built.store().len(), expected.store().len());
+
+        let root = tree.root().unwrap();
+        for (index, item) in user_data.iter().enumerate() {
+            let (_node, path) = tree.proof_by_index(index).unwrap();
+            assert!(verify_proof_with_options(&options, &root, item, &path));
+        }
+
+        tree.update(3, user_data[0].clone()).unwrap();
+        tree.compact();
+        tree.remove(0).unwrap();
+        let mut remaining = user_data[1..].to_vec();
+        remaining[2] = user_data[0].clone();
+        let rebuilt: MerkleTree<_> = MerkleTree::build_with_options(options, &remaining);
+        assert_eq!(tree.root(), rebuilt.root());
+        assert_eq!(tree.into_leaves().count(), 8);
+    }
+
+    #[test]
+    fn it_can_reattach_tree_to_populated_store() {
+        let user_data = util::generate_random_user_data(7);
+        let options = TreeOptions::tagged("ProofOfReserve_Leaf", "ProofOfReserve_Branch")
+            .with_padding(Padding::PadWithEmpty);
+        let tree: MerkleTree<_> = MerkleTree::build_with_options(options.clone(), &user_data);
+        let metadata = tree.metadata();
+        let store = tree.store().clone();
+
+        assert_eq!(
+            MerkleTree::with_store(options.clone(), store.clone()).err(),
+            Some(MerkleError::InvalidStore)
+        );
+
+        let mut reattached: MerkleTree<_> =
+            MerkleTree::from_store(options.clone(), store.clone(), metadata).unwrap();
+        assert_eq!(reattached.root(), tree.root());
+        assert_eq!(reattached.leaf_count(), 7);
+        assert_eq!(reattached.leaf_nodes(), tree.leaf_nodes());
+
+        let extra = util::UserData {
+            id: 8,
+            balance: 800,
+        };
+        reattached.push(extra.clone());
+        let mut extended = user_data.clone();
+        extended.push(extra);
+        let expected: MerkleTree<_> = MerkleTree::build_with_options(options.clone(), &extended);
+        assert_eq!(reattached.root(), expected.root());
+
+        for metadata in [
+            TreeMetadata {
+                root: Some(store.len()),
+                ..metadata
+            },
+            TreeMetadata {
+                leaf_count: 0,
+                ..metadata
+            },
+            TreeMetadata {
+                root: None,
+                ..metadata
+            },
+        ] {
+            assert_eq!(
+                MerkleTree::<util::UserData>::from_store(options.clone(), store.clone(), metadata)
+                    .err(),
+                Some(MerkleError::InvalidStore)
+            );
+        }
+    }
+}
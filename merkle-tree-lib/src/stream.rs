@@ -0,0 +1,256 @@
+use alloc::vec;
+use alloc::vec::Vec;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use sha2::digest::Output;
+use sha2::Digest;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+
+use crate::mapped::{padding_code, NONE};
+use crate::{MerkleNode, MerkleTree, NodeStore, Padding, TreeFileError, TreeMetadata, TreeOptions};
+
+/// The first bytes of a streamed tree.
+const MAGIC: &[u8; 8] = b"MRKLSTRM";
+
+impl<T, D: Digest, S: NodeStore<T, D>> MerkleTree<T, D, S> {
+    /// Streams the current version of the tree to a writer, level by level from the leaves up,
+    /// to be read back with `read_from`.
+    ///
+    /// Each leaf is written as its hash and its JSON encoded user data, and each branch as its
+    /// hash, so the serialized form is never held in memory as a whole.
+    ///
+    /// # Arguments
+    ///
+    /// * `writer`: Where to write the tree to, such as a `std::fs::File`.
+    pub fn write_to(&self, writer: impl Write) -> io::Result<()>
+    where
+        T: Serialize,
+    {
+        let sizes = self.level_sizes();
+        let promote = self.options.padding == Padding::Promote;
+
+        // Collect the nodes of each level from the root down, as `node_in` walks them.
+        let mut levels = vec![Vec::new(); sizes.len()];
+        levels[sizes.len() - 1].extend(self.root);
+        for level in (1..sizes.len()).rev() {
+            let lower = (0..sizes[level - 1])
+                .map(|position| {
+                    let parent = levels[level][position >> 1];
+                    let unpaired = position & 1 == 0 && position == sizes[level - 1] - 1;
+                    if unpaired && promote {
+                        parent
+                    } else if position & 1 == 0 {
+                        self.nodes[parent].left.unwrap()
+                    } else {
+                        self.nodes[parent].right.unwrap()
+                    }
+                })
+                .collect();
+            levels[level - 1] = lower;
+        }
+
+        let mut writer = BufWriter::new(writer);
+        writer.write_all(MAGIC)?;
+        writer.write_all(&(<D as Digest>::output_size() as u32).to_le_bytes())?;
+        writer.write_all(&[padding_code(self.options.padding), 0, 0, 0])?;
+        writer.write_all(&(self.leaf_count as u64).to_le_bytes())?;
+
+        for &leaf in &levels[0] {
+            let node = &self.nodes[leaf];
+            writer.write_all(&node.hash)?;
+            match node.user_data.as_ref() {
+                Some(user_data) => {
+                    let json = serde_json::to_vec(user_data)?;
+                    writer.write_all(&(json.len() as u64).to_le_bytes())?;
+                    writer.write_all(&json)?;
+                }
+                None => writer.write_all(&NONE.to_le_bytes())?,
+            }
+        }
+
+        for level in 1..sizes.len() {
+            for (position, &node) in levels[level].iter().enumerate() {
+                // A promoted node was already written on the level below.
+                if promote && position << 1 == sizes[level - 1] - 1 {
+                    continue;
+                }
+                writer.write_all(&self.nodes[node].hash)?;
+            }
+        }
+
+        writer.flush()
+    }
+
+    /// Reads a tree streamed with `write_to`.
+    ///
+    /// # Arguments
+    ///
+    /// * `tag_leaf`: The tag the tree was hashed with for leaf nodes.
+    /// * `tag_branch`: The tag the tree was hashed with for branch nodes.
+    /// * `reader`: Where to read the tree from, such as a `std::fs::File`.
+    ///
+    /// # Returns
+    ///
+    /// The tree, or an error if the stream cannot be read or was not written by `write_to`
+    /// with the same options.
+    pub fn read_from(
+        tag_leaf: &str,
+        tag_branch: &str,
+        reader: impl Read,
+    ) -> Result<Self, TreeFileError>
+    where
+        T: DeserializeOwned,
+        S: Default,
+    {
+        Self::read_from_with_options(TreeOptions::tagged(tag_leaf, tag_branch), reader)
+    }
+
+    /// Reads a tree streamed with `write_to` that was built with the given hashing
+    /// configuration.
+    ///
+    /// The nodes are added to the store level by level as they are read. The hashes are
+    /// taken from the stream without recomputing them.
+    ///
+    /// # Arguments
+    ///
+    /// * `options`: The options the tree was built with.
+    /// * `reader`: Where to read the tree from, such as a `std::fs::File`.
+    ///
+    /// # Returns
+    ///
+    /// The tree, or an error if the stream cannot be read or was not written by `write_to`
+    /// with the same options.
+    pub fn read_from_with_options(
+        options: TreeOptions,
+        reader: impl Read,
+    ) -> Result<Self, TreeFileError>
+    where
+        T: DeserializeOwned,
+        S: Default,
+    {
+        let mut reader = BufReader::new(reader);
+        let mut header = [0; 24];
+        reader.read_exact(&mut header)?;
+        if &header[..8] != MAGIC {
+            return Err(TreeFileError::Malformed);
+        }
+        let hash_size = u32::from_le_bytes(header[8..12].try_into().unwrap()) as usize;
+        if hash_size != <D as Digest>::output_size() || header[12] != padding_code(options.padding)
+        {
+            return Err(TreeFileError::OptionsMismatch);
+        }
+        let leaf_count = u64::from_le_bytes(header[16..].try_into().unwrap()) as usize;
+
+        let mut tree = Self::from_parts(options, S::default(), TreeMetadata::default());
+        let mut level = Vec::new();
+
+        for _ in 0..leaf_count {
+            let hash = read_hash::<D>(&mut reader)?;
+            let user_data = match read_u64(&mut reader)? {
+                NONE => None,
+                len => {
+                    let mut json = Vec::new();
+                    reader.by_ref().take(len).read_to_end(&mut json)?;
+                    if json.len() as u64 != len {
+                        return Err(TreeFileError::Malformed);
+                    }
+                    let user_data =
+                        serde_json::from_slice(&json).map_err(|_| TreeFileError::Malformed)?;
+                    Some(user_data)
+                }
+            };
+            tree.nodes.push(MerkleNode::new_leaf(hash, user_data));
+            level.push(tree.nodes.len() - 1);
+        }
+        tree.leaf_count = leaf_count;
+
+        while level.len() > 1 {
+            let mut next_level = Vec::with_capacity(level.len().div_ceil(2));
+
+            for pair in level.chunks(2) {
+                let (left, right) = match *pair {
+                    [left, right] => (left, right),
+                    [single] => match tree.options.padding {
+                        Padding::DuplicateLast => (single, single),
+                        Padding::Promote => {
+                            next_level.push(single);
+                            continue;
+                        }
+                        Padding::PadWithEmpty => (single, tree.empty_leaf()),
+                    },
+                    _ => unreachable!(),
+                };
+                tree.nodes.push(MerkleNode {
+                    hash: read_hash::<D>(&mut reader)?,
+                    left: Some(left),
+                    right: Some(right),
+                    user_data: None,
+                });
+                next_level.push(tree.nodes.len() - 1);
+            }
+
+            level = next_level;
+        }
+
+        tree.root = level.first().copied();
+        Ok(tree)
+    }
+}
+
+/// Reads a hash of the digest `D`.
+fn read_hash<D: Digest>(reader: &mut impl Read) -> io::Result<Output<D>> {
+    let mut hash = Output::<D>::default();
+    reader.read_exact(&mut hash)?;
+    Ok(hash)
+}
+
+/// Reads a little-endian `u64`.
+fn read_u64(reader: &mut impl Read) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(Padding::DuplicateLast, 13)]
+    #[case(Padding::Promote, 13)]
+    #[case(Padding::PadWithEmpty, 13)]
+    #[case(Padding::DuplicateLast, 1)]
+    #[case(Padding::Promote, 0)]
+    fn it_can_stream_tree_to_writer_and_read_it_back(#[case] padding: Padding, #[case] n: usize) {
+        let options = TreeOptions::tagged("ProofOfReserve_Leaf", "ProofOfReserve_Branch")
+            .with_padding(padding);
+        let user_data = util::generate_random_user_data(n);
+        let mut tree: MerkleTree<_> = MerkleTree::build_with_options(options.clone(), &user_data);
+        if n > 5 {
+            tree.update(5, user_data[0].clone()).unwrap();
+        }
+
+        let mut bytes = Vec::new();
+        tree.write_to(&mut bytes).unwrap();
+        let read: MerkleTree<util::UserData> =
+            MerkleTree::read_from_with_options(options.clone(), &bytes[..]).unwrap();
+
+        assert_eq!(read.root(), tree.root());
+        assert_eq!(read.leaf_count(), n);
+        assert!(read.store().len() <= tree.store().len());
+        for index in 0..n {
+            let (node, path) = read.proof_by_index(index).unwrap();
+            let (expected_node, expected) = tree.proof_by_index(index).unwrap();
+            assert_eq!(node.hash, expected_node.hash);
+            assert_eq!(path.siblings, expected.siblings);
+        }
+        let ids: Vec<_> = read.leaves().map(|(_, item)| item.id).collect();
+        let expected: Vec<_> = tree.leaves().map(|(_, item)| item.id).collect();
+        assert_eq!(ids, expected);
+
+        let truncated = &bytes[..bytes.len() - 1];
+        assert!(MerkleTree::<util::UserData>::read_from_with_options(options, truncated).is_err());
+    }
+}
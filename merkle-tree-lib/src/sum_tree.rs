@@ -0,0 +1,472 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+use core::marker::PhantomData;
+use sha2::digest::Output;
+use sha2::Digest;
+
+use crate::root::roots_match;
+use crate::{Balance, MerkleError, MerkleTreeData, NodeDirection, Sha256, TreeOptions};
+
+/// User data that carries a balance to be summed up by a `SumMerkleTree`.
+pub trait MerkleSumData<B = u64>: MerkleTreeData {
+    fn balance(&self) -> B;
+}
+
+/// A node of a `SumMerkleTree`, committing to a hash and the sum of the balances below it.
+struct SumNode<D: Digest, B> {
+    hash: Output<D>,
+    sum: B,
+}
+
+impl<D: Digest, B: Balance> SumNode<D, B> {
+    /// Creates the parent of the given nodes, or `None` if the sum overflows.
+    ///
+    /// # Arguments
+    ///
+    /// * `options`: The hashing configuration of the tree.
+    /// * `left`: The hash and sum of the left child node.
+    /// * `right`: The hash and sum of the right child node.
+    fn branch(
+        options: &TreeOptions,
+        left: (&Output<D>, &B),
+        right: (&Output<D>, &B),
+    ) -> Option<Self> {
+        Some(SumNode {
+            hash: options.hash_branch_parts::<D>(&[
+                left.0,
+                left.1.to_be_bytes().as_ref(),
+                right.0,
+                right.1.to_be_bytes().as_ref(),
+            ]),
+            sum: left.1.checked_add(right.1)?,
+        })
+    }
+}
+
+/// A Merkle sum tree for proofs of liabilities, as proposed by Maxwell.
+///
+/// Every branch hashes `left_hash || left_sum || right_hash || right_sum` with the sums in
+/// the encoding of the `Balance` type `B`, big-endian `u64` by default, and carries the sum
+/// of its children. The root therefore commits to
+/// the total of all balances, and an inclusion proof shows that a user's balance is
+/// counted in that total.
+///
+/// An unpaired node is always promoted to the next level, regardless of the padding
+/// policy in the options, so that no balance is counted twice. Pair sorting of
+/// `HashMode::SortedPair` is not applied since the proof directions are needed anyway.
+pub struct SumMerkleTree<T, D: Digest = Sha256, B = u64> {
+    options: TreeOptions,
+    levels: Vec<Vec<SumNode<D, B>>>,
+    leaves: Vec<T>,
+}
+
+impl<T, D, B> SumMerkleTree<T, D, B>
+where
+    T: Clone + MerkleSumData<B>,
+    D: Digest,
+    B: Balance,
+{
+    /// Builds a Merkle sum tree from the given user data.
+    ///
+    /// # Arguments
+    ///
+    /// * `tag_leaf`: The tag used for hashing leaf nodes.
+    /// * `tag_branch`: The tag used for hashing branch nodes.
+    /// * `input`: The user data to commit to, in leaf order.
+    ///
+    /// # Returns
+    ///
+    /// The tree, or `MerkleError::Overflow` if the total of the balances overflows the
+    /// balance type.
+    pub fn build(tag_leaf: &str, tag_branch: &str, input: &[T]) -> Result<Self, MerkleError> {
+        Self::build_with_options(TreeOptions::tagged(tag_leaf, tag_branch), input)
+    }
+
+    /// Builds a Merkle sum tree from the given user data with the given hashing configuration.
+    ///
+    /// # Arguments
+    ///
+    /// * `options`: The hashing configuration of the tree.
+    /// * `input`: The user data to commit to, in leaf order.
+    ///
+    /// # Returns
+    ///
+    /// The tree, or `MerkleError::Overflow` if the total of the balances overflows the
+    /// balance type.
+    pub fn build_with_options(options: TreeOptions, input: &[T]) -> Result<Self, MerkleError> {
+        let mut level: Vec<SumNode<D, B>> = input
+            .iter()
+            .map(|data| SumNode {
+                hash: options.hash_leaf_data::<D>(data),
+                sum: data.balance(),
+            })
+            .collect();
+        let mut levels = Vec::new();
+
+        while level.len() > 1 {
+            let mut next_level = Vec::with_capacity(level.len().div_ceil(2));
+
+            for pair in level.chunks(2) {
+                next_level.push(match pair {
+                    [left, right] => SumNode::branch(
+                        &options,
+                        (&left.hash, &left.sum),
+                        (&right.hash, &right.sum),
+                    )
+                    .ok_or(MerkleError::Overflow)?,
+                    [single] => SumNode {
+                        hash: single.hash.clone(),
+                        sum: single.sum.clone(),
+                    },
+                    _ => unreachable!(),
+                });
+            }
+
+            levels.push(level);
+            level = next_level;
+        }
+        levels.push(level);
+
+        Ok(SumMerkleTree {
+            options,
+            levels,
+            leaves: input.to_vec(),
+        })
+    }
+}
+
+impl<T, D: Digest, B: Balance> SumMerkleTree<T, D, B> {
+    /// Returns the hashing configuration the tree was built with.
+    pub fn options(&self) -> &TreeOptions {
+        &self.options
+    }
+
+    /// Returns the number of leaves in the tree.
+    pub fn leaf_count(&self) -> usize {
+        self.leaves.len()
+    }
+
+    /// Returns the hash of the root node of the tree.
+    ///
+    /// # Returns
+    ///
+    /// The hex encoded root, or `MerkleError::EmptyTree` if the tree has no leaves.
+    pub fn root(&self) -> Result<String, MerkleError> {
+        self.root_node().map(|node| hex::encode(&node.hash))
+    }
+
+    /// Returns the total of all balances committed to by the root.
+    ///
+    /// # Returns
+    ///
+    /// The total, or `MerkleError::EmptyTree` if the tree has no leaves.
+    pub fn total(&self) -> Result<B, MerkleError> {
+        self.root_node().map(|node| node.sum.clone())
+    }
+
+    fn root_node(&self) -> Result<&SumNode<D, B>, MerkleError> {
+        (self.levels.last())
+            .and_then(|level| level.first())
+            .ok_or(MerkleError::EmptyTree)
+    }
+
+    /// Generates the proof for the leaf at the given index.
+    ///
+    /// # Arguments
+    ///
+    /// * `index`: The position of the leaf in the input the tree was built from.
+    ///
+    /// # Returns
+    ///
+    /// A tuple of the leaf's user data and its `SumProof`, or `MerkleError::LeafNotFound` if
+    /// the index is out of bounds.
+    pub fn proof_by_index(&self, index: usize) -> Result<(&T, SumProof<D, B>), MerkleError> {
+        let leaf = self.leaves.get(index).ok_or(MerkleError::LeafNotFound)?;
+        let mut siblings = Vec::with_capacity(self.levels.len());
+        let mut directions = Vec::with_capacity(self.levels.len());
+
+        for (level, nodes) in self.levels.iter().enumerate().take(self.levels.len() - 1) {
+            let position = index >> level;
+            let Some(sibling) = nodes.get(position ^ 1) else {
+                // The unpaired node is promoted without a sibling.
+                continue;
+            };
+
+            siblings.push((hex::encode(&sibling.hash), sibling.sum.clone()));
+            directions.push(if position & 1 == 0 {
+                NodeDirection::Left
+            } else {
+                NodeDirection::Right
+            });
+        }
+
+        siblings.reverse();
+        directions.reverse();
+        Ok((leaf, SumProof::from_steps(siblings, directions)))
+    }
+}
+
+/// A Merkle sum proof for a single leaf.
+///
+/// Each step records the hash and sum of the sibling node and the direction taken from the
+/// parent node, ordered from the root down to the leaf.
+pub struct SumProof<D = Sha256, B = u64> {
+    pub siblings: Vec<(String, B)>,
+    pub directions: Vec<NodeDirection>,
+    digest: PhantomData<fn() -> D>,
+}
+
+impl<D, B: Clone> Clone for SumProof<D, B> {
+    fn clone(&self) -> Self {
+        Self::from_steps(self.siblings.clone(), self.directions.clone())
+    }
+}
+
+impl<D, B: fmt::Debug> fmt::Debug for SumProof<D, B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SumProof")
+            .field("siblings", &self.siblings)
+            .field("directions", &self.directions)
+            .finish()
+    }
+}
+
+impl<D, B> SumProof<D, B> {
+    /// Creates a `SumProof` from sibling hashes and sums and directions ordered from the
+    /// root down.
+    ///
+    /// # Arguments
+    ///
+    /// * `siblings`: The hex encoded sibling hashes with their sums.
+    /// * `directions`: The direction taken at each step.
+    pub fn from_steps(siblings: Vec<(String, B)>, directions: Vec<NodeDirection>) -> Self {
+        SumProof {
+            siblings,
+            directions,
+            digest: PhantomData,
+        }
+    }
+
+    /// Recomputes the root hash and total committed to by this proof for the given leaf.
+    ///
+    /// # Arguments
+    ///
+    /// * `options`: The options the tree was built with.
+    /// * `leaf_data`: The user data of the leaf this proof belongs to.
+    ///
+    /// # Returns
+    ///
+    /// The hex encoded root and the total, `MerkleError::InvalidProof` if the proof is
+    /// malformed or `MerkleError::Overflow` if the sum overflows.
+    pub fn compute_root_with_options<T>(
+        &self,
+        options: &TreeOptions,
+        leaf_data: &T,
+    ) -> Result<(String, B), MerkleError>
+    where
+        T: MerkleSumData<B>,
+        D: Digest,
+        B: Balance,
+    {
+        if self.siblings.len() != self.directions.len() {
+            return Err(MerkleError::InvalidProof);
+        }
+
+        let mut hash = options.hash_leaf_data::<D>(leaf_data);
+        let mut sum = leaf_data.balance();
+
+        for ((sibling, sibling_sum), direction) in
+            self.siblings.iter().zip(self.directions.iter()).rev()
+        {
+            let sibling = match hex::decode(sibling) {
+                Ok(bytes) if bytes.len() == hash.len() => Output::<D>::clone_from_slice(&bytes),
+                _ => return Err(MerkleError::InvalidProof),
+            };
+
+            let node = match direction {
+                NodeDirection::Left => {
+                    SumNode::<D, B>::branch(options, (&hash, &sum), (&sibling, sibling_sum))
+                }
+                NodeDirection::Right => {
+                    SumNode::<D, B>::branch(options, (&sibling, sibling_sum), (&hash, &sum))
+                }
+                NodeDirection::Root => return Err(MerkleError::InvalidProof),
+            };
+            let node = node.ok_or(MerkleError::Overflow)?;
+            hash = node.hash;
+            sum = node.sum;
+        }
+
+        Ok((hex::encode(hash), sum))
+    }
+}
+
+/// Verifies that the given leaf's balance is included in the published total of a Merkle
+/// sum tree.
+///
+/// # Arguments
+///
+/// * `tag_leaf`: The tag used for hashing leaf nodes.
+/// * `tag_branch`: The tag used for hashing branch nodes.
+/// * `root`: The hex encoded root to verify against.
+/// * `total`: The published total of all balances.
+/// * `leaf_data`: The user data that is claimed to be included in the tree.
+/// * `proof`: The proof returned by `SumMerkleTree::proof_by_index`.
+///
+/// # Returns
+///
+/// `true` if the proof reconstructs both the root and the total, `false` otherwise.
+pub fn verify_sum_proof<T, D, B>(
+    tag_leaf: &str,
+    tag_branch: &str,
+    root: &str,
+    total: B,
+    leaf_data: &T,
+    proof: &SumProof<D, B>,
+) -> bool
+where
+    T: MerkleSumData<B>,
+    D: Digest,
+    B: Balance,
+{
+    verify_sum_proof_with_options(
+        &TreeOptions::tagged(tag_leaf, tag_branch),
+        root,
+        total,
+        leaf_data,
+        proof,
+    )
+}
+
+/// Verifies that the given leaf's balance is included in the published total of a Merkle
+/// sum tree, using the hashing configuration the tree was built with.
+///
+/// # Arguments
+///
+/// * `options`: The options the tree was built with.
+/// * `root`: The hex encoded root to verify against.
+/// * `total`: The published total of all balances.
+/// * `leaf_data`: The user data that is claimed to be included in the tree.
+/// * `proof`: The proof returned by `SumMerkleTree::proof_by_index`.
+///
+/// # Returns
+///
+/// `true` if the proof reconstructs both the root and the total, `false` otherwise.
+pub fn verify_sum_proof_with_options<T, D, B>(
+    options: &TreeOptions,
+    root: &str,
+    total: B,
+    leaf_data: &T,
+    proof: &SumProof<D, B>,
+) -> bool
+where
+    T: MerkleSumData<B>,
+    D: Digest,
+    B: Balance,
+{
+    proof
+        .compute_root_with_options(options, leaf_data)
+        .is_ok_and(|(computed, sum)| roots_match(&computed, root) && sum == total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util;
+    use rstest::rstest;
+
+    #[test]
+    fn it_can_build_sum_tree() {
+        let user_data = util::generate_random_user_data(5);
+
+        let tree: SumMerkleTree<_> =
+            SumMerkleTree::build("ProofOfReserve_Leaf", "ProofOfReserve_Branch", &user_data)
+                .unwrap();
+
+        assert_eq!(
+            tree.root().unwrap(),
+            "72d7fa339f251ff4c87d67a459455ac18a44c1b93f7b060983e0614831e0f028"
+        );
+        assert_eq!(tree.total(), Ok(15000));
+    }
+
+    #[rstest]
+    #[case(1)]
+    #[case(2)]
+    #[case(7)]
+    #[case(16)]
+    fn it_can_verify_sum_proof(#[case] leaf_count: usize) {
+        let user_data = util::generate_random_user_data(leaf_count);
+        let total = user_data.iter().map(|data| data.balance as u64).sum();
+
+        let tree: SumMerkleTree<_> =
+            SumMerkleTree::build("ProofOfReserve_Leaf", "ProofOfReserve_Branch", &user_data)
+                .unwrap();
+        let root = tree.root().unwrap();
+        assert_eq!(tree.total(), Ok(total));
+        assert_eq!(
+            tree.proof_by_index(leaf_count).err(),
+            Some(MerkleError::LeafNotFound)
+        );
+
+        for index in 0..leaf_count {
+            let (leaf, proof) = tree.proof_by_index(index).unwrap();
+            assert!(verify_sum_proof(
+                "ProofOfReserve_Leaf",
+                "ProofOfReserve_Branch",
+                &root,
+                total,
+                leaf,
+                &proof
+            ));
+            assert!(!verify_sum_proof(
+                "ProofOfReserve_Leaf",
+                "ProofOfReserve_Branch",
+                &root,
+                total + 1,
+                leaf,
+                &proof
+            ));
+
+            if !proof.siblings.is_empty() {
+                let mut tampered = proof.clone();
+                tampered.siblings[0].1 -= 1;
+                assert!(!verify_sum_proof(
+                    "ProofOfReserve_Leaf",
+                    "ProofOfReserve_Branch",
+                    &root,
+                    total,
+                    leaf,
+                    &tampered
+                ));
+            }
+        }
+    }
+
+    #[test]
+    fn it_cannot_build_sum_tree_with_overflowing_total() {
+        #[derive(Clone)]
+        struct Whale(u64);
+
+        impl MerkleTreeData for Whale {
+            fn serialize(&self) -> Vec<u8> {
+                self.0.to_be_bytes().to_vec()
+            }
+
+            fn mermaid_node_label(&self) -> String {
+                String::new()
+            }
+        }
+
+        impl MerkleSumData for Whale {
+            fn balance(&self) -> u64 {
+                self.0
+            }
+        }
+
+        let tree: Result<SumMerkleTree<_>, _> =
+            SumMerkleTree::build("Leaf", "Branch", &[Whale(u64::MAX), Whale(1)]);
+        assert_eq!(tree.err(), Some(MerkleError::Overflow));
+    }
+}
@@ -0,0 +1,579 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use sha2::digest::Output;
+use sha2::Digest;
+
+use crate::multiproof::decode_hash;
+use crate::root::{hash_matches, roots_match};
+use crate::{MerkleError, MerkleTree, NodeStore, Padding, TreeOptions};
+
+impl<T, D: Digest, S: NodeStore<T, D>> MerkleTree<T, D, S> {
+    /// Returns the audit path of the leaf at the given index, as defined by RFC 6962.
+    ///
+    /// The audit path is the list of sibling hashes from the leaf up to the root. Together
+    /// with the leaf index and the leaf count it is the inclusion proof served by CT logs. It
+    /// is only meaningful for trees built with `TreeOptions::certificate_transparency`.
+    ///
+    /// # Arguments
+    ///
+    /// * `index`: The index of the leaf.
+    ///
+    /// # Returns
+    ///
+    /// The hex encoded sibling hashes, or `MerkleError::LeafNotFound` if the index is out of
+    /// bounds.
+    pub fn audit_path(&self, index: usize) -> Result<Vec<String>, MerkleError> {
+        let (_, path) = self.proof_by_index(index)?;
+        let mut siblings = path.siblings;
+        siblings.reverse();
+        Ok(siblings)
+    }
+
+    /// Generates a proof that the tree over the first `new_size` leaves extends the tree over
+    /// the first `old_size` leaves, as defined by RFC 6962.
+    ///
+    /// The proof shows that the older tree's leaves are unchanged and in the same order at the
+    /// start of the newer tree, so nothing committed to by the older root was removed or
+    /// rewritten. The tree must use `Padding::Promote`, as trees built with
+    /// `TreeOptions::certificate_transparency` do.
+    ///
+    /// # Arguments
+    ///
+    /// * `old_size`: The leaf count of the older tree, at least 1.
+    /// * `new_size`: The leaf count of the newer tree, at most the current leaf count.
+    ///
+    /// # Returns
+    ///
+    /// The hex encoded proof hashes, `MerkleError::InvalidRange` if the sizes are out of
+    /// bounds or `MerkleError::UnsupportedPadding` if the tree does not promote unpaired
+    /// nodes.
+    pub fn consistency_proof(
+        &self,
+        old_size: usize,
+        new_size: usize,
+    ) -> Result<Vec<String>, MerkleError> {
+        if self.options.padding != Padding::Promote {
+            return Err(MerkleError::UnsupportedPadding);
+        }
+        if old_size == 0 || old_size > new_size || new_size > self.leaf_count {
+            return Err(MerkleError::InvalidRange);
+        }
+
+        let mut proof = Vec::new();
+        self.consistency_subproof(old_size, 0, new_size, true, &mut proof)
+            .ok_or(MerkleError::InvalidRange)?;
+        Ok(proof.iter().map(hex::encode).collect())
+    }
+
+    /// Appends the hashes of the `SUBPROOF` of RFC 6962 over the leaves `start..end`.
+    ///
+    /// # Arguments
+    ///
+    /// * `old_size`: The number of leaves from `start` that are in the older tree.
+    /// * `start`: The first leaf of the subtree.
+    /// * `end`: The leaf after the last leaf of the subtree.
+    /// * `complete`: Whether the subtree over the older leaves is known to the verifier.
+    /// * `proof`: The proof to append to.
+    fn consistency_subproof(
+        &self,
+        old_size: usize,
+        start: usize,
+        end: usize,
+        complete: bool,
+        proof: &mut Vec<Output<D>>,
+    ) -> Option<()> {
+        if start + old_size == end {
+            if !complete {
+                proof.push(self.range_hash(start, end)?);
+            }
+            return Some(());
+        }
+
+        let split = largest_power_of_two_below(end - start);
+        if old_size <= split {
+            self.consistency_subproof(old_size, start, start + split, complete, proof)?;
+            proof.push(self.range_hash(start + split, end)?);
+        } else {
+            self.consistency_subproof(old_size - split, start + split, end, false, proof)?;
+            proof.push(self.range_hash(start, start + split)?);
+        }
+        Some(())
+    }
+
+    /// Calculates the hash of the subtree over the leaves `start..end`, splitting it at the
+    /// largest power of two as RFC 6962 does.
+    ///
+    /// `start` must be aligned to the size of the largest complete subtree in the range, which
+    /// holds for every range visited by `consistency_subproof`. Complete subtrees are nodes of
+    /// the tree, so only the hashes along the right edge of the range are computed.
+    ///
+    /// # Arguments
+    ///
+    /// * `start`: The first leaf of the range.
+    /// * `end`: The leaf after the last leaf of the range.
+    fn range_hash(&self, start: usize, end: usize) -> Option<Output<D>> {
+        let len = end - start;
+        if len.is_power_of_two() {
+            let level = len.trailing_zeros() as usize;
+            let node = self.node_at(level, start >> level)?;
+            return Some(self.nodes[node].hash.clone());
+        }
+
+        let split = largest_power_of_two_below(len);
+        let left = self.range_hash(start, start + split)?;
+        let right = self.range_hash(start + split, end)?;
+        Some(self.options.hash_branch::<D>(&left, &right))
+    }
+}
+
+/// Verifies an RFC 6962 inclusion proof, following the algorithm of RFC 9162 section 2.1.3.2.
+///
+/// # Arguments
+///
+/// * `root`: The hex encoded root of the tree, such as the `sha256_root_hash` of a CT log's
+///   signed tree head.
+/// * `leaf_index`: The index of the leaf.
+/// * `tree_size`: The number of leaves in the tree.
+/// * `leaf`: The leaf data, hashed as `H(0x00 || leaf)`.
+/// * `audit_path`: The hex encoded sibling hashes from the leaf up to the root.
+///
+/// # Returns
+///
+/// `true` if the audit path reconstructs the given root, `false` otherwise.
+pub fn verify_inclusion_proof<D: Digest>(
+    root: &str,
+    leaf_index: usize,
+    tree_size: usize,
+    leaf: &[u8],
+    audit_path: &[String],
+) -> bool {
+    verify_inclusion_proof_with_options::<D>(
+        &TreeOptions::certificate_transparency(),
+        root,
+        leaf_index,
+        tree_size,
+        leaf,
+        audit_path,
+    )
+}
+
+/// Verifies an inclusion proof of a tree that promotes unpaired nodes, using the hashing
+/// configuration the tree was built with.
+///
+/// # Arguments
+///
+/// * `options`: The options the tree was built with.
+/// * `root`: The hex encoded root of the tree.
+/// * `leaf_index`: The index of the leaf.
+/// * `tree_size`: The number of leaves in the tree.
+/// * `leaf`: The serialized leaf data.
+/// * `audit_path`: The hex encoded sibling hashes from the leaf up to the root.
+///
+/// # Returns
+///
+/// `true` if the audit path reconstructs the given root, `false` otherwise.
+pub fn verify_inclusion_proof_with_options<D: Digest>(
+    options: &TreeOptions,
+    root: &str,
+    leaf_index: usize,
+    tree_size: usize,
+    leaf: &[u8],
+    audit_path: &[String],
+) -> bool {
+    if leaf_index >= tree_size {
+        return false;
+    }
+
+    let mut index = leaf_index;
+    let mut last = tree_size - 1;
+    let mut hash = options.hash_leaf::<D>(leaf);
+
+    for sibling in audit_path {
+        let Some(sibling) = decode_hash::<D>(sibling) else {
+            return false;
+        };
+        if last == 0 {
+            return false;
+        }
+
+        if index & 1 == 1 || index == last {
+            hash = options.hash_branch::<D>(&sibling, &hash);
+            // The node was promoted over the levels where it had no sibling.
+            while index & 1 == 0 && index != 0 {
+                index >>= 1;
+                last >>= 1;
+            }
+        } else {
+            hash = options.hash_branch::<D>(&hash, &sibling);
+        }
+        index >>= 1;
+        last >>= 1;
+    }
+
+    last == 0 && hash_matches(&hash, root)
+}
+
+/// Verifies an RFC 6962 consistency proof, following the algorithm of RFC 9162 section
+/// 2.1.4.2.
+///
+/// # Arguments
+///
+/// * `old_root`: The hex encoded root of the older tree.
+/// * `new_root`: The hex encoded root of the newer tree.
+/// * `old_size`: The leaf count of the older tree.
+/// * `new_size`: The leaf count of the newer tree.
+/// * `proof`: The hex encoded proof hashes returned by `MerkleTree::consistency_proof`.
+///
+/// # Returns
+///
+/// `true` if the newer tree extends the older tree, `false` otherwise.
+pub fn verify_consistency_proof<D: Digest>(
+    old_root: &str,
+    new_root: &str,
+    old_size: usize,
+    new_size: usize,
+    proof: &[String],
+) -> bool {
+    verify_consistency_proof_with_options::<D>(
+        &TreeOptions::certificate_transparency(),
+        old_root,
+        new_root,
+        old_size,
+        new_size,
+        proof,
+    )
+}
+
+/// Verifies a consistency proof of a tree that promotes unpaired nodes, using the hashing
+/// configuration the tree was built with.
+///
+/// # Arguments
+///
+/// * `options`: The options the tree was built with.
+/// * `old_root`: The hex encoded root of the older tree.
+/// * `new_root`: The hex encoded root of the newer tree.
+/// * `old_size`: The leaf count of the older tree.
+/// * `new_size`: The leaf count of the newer tree.
+/// * `proof`: The hex encoded proof hashes returned by `MerkleTree::consistency_proof`.
+///
+/// # Returns
+///
+/// `true` if the newer tree extends the older tree, `false` otherwise.
+pub fn verify_consistency_proof_with_options<D: Digest>(
+    options: &TreeOptions,
+    old_root: &str,
+    new_root: &str,
+    old_size: usize,
+    new_size: usize,
+    proof: &[String],
+) -> bool {
+    if old_size == 0 || old_size > new_size {
+        return false;
+    }
+    if old_size == new_size {
+        return proof.is_empty() && roots_match(old_root, new_root);
+    }
+
+    let Some(mut hashes) = proof
+        .iter()
+        .map(|hash| decode_hash::<D>(hash))
+        .collect::<Option<Vec<_>>>()
+    else {
+        return false;
+    };
+    // The older root is part of the proof when the older tree is a complete subtree.
+    if old_size.is_power_of_two() {
+        let Some(old_hash) = decode_hash::<D>(old_root) else {
+            return false;
+        };
+        hashes.insert(0, old_hash);
+    }
+    let Some((first, rest)) = hashes.split_first() else {
+        return false;
+    };
+
+    let mut index = old_size - 1;
+    let mut last = new_size - 1;
+    while index & 1 == 1 {
+        index >>= 1;
+        last >>= 1;
+    }
+
+    let mut old_hash = first.clone();
+    let mut new_hash = first.clone();
+    for hash in rest {
+        if last == 0 {
+            return false;
+        }
+
+        if index & 1 == 1 || index == last {
+            old_hash = options.hash_branch::<D>(hash, &old_hash);
+            new_hash = options.hash_branch::<D>(hash, &new_hash);
+            while index & 1 == 0 && index != 0 {
+                index >>= 1;
+                last >>= 1;
+            }
+        } else {
+            new_hash = options.hash_branch::<D>(&new_hash, hash);
+        }
+        index >>= 1;
+        last >>= 1;
+    }
+
+    last == 0 && hash_matches(&old_hash, old_root) && hash_matches(&new_hash, new_root)
+}
+
+/// Returns the largest power of two that is smaller than `n`, which must be at least 2.
+fn largest_power_of_two_below(n: usize) -> usize {
+    1 << (usize::BITS - 1 - (n - 1).leading_zeros())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{util, RootAccumulator, Sha256};
+    use rstest::rstest;
+
+    fn rfc6962_leaves() -> Vec<Vec<u8>> {
+        [
+            "",
+            "00",
+            "10",
+            "2021",
+            "3031",
+            "40414243",
+            "5051525354555657",
+            "606162636465666768696a6b6c6d6e6f",
+        ]
+        .iter()
+        .map(|leaf| hex::decode(leaf).unwrap())
+        .collect()
+    }
+
+    fn generate_rfc6962_tree(size: usize) -> MerkleTree<()> {
+        MerkleTree::from_leaf_bytes_with_options(
+            TreeOptions::certificate_transparency(),
+            rfc6962_leaves()[..size].iter().map(Vec::as_slice),
+        )
+    }
+
+    #[rstest]
+    #[case(1, "6e340b9cffb37a989ca544e6bb780a2c78901d3fb33738768511a30617afa01d")]
+    #[case(2, "fac54203e7cc696cf0dfcb42c92a1d9dbaf70ad9e621f4bd8d98662f00e3c125")]
+    #[case(3, "aeb6bcfe274b70a14fb067a5e5578264db0fa9b51af5e0ba159158f329e06e77")]
+    #[case(4, "d37ee418976dd95753c1c73862b9398fa2a2cf9b4ff0fdfe8b30cd95209614b7")]
+    #[case(5, "4e3bbb1f7b478dcfe71fb631631519a3bca12c9aefca1612bfce4c13a86264d4")]
+    #[case(6, "76e67dadbcdf1e10e1b74ddc608abd2f98dfb16fbce75277b5232a127f2087ef")]
+    #[case(7, "ddb89be403809e325750d3d263cd78929c2942b7942a34b77e122c9594a74c8c")]
+    #[case(8, "5dc9da79a70659a9ad559cb701ded9a2ab9d823aad2f4960cfe370eff4604328")]
+    fn it_can_reproduce_rfc6962_root(#[case] size: usize, #[case] expected: &str) {
+        let tree = generate_rfc6962_tree(size);
+        assert_eq!(tree.root().unwrap(), expected);
+
+        let options = TreeOptions::certificate_transparency();
+        let mut accumulator: RootAccumulator = RootAccumulator::new(options.clone());
+        for leaf in &rfc6962_leaves()[..size] {
+            accumulator.push_leaf_hash(options.hash_leaf::<Sha256>(leaf));
+        }
+        assert_eq!(accumulator.root().unwrap(), expected);
+    }
+
+    #[rstest]
+    #[case(
+        0,
+        8,
+        &[
+            "96a296d224f285c67bee93c30f8a309157f0daa35dc5b87e410b78630a09cfc7",
+            "5f083f0a1a33ca076a95279832580db3e0ef4584bdff1f54c8a360f50de3031e",
+            "6b47aaf29ee3c2af9af889bc1fb9254dabd31177f16232dd6aab035ca39bf6e4",
+        ]
+    )]
+    #[case(
+        5,
+        8,
+        &[
+            "bc1a0643b12e4d2d7c77918f44e0f4f79a838b6cf9ec5b5c283e1f4d88599e6b",
+            "ca854ea128ed050b41b35ffc1b87b8eb2bde461e9e3b5596ece6b9d5975a0ae0",
+            "d37ee418976dd95753c1c73862b9398fa2a2cf9b4ff0fdfe8b30cd95209614b7",
+        ]
+    )]
+    #[case(
+        2,
+        3,
+        &["fac54203e7cc696cf0dfcb42c92a1d9dbaf70ad9e621f4bd8d98662f00e3c125"]
+    )]
+    #[case(
+        1,
+        5,
+        &[
+            "6e340b9cffb37a989ca544e6bb780a2c78901d3fb33738768511a30617afa01d",
+            "5f083f0a1a33ca076a95279832580db3e0ef4584bdff1f54c8a360f50de3031e",
+            "bc1a0643b12e4d2d7c77918f44e0f4f79a838b6cf9ec5b5c283e1f4d88599e6b",
+        ]
+    )]
+    fn it_can_reproduce_rfc6962_audit_path(
+        #[case] index: usize,
+        #[case] size: usize,
+        #[case] expected: &[&str],
+    ) {
+        let tree = generate_rfc6962_tree(size);
+        assert_eq!(tree.audit_path(index).unwrap(), expected);
+    }
+
+    #[test]
+    fn it_can_verify_inclusion_proofs() {
+        let leaves = rfc6962_leaves();
+        for size in 1..=leaves.len() {
+            let tree = generate_rfc6962_tree(size);
+            let root = tree.root().unwrap();
+
+            for (index, leaf) in leaves[..size].iter().enumerate() {
+                let path = tree.audit_path(index).unwrap();
+                assert!(verify_inclusion_proof::<Sha256>(
+                    &root, index, size, leaf, &path
+                ));
+                assert!(!verify_inclusion_proof::<Sha256>(
+                    &root,
+                    index,
+                    size,
+                    &leaves[(index + 1) % leaves.len()],
+                    &path
+                ));
+                assert!(!verify_inclusion_proof::<Sha256>(
+                    &root,
+                    index ^ 1,
+                    size,
+                    leaf,
+                    &path
+                ));
+                if let Some((_, shorter)) = path.split_last() {
+                    assert!(!verify_inclusion_proof::<Sha256>(
+                        &root, index, size, leaf, shorter
+                    ));
+                }
+            }
+        }
+    }
+
+    #[rstest]
+    #[case(1, 1, &[])]
+    #[case(
+        1,
+        8,
+        &[
+            "96a296d224f285c67bee93c30f8a309157f0daa35dc5b87e410b78630a09cfc7",
+            "5f083f0a1a33ca076a95279832580db3e0ef4584bdff1f54c8a360f50de3031e",
+            "6b47aaf29ee3c2af9af889bc1fb9254dabd31177f16232dd6aab035ca39bf6e4",
+        ]
+    )]
+    #[case(
+        6,
+        8,
+        &[
+            "0ebc5d3437fbe2db158b9f126a1d118e308181031d0a949f8dededebc558ef6a",
+            "ca854ea128ed050b41b35ffc1b87b8eb2bde461e9e3b5596ece6b9d5975a0ae0",
+            "d37ee418976dd95753c1c73862b9398fa2a2cf9b4ff0fdfe8b30cd95209614b7",
+        ]
+    )]
+    #[case(
+        2,
+        5,
+        &[
+            "5f083f0a1a33ca076a95279832580db3e0ef4584bdff1f54c8a360f50de3031e",
+            "bc1a0643b12e4d2d7c77918f44e0f4f79a838b6cf9ec5b5c283e1f4d88599e6b",
+        ]
+    )]
+    #[case(
+        4,
+        7,
+        &["837dbb152e9b079010717e84e865da4ebc0fa198a806d59d31bf15accef22d0e"]
+    )]
+    fn it_can_reproduce_rfc6962_consistency_proof(
+        #[case] old_size: usize,
+        #[case] new_size: usize,
+        #[case] expected: &[&str],
+    ) {
+        let tree = generate_rfc6962_tree(8);
+        assert_eq!(
+            tree.consistency_proof(old_size, new_size).unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn it_can_verify_consistency_proofs() {
+        let tree = generate_rfc6962_tree(8);
+        let roots: Vec<String> = (1..=8)
+            .map(|size| generate_rfc6962_tree(size).root().unwrap())
+            .collect();
+
+        for old_size in 1..=8 {
+            for new_size in old_size..=8 {
+                let proof = tree.consistency_proof(old_size, new_size).unwrap();
+                let (old_root, new_root) = (&roots[old_size - 1], &roots[new_size - 1]);
+                assert!(verify_consistency_proof::<Sha256>(
+                    old_root, new_root, old_size, new_size, &proof
+                ));
+
+                if old_size < new_size {
+                    assert!(!verify_consistency_proof::<Sha256>(
+                        new_root, new_root, old_size, new_size, &proof
+                    ));
+                    assert!(!verify_consistency_proof::<Sha256>(
+                        old_root,
+                        new_root,
+                        old_size,
+                        new_size,
+                        &proof[1..]
+                    ));
+                }
+            }
+        }
+
+        assert_eq!(tree.consistency_proof(0, 4), Err(MerkleError::InvalidRange));
+        assert_eq!(tree.consistency_proof(5, 4), Err(MerkleError::InvalidRange));
+        assert_eq!(tree.consistency_proof(4, 9), Err(MerkleError::InvalidRange));
+
+        let duplicated: MerkleTree<()> = MerkleTree::from_leaf_bytes_with_options(
+            TreeOptions::certificate_transparency().with_padding(Padding::DuplicateLast),
+            rfc6962_leaves().iter().map(Vec::as_slice),
+        );
+        assert_eq!(
+            duplicated.consistency_proof(4, 8),
+            Err(MerkleError::UnsupportedPadding)
+        );
+    }
+
+    #[test]
+    fn it_can_prove_consistency_between_epochs() {
+        let options = TreeOptions::tagged("ProofOfReserve_Leaf", "ProofOfReserve_Branch")
+            .with_padding(Padding::Promote);
+        let user_data = util::generate_random_user_data(12);
+
+        let old: MerkleTree<_> = MerkleTree::build_with_options(options.clone(), &user_data[..5]);
+        let new: MerkleTree<_> = MerkleTree::build_with_options(options.clone(), &user_data);
+        let proof = new.consistency_proof(5, 12).unwrap();
+        assert!(verify_consistency_proof_with_options::<Sha256>(
+            &options,
+            &old.root().unwrap(),
+            &new.root().unwrap(),
+            5,
+            12,
+            &proof
+        ));
+
+        // An account that disappeared from the newer tree breaks the proof.
+        let mut rewritten = user_data.clone();
+        rewritten.remove(2);
+        let rewritten: MerkleTree<_> = MerkleTree::build_with_options(options.clone(), &rewritten);
+        let proof = rewritten.consistency_proof(5, 11).unwrap();
+        assert!(!verify_consistency_proof_with_options::<Sha256>(
+            &options,
+            &old.root().unwrap(),
+            &rewritten.root().unwrap(),
+            5,
+            11,
+            &proof
+        ));
+    }
+}
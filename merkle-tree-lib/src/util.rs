@@ -1,14 +1,29 @@
-use crate::MerkleTreeData;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
 
+use crate::{
+    AssetBalances, Balance, BigBalance, Decimal, MerkleSumData, MerkleTreeData, MerkleTreeKey,
+};
+
+/// Example user data with an id and a balance.
+///
+/// The balance is a `u32` by default. Use `u64` or `u128` for balances in satoshis or wei,
+/// which a `u32` cannot hold, `Decimal` for amounts with decimals or `BigBalance` for
+/// integers of any size, and `AssetBalances` for balances in several assets. `u32` and `u64`
+/// balances are summed up as `u64` by a `SumMerkleTree`, and the others as their own type.
 #[derive(Debug, Default, Clone)]
-pub struct UserData {
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct UserData<B = u32> {
     pub id: u32,
-    pub balance: u32,
+    pub balance: B,
 }
 
-impl MerkleTreeData for UserData {
+impl<B: fmt::Display> MerkleTreeData for UserData<B> {
     fn serialize(&self) -> Vec<u8> {
-        format!("{},{}", self.id, self.balance).as_bytes().to_vec()
+        format!("{},{}", self.id, self.balance).into_bytes()
     }
 
     fn mermaid_node_label(&self) -> String {
@@ -16,6 +31,52 @@ impl MerkleTreeData for UserData {
     }
 }
 
+impl MerkleSumData for UserData<u32> {
+    fn balance(&self) -> u64 {
+        self.balance.into()
+    }
+}
+
+impl MerkleSumData for UserData<u64> {
+    fn balance(&self) -> u64 {
+        self.balance
+    }
+}
+
+impl MerkleSumData<u128> for UserData<u128> {
+    fn balance(&self) -> u128 {
+        self.balance
+    }
+}
+
+impl<const DECIMALS: u32> MerkleSumData<Decimal<DECIMALS>> for UserData<Decimal<DECIMALS>> {
+    fn balance(&self) -> Decimal<DECIMALS> {
+        self.balance
+    }
+}
+
+impl MerkleSumData<BigBalance> for UserData<BigBalance> {
+    fn balance(&self) -> BigBalance {
+        self.balance.clone()
+    }
+}
+
+impl<B: Balance + fmt::Display, const ASSETS: usize> MerkleSumData<AssetBalances<B, ASSETS>>
+    for UserData<AssetBalances<B, ASSETS>>
+{
+    fn balance(&self) -> AssetBalances<B, ASSETS> {
+        self.balance.clone()
+    }
+}
+
+impl<B> MerkleTreeKey for UserData<B> {
+    type Key = u32;
+
+    fn key(&self) -> u32 {
+        self.id
+    }
+}
+
 pub fn generate_random_user_data(n: usize) -> Vec<UserData> {
     vec![0; n]
         .iter()
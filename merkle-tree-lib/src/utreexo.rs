@@ -0,0 +1,478 @@
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+use core::marker::PhantomData;
+use sha2::digest::Output;
+use sha2::Digest;
+
+use crate::multiproof::decode_hash;
+use crate::{constant_time_eq, MerkleError, MerkleTreeData, NodeDirection, Sha256, TreeOptions};
+
+/// A Utreexo accumulator, which commits to a set of leaves with one root per perfect subtree
+/// of a forest, without storing the leaves.
+///
+/// The forest has a tree of height `h` for each set bit `h` of the leaf count. Adding a leaf
+/// merges trees of equal height like a binary counter. Deleting a leaf with its proof breaks
+/// its tree into the perfect subtrees hanging off the path, the siblings of the proof, which
+/// are merged back with the other roots, so both take O(log n) hashes.
+pub struct UtreexoAccumulator<D: Digest = Sha256> {
+    options: TreeOptions,
+    roots: Vec<Option<Output<D>>>,
+    leaf_count: usize,
+}
+
+impl<D: Digest> Clone for UtreexoAccumulator<D> {
+    fn clone(&self) -> Self {
+        UtreexoAccumulator {
+            options: self.options.clone(),
+            roots: self.roots.clone(),
+            leaf_count: self.leaf_count,
+        }
+    }
+}
+
+impl<D: Digest> UtreexoAccumulator<D> {
+    /// Creates an empty accumulator.
+    ///
+    /// # Arguments
+    ///
+    /// * `options`: The hashing configuration. The padding policy is not used.
+    pub fn new(options: TreeOptions) -> Self {
+        UtreexoAccumulator {
+            options,
+            roots: Vec::new(),
+            leaf_count: 0,
+        }
+    }
+
+    /// Returns the hashing configuration of the accumulator.
+    pub fn options(&self) -> &TreeOptions {
+        &self.options
+    }
+
+    /// Returns the number of leaves in the set.
+    pub fn leaf_count(&self) -> usize {
+        self.leaf_count
+    }
+
+    /// Returns the hashes of the roots, from the highest tree to the lowest.
+    pub fn roots(&self) -> Vec<String> {
+        self.roots.iter().rev().flatten().map(hex::encode).collect()
+    }
+
+    /// Adds a leaf.
+    ///
+    /// # Arguments
+    ///
+    /// * `leaf`: The user data of the leaf.
+    pub fn add<T: MerkleTreeData>(&mut self, leaf: &T) {
+        self.add_leaf_hash(self.options.hash_leaf_data::<D>(leaf));
+    }
+
+    /// Adds a leaf hash that was computed elsewhere.
+    ///
+    /// # Arguments
+    ///
+    /// * `hash`: The hash of the leaf.
+    pub fn add_leaf_hash(&mut self, hash: Output<D>) {
+        let mut hash = hash;
+        for height in 0.. {
+            if height == self.roots.len() {
+                self.roots.push(None);
+            }
+            match self.roots[height].take() {
+                Some(root) => hash = self.options.hash_branch::<D>(&root, &hash),
+                None => {
+                    self.roots[height] = Some(hash);
+                    break;
+                }
+            }
+        }
+        self.leaf_count += 1;
+    }
+
+    /// Verifies that a leaf is in the set.
+    ///
+    /// # Arguments
+    ///
+    /// * `leaf`: The user data of the leaf.
+    /// * `proof`: The proof returned by `UtreexoForest::proof` for the current set.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the proof reconstructs the root of a tree of the forest, `false` otherwise.
+    pub fn verify<T: MerkleTreeData>(&self, leaf: &T, proof: &UtreexoProof<D>) -> bool {
+        let hash = self.options.hash_leaf_data::<D>(leaf);
+        self.verify_leaf_hash(&hash, proof).is_some()
+    }
+
+    /// Deletes a leaf from the set.
+    ///
+    /// # Arguments
+    ///
+    /// * `leaf`: The user data of the leaf.
+    /// * `proof`: The proof returned by `UtreexoForest::proof` for the current set.
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())`, or `MerkleError::InvalidProof` if the proof does not verify, in which case
+    /// the accumulator is unchanged.
+    pub fn delete<T: MerkleTreeData>(
+        &mut self,
+        leaf: &T,
+        proof: &UtreexoProof<D>,
+    ) -> Result<(), MerkleError> {
+        let hash = self.options.hash_leaf_data::<D>(leaf);
+        let siblings = self
+            .verify_leaf_hash(&hash, proof)
+            .ok_or(MerkleError::InvalidProof)?;
+
+        let height = siblings.len();
+        let mut carry: Option<Output<D>> = None;
+        for (level, sibling) in siblings.into_iter().enumerate() {
+            carry = match (carry, self.roots[level].take()) {
+                (Some(carry), root) => {
+                    self.roots[level] = root;
+                    Some(self.options.hash_branch::<D>(&sibling, &carry))
+                }
+                (None, None) => {
+                    self.roots[level] = Some(sibling);
+                    None
+                }
+                (None, Some(root)) => Some(self.options.hash_branch::<D>(&sibling, &root)),
+            };
+        }
+        self.roots[height] = carry;
+        self.leaf_count -= 1;
+        Ok(())
+    }
+
+    /// Recomputes the root of the tree of a leaf and compares it with the accumulator.
+    ///
+    /// # Returns
+    ///
+    /// The decoded siblings of the proof if it verifies, `None` otherwise.
+    fn verify_leaf_hash(
+        &self,
+        hash: &Output<D>,
+        proof: &UtreexoProof<D>,
+    ) -> Option<Vec<Output<D>>> {
+        if proof.siblings.len() != proof.directions.len() {
+            return None;
+        }
+
+        let siblings = proof
+            .siblings
+            .iter()
+            .map(|sibling| decode_hash::<D>(sibling))
+            .collect::<Option<Vec<_>>>()?;
+        let mut hash = hash.clone();
+        for (sibling, direction) in siblings.iter().zip(&proof.directions) {
+            hash = match direction {
+                NodeDirection::Left => self.options.hash_branch::<D>(&hash, sibling),
+                NodeDirection::Right => self.options.hash_branch::<D>(sibling, &hash),
+                NodeDirection::Root => return None,
+            };
+        }
+
+        let root = self.roots.get(siblings.len())?.as_ref()?;
+        constant_time_eq(root, &hash).then_some(siblings)
+    }
+}
+
+/// A proof that a leaf is in a Utreexo set.
+///
+/// Holds the siblings from the leaf up to the root of its tree, and for each of them whether
+/// the path goes through the left or the right child. A proof is only valid until the next
+/// deletion from the set, which may move the leaf to another tree.
+pub struct UtreexoProof<D = Sha256> {
+    pub siblings: Vec<String>,
+    pub directions: Vec<NodeDirection>,
+    digest: PhantomData<fn() -> D>,
+}
+
+impl<D> Clone for UtreexoProof<D> {
+    fn clone(&self) -> Self {
+        UtreexoProof {
+            siblings: self.siblings.clone(),
+            directions: self.directions.clone(),
+            digest: PhantomData,
+        }
+    }
+}
+
+impl<D> fmt::Debug for UtreexoProof<D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UtreexoProof")
+            .field("siblings", &self.siblings)
+            .field("directions", &self.directions)
+            .finish()
+    }
+}
+
+/// A node of a `UtreexoForest`.
+struct ForestNode<D: Digest> {
+    hash: Output<D>,
+    children: Option<(usize, usize)>,
+    parent: Option<usize>,
+}
+
+/// The whole forest of a Utreexo set, which keeps every node to generate the proofs that an
+/// `UtreexoAccumulator` verifies.
+///
+/// Leaves and branches are added and merged exactly as by `UtreexoAccumulator`, so the roots
+/// of the forest always match those of an accumulator given the same additions and deletions.
+pub struct UtreexoForest<D: Digest = Sha256> {
+    accumulator: UtreexoAccumulator<D>,
+    nodes: Vec<ForestNode<D>>,
+    free: Vec<usize>,
+    roots: Vec<Option<usize>>,
+    leaves: BTreeMap<Output<D>, usize>,
+}
+
+impl<D: Digest> UtreexoForest<D> {
+    /// Creates an empty forest.
+    ///
+    /// # Arguments
+    ///
+    /// * `options`: The hashing configuration. The padding policy is not used.
+    pub fn new(options: TreeOptions) -> Self {
+        UtreexoForest {
+            accumulator: UtreexoAccumulator::new(options),
+            nodes: Vec::new(),
+            free: Vec::new(),
+            roots: Vec::new(),
+            leaves: BTreeMap::new(),
+        }
+    }
+
+    /// Returns the accumulator of the current set, which only holds the roots.
+    pub fn accumulator(&self) -> &UtreexoAccumulator<D> {
+        &self.accumulator
+    }
+
+    /// Returns the number of leaves in the set.
+    pub fn leaf_count(&self) -> usize {
+        self.leaves.len()
+    }
+
+    /// Adds a leaf.
+    ///
+    /// # Arguments
+    ///
+    /// * `leaf`: The user data of the leaf.
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())`, or `MerkleError::DuplicateKey` if a leaf with the same hash is in the set.
+    pub fn add<T: MerkleTreeData>(&mut self, leaf: &T) -> Result<(), MerkleError> {
+        let hash = self.accumulator.options.hash_leaf_data::<D>(leaf);
+        self.add_leaf_hash(hash)
+    }
+
+    /// Adds a leaf hash that was computed elsewhere.
+    ///
+    /// # Arguments
+    ///
+    /// * `hash`: The hash of the leaf.
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())`, or `MerkleError::DuplicateKey` if the hash is in the set.
+    pub fn add_leaf_hash(&mut self, hash: Output<D>) -> Result<(), MerkleError> {
+        if self.leaves.contains_key(&hash) {
+            return Err(MerkleError::DuplicateKey);
+        }
+
+        self.accumulator.add_leaf_hash(hash.clone());
+        let mut node = self.new_node(hash.clone(), None);
+        self.leaves.insert(hash, node);
+
+        for height in 0.. {
+            if height == self.roots.len() {
+                self.roots.push(None);
+            }
+            match self.roots[height].take() {
+                Some(root) => node = self.new_branch(root, node),
+                None => {
+                    self.roots[height] = Some(node);
+                    break;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Generates the proof that a leaf is in the current set.
+    ///
+    /// # Arguments
+    ///
+    /// * `leaf`: The user data of the leaf.
+    ///
+    /// # Returns
+    ///
+    /// The proof, or `MerkleError::LeafNotFound` if the leaf is not in the set.
+    pub fn proof<T: MerkleTreeData>(&self, leaf: &T) -> Result<UtreexoProof<D>, MerkleError> {
+        let hash = self.accumulator.options.hash_leaf_data::<D>(leaf);
+        let mut node = *self.leaves.get(&hash).ok_or(MerkleError::LeafNotFound)?;
+
+        let mut siblings = Vec::new();
+        let mut directions = Vec::new();
+        while let Some(parent) = self.nodes[node].parent {
+            let (left, right) = self.nodes[parent]
+                .children
+                .ok_or(MerkleError::InvalidProof)?;
+            let (sibling, direction) = if left == node {
+                (right, NodeDirection::Left)
+            } else {
+                (left, NodeDirection::Right)
+            };
+            siblings.push(hex::encode(&self.nodes[sibling].hash));
+            directions.push(direction);
+            node = parent;
+        }
+
+        Ok(UtreexoProof {
+            siblings,
+            directions,
+            digest: PhantomData,
+        })
+    }
+
+    /// Deletes a leaf from the set.
+    ///
+    /// # Arguments
+    ///
+    /// * `leaf`: The user data of the leaf.
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())`, or `MerkleError::LeafNotFound` if the leaf is not in the set.
+    pub fn delete<T: MerkleTreeData>(&mut self, leaf: &T) -> Result<(), MerkleError> {
+        let proof = self.proof(leaf)?;
+        self.accumulator.delete(leaf, &proof)?;
+
+        let hash = self.accumulator.options.hash_leaf_data::<D>(leaf);
+        let mut node = self.leaves.remove(&hash).ok_or(MerkleError::LeafNotFound)?;
+
+        // Detach the siblings along the path and free the nodes on the path.
+        let mut siblings = Vec::new();
+        while let Some(parent) = self.nodes[node].parent {
+            let (left, right) = self.nodes[parent]
+                .children
+                .ok_or(MerkleError::InvalidProof)?;
+            let sibling = if left == node { right } else { left };
+            self.nodes[sibling].parent = None;
+            siblings.push(sibling);
+            self.free.push(node);
+            node = parent;
+        }
+        self.free.push(node);
+
+        let height = siblings.len();
+        let mut carry = None;
+        for (level, sibling) in siblings.into_iter().enumerate() {
+            carry = match (carry, self.roots[level].take()) {
+                (Some(carry), root) => {
+                    self.roots[level] = root;
+                    Some(self.new_branch(sibling, carry))
+                }
+                (None, None) => {
+                    self.roots[level] = Some(sibling);
+                    None
+                }
+                (None, Some(root)) => Some(self.new_branch(sibling, root)),
+            };
+        }
+        self.roots[height] = carry;
+        Ok(())
+    }
+
+    /// Stores a node, reusing the slot of a deleted one if there is any.
+    fn new_node(&mut self, hash: Output<D>, children: Option<(usize, usize)>) -> usize {
+        let node = ForestNode {
+            hash,
+            children,
+            parent: None,
+        };
+        match self.free.pop() {
+            Some(index) => {
+                self.nodes[index] = node;
+                index
+            }
+            None => {
+                self.nodes.push(node);
+                self.nodes.len() - 1
+            }
+        }
+    }
+
+    /// Creates a branch node over the given roots of subtrees.
+    fn new_branch(&mut self, left: usize, right: usize) -> usize {
+        let hash = self
+            .accumulator
+            .options
+            .hash_branch::<D>(&self.nodes[left].hash, &self.nodes[right].hash);
+        let branch = self.new_node(hash, Some((left, right)));
+        self.nodes[left].parent = Some(branch);
+        self.nodes[right].parent = Some(branch);
+        branch
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util;
+
+    #[test]
+    fn it_can_add_and_delete_utreexo_leaves() {
+        let user_data = util::generate_random_user_data(13);
+        let options = TreeOptions::tagged("ProofOfReserve_Leaf", "ProofOfReserve_Branch");
+        let mut forest: UtreexoForest = UtreexoForest::new(options.clone());
+        let mut accumulator: UtreexoAccumulator = UtreexoAccumulator::new(options);
+
+        for item in &user_data {
+            forest.add(item).unwrap();
+            accumulator.add(item);
+        }
+        assert_eq!(forest.add(&user_data[0]), Err(MerkleError::DuplicateKey));
+        // 13 = 8 + 4 + 1 leaves make three trees.
+        assert_eq!(accumulator.roots().len(), 3);
+        assert_eq!(accumulator.roots(), forest.accumulator().roots());
+
+        for (deleted, item) in user_data.iter().enumerate().rev().step_by(2) {
+            let proof = forest.proof(item).unwrap();
+            assert!(proof.siblings.len() <= 3);
+
+            assert_eq!(
+                accumulator.delete(&user_data[(deleted + 2) % 13], &proof),
+                Err(MerkleError::InvalidProof)
+            );
+            accumulator.delete(item, &proof).unwrap();
+            forest.delete(item).unwrap();
+            assert_eq!(accumulator.roots(), forest.accumulator().roots());
+            assert_eq!(accumulator.leaf_count(), forest.leaf_count());
+
+            assert!(!accumulator.verify(item, &proof));
+            assert_eq!(forest.proof(item).unwrap_err(), MerkleError::LeafNotFound);
+        }
+        assert_eq!(accumulator.leaf_count(), 6);
+
+        for (i, item) in user_data.iter().enumerate() {
+            match forest.proof(item) {
+                Ok(proof) => assert!(i % 2 == 1 && accumulator.verify(item, &proof)),
+                Err(error) => assert!(i % 2 == 0 && error == MerkleError::LeafNotFound),
+            }
+        }
+
+        for item in user_data.iter().skip(1).step_by(2) {
+            let proof = forest.proof(item).unwrap();
+            accumulator.delete(item, &proof).unwrap();
+            forest.delete(item).unwrap();
+        }
+        assert!(accumulator.roots().is_empty());
+        assert!(forest.accumulator().roots().is_empty());
+    }
+}
@@ -0,0 +1,353 @@
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use sha2::Digest;
+
+use crate::{MerkleError, MerkleNode, MerkleTree, NodeStore, TraversePath};
+
+/// A handle to a version of a `MerkleTree`, returned by `MerkleTree::version`.
+///
+/// Once a version is handed out, `MerkleTree::update` and `MerkleTree::push` add the nodes
+/// they change next to the old ones instead of overwriting them, so the earlier version shares
+/// its unchanged subtrees with the current one and can still serve roots and proofs, for
+/// example for the previous epoch while the next one is being built. A tree that never hands
+/// out a version is changed in place. `MerkleTree::remove` and `MerkleTree::compact` discard
+/// the old nodes and invalidate the earlier versions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TreeVersion {
+    root: Option<usize>,
+    leaf_count: usize,
+    generation: usize,
+}
+
+impl TreeVersion {
+    /// Returns the number of leaves in this version of the tree.
+    pub fn leaf_count(&self) -> usize {
+        self.leaf_count
+    }
+}
+
+impl<T, D: Digest, S: NodeStore<T, D>> MerkleTree<T, D, S> {
+    /// Returns a handle to the current version of the tree.
+    ///
+    /// The nodes of the current version are kept from then on, so that later changes copy
+    /// them rather than overwrite them, until `compact` discards them.
+    pub fn version(&mut self) -> TreeVersion {
+        self.pinned = self.nodes.len();
+        TreeVersion {
+            root: self.root,
+            leaf_count: self.leaf_count,
+            generation: self.generation,
+        }
+    }
+
+    /// Takes a snapshot of the tree to roll back to with `restore`, for example when a batch
+    /// of updates fails validation partway through. It is the same handle as `version`.
+    pub fn snapshot(&mut self) -> TreeVersion {
+        self.version()
+    }
+
+    /// Rolls the tree back to the given snapshot in O(1) time.
+    ///
+    /// The nodes added since the snapshot are kept until `compact`, so versions taken after
+    /// the snapshot can still be restored as well.
+    ///
+    /// # Arguments
+    ///
+    /// * `snapshot`: A handle returned by `snapshot` or `version`.
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` if the tree was rolled back, or `MerkleError::StaleVersion` if the snapshot was
+    /// discarded by `remove` or `compact`.
+    pub fn restore(&mut self, snapshot: TreeVersion) -> Result<(), MerkleError> {
+        self.check_version(snapshot)?;
+        self.root = snapshot.root;
+        self.leaf_count = snapshot.leaf_count;
+        Ok(())
+    }
+
+    /// Returns the hex encoded root of the given version of the tree.
+    ///
+    /// # Arguments
+    ///
+    /// * `version`: A handle returned by `version`.
+    ///
+    /// # Returns
+    ///
+    /// The root, `MerkleError::StaleVersion` if the version was discarded by `remove` or
+    /// `compact`, or `MerkleError::EmptyTree` if the version has no leaves and the empty root
+    /// is `EmptyRoot::Undefined`.
+    pub fn root_at(&self, version: TreeVersion) -> Result<String, MerkleError> {
+        self.check_version(version)?;
+        match version.root {
+            Some(node) => Ok(hex::encode(&self.nodes[node].hash)),
+            None => self
+                .options
+                .empty_root_hash::<D>()
+                .map(hex::encode)
+                .ok_or(MerkleError::EmptyTree),
+        }
+    }
+
+    /// Generates the proof for the leaf at the given index of the given version of the tree.
+    ///
+    /// # Arguments
+    ///
+    /// * `version`: A handle returned by `version`.
+    /// * `index`: The position of the leaf in that version.
+    ///
+    /// # Returns
+    ///
+    /// A tuple of `(&MerkleNode, TraversePath)` that verifies against `root_at(version)`,
+    /// `MerkleError::StaleVersion` if the version was discarded by `remove` or `compact`, or
+    /// `MerkleError::LeafNotFound` if the index is out of range.
+    pub fn proof_by_index_at(
+        &self,
+        version: TreeVersion,
+        index: usize,
+    ) -> Result<(&MerkleNode<T, D>, TraversePath<D>), MerkleError> {
+        self.check_version(version)?;
+        self.proof_in(version.root, version.leaf_count, index)
+    }
+
+    /// Returns the leaf node at the given index of the given version of the tree.
+    ///
+    /// # Arguments
+    ///
+    /// * `version`: A handle returned by `version`.
+    /// * `index`: The position of the leaf in that version.
+    ///
+    /// # Returns
+    ///
+    /// The leaf node, `MerkleError::StaleVersion` if the version was discarded by `remove` or
+    /// `compact`, or `MerkleError::LeafNotFound` if the index is out of range.
+    pub fn leaf_at(
+        &self,
+        version: TreeVersion,
+        index: usize,
+    ) -> Result<&MerkleNode<T, D>, MerkleError> {
+        self.check_version(version)?;
+        self.node_in(version.root, version.leaf_count, 0, index)
+            .map(|node| &self.nodes[node])
+            .ok_or(MerkleError::LeafNotFound)
+    }
+
+    /// Discards the nodes that are only used by earlier versions of the tree.
+    ///
+    /// The nodes of the current version are moved without rehashing, so this takes O(n)
+    /// time. All earlier versions are invalidated.
+    pub fn compact(&mut self) {
+        let mut remap = vec![None; self.nodes.len()];
+        let mut order = Vec::new();
+        let mut stack: Vec<usize> = self.root.into_iter().chain(self.empty_leaf).collect();
+
+        while let Some(node) = stack.pop() {
+            if remap[node].is_some() {
+                continue;
+            }
+            remap[node] = Some(order.len());
+            order.push(node);
+            stack.extend(self.nodes[node].right);
+            stack.extend(self.nodes[node].left);
+        }
+
+        let mut nodes: Vec<Option<MerkleNode<T, D>>> =
+            self.nodes.take_nodes().into_iter().map(Some).collect();
+        let new_index = |node: usize| remap[node].unwrap();
+
+        for mut node in order.into_iter().filter_map(|node| nodes[node].take()) {
+            node.left = node.left.map(new_index);
+            node.right = node.right.map(new_index);
+            self.nodes.push(node);
+        }
+        self.root = self.root.map(new_index);
+        self.empty_leaf = self.empty_leaf.map(new_index);
+        self.generation += 1;
+        self.pinned = 0;
+    }
+
+    /// Returns an error if the version was invalidated by rebuilding the node arena.
+    fn check_version(&self, version: TreeVersion) -> Result<(), MerkleError> {
+        if version.generation == self.generation {
+            Ok(())
+        } else {
+            Err(MerkleError::StaleVersion)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{util, verify_proof, verify_proof_with_options, Padding, TreeOptions};
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(Padding::DuplicateLast)]
+    #[case(Padding::Promote)]
+    #[case(Padding::PadWithEmpty)]
+    fn it_can_serve_proofs_for_earlier_versions(#[case] padding: Padding) {
+        let user_data = util::generate_random_user_data(7);
+        let options = TreeOptions::tagged("ProofOfReserve_Leaf", "ProofOfReserve_Branch")
+            .with_padding(padding);
+        let mut tree: MerkleTree<_> = MerkleTree::build_with_options(options.clone(), &user_data);
+
+        let previous = tree.version();
+        let previous_root = tree.root().unwrap();
+
+        let updated = util::UserData {
+            id: 3,
+            balance: 3333,
+        };
+        tree.update(2, updated.clone()).unwrap();
+        tree.update(6, updated.clone()).unwrap();
+        tree.push(updated.clone());
+        assert_ne!(tree.root().unwrap(), previous_root);
+        assert_eq!(tree.root_at(previous).unwrap(), previous_root);
+        let current = tree.version();
+        assert_eq!(tree.root_at(current), tree.root());
+        assert_eq!(previous.leaf_count(), 7);
+
+        for (index, item) in user_data.iter().enumerate() {
+            let (node, path) = tree.proof_by_index_at(previous, index).unwrap();
+            assert_eq!(node.user_data.as_ref().unwrap().balance, item.balance);
+            assert!(verify_proof_with_options(
+                &options,
+                &previous_root,
+                item,
+                &path
+            ));
+        }
+        assert_eq!(
+            tree.proof_by_index_at(previous, 7).err(),
+            Some(MerkleError::LeafNotFound)
+        );
+        assert_eq!(
+            tree.leaf_at(previous, 2)
+                .unwrap()
+                .user_data
+                .as_ref()
+                .unwrap()
+                .balance,
+            3000
+        );
+        assert_eq!(
+            tree.leaf(2).unwrap().user_data.as_ref().unwrap().balance,
+            3333
+        );
+
+        let rebuilt: MerkleTree<_> = MerkleTree::build_with_options(
+            options.clone(),
+            &tree
+                .leaves()
+                .map(|(_, item)| item.clone())
+                .collect::<Vec<_>>(),
+        );
+        assert_eq!(tree.root(), rebuilt.root());
+
+        let current = tree.version();
+        tree.compact();
+        assert_eq!(tree.root(), rebuilt.root());
+        assert!(tree.nodes.len() <= rebuilt.nodes.len() + 1);
+        assert_eq!(tree.root_at(previous), Err(MerkleError::StaleVersion));
+        assert_eq!(tree.root_at(current), Err(MerkleError::StaleVersion));
+        let root = tree.root().unwrap();
+        for (index, item) in rebuilt.leaves() {
+            let (_node, path) = tree.proof_by_index(index).unwrap();
+            assert!(verify_proof_with_options(&options, &root, item, &path));
+        }
+
+        let current = tree.version();
+        tree.remove(0).unwrap();
+        assert_eq!(
+            tree.proof_by_index_at(current, 0).err(),
+            Some(MerkleError::StaleVersion)
+        );
+    }
+
+    #[test]
+    fn it_copies_only_the_nodes_of_versions() {
+        let user_data = util::generate_random_user_data(8);
+        let mut tree: MerkleTree<_> =
+            MerkleTree::build("ProofOfReserve_Leaf", "ProofOfReserve_Branch", &user_data);
+        let node_count = tree.nodes.len();
+        let mut updated = user_data[2].clone();
+
+        updated.balance += 1;
+        tree.update(2, updated.clone()).unwrap();
+        assert_eq!(tree.nodes.len(), node_count);
+
+        let previous = tree.version();
+        let previous_root = tree.root().unwrap();
+        updated.balance += 1;
+        tree.update(2, updated.clone()).unwrap();
+        assert_eq!(tree.nodes.len(), node_count + 4);
+
+        // The copied path is not part of any version, so it is overwritten.
+        updated.balance += 1;
+        tree.update(2, updated.clone()).unwrap();
+        assert_eq!(tree.nodes.len(), node_count + 4);
+        assert_eq!(tree.root_at(previous).unwrap(), previous_root);
+
+        tree.compact();
+        assert_eq!(tree.nodes.len(), node_count);
+        tree.update(2, updated).unwrap();
+        assert_eq!(tree.nodes.len(), node_count);
+    }
+
+    #[test]
+    fn it_can_restore_snapshot() {
+        let user_data = util::generate_random_user_data(5);
+        let mut tree: MerkleTree<_> =
+            MerkleTree::build("ProofOfReserve_Leaf", "ProofOfReserve_Branch", &user_data);
+        let root = tree.root().unwrap();
+        let total = |tree: &MerkleTree<util::UserData>| -> u32 {
+            tree.leaves().map(|(_, item)| item.balance).sum()
+        };
+
+        let snapshot = tree.snapshot();
+        let batch = [(1, 1000), (3, 0), (4, 9000)];
+        for (index, balance) in batch {
+            let id = user_data[index].id;
+            tree.update(index, util::UserData { id, balance }).unwrap();
+        }
+        tree.push(util::UserData {
+            id: 6,
+            balance: 6000,
+        });
+        assert_eq!(total(&tree), 20000);
+        assert_ne!(tree.root().unwrap(), root);
+
+        let changed = tree.snapshot();
+        assert_eq!(tree.restore(snapshot), Ok(()));
+        assert_eq!(tree.root().unwrap(), root);
+        assert_eq!(tree.leaf_count(), 5);
+        assert_eq!(total(&tree), 15000);
+        for (index, item) in user_data.iter().enumerate() {
+            let (_node, path) = tree.proof_by_index(index).unwrap();
+            assert!(verify_proof(
+                "ProofOfReserve_Leaf",
+                "ProofOfReserve_Branch",
+                &root,
+                item,
+                &path
+            ));
+        }
+
+        assert_eq!(tree.restore(changed), Ok(()));
+        assert_eq!(tree.leaf_count(), 6);
+        assert_eq!(tree.restore(snapshot), Ok(()));
+
+        tree.push(user_data[0].clone());
+        let rebuilt: MerkleTree<_> = MerkleTree::build(
+            "ProofOfReserve_Leaf",
+            "ProofOfReserve_Branch",
+            &[user_data.clone(), vec![user_data[0].clone()]].concat(),
+        );
+        assert_eq!(tree.root(), rebuilt.root());
+
+        tree.compact();
+        assert_eq!(tree.restore(snapshot), Err(MerkleError::StaleVersion));
+    }
+}
@@ -0,0 +1,153 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use sha2::Digest;
+
+use crate::{MerkleError, MerkleTree, NodeDirection, NodeStore, Padding};
+
+/// The inputs of a Merkle inclusion circuit for one leaf, in the layout of the
+/// `MerkleTreeChecker` templates of circom, which halo2 gadgets take in the same order.
+///
+/// The hashes are decimal strings of their big endian value, as circom reads field elements
+/// from JSON. They are field elements if the tree was built with a field based digest such as
+/// `Poseidon`. With the `serde` feature the witness serializes to the JSON input file of the
+/// circuit, with `pathElements` and `pathIndices` as the keys of the path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct CircuitWitness {
+    /// The hash of the leaf.
+    pub leaf: String,
+    /// The root hash of the tree.
+    pub root: String,
+    /// The sibling hashes from the leaf up to the root.
+    pub path_elements: Vec<String>,
+    /// For each sibling, 0 if the path goes through the left child and the sibling is on the
+    /// right, or 1 if the path goes through the right child.
+    pub path_indices: Vec<u8>,
+}
+
+impl<T, D: Digest, S: NodeStore<T, D>> MerkleTree<T, D, S> {
+    /// Exports the path of the leaf at the given index as the witness of an inclusion
+    /// circuit.
+    ///
+    /// # Arguments
+    ///
+    /// * `index`: The position of the leaf.
+    ///
+    /// # Returns
+    ///
+    /// The witness, the errors of `proof_by_index`, or `MerkleError::UnsupportedPadding` if
+    /// the tree was built with `Padding::Promote`, whose paths are shorter for some leaves
+    /// while circuits have a fixed depth.
+    pub fn circuit_witness(&self, index: usize) -> Result<CircuitWitness, MerkleError> {
+        if self.options.padding == Padding::Promote {
+            return Err(MerkleError::UnsupportedPadding);
+        }
+
+        let (node, path) = self.proof_by_index(index)?;
+        let mut path_elements = Vec::with_capacity(path.siblings.len());
+        let mut path_indices = Vec::with_capacity(path.directions.len());
+        for (sibling, direction) in path.siblings.iter().zip(&path.directions).rev() {
+            let sibling = hex::decode(sibling).map_err(|_| MerkleError::InvalidProof)?;
+            path_elements.push(to_decimal(&sibling));
+            path_indices.push(match direction {
+                NodeDirection::Left => 0,
+                NodeDirection::Right => 1,
+                NodeDirection::Root => return Err(MerkleError::InvalidProof),
+            });
+        }
+
+        Ok(CircuitWitness {
+            leaf: to_decimal(&node.hash),
+            root: to_decimal(&self.root_hash()?),
+            path_elements,
+            path_indices,
+        })
+    }
+}
+
+/// Formats a big endian number as a decimal string.
+///
+/// # Arguments
+///
+/// * `bytes`: The big endian bytes of the number.
+pub(crate) fn to_decimal(bytes: &[u8]) -> String {
+    let mut number = bytes.to_vec();
+    let mut digits = Vec::new();
+
+    while number.iter().any(|byte| *byte != 0) {
+        let mut remainder = 0u32;
+        for byte in number.iter_mut() {
+            let value = remainder << 8 | u32::from(*byte);
+            *byte = (value / 10) as u8;
+            remainder = value % 10;
+        }
+        digits.push(b'0' + remainder as u8);
+    }
+    if digits.is_empty() {
+        digits.push(b'0');
+    }
+
+    digits.reverse();
+    String::from_utf8(digits).expect("decimal digits are ASCII")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "json")]
+    use crate::util;
+    use crate::{Sha256, TreeOptions};
+    use sha2::digest::Output;
+
+    #[test]
+    fn it_can_export_a_circuit_witness() {
+        let leaves: Vec<Output<Sha256>> = (1u8..=4)
+            .map(|leaf| {
+                let mut hash = Output::<Sha256>::default();
+                hash[31] = leaf;
+                hash
+            })
+            .collect();
+        let tree: MerkleTree<()> =
+            MerkleTree::from_leaf_hashes_with_options(TreeOptions::tagged("", ""), &leaves);
+
+        let witness = tree.circuit_witness(2).unwrap();
+        assert_eq!(witness.leaf, "3");
+        assert_eq!(
+            witness.path_elements,
+            [
+                "4",
+                "85264084402629926470469849708000648376397991571326653325291651359813302588987"
+            ]
+        );
+        assert_eq!(witness.path_indices, [0, 1]);
+        assert_eq!(
+            witness.root,
+            "41585746707495061205070791278462990720068938820219341367084570535573198377510"
+        );
+
+        let tree: MerkleTree<()> = MerkleTree::from_leaf_hashes_with_options(
+            TreeOptions::tagged("", "").with_padding(Padding::Promote),
+            &leaves,
+        );
+        assert_eq!(
+            tree.circuit_witness(0),
+            Err(MerkleError::UnsupportedPadding)
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn it_can_serialize_a_circuit_witness() {
+        let user_data = util::generate_random_user_data(5);
+        let tree: MerkleTree<_> = MerkleTree::build("Leaf", "Branch", &user_data);
+        let witness = tree.circuit_witness(4).unwrap();
+
+        let json: serde_json::Value = serde_json::to_value(&witness).unwrap();
+        assert_eq!(json["leaf"], witness.leaf.as_str());
+        assert_eq!(json["root"], witness.root.as_str());
+        assert_eq!(json["pathElements"].as_array().unwrap().len(), 3);
+        assert_eq!(json["pathIndices"], serde_json::json!([0, 0, 1]));
+    }
+}
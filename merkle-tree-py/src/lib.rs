@@ -0,0 +1,172 @@
+use merkle_tree_lib::{MerkleTreeData, NodeDirection, Sha256, TraversePath};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+/// The tag the proof of reserve app hashes leaf nodes with.
+const TAG_LEAF: &str = "ProofOfReserve_Leaf";
+
+/// The tag the proof of reserve app hashes branch nodes with.
+const TAG_BRANCH: &str = "ProofOfReserve_Branch";
+
+/// A leaf given as raw bytes, which are hashed as they are.
+#[derive(Debug, Default, Clone)]
+struct Leaf(Vec<u8>);
+
+impl MerkleTreeData for Leaf {
+    fn serialize(&self) -> Vec<u8> {
+        self.0.clone()
+    }
+
+    fn mermaid_node_label(&self) -> String {
+        format!("<br>{}", hex::encode(&self.0))
+    }
+}
+
+/// Converts a library error to a Python `ValueError`.
+fn value_error(err: impl ToString) -> PyErr {
+    PyValueError::new_err(err.to_string())
+}
+
+/// A SHA-256 Merkle Tree of byte string leaves, such as `b"1,1111"` for the user with id 1
+/// and balance 1111 in the proof of reserve app.
+#[pyclass(name = "MerkleTree", frozen)]
+struct PyMerkleTree {
+    tree: merkle_tree_lib::MerkleTree<Leaf>,
+}
+
+#[pymethods]
+impl PyMerkleTree {
+    /// Builds the tree, with the tags of the proof of reserve app by default.
+    #[new]
+    #[pyo3(signature = (leaves, tag_leaf = TAG_LEAF, tag_branch = TAG_BRANCH))]
+    fn new(leaves: Vec<Vec<u8>>, tag_leaf: &str, tag_branch: &str) -> Self {
+        let leaves: Vec<Leaf> = leaves.into_iter().map(Leaf).collect();
+        PyMerkleTree {
+            tree: merkle_tree_lib::MerkleTree::build(tag_leaf, tag_branch, &leaves),
+        }
+    }
+
+    /// Returns the hex encoded Merkle root.
+    fn root(&self) -> PyResult<String> {
+        self.tree.root().map_err(value_error)
+    }
+
+    fn __len__(&self) -> usize {
+        self.tree.leaf_count()
+    }
+
+    /// Returns the proof of the leaf at the given index as `(sibling hash, direction)` tuples
+    /// from the root down, in the format of the `proof` field of the proof of reserve app.
+    fn proof(&self, index: usize) -> PyResult<Vec<(String, u8)>> {
+        let (_node, path) = self.tree.proof_by_index(index).map_err(value_error)?;
+        Ok(path.to_vec())
+    }
+}
+
+/// Verifies that a leaf is committed to by a root, with the tags of the proof of reserve app
+/// by default.
+///
+/// The proof is a list of `(sibling hash, direction)` tuples as returned by
+/// `MerkleTree.proof`. The `proof` field of the app's JSON response holds lists, which can be
+/// converted with `[tuple(step) for step in proof]`.
+#[pyfunction]
+#[pyo3(signature = (root, leaf, proof, tag_leaf = TAG_LEAF, tag_branch = TAG_BRANCH))]
+fn verify_proof(
+    root: &str,
+    leaf: Vec<u8>,
+    proof: Vec<(String, u8)>,
+    tag_leaf: &str,
+    tag_branch: &str,
+) -> PyResult<bool> {
+    let mut siblings = Vec::with_capacity(proof.len());
+    let mut directions = Vec::with_capacity(proof.len());
+    for (sibling, direction) in proof {
+        siblings.push(sibling);
+        directions.push(NodeDirection::try_from(direction).map_err(value_error)?);
+    }
+
+    Ok(merkle_tree_lib::verify_proof(
+        tag_leaf,
+        tag_branch,
+        root,
+        &Leaf(leaf),
+        &TraversePath::<Sha256>::from_steps(siblings, directions),
+    ))
+}
+
+/// Returns the hex encoded BIP-340 style tagged hash `SHA256(SHA256(tag) || SHA256(tag) ||
+/// data)` the tree hashes its nodes with.
+#[pyfunction]
+fn tagged_hash(tag: &str, data: Vec<u8>) -> String {
+    hex::encode(merkle_tree_lib::tagged_hash::<Sha256>(tag, &data))
+}
+
+/// Merkle Trees and proofs hashed exactly as by the Rust library.
+#[pymodule]
+fn merkle_tree(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyMerkleTree>()?;
+    m.add_function(wrap_pyfunction!(verify_proof, m)?)?;
+    m.add_function(wrap_pyfunction!(tagged_hash, m)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use merkle_tree_lib::util;
+
+    #[test]
+    fn it_can_build_prove_and_verify_like_the_library() {
+        let user_data = util::generate_random_user_data(5);
+        let expected: merkle_tree_lib::MerkleTree<_> =
+            merkle_tree_lib::MerkleTree::build(TAG_LEAF, TAG_BRANCH, &user_data);
+        let leaves: Vec<Vec<u8>> = user_data.iter().map(MerkleTreeData::serialize).collect();
+
+        let tree = PyMerkleTree::new(leaves.clone(), TAG_LEAF, TAG_BRANCH);
+        assert_eq!(tree.__len__(), 5);
+        let root = tree.root().unwrap();
+        assert_eq!(root, expected.root().unwrap());
+
+        for (index, leaf) in leaves.iter().enumerate() {
+            let proof = tree.proof(index).unwrap();
+            let (_node, path) = expected.proof_by_index(index).unwrap();
+            assert_eq!(proof, path.to_vec());
+
+            let verify = |leaf: &[u8]| {
+                verify_proof(&root, leaf.to_vec(), proof.clone(), TAG_LEAF, TAG_BRANCH).unwrap()
+            };
+            assert!(verify(leaf));
+            assert!(!verify(b"6,6666"));
+        }
+
+        assert!(tree.proof(5).is_err());
+        let mut proof = tree.proof(0).unwrap();
+        proof[0].1 = 3;
+        assert!(verify_proof(&root, leaves[0].clone(), proof, TAG_LEAF, TAG_BRANCH).is_err());
+    }
+
+    #[test]
+    fn it_can_build_with_other_tags() {
+        let leaves: Vec<Vec<u8>> = vec![b"aaa".to_vec(), b"bbb".to_vec(), b"ccc".to_vec()];
+        let tree = PyMerkleTree::new(leaves.clone(), "Leaf", "Branch");
+        let root = tree.root().unwrap();
+        assert_ne!(
+            root,
+            PyMerkleTree::new(leaves.clone(), TAG_LEAF, TAG_BRANCH)
+                .root()
+                .unwrap()
+        );
+
+        let proof = tree.proof(2).unwrap();
+        assert!(verify_proof(&root, leaves[2].clone(), proof.clone(), "Leaf", "Branch").unwrap());
+        assert!(!verify_proof(&root, leaves[2].clone(), proof, TAG_LEAF, TAG_BRANCH).unwrap());
+
+        assert_eq!(
+            tagged_hash("Leaf", b"aaa".to_vec()),
+            hex::encode(merkle_tree_lib::tagged_hash::<Sha256>("Leaf", b"aaa"))
+        );
+        assert!(PyMerkleTree::new(vec![], TAG_LEAF, TAG_BRANCH)
+            .root()
+            .is_err());
+    }
+}
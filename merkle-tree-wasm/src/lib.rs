@@ -0,0 +1,282 @@
+use merkle_tree_lib::util::UserData;
+use merkle_tree_lib::{
+    Digest, IndexedMerkleTree, MerkleError, NodeDirection, Sha224, Sha256, Sha384, Sha512,
+    TraversePath,
+};
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+
+/// The tag the proof of reserve app hashes leaf nodes with by default.
+const TAG_LEAF: &str = "ProofOfReserve_Leaf";
+
+/// The tag the proof of reserve app hashes branch nodes with by default.
+const TAG_BRANCH: &str = "ProofOfReserve_Branch";
+
+/// The hash algorithms the proof of reserve app can be configured with.
+#[derive(Clone, Copy)]
+enum HashAlgorithm {
+    Sha224,
+    Sha256,
+    Sha384,
+    Sha512,
+}
+
+impl HashAlgorithm {
+    /// Parses the lowercase name of the algorithm, as configured in the app.
+    ///
+    /// # Returns
+    ///
+    /// The algorithm, SHA-256 if `name` is `None`, or an error for an unknown name.
+    fn parse(name: Option<&str>) -> Result<Self, JsError> {
+        Ok(match name {
+            Some("sha224") => HashAlgorithm::Sha224,
+            None | Some("sha256") => HashAlgorithm::Sha256,
+            Some("sha384") => HashAlgorithm::Sha384,
+            Some("sha512") => HashAlgorithm::Sha512,
+            Some(name) => return Err(JsError::new(&format!("unknown hash algorithm {name}"))),
+        })
+    }
+}
+
+/// The tree of the users, hashed with the chosen algorithm.
+enum UserTree {
+    Sha224(IndexedMerkleTree<UserData<u64>, Sha224>),
+    Sha256(IndexedMerkleTree<UserData<u64>, Sha256>),
+    Sha384(IndexedMerkleTree<UserData<u64>, Sha384>),
+    Sha512(IndexedMerkleTree<UserData<u64>, Sha512>),
+}
+
+/// Evaluates an expression with the tree of any hash algorithm bound to a name.
+macro_rules! with_tree {
+    ($tree:expr, $name:ident => $body:expr) => {
+        match $tree {
+            UserTree::Sha224($name) => $body,
+            UserTree::Sha256($name) => $body,
+            UserTree::Sha384($name) => $body,
+            UserTree::Sha512($name) => $body,
+        }
+    };
+}
+
+/// The proof returned by the `/proof/<user-id>` endpoint of the proof of reserve app.
+#[derive(Serialize, Deserialize)]
+struct MerkleProof {
+    user_balance: u64,
+    proof: Vec<(String, u8)>,
+}
+
+/// A proof of reserve Merkle Tree of user balances, hashed the same way as the proof of
+/// reserve app.
+#[wasm_bindgen]
+pub struct ProofOfReserveTree {
+    tree: UserTree,
+}
+
+#[wasm_bindgen]
+impl ProofOfReserveTree {
+    /// Builds the tree from the balances of the users.
+    ///
+    /// # Arguments
+    ///
+    /// * `ids`: The user ids, which must be unique.
+    /// * `balances`: The balance of each user, in the order of `ids`.
+    /// * `tag_leaf`: The tag of the leaf nodes, `ProofOfReserve_Leaf` if omitted.
+    /// * `tag_branch`: The tag of the branch nodes, `ProofOfReserve_Branch` if omitted.
+    /// * `hash`: The lowercase name of the hash algorithm as configured in the app, `sha256`
+    ///   if omitted.
+    #[wasm_bindgen(constructor)]
+    pub fn new(
+        ids: &[u32],
+        balances: &[u64],
+        tag_leaf: Option<String>,
+        tag_branch: Option<String>,
+        hash: Option<String>,
+    ) -> Result<ProofOfReserveTree, JsError> {
+        fn build<D: Digest>(
+            tag_leaf: &str,
+            tag_branch: &str,
+            users: &[UserData<u64>],
+        ) -> Result<IndexedMerkleTree<UserData<u64>, D>, MerkleError> {
+            IndexedMerkleTree::build(tag_leaf, tag_branch, users)
+        }
+
+        if ids.len() != balances.len() {
+            return Err(JsError::new("every user id needs a balance"));
+        }
+
+        let user_data: Vec<UserData<u64>> = ids
+            .iter()
+            .zip(balances)
+            .map(|(&id, &balance)| UserData { id, balance })
+            .collect();
+        let tag_leaf = tag_leaf.as_deref().unwrap_or(TAG_LEAF);
+        let tag_branch = tag_branch.as_deref().unwrap_or(TAG_BRANCH);
+        let tree = match HashAlgorithm::parse(hash.as_deref())? {
+            HashAlgorithm::Sha224 => UserTree::Sha224(build(tag_leaf, tag_branch, &user_data)?),
+            HashAlgorithm::Sha256 => UserTree::Sha256(build(tag_leaf, tag_branch, &user_data)?),
+            HashAlgorithm::Sha384 => UserTree::Sha384(build(tag_leaf, tag_branch, &user_data)?),
+            HashAlgorithm::Sha512 => UserTree::Sha512(build(tag_leaf, tag_branch, &user_data)?),
+        };
+        Ok(ProofOfReserveTree { tree })
+    }
+
+    /// Returns the hex encoded Merkle root, as served by `/proof`.
+    pub fn root(&self) -> Result<String, JsError> {
+        Ok(with_tree!(&self.tree, tree => tree.root())?)
+    }
+
+    /// Generates the proof for a user.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id`: The id of the user.
+    ///
+    /// # Returns
+    ///
+    /// The proof as JSON, in the format served by `/proof/<user-id>`.
+    pub fn proof(&self, user_id: u32) -> Result<String, JsError> {
+        let (user_balance, proof) = with_tree!(&self.tree, tree => {
+            let (node, path) = tree.proof_for_key(&user_id)?;
+            let user = node.user_data.as_ref().ok_or(MerkleError::LeafNotFound)?;
+            (user.balance, path.to_vec())
+        });
+
+        Ok(serde_json::to_string(&MerkleProof {
+            user_balance,
+            proof,
+        })?)
+    }
+}
+
+/// Verifies a proof served by `/proof/<user-id>` of the proof of reserve app against the
+/// published root.
+///
+/// # Arguments
+///
+/// * `root`: The hex encoded Merkle root served by `/proof`.
+/// * `user_id`: The id of the user the proof was requested for.
+/// * `proof`: The proof as JSON.
+/// * `tag_leaf`: The tag of the leaf nodes, `ProofOfReserve_Leaf` if omitted.
+/// * `tag_branch`: The tag of the branch nodes, `ProofOfReserve_Branch` if omitted.
+/// * `hash`: The lowercase name of the hash algorithm as configured in the app, `sha256` if
+///   omitted.
+///
+/// # Returns
+///
+/// `true` if the balance in the proof is committed to by `root`, `false` otherwise, or an
+/// error if the proof is not valid JSON or the hash algorithm is unknown.
+#[wasm_bindgen(js_name = verifyProof)]
+pub fn verify_proof(
+    root: &str,
+    user_id: u32,
+    proof: &str,
+    tag_leaf: Option<String>,
+    tag_branch: Option<String>,
+    hash: Option<String>,
+) -> Result<bool, JsError> {
+    fn verify<D: Digest>(
+        tag_leaf: &str,
+        tag_branch: &str,
+        root: &str,
+        user: &UserData<u64>,
+        steps: &[(String, u8)],
+    ) -> bool {
+        traverse_path::<D>(steps).is_some_and(|path| {
+            merkle_tree_lib::verify_proof(tag_leaf, tag_branch, root, user, &path)
+        })
+    }
+
+    let algorithm = HashAlgorithm::parse(hash.as_deref())?;
+    let proof: MerkleProof = serde_json::from_str(proof)?;
+    let user = UserData {
+        id: user_id,
+        balance: proof.user_balance,
+    };
+    let tag_leaf = tag_leaf.as_deref().unwrap_or(TAG_LEAF);
+    let tag_branch = tag_branch.as_deref().unwrap_or(TAG_BRANCH);
+    let steps = &proof.proof;
+
+    Ok(match algorithm {
+        HashAlgorithm::Sha224 => verify::<Sha224>(tag_leaf, tag_branch, root, &user, steps),
+        HashAlgorithm::Sha256 => verify::<Sha256>(tag_leaf, tag_branch, root, &user, steps),
+        HashAlgorithm::Sha384 => verify::<Sha384>(tag_leaf, tag_branch, root, &user, steps),
+        HashAlgorithm::Sha512 => verify::<Sha512>(tag_leaf, tag_branch, root, &user, steps),
+    })
+}
+
+/// Converts the `(sibling hash, direction)` steps of `TraversePath::to_vec` back to a path.
+///
+/// # Returns
+///
+/// The path, or `None` if a direction is not a valid code.
+fn traverse_path<D: Digest>(steps: &[(String, u8)]) -> Option<TraversePath<D>> {
+    let directions = steps
+        .iter()
+        .map(|(_, direction)| NodeDirection::try_from(*direction).ok())
+        .collect::<Option<Vec<_>>>()?;
+    let siblings = steps.iter().map(|(sibling, _)| sibling.clone()).collect();
+    Some(TraversePath::from_steps(siblings, directions))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_can_verify_proofs_of_the_app() {
+        let ids: Vec<u32> = (1..=8).collect();
+        let balances: Vec<u64> = ids.iter().map(|&id| u64::from(id) * 1111).collect();
+        let tree = ProofOfReserveTree::new(&ids, &balances, None, None, None).unwrap();
+        let root = tree.root().unwrap();
+
+        for &id in &ids {
+            let proof = tree.proof(id).unwrap();
+            assert!(verify_proof(&root, id, &proof, None, None, None).unwrap());
+            assert!(!verify_proof(&root, id % 8 + 1, &proof, None, None, None).unwrap());
+        }
+
+        let tampered = tree.proof(3).unwrap().replace("3333", "3334");
+        assert!(!verify_proof(&root, 3, &tampered, None, None, None).unwrap());
+    }
+
+    #[test]
+    fn it_can_verify_balances_above_u32_max() {
+        let ids = [1, 2, 3];
+        let balances = [u64::from(u32::MAX) + 1, 5_000_000_000, 1];
+        let tree = ProofOfReserveTree::new(&ids, &balances, None, None, None).unwrap();
+        let root = tree.root().unwrap();
+
+        let proof = tree.proof(2).unwrap();
+        assert!(proof.contains("\"user_balance\":5000000000"));
+        assert!(verify_proof(&root, 2, &proof, None, None, None).unwrap());
+
+        let tampered = proof.replace("5000000000", "5000000001");
+        assert!(!verify_proof(&root, 2, &tampered, None, None, None).unwrap());
+    }
+
+    #[test]
+    fn it_can_verify_proofs_with_other_tags_and_hash() {
+        let ids = [1, 2, 3];
+        let balances = [1111, 2222, 3333];
+        let tags = || (Some("Other_Leaf".into()), Some("Other_Branch".into()));
+        let sha512 = || Some("sha512".into());
+        let (tag_leaf, tag_branch) = tags();
+        let tree =
+            ProofOfReserveTree::new(&ids, &balances, tag_leaf, tag_branch, sha512()).unwrap();
+        let root = tree.root().unwrap();
+
+        let users: Vec<_> = (ids.iter().zip(balances))
+            .map(|(&id, balance)| UserData { id, balance })
+            .collect();
+        let expected: IndexedMerkleTree<_, Sha512> =
+            IndexedMerkleTree::build("Other_Leaf", "Other_Branch", &users).unwrap();
+        assert_eq!(root, expected.root().unwrap());
+
+        let proof = tree.proof(2).unwrap();
+        let (tag_leaf, tag_branch) = tags();
+        assert!(verify_proof(&root, 2, &proof, tag_leaf, tag_branch, sha512()).unwrap());
+        assert!(!verify_proof(&root, 2, &proof, None, None, sha512()).unwrap());
+        let (tag_leaf, tag_branch) = tags();
+        assert!(!verify_proof(&root, 2, &proof, tag_leaf, tag_branch, None).unwrap());
+    }
+}
@@ -0,0 +1,77 @@
+use rocket::serde::{json, Serialize};
+use std::io::Write;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::users::UserChange;
+
+/// A record of a change made to the users through the API.
+#[derive(Debug, Clone, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct AuditEntry {
+    /// The seconds since the Unix epoch when the change was applied.
+    pub timestamp: u64,
+    /// The kind of the change: `insert`, `update_balance` or `remove`.
+    pub action: &'static str,
+    pub user_id: u32,
+    /// The root before the change, or `None` if the tree had no users.
+    pub old_root: Option<String>,
+    /// The root after the change, or `None` if the last user was removed.
+    pub new_root: Option<String>,
+    /// The hex encoded hash of the leaf the change replaced or removed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old_leaf: Option<String>,
+}
+
+impl AuditEntry {
+    /// Records a change applied now.
+    ///
+    /// # Arguments
+    ///
+    /// * `change`: The applied change.
+    /// * `old_root`: The root before the change.
+    /// * `new_root`: The root after the change.
+    /// * `old_leaf`: The hash of the leaf the change replaced or removed.
+    pub fn new(
+        change: &UserChange,
+        old_root: Option<String>,
+        new_root: Option<String>,
+        old_leaf: Option<String>,
+    ) -> Self {
+        let action = match change {
+            UserChange::Insert(_) => "insert",
+            UserChange::UpdateBalance { .. } => "update_balance",
+            UserChange::Remove(_) => "remove",
+        };
+        AuditEntry {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_secs()),
+            action,
+            user_id: change.id(),
+            old_root,
+            new_root,
+            old_leaf,
+        }
+    }
+
+    /// Returns the entry as a line of JSON.
+    pub fn to_json_line(&self) -> String {
+        json::to_string(self).expect("audit entries are always serializable")
+    }
+}
+
+/// Appends an entry as a line of JSON to an audit log, creating the file if it does not exist.
+///
+/// # Arguments
+///
+/// * `path`: The path of the audit log.
+/// * `entry`: The entry to append.
+pub fn append_entry(path: &Path, entry: &AuditEntry) -> std::io::Result<()> {
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    writeln!(file, "{}", entry.to_json_line())?;
+    file.sync_data()
+}
@@ -0,0 +1,65 @@
+use rocket::serde::Deserialize;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// The configuration of the app, read from `Rocket.toml` or `ROCKET_` environment variables
+/// along with the configuration of Rocket.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(crate = "rocket::serde", default)]
+pub struct AppConfig {
+    /// The tag used for hashing leaf nodes.
+    pub tag_leaf: String,
+    /// The tag used for hashing branch nodes.
+    pub tag_branch: String,
+    /// The CSV or TSV file of `id,balance` rows to commit to.
+    pub users: PathBuf,
+    /// The hash algorithm of the tree.
+    pub hash: HashAlgorithm,
+    /// The URL of an SQL database, such as `sqlite://users.db` or `postgres://host/db`, to
+    /// load the users from instead of `users`.
+    pub database_url: Option<String>,
+    /// The maximum number of connections to the database.
+    pub database_connections: u32,
+    /// The number of seconds between rebuilds of the tree from the users, or 0 to build it
+    /// only at startup.
+    pub rebuild_interval: u64,
+    /// The file to append a line of JSON to for every change made through the API, or `None`
+    /// to only log the changes.
+    pub audit_log: Option<PathBuf>,
+    /// The maximum number of user ids in a request for a batch of proofs.
+    pub max_batch_size: usize,
+}
+
+impl AppConfig {
+    /// Returns the time between rebuilds of the tree, or `None` if it is only built at startup.
+    pub fn rebuild_interval(&self) -> Option<Duration> {
+        (self.rebuild_interval > 0).then(|| Duration::from_secs(self.rebuild_interval))
+    }
+}
+
+impl Default for AppConfig {
+    fn default() -> Self {
+        AppConfig {
+            tag_leaf: "ProofOfReserve_Leaf".to_string(),
+            tag_branch: "ProofOfReserve_Branch".to_string(),
+            users: PathBuf::from("users.csv"),
+            hash: HashAlgorithm::default(),
+            database_url: None,
+            database_connections: 5,
+            rebuild_interval: 0,
+            audit_log: None,
+            max_batch_size: 1000,
+        }
+    }
+}
+
+/// The hash algorithms a tree can be built with, configured by their lowercase names.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(crate = "rocket::serde", rename_all = "lowercase")]
+pub enum HashAlgorithm {
+    Sha224,
+    #[default]
+    Sha256,
+    Sha384,
+    Sha512,
+}
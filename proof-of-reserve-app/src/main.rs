@@ -1,87 +1,913 @@
-use merkle_tree_lib::{self, MerkleTreeData};
-use rocket::serde::{json::Json, Serialize};
-use rocket::State;
+use merkle_tree_lib::MerkleError;
+use rocket::fairing::{self, AdHoc};
+use rocket::figment::Figment;
+use rocket::http::Status;
+use rocket::serde::{json::Json, Deserialize, Serialize};
+use rocket::tokio::sync::Mutex;
+use rocket::{Build, Request, Rocket, State};
+use std::sync::{Arc, PoisonError, RwLock, RwLockReadGuard};
+use std::time::Duration;
+
+use crate::audit::AuditEntry;
+use crate::config::AppConfig;
+use crate::tree::UserTree;
+use crate::users::{UserChange, UserData, UserSource};
 
 #[macro_use]
 extern crate rocket;
 
+mod audit;
+mod config;
+mod tree;
+mod users;
+
+/// The JSON body of an error response.
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct ErrorBody {
+    error: String,
+}
+
+/// An error response with its HTTP status and JSON body.
+type ErrorResponse = (Status, Json<ErrorBody>);
+
+/// Creates an error response.
+///
+/// # Arguments
+///
+/// * `status`: The HTTP status of the response.
+/// * `message`: The message of the body.
+fn error_response(status: Status, message: impl ToString) -> ErrorResponse {
+    (
+        status,
+        Json(ErrorBody {
+            error: message.to_string(),
+        }),
+    )
+}
+
+/// Maps a library error to the HTTP status and body of the response.
+fn merkle_error(error: MerkleError) -> ErrorResponse {
+    let status = match error {
+        MerkleError::EmptyTree | MerkleError::LeafNotFound => Status::NotFound,
+        MerkleError::DuplicateKey => Status::Conflict,
+        _ => Status::InternalServerError,
+    };
+    error_response(status, error)
+}
+
+#[catch(400)]
+fn bad_request(request: &Request) -> Json<ErrorBody> {
+    Json(ErrorBody {
+        error: format!("bad request to {}", request.uri()),
+    })
+}
+
+#[catch(404)]
+fn not_found(request: &Request) -> Json<ErrorBody> {
+    Json(ErrorBody {
+        error: format!("{} was not found", request.uri()),
+    })
+}
+
+#[catch(422)]
+fn unprocessable_entity(request: &Request) -> Json<ErrorBody> {
+    Json(ErrorBody {
+        error: format!("malformed request to {}", request.uri()),
+    })
+}
+
+/// Also responds to panics in handlers, without leaking their messages to clients.
+#[catch(500)]
+fn internal_error() -> Json<ErrorBody> {
+    Json(ErrorBody {
+        error: "internal server error".to_string(),
+    })
+}
+
+/// Parses a user id from a path segment.
+///
+/// # Arguments
+///
+/// * `user_id`: The parsed id, or the segment if it is not a u32.
+///
+/// # Returns
+///
+/// The id, or a 400 response if the segment is not a u32.
+fn parse_user_id(user_id: Result<u32, &str>) -> Result<u32, ErrorResponse> {
+    user_id.map_err(|user_id| {
+        error_response(
+            Status::BadRequest,
+            format!("invalid user id {user_id:?}, expected a non-negative integer"),
+        )
+    })
+}
+
+/// Maps an error generating the proof of a user to the HTTP status and body of the response.
+///
+/// # Arguments
+///
+/// * `user_id`: The id of the user.
+/// * `error`: The error of the library.
+fn proof_error(user_id: u32, error: MerkleError) -> ErrorResponse {
+    match error {
+        MerkleError::LeafNotFound => {
+            error_response(Status::NotFound, format!("user {user_id} was not found"))
+        }
+        error => merkle_error(error),
+    }
+}
+
 #[get("/proof")]
-fn proof_all_users(state: &State<AppState>) -> String {
-    state.tree.root().unwrap()
+fn proof_all_users(state: &State<AppState>) -> Result<String, ErrorResponse> {
+    state.tree().root().map_err(merkle_error)
 }
 
 #[get("/proof/mermaid")]
 fn proof_all_users_display_mermaid_diagram(state: &State<AppState>) -> String {
-    state.tree.display_mermaid_diagram()
+    state.tree().display_mermaid_diagram()
 }
 
 #[derive(Serialize)]
 #[serde(crate = "rocket::serde")]
 struct MerkleProof {
-    user_balance: u32,
+    user_balance: u64,
     proof: Vec<(String, u8)>,
 }
 
 #[get("/proof/<user_id>")]
-fn proof_by_user_id(state: &State<AppState>, user_id: &str) -> Json<MerkleProof> {
-    let (node, path) = state
-        .tree
-        .search_with_path(|user_data| user_data.id == user_id.parse::<u32>().unwrap())
-        .unwrap();
-
-    Json(MerkleProof {
-        user_balance: node.user_data.as_ref().unwrap().balance,
-        proof: path.to_vec(),
-    })
+fn proof_by_user_id(
+    state: &State<AppState>,
+    user_id: Result<u32, &str>,
+) -> Result<Json<MerkleProof>, ErrorResponse> {
+    let user_id = parse_user_id(user_id)?;
+    let (user, proof) = state
+        .tree()
+        .proof_for_id(user_id)
+        .map_err(|error| proof_error(user_id, error))?;
+
+    Ok(Json(MerkleProof {
+        user_balance: user.balance,
+        proof,
+    }))
 }
 
-#[derive(Debug, Default, Clone)]
-struct UserData {
-    id: u32,
-    balance: u32,
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct BatchProof {
+    user_id: u32,
+    user_balance: u64,
+    proof: Vec<(String, u8)>,
 }
 
-impl MerkleTreeData for UserData {
-    fn serialize(&self) -> Vec<u8> {
-        format!("{},{}", self.id, self.balance).as_bytes().to_vec()
-    }
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct BatchProofResponse {
+    root: String,
+    proofs: Vec<BatchProof>,
+}
 
-    fn mermaid_node_label(&self) -> String {
-        format!("<br>User ID: {}<br>Balance: {}", self.id, self.balance)
+/// Generates the proofs of several users at once, all against the same root.
+#[post("/proofs", data = "<user_ids>")]
+fn proofs_by_user_ids(
+    state: &State<AppState>,
+    user_ids: Json<Vec<u32>>,
+) -> Result<Json<BatchProofResponse>, ErrorResponse> {
+    let max_batch_size = state.config.max_batch_size;
+    if user_ids.len() > max_batch_size {
+        return Err(error_response(
+            Status::PayloadTooLarge,
+            format!(
+                "{} user ids were requested, but at most {max_batch_size} are allowed",
+                user_ids.len()
+            ),
+        ));
     }
+
+    // The proofs are generated from one tree, so that a concurrent change cannot mix roots.
+    let tree = state.tree();
+    let root = tree.root().map_err(merkle_error)?;
+    let proofs = (user_ids.iter())
+        .map(|&user_id| {
+            let (user, proof) = tree
+                .proof_for_id(user_id)
+                .map_err(|error| proof_error(user_id, error))?;
+            Ok(BatchProof {
+                user_id,
+                user_balance: user.balance,
+                proof,
+            })
+        })
+        .collect::<Result<_, _>>()?;
+    Ok(Json(BatchProofResponse { root, proofs }))
 }
 
+#[derive(Clone)]
 struct AppState {
-    tree: merkle_tree_lib::MerkleTree<UserData>,
+    config: AppConfig,
+    source: UserSource,
+    tree: Arc<RwLock<UserTree>>,
+    /// Held while the users are changed or reloaded, so that the source and the tree are
+    /// changed in the same order.
+    writes: Arc<Mutex<()>>,
+}
+
+impl AppState {
+    /// Returns the current tree, waiting for a rebuild to swap it in if one is in progress.
+    fn tree(&self) -> RwLockReadGuard<'_, UserTree> {
+        self.tree.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Loads the users from the source again and swaps in their tree, keeping the current
+    /// tree if the users cannot be loaded.
+    ///
+    /// # Returns
+    ///
+    /// The number of users, or an error for every problem with the source.
+    async fn reload(&self) -> Result<usize, Vec<String>> {
+        let _writes = self.writes.lock().await;
+        let users = self.source.load().await?;
+        let tree = UserTree::build(&self.config, &users)
+            .map_err(|err| vec![format!("{}: {err}", self.source)])?;
+        *self.tree.write().unwrap_or_else(PoisonError::into_inner) = tree;
+        Ok(users.len())
+    }
+
+    /// Saves a change to the source of the users, applies it to the tree and records it in the
+    /// audit log.
+    ///
+    /// # Arguments
+    ///
+    /// * `change`: The change to make.
+    ///
+    /// # Returns
+    ///
+    /// The audit entry of the change, or the error response if the change does not fit the
+    /// users or cannot be saved, in which case neither the source nor the tree is changed.
+    async fn change(&self, change: UserChange) -> Result<AuditEntry, ErrorResponse> {
+        let id = change.id();
+        let not_fitting = |error| match error {
+            MerkleError::DuplicateKey => {
+                error_response(Status::Conflict, format!("user {id} already exists"))
+            }
+            MerkleError::LeafNotFound => {
+                error_response(Status::NotFound, format!("user {id} was not found"))
+            }
+            error => merkle_error(error),
+        };
+
+        let _writes = self.writes.lock().await;
+        let mut users = self.tree().users();
+        change
+            .apply_to(&mut users, self.source.orders_by_id())
+            .map_err(not_fitting)?;
+
+        // The change is applied to a copy of the tree, which is only swapped in once the change
+        // is saved, so that a failure on either side leaves both unchanged.
+        let (old_root, mut tree) = {
+            let tree = self.tree();
+            (tree.root().ok(), tree.clone())
+        };
+        let old_leaf = tree
+            .apply(&self.config, &change, &users)
+            .map_err(not_fitting)?;
+        let new_root = tree.root().ok();
+        self.source
+            .save(&change, &users)
+            .await
+            .map_err(|err| error_response(Status::InternalServerError, err))?;
+        *self.tree.write().unwrap_or_else(PoisonError::into_inner) = tree;
+
+        let entry = AuditEntry::new(&change, old_root, new_root, old_leaf);
+        self.record(&entry).await;
+        Ok(entry)
+    }
+
+    /// Logs an applied change and appends it to the audit log if one is configured.
+    ///
+    /// The change is already saved when it is recorded, so a failure to append to the audit log
+    /// is logged as an error with the entry instead of failing the request.
+    ///
+    /// # Arguments
+    ///
+    /// * `entry`: The audit entry of the change.
+    async fn record(&self, entry: &AuditEntry) {
+        let line = entry.to_json_line();
+        info!("audit: {line}");
+
+        let Some(path) = self.config.audit_log.clone() else {
+            return;
+        };
+        let entry = entry.clone();
+        let appended =
+            rocket::tokio::task::spawn_blocking(move || audit::append_entry(&path, &entry))
+                .await
+                .map_err(|err| err.to_string())
+                .and_then(|appended| appended.map_err(|err| err.to_string()));
+        if let Err(err) = appended {
+            error!("audit log: failed to append {line}: {err}");
+        }
+    }
+}
+
+/// Reads the configuration and commits to the configured users, aborting the launch with the
+/// errors of the configuration or the source of the users if either cannot be used.
+async fn load_users(rocket: Rocket<Build>) -> fairing::Result {
+    let config: AppConfig = match rocket.figment().extract() {
+        Ok(config) => config,
+        Err(err) => {
+            error!("invalid configuration: {err}");
+            return Err(rocket);
+        }
+    };
+    let source = match UserSource::connect(&config).await {
+        Ok(source) => source,
+        Err(err) => {
+            error!("{err}");
+            return Err(rocket);
+        }
+    };
+
+    let tree = source.load().await.and_then(|users| {
+        UserTree::build(&config, &users).map_err(|err| vec![format!("{source}: {err}")])
+    });
+    match tree {
+        Ok(tree) => {
+            info!("{source}: committed to {} users", tree.leaf_count());
+            let tree = Arc::new(RwLock::new(tree));
+            Ok(rocket.manage(AppState {
+                config,
+                source,
+                tree,
+                writes: Arc::default(),
+            }))
+        }
+        Err(errors) => {
+            for err in errors {
+                error!("{err}");
+            }
+            Err(rocket)
+        }
+    }
+}
+
+/// Reloads the users every interval, keeping the previous tree if they cannot be loaded.
+async fn rebuild_periodically(state: AppState, interval: Duration) {
+    let mut ticks = rocket::tokio::time::interval(interval);
+    // The first tick completes immediately, right after the tree was built.
+    ticks.tick().await;
+
+    loop {
+        ticks.tick().await;
+        match state.reload().await {
+            Ok(count) => info!("{}: rebuilt the tree of {count} users", state.source),
+            Err(errors) => {
+                for err in errors {
+                    warn!("keeping the previous tree: {err}");
+                }
+            }
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct ReloadResponse {
+    root: String,
+    user_count: usize,
+}
+
+/// Loads the users from the source again, such as after the database was updated.
+#[post("/reload")]
+async fn reload_users(state: &State<AppState>) -> Result<Json<ReloadResponse>, ErrorResponse> {
+    let user_count = state
+        .reload()
+        .await
+        .map_err(|errors| error_response(Status::InternalServerError, errors.join("; ")))?;
+    let root = state.tree().root().map_err(merkle_error)?;
+    Ok(Json(ReloadResponse { root, user_count }))
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct RootResponse {
+    root: String,
+}
+
+/// Adds a user after the existing ones, extending the tree.
+#[post("/users", data = "<user>")]
+async fn insert_user(
+    state: &State<AppState>,
+    user: Json<UserData>,
+) -> Result<(Status, Json<RootResponse>), ErrorResponse> {
+    let entry = state.change(UserChange::Insert(user.into_inner())).await?;
+    let root = entry
+        .new_root
+        .ok_or_else(|| merkle_error(MerkleError::EmptyTree))?;
+    Ok((Status::Created, Json(RootResponse { root })))
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+struct BalanceUpdate {
+    balance: u64,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct RootChangeResponse {
+    old_root: Option<String>,
+    new_root: Option<String>,
+}
+
+/// Corrects the balance of a user, updating their leaf in place.
+#[put("/users/<user_id>/balance", data = "<update>")]
+async fn update_balance(
+    state: &State<AppState>,
+    user_id: Result<u32, &str>,
+    update: Json<BalanceUpdate>,
+) -> Result<Json<RootChangeResponse>, ErrorResponse> {
+    let change = UserChange::UpdateBalance {
+        id: parse_user_id(user_id)?,
+        balance: update.balance,
+    };
+    let entry = state.change(change).await?;
+    Ok(Json(RootChangeResponse {
+        old_root: entry.old_root,
+        new_root: entry.new_root,
+    }))
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct RemovalResponse {
+    root: Option<String>,
+    removed_leaf: String,
+}
+
+/// Removes a user, rebuilding the tree without their leaf.
+#[delete("/users/<user_id>")]
+async fn remove_user(
+    state: &State<AppState>,
+    user_id: Result<u32, &str>,
+) -> Result<Json<RemovalResponse>, ErrorResponse> {
+    let entry = state
+        .change(UserChange::Remove(parse_user_id(user_id)?))
+        .await?;
+    Ok(Json(RemovalResponse {
+        root: entry.new_root,
+        removed_leaf: entry.old_leaf.unwrap_or_default(),
+    }))
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+struct VerifyRequest {
+    user_id: u32,
+    balance: u64,
+    proof: Vec<(String, u8)>,
+    root: String,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct VerifyResponse {
+    valid: bool,
+}
+
+/// Checks a proof given to a user against the root they expect, without looking up the user.
+#[post("/verify", data = "<request>")]
+fn verify_proof(
+    state: &State<AppState>,
+    request: Json<VerifyRequest>,
+) -> Result<Json<VerifyResponse>, ErrorResponse> {
+    let user = UserData {
+        id: request.user_id,
+        balance: request.balance,
+    };
+    let valid = tree::verify_proof(&state.config, &request.root, &user, &request.proof)
+        .map_err(|err| error_response(Status::BadRequest, err))?;
+    Ok(Json(VerifyResponse { valid }))
+}
+
+/// Creates the app with the given configuration.
+///
+/// # Arguments
+///
+/// * `figment`: The configuration of Rocket and the app.
+fn app(figment: Figment) -> Rocket<Build> {
+    rocket::custom(figment)
+        .attach(AdHoc::try_on_ignite("User data", load_users))
+        .attach(AdHoc::on_liftoff("Tree rebuilds", |rocket| {
+            Box::pin(async move {
+                let Some(state) = rocket.state::<AppState>() else {
+                    return;
+                };
+                if let Some(interval) = state.config.rebuild_interval() {
+                    rocket::tokio::spawn(rebuild_periodically(state.clone(), interval));
+                }
+            })
+        }))
+        .mount(
+            "/",
+            routes![
+                proof_all_users,
+                proof_all_users_display_mermaid_diagram,
+                proof_by_user_id,
+                proofs_by_user_ids,
+                reload_users,
+                insert_user,
+                update_balance,
+                remove_user,
+                verify_proof
+            ],
+        )
+        .register(
+            "/",
+            catchers![bad_request, not_found, unprocessable_entity, internal_error],
+        )
 }
 
 #[launch]
 fn rocket() -> _ {
-    let user_data = vec![
-        (1, 1111),
-        (2, 2222),
-        (3, 3333),
-        (4, 4444),
-        (5, 5555),
-        (6, 6666),
-        (7, 7777),
-        (8, 8888),
-    ]
-    .into_iter()
-    .map(|(id, balance)| UserData { id, balance })
-    .collect();
-
-    let tag_leaf = "ProofOfReserve_Leaf";
-    let tag_branch = "ProofOfReserve_Branch";
-
-    let tree = merkle_tree_lib::MerkleTree::build(tag_leaf, tag_branch, &user_data);
-
-    rocket::build().manage(AppState { tree }).mount(
-        "/",
-        routes![
-            proof_all_users,
-            proof_all_users_display_mermaid_diagram,
-            proof_by_user_id
-        ],
-    )
+    app(rocket::Config::figment())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{app, rocket};
+    use rocket::error::ErrorKind;
+    use rocket::http::Status;
+    use rocket::local::asynchronous::Client as AsyncClient;
+    use rocket::local::blocking::Client;
+    use rocket::serde::json::{json, Value};
+    use std::path::PathBuf;
+
+    #[test]
+    fn it_can_prove_a_known_user() {
+        let client = Client::tracked(rocket()).unwrap();
+        let response = client.get("/proof/3").dispatch();
+        assert_eq!(response.status(), Status::Ok);
+
+        let body: Value = response.into_json().unwrap();
+        assert_eq!(body["user_balance"], 3333);
+        assert_eq!(body["proof"].as_array().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn it_can_respond_not_found_for_an_unknown_user() {
+        let client = Client::tracked(rocket()).unwrap();
+        let response = client.get("/proof/42").dispatch();
+        assert_eq!(response.status(), Status::NotFound);
+
+        let body: Value = response.into_json().unwrap();
+        assert_eq!(body["error"], "user 42 was not found");
+    }
+
+    #[test]
+    fn it_can_reject_invalid_user_ids() {
+        let client = Client::tracked(rocket()).unwrap();
+        for uri in ["/proof/abc", "/proof/-1", "/proof/4294967296"] {
+            let response = client.get(uri).dispatch();
+            assert_eq!(response.status(), Status::BadRequest);
+
+            let body: Value = response.into_json().unwrap();
+            assert!(body["error"]
+                .as_str()
+                .unwrap()
+                .starts_with("invalid user id"));
+        }
+    }
+
+    #[test]
+    fn it_can_respond_with_json_for_unknown_routes() {
+        let client = Client::tracked(rocket()).unwrap();
+        let response = client.get("/unknown").dispatch();
+        assert_eq!(response.status(), Status::NotFound);
+
+        let body: Value = response.into_json().unwrap();
+        assert_eq!(body["error"], "/unknown was not found");
+    }
+
+    #[test]
+    fn it_can_configure_the_tree() {
+        let root = |figment| {
+            let client = Client::tracked(app(figment)).unwrap();
+            client.get("/proof").dispatch().into_string().unwrap()
+        };
+        let default = root(rocket::Config::figment());
+        assert_eq!(default.len(), 64);
+
+        let sha512 = root(rocket::Config::figment().merge(("hash", "sha512")));
+        assert_eq!(sha512.len(), 128);
+
+        let tagged = root(rocket::Config::figment().merge(("tag_leaf", "Other_Leaf")));
+        assert_ne!(tagged, default);
+
+        // Rocket panics when an error of a failed launch is dropped without being inspected.
+        let missing = rocket::Config::figment().merge(("users", "missing.csv"));
+        match Client::tracked(app(missing)) {
+            Err(err) => assert!(matches!(err.kind(), ErrorKind::FailedFairings(_))),
+            Ok(_) => panic!("the app launched without its users"),
+        }
+    }
+
+    #[rocket::async_test]
+    async fn it_can_load_users_from_a_database() {
+        let path = std::env::temp_dir().join(format!("proof-of-reserve-{}.db", std::process::id()));
+        let url = format!("sqlite://{}?mode=rwc", path.display());
+        std::fs::remove_file(&path).ok();
+        sqlx::any::install_default_drivers();
+        let pool = sqlx::AnyPool::connect(&url).await.unwrap();
+        sqlx::query("CREATE TABLE users (id BIGINT PRIMARY KEY, balance BIGINT NOT NULL)")
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO users (id, balance) VALUES (1, 1111), (2, 2222), (3, 3333)")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let figment = rocket::Config::figment().merge(("database_url", &url));
+        let client = AsyncClient::tracked(app(figment)).await.unwrap();
+        let response = client.get("/proof/2").dispatch().await;
+        assert_eq!(response.status(), Status::Ok);
+        let body: Value = response.into_json().await.unwrap();
+        assert_eq!(body["user_balance"], 2222);
+        assert_eq!(body["proof"].as_array().unwrap().len(), 2);
+
+        sqlx::query("INSERT INTO users (id, balance) VALUES (4, 4444)")
+            .execute(&pool)
+            .await
+            .unwrap();
+        let response = client.get("/proof/4").dispatch().await;
+        assert_eq!(response.status(), Status::NotFound);
+
+        let response = client.post("/reload").dispatch().await;
+        assert_eq!(response.status(), Status::Ok);
+        let body: Value = response.into_json().await.unwrap();
+        assert_eq!(body["user_count"], 4);
+        let response = client.get("/proof/4").dispatch().await;
+        assert_eq!(response.status(), Status::Ok);
+
+        // A user inserted before the others is committed to where the next load puts them.
+        let response = client
+            .post("/users")
+            .json(&json!({ "id": 0, "balance": 1 }))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Created);
+        let body: Value = response.into_json().await.unwrap();
+        client.post("/reload").dispatch().await;
+        let response = client.get("/proof").dispatch().await;
+        assert_eq!(body["root"], response.into_string().await.unwrap().as_str());
+
+        pool.close().await;
+        std::fs::remove_file(path).ok();
+    }
+
+    /// Copies the sample users to a file of their own, so that tests can change them.
+    fn temporary_users(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "proof-of-reserve-{}-{name}.csv",
+            std::process::id()
+        ));
+        std::fs::copy("users.csv", &path).unwrap();
+        path
+    }
+
+    #[test]
+    fn it_can_insert_users() {
+        let path = temporary_users("insert");
+        let figment = rocket::Config::figment().merge(("users", &path));
+        let client = Client::tracked(app(figment)).unwrap();
+        let old_root = client.get("/proof").dispatch().into_string().unwrap();
+
+        let response = client
+            .post("/users")
+            .json(&json!({ "id": 9, "balance": 9999 }))
+            .dispatch();
+        assert_eq!(response.status(), Status::Created);
+        let body: Value = response.into_json().unwrap();
+        let root = client.get("/proof").dispatch().into_string().unwrap();
+        assert_eq!(body["root"], root.as_str());
+        assert_ne!(root, old_root);
+
+        let body: Value = client.get("/proof/9").dispatch().into_json().unwrap();
+        assert_eq!(body["user_balance"], 9999);
+        assert!(std::fs::read_to_string(&path)
+            .unwrap()
+            .ends_with("8,8888\n9,9999\n"));
+
+        let response = client
+            .post("/users")
+            .json(&json!({ "id": 9, "balance": 1 }))
+            .dispatch();
+        assert_eq!(response.status(), Status::Conflict);
+        let body: Value = response.into_json().unwrap();
+        assert_eq!(body["error"], "user 9 already exists");
+
+        let response = client
+            .post("/users")
+            .json(&json!({ "id": "ten" }))
+            .dispatch();
+        assert_eq!(response.status(), Status::UnprocessableEntity);
+        let body: Value = response.into_json().unwrap();
+        assert_eq!(body["error"], "malformed request to /users");
+
+        // The users survive a reload from the file.
+        client.post("/reload").dispatch();
+        let reloaded = client.get("/proof").dispatch().into_string().unwrap();
+        assert_eq!(reloaded, root);
+        std::fs::remove_file(path).ok();
+    }
+
+    #[test]
+    fn it_can_update_balances() {
+        let path = temporary_users("update");
+        let figment = rocket::Config::figment().merge(("users", &path));
+        let client = Client::tracked(app(figment)).unwrap();
+        let old_root = client.get("/proof").dispatch().into_string().unwrap();
+
+        let response = client
+            .put("/users/3/balance")
+            .json(&json!({ "balance": 3000 }))
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let body: Value = response.into_json().unwrap();
+        let root = client.get("/proof").dispatch().into_string().unwrap();
+        assert_eq!(body["old_root"], old_root.as_str());
+        assert_eq!(body["new_root"], root.as_str());
+        assert_ne!(root, old_root);
+
+        let body: Value = client.get("/proof/3").dispatch().into_json().unwrap();
+        assert_eq!(body["user_balance"], 3000);
+        assert!(std::fs::read_to_string(&path)
+            .unwrap()
+            .contains("\n3,3000\n"));
+
+        let response = client
+            .put("/users/42/balance")
+            .json(&json!({ "balance": 1 }))
+            .dispatch();
+        assert_eq!(response.status(), Status::NotFound);
+        let body: Value = response.into_json().unwrap();
+        assert_eq!(body["error"], "user 42 was not found");
+
+        let response = client
+            .put("/users/abc/balance")
+            .json(&json!({ "balance": 1 }))
+            .dispatch();
+        assert_eq!(response.status(), Status::BadRequest);
+
+        // The balance survives a reload from the file.
+        client.post("/reload").dispatch();
+        let reloaded = client.get("/proof").dispatch().into_string().unwrap();
+        assert_eq!(reloaded, root);
+        std::fs::remove_file(path).ok();
+    }
+
+    #[test]
+    fn it_keeps_the_tree_if_a_change_cannot_be_saved() {
+        let path = temporary_users("unsaved");
+        let figment = rocket::Config::figment().merge(("users", &path));
+        let client = Client::tracked(app(figment)).unwrap();
+        let old_root = client.get("/proof").dispatch().into_string().unwrap();
+
+        // The file is replaced through a temporary file next to it, which a directory blocks.
+        let blocker = path.with_extension("tmp");
+        std::fs::create_dir(&blocker).unwrap();
+        let response = client
+            .put("/users/3/balance")
+            .json(&json!({ "balance": 3000 }))
+            .dispatch();
+        assert_eq!(response.status(), Status::InternalServerError);
+
+        let root = client.get("/proof").dispatch().into_string().unwrap();
+        assert_eq!(root, old_root);
+        let body: Value = client.get("/proof/3").dispatch().into_json().unwrap();
+        assert_eq!(body["user_balance"], 3333);
+        std::fs::remove_dir(blocker).ok();
+        std::fs::remove_file(path).ok();
+    }
+
+    #[test]
+    fn it_can_remove_users() {
+        let path = temporary_users("remove");
+        let audit_log = path.with_extension("log");
+        let figment = rocket::Config::figment()
+            .merge(("users", &path))
+            .merge(("audit_log", &audit_log));
+        let client = Client::tracked(app(figment)).unwrap();
+        let old_root = client.get("/proof").dispatch().into_string().unwrap();
+
+        let response = client.delete("/users/3").dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let body: Value = response.into_json().unwrap();
+        let root = client.get("/proof").dispatch().into_string().unwrap();
+        assert_eq!(body["root"], root.as_str());
+        assert_ne!(root, old_root);
+        assert_eq!(body["removed_leaf"].as_str().unwrap().len(), 64);
+
+        let response = client.get("/proof/3").dispatch();
+        assert_eq!(response.status(), Status::NotFound);
+        let users = std::fs::read_to_string(&path).unwrap();
+        assert!(users.contains("\n2,2222\n4,4444\n"));
+
+        let response = client.delete("/users/3").dispatch();
+        assert_eq!(response.status(), Status::NotFound);
+        let body: Value = response.into_json().unwrap();
+        assert_eq!(body["error"], "user 3 was not found");
+
+        let log = std::fs::read_to_string(&audit_log).unwrap();
+        let entries: Vec<Value> = log
+            .lines()
+            .map(|line| rocket::serde::json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0]["action"], "remove");
+        assert_eq!(entries[0]["user_id"], 3);
+        assert_eq!(entries[0]["old_root"], old_root.as_str());
+        assert_eq!(entries[0]["new_root"], root.as_str());
+
+        // The removal survives a reload from the file.
+        client.post("/reload").dispatch();
+        let reloaded = client.get("/proof").dispatch().into_string().unwrap();
+        assert_eq!(reloaded, root);
+        std::fs::remove_file(path).ok();
+        std::fs::remove_file(audit_log).ok();
+    }
+
+    #[test]
+    fn it_can_verify_proofs() {
+        let client = Client::tracked(rocket()).unwrap();
+        let root = client.get("/proof").dispatch().into_string().unwrap();
+        let proof: Value = client.get("/proof/3").dispatch().into_json().unwrap();
+        let verify = |user_id, balance, proof: &Value, root: &str| {
+            let response = client
+                .post("/verify")
+                .json(&json!({
+                    "user_id": user_id,
+                    "balance": balance,
+                    "proof": proof,
+                    "root": root,
+                }))
+                .dispatch();
+            (response.status(), response.into_json::<Value>().unwrap())
+        };
+
+        let (status, body) = verify(3, 3333, &proof["proof"], &root);
+        assert_eq!(status, Status::Ok);
+        assert_eq!(body["valid"], true);
+
+        assert_eq!(verify(3, 3334, &proof["proof"], &root).1["valid"], false);
+        assert_eq!(verify(4, 3333, &proof["proof"], &root).1["valid"], false);
+        assert_eq!(
+            verify(3, 3333, &proof["proof"], &"0".repeat(64)).1["valid"],
+            false
+        );
+
+        let mut tampered = proof["proof"].clone();
+        tampered[0][1] = json!(2);
+        let (status, body) = verify(3, 3333, &tampered, &root);
+        assert_eq!(status, Status::BadRequest);
+        assert_eq!(
+            body["error"],
+            "proof step 0 has direction 2, expected 0 or 1"
+        );
+    }
+
+    #[test]
+    fn it_can_prove_users_in_a_batch() {
+        let figment = rocket::Config::figment().merge(("max_batch_size", 3));
+        let client = Client::tracked(app(figment)).unwrap();
+        let root = client.get("/proof").dispatch().into_string().unwrap();
+
+        let response = client.post("/proofs").json(&json!([3, 1, 8])).dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let body: Value = response.into_json().unwrap();
+        assert_eq!(body["root"], root.as_str());
+        let proofs = body["proofs"].as_array().unwrap();
+        assert_eq!(proofs.len(), 3);
+        for (proof, user_id) in proofs.iter().zip([3, 1, 8]) {
+            let response = client.get(format!("/proof/{user_id}")).dispatch();
+            let single: Value = response.into_json().unwrap();
+            assert_eq!(proof["user_id"], user_id);
+            assert_eq!(proof["user_balance"], single["user_balance"]);
+            assert_eq!(proof["proof"], single["proof"]);
+        }
+
+        let response = client.post("/proofs").json(&json!([1, 42])).dispatch();
+        assert_eq!(response.status(), Status::NotFound);
+        let body: Value = response.into_json().unwrap();
+        assert_eq!(body["error"], "user 42 was not found");
+
+        let response = client.post("/proofs").json(&json!([1, 2, 3, 4])).dispatch();
+        assert_eq!(response.status(), Status::PayloadTooLarge);
+        let body: Value = response.into_json().unwrap();
+        assert_eq!(
+            body["error"],
+            "4 user ids were requested, but at most 3 are allowed"
+        );
+    }
 }
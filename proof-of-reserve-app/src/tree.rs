@@ -0,0 +1,197 @@
+use merkle_tree_lib::{
+    Digest, IndexedMerkleTree, MerkleError, NodeDirection, Sha224, Sha256, Sha384, Sha512,
+    TraversePath,
+};
+
+use crate::config::{AppConfig, HashAlgorithm};
+use crate::users::{UserChange, UserData};
+
+/// A user with the sibling hashes and directions of the proof of their leaf.
+pub type UserProof = (UserData, Vec<(String, u8)>);
+
+/// The tree of the users, hashed with the configured algorithm.
+#[derive(Clone)]
+pub enum UserTree {
+    Sha224(IndexedMerkleTree<UserData, Sha224>),
+    Sha256(IndexedMerkleTree<UserData, Sha256>),
+    Sha384(IndexedMerkleTree<UserData, Sha384>),
+    Sha512(IndexedMerkleTree<UserData, Sha512>),
+}
+
+/// Evaluates an expression with the tree of any hash algorithm bound to a name.
+macro_rules! with_tree {
+    ($tree:expr, $name:ident => $body:expr) => {
+        match $tree {
+            UserTree::Sha224($name) => $body,
+            UserTree::Sha256($name) => $body,
+            UserTree::Sha384($name) => $body,
+            UserTree::Sha512($name) => $body,
+        }
+    };
+}
+
+impl UserTree {
+    /// Builds the tree of the given users with the configured tags and hash algorithm.
+    ///
+    /// # Arguments
+    ///
+    /// * `config`: The configuration of the app.
+    /// * `users`: The users to commit to.
+    ///
+    /// # Returns
+    ///
+    /// The tree, or `MerkleError::DuplicateKey` if two users have the same id.
+    pub fn build(config: &AppConfig, users: &[UserData]) -> Result<Self, MerkleError> {
+        fn build<D: Digest>(
+            config: &AppConfig,
+            users: &[UserData],
+        ) -> Result<IndexedMerkleTree<UserData, D>, MerkleError> {
+            IndexedMerkleTree::build(&config.tag_leaf, &config.tag_branch, users)
+        }
+
+        Ok(match config.hash {
+            HashAlgorithm::Sha224 => UserTree::Sha224(build(config, users)?),
+            HashAlgorithm::Sha256 => UserTree::Sha256(build(config, users)?),
+            HashAlgorithm::Sha384 => UserTree::Sha384(build(config, users)?),
+            HashAlgorithm::Sha512 => UserTree::Sha512(build(config, users)?),
+        })
+    }
+
+    /// Returns the hex encoded root of the tree.
+    pub fn root(&self) -> Result<String, MerkleError> {
+        with_tree!(self, tree => tree.root())
+    }
+
+    /// Returns the number of users in the tree.
+    pub fn leaf_count(&self) -> usize {
+        with_tree!(self, tree => tree.leaf_count())
+    }
+
+    /// Returns the users in the order they are committed in.
+    pub fn users(&self) -> Vec<UserData> {
+        with_tree!(self, tree => tree.leaves().map(|(_, user)| user.clone()).collect())
+    }
+
+    /// Applies a change to the tree, rebuilding it only if a user is inserted before the
+    /// existing ones.
+    ///
+    /// No versions of the tree are taken, so the nodes replaced by the change are overwritten
+    /// and a tree changed through the API does not grow with every change.
+    ///
+    /// # Arguments
+    ///
+    /// * `config`: The configuration of the app.
+    /// * `change`: The change to apply.
+    /// * `users`: All users after the change, in the order of the source, which the tree is
+    ///   rebuilt from if needed.
+    ///
+    /// # Returns
+    ///
+    /// The hex encoded hash of the leaf the change replaced or removed, `None` for an inserted
+    /// user, `MerkleError::DuplicateKey` if a user to insert already exists or
+    /// `MerkleError::LeafNotFound` if a user to update or remove does not exist.
+    pub fn apply(
+        &mut self,
+        config: &AppConfig,
+        change: &UserChange,
+        users: &[UserData],
+    ) -> Result<Option<String>, MerkleError> {
+        if let UserChange::Insert(user) = change {
+            let appended = users.last().is_some_and(|last| last.id == user.id);
+            if !appended {
+                *self = UserTree::build(config, users)?;
+                return Ok(None);
+            }
+        }
+
+        with_tree!(self, tree => {
+            let index = tree.index_of(&change.id());
+            let old_leaf = index
+                .map(|index| tree.leaf(index).map(|leaf| format!("{:x}", leaf.hash())))
+                .transpose()?;
+            match (change, index) {
+                (UserChange::Insert(user), _) => tree.push(user.clone())?,
+                (UserChange::UpdateBalance { id, balance }, Some(index)) => {
+                    tree.update(index, UserData { id: *id, balance: *balance })?
+                }
+                (UserChange::Remove(_), Some(index)) => tree.remove(index)?,
+                (_, None) => return Err(MerkleError::LeafNotFound),
+            }
+            Ok(old_leaf)
+        })
+    }
+
+    /// Displays the tree as a Mermaid diagram.
+    pub fn display_mermaid_diagram(&self) -> String {
+        with_tree!(self, tree => tree.display_mermaid_diagram())
+    }
+
+    /// Generates the proof of the user with the given id.
+    ///
+    /// # Arguments
+    ///
+    /// * `id`: The id of the user.
+    ///
+    /// # Returns
+    ///
+    /// The user with their proof, or `MerkleError::LeafNotFound` if no user has the id.
+    pub fn proof_for_id(&self, id: u32) -> Result<UserProof, MerkleError> {
+        with_tree!(self, tree => {
+            let (node, path) = tree.proof_for_key(&id)?;
+            let user = node.user_data.clone().ok_or(MerkleError::LeafNotFound)?;
+            Ok((user, path.to_vec()))
+        })
+    }
+}
+
+/// Verifies that a user is committed to by a root with a proof of the app, hashed with the
+/// configured tags and algorithm.
+///
+/// # Arguments
+///
+/// * `config`: The configuration of the app.
+/// * `root`: The hex encoded root to verify against.
+/// * `user`: The user that is claimed to be committed to.
+/// * `proof`: The sibling hashes and directions of the proof, as `GET /proof/<id>` returns.
+///
+/// # Returns
+///
+/// `true` if the proof leads from the user to `root`, `false` otherwise, or an error if a
+/// direction is not 0 or 1.
+pub fn verify_proof(
+    config: &AppConfig,
+    root: &str,
+    user: &UserData,
+    proof: &[(String, u8)],
+) -> Result<bool, String> {
+    fn verify<D: Digest>(
+        config: &AppConfig,
+        root: &str,
+        user: &UserData,
+        siblings: Vec<String>,
+        directions: Vec<NodeDirection>,
+    ) -> bool {
+        let path = TraversePath::<D>::from_steps(siblings, directions);
+        merkle_tree_lib::verify_proof(&config.tag_leaf, &config.tag_branch, root, user, &path)
+    }
+
+    let directions = (proof.iter().enumerate())
+        .map(|(step, (_, direction))| {
+            // `Root` is a valid direction code, but never the direction of a proof step.
+            match NodeDirection::try_from(*direction) {
+                Ok(direction @ (NodeDirection::Left | NodeDirection::Right)) => Ok(direction),
+                _ => Err(format!(
+                    "proof step {step} has direction {direction}, expected 0 or 1"
+                )),
+            }
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let siblings = proof.iter().map(|(sibling, _)| sibling.clone()).collect();
+
+    Ok(match config.hash {
+        HashAlgorithm::Sha224 => verify::<Sha224>(config, root, user, siblings, directions),
+        HashAlgorithm::Sha256 => verify::<Sha256>(config, root, user, siblings, directions),
+        HashAlgorithm::Sha384 => verify::<Sha384>(config, root, user, siblings, directions),
+        HashAlgorithm::Sha512 => verify::<Sha512>(config, root, user, siblings, directions),
+    })
+}
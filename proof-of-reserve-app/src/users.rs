@@ -0,0 +1,414 @@
+use merkle_tree_lib::{MerkleError, MerkleTreeData, MerkleTreeKey};
+use rocket::serde::Deserialize;
+use sqlx::any::AnyPoolOptions;
+use sqlx::AnyPool;
+use std::collections::HashSet;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::config::AppConfig;
+
+/// Creates the table of users if the database does not have it yet.
+const CREATE_USERS_TABLE: &str =
+    "CREATE TABLE IF NOT EXISTS users (id BIGINT PRIMARY KEY, balance BIGINT NOT NULL)";
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct UserData {
+    pub id: u32,
+    pub balance: u64,
+}
+
+impl MerkleTreeData for UserData {
+    fn serialize(&self) -> Vec<u8> {
+        format!("{},{}", self.id, self.balance).as_bytes().to_vec()
+    }
+
+    fn mermaid_node_label(&self) -> String {
+        format!("<br>User ID: {}<br>Balance: {}", self.id, self.balance)
+    }
+}
+
+impl MerkleTreeKey for UserData {
+    type Key = u32;
+
+    fn key(&self) -> u32 {
+        self.id
+    }
+}
+
+/// Parses users from CSV or TSV rows of `id,balance`.
+///
+/// # Arguments
+///
+/// * `text`: The CSV or TSV text, optionally starting with an `id,balance` header row in any
+///   case.
+///
+/// # Returns
+///
+/// The users, or an error for every malformed row or duplicate id.
+pub fn parse_users(text: &str) -> Result<Vec<UserData>, Vec<String>> {
+    let mut users = Vec::new();
+    let mut ids = HashSet::new();
+    let mut errors = Vec::new();
+    let is_header = |line: &str| {
+        line.split_once([',', '\t']).is_some_and(|(id, balance)| {
+            id.trim().eq_ignore_ascii_case("id") && balance.trim().eq_ignore_ascii_case("balance")
+        })
+    };
+
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || (number == 0 && is_header(line)) {
+            continue;
+        }
+
+        let fields = line.split_once([',', '\t']).and_then(|(id, balance)| {
+            Some((id.trim().parse().ok()?, balance.trim().parse().ok()?))
+        });
+        let Some((id, balance)) = fields else {
+            errors.push(format!(
+                "line {}: expected `id,balance` with a u32 id and a u64 balance",
+                number + 1
+            ));
+            continue;
+        };
+
+        if !ids.insert(id) {
+            errors.push(format!("line {}: duplicate user id {id}", number + 1));
+            continue;
+        }
+        users.push(UserData { id, balance });
+    }
+
+    if errors.is_empty() {
+        Ok(users)
+    } else {
+        Err(errors)
+    }
+}
+
+/// Reads users from a CSV or TSV file of `id,balance` rows.
+///
+/// # Arguments
+///
+/// * `path`: The path of the file.
+///
+/// # Returns
+///
+/// The users, or an error for every malformed row or duplicate id, each prefixed with the
+/// path.
+pub fn read_users(path: &Path) -> Result<Vec<UserData>, Vec<String>> {
+    let prefix = |err: String| format!("{}: {err}", path.display());
+    let text = std::fs::read_to_string(path).map_err(|err| vec![prefix(err.to_string())])?;
+    parse_users(&text).map_err(|errors| errors.into_iter().map(prefix).collect())
+}
+
+/// Writes users to a CSV file of `id,balance` rows with a header row, replacing the file at
+/// once so that a failed write leaves the previous file.
+///
+/// # Arguments
+///
+/// * `path`: The path of the file.
+/// * `users`: The users to write.
+fn write_users(path: &Path, users: &[UserData]) -> std::io::Result<()> {
+    let mut text = String::from("id,balance\n");
+    for user in users {
+        text.push_str(&format!("{},{}\n", user.id, user.balance));
+    }
+
+    let temporary = path.with_extension("tmp");
+    std::fs::write(&temporary, text)?;
+    std::fs::rename(temporary, path)
+}
+
+/// A change to the users made through the API.
+#[derive(Debug, Clone)]
+pub enum UserChange {
+    /// Adds a user after the existing ones, or at the position of their id if the users are
+    /// ordered by id.
+    Insert(UserData),
+    /// Sets the balance of an existing user, keeping their position.
+    UpdateBalance { id: u32, balance: u64 },
+    /// Removes a user, moving the following users up by one position.
+    Remove(u32),
+}
+
+impl UserChange {
+    /// Returns the id of the user the change is about.
+    pub fn id(&self) -> u32 {
+        match self {
+            UserChange::Insert(user) => user.id,
+            UserChange::UpdateBalance { id, .. } => *id,
+            UserChange::Remove(id) => *id,
+        }
+    }
+
+    /// Applies the change to users in the order they are committed in.
+    ///
+    /// # Arguments
+    ///
+    /// * `users`: The users to change.
+    /// * `ordered_by_id`: Whether the users are ordered by id, see `UserSource::orders_by_id`.
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` if the change was applied, `MerkleError::DuplicateKey` if a user to insert
+    /// already exists or `MerkleError::LeafNotFound` if a user to update or remove does not
+    /// exist.
+    pub fn apply_to(
+        &self,
+        users: &mut Vec<UserData>,
+        ordered_by_id: bool,
+    ) -> Result<(), MerkleError> {
+        match self {
+            UserChange::Insert(user) => {
+                if users.iter().any(|existing| existing.id == user.id) {
+                    return Err(MerkleError::DuplicateKey);
+                }
+                let position = if ordered_by_id {
+                    users.partition_point(|existing| existing.id < user.id)
+                } else {
+                    users.len()
+                };
+                users.insert(position, user.clone());
+            }
+            UserChange::UpdateBalance { id, balance } => {
+                let user = users
+                    .iter_mut()
+                    .find(|user| user.id == *id)
+                    .ok_or(MerkleError::LeafNotFound)?;
+                user.balance = *balance;
+            }
+            UserChange::Remove(id) => {
+                let position = users
+                    .iter()
+                    .position(|user| user.id == *id)
+                    .ok_or(MerkleError::LeafNotFound)?;
+                users.remove(position);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Converts a balance to the signed integer of a database column.
+fn balance_column(balance: u64) -> Result<i64, String> {
+    i64::try_from(balance).map_err(|_| format!("database: balance {balance} does not fit BIGINT"))
+}
+
+/// Validates rows of `(id, balance)` read from a database, whose integers are signed.
+///
+/// # Arguments
+///
+/// * `rows`: The rows of the `users` table.
+///
+/// # Returns
+///
+/// The users, or an error for every row whose id is not a u32 or whose balance is negative.
+fn users_from_rows(rows: Vec<(i64, i64)>) -> Result<Vec<UserData>, Vec<String>> {
+    let mut users = Vec::with_capacity(rows.len());
+    let mut errors = Vec::new();
+
+    for (id, balance) in rows {
+        match (u32::try_from(id), u64::try_from(balance)) {
+            (Ok(id), Ok(balance)) => users.push(UserData { id, balance }),
+            (Err(_), _) => errors.push(format!("user id {id} is not a u32")),
+            (_, Err(_)) => errors.push(format!("user {id} has a negative balance {balance}")),
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(users)
+    } else {
+        Err(errors)
+    }
+}
+
+/// Where the users are loaded from.
+#[derive(Clone)]
+pub enum UserSource {
+    /// A CSV or TSV file of `id,balance` rows.
+    File(PathBuf),
+    /// The `users` table of an SQL database, with columns `id` and `balance`.
+    Database(AnyPool),
+}
+
+impl UserSource {
+    /// Connects to the configured database, creating the `users` table if it does not exist,
+    /// or selects the configured file if no database is configured.
+    ///
+    /// # Arguments
+    ///
+    /// * `config`: The configuration of the app.
+    pub async fn connect(config: &AppConfig) -> Result<Self, String> {
+        let Some(url) = &config.database_url else {
+            return Ok(UserSource::File(config.users.clone()));
+        };
+
+        // The URL is not included in errors, as it may contain a password.
+        sqlx::any::install_default_drivers();
+        let pool = AnyPoolOptions::new()
+            .max_connections(config.database_connections)
+            .connect(url)
+            .await
+            .map_err(|err| format!("database: {err}"))?;
+        sqlx::query(CREATE_USERS_TABLE)
+            .execute(&pool)
+            .await
+            .map_err(|err| format!("database: {err}"))?;
+        Ok(UserSource::Database(pool))
+    }
+
+    /// Returns `true` if the users are loaded in the order of their ids rather than in the
+    /// order they were added, so that a change must keep them in that order to give the same
+    /// root as the next load.
+    pub fn orders_by_id(&self) -> bool {
+        matches!(self, UserSource::Database(_))
+    }
+
+    /// Loads the users, in the order of the rows of the file or by id from the database.
+    ///
+    /// # Returns
+    ///
+    /// The users, or an error for every problem with the source.
+    pub async fn load(&self) -> Result<Vec<UserData>, Vec<String>> {
+        match self {
+            UserSource::File(path) => {
+                let path = path.clone();
+                rocket::tokio::task::spawn_blocking(move || read_users(&path))
+                    .await
+                    .map_err(|err| vec![err.to_string()])?
+            }
+            UserSource::Database(pool) => {
+                let rows = sqlx::query_as("SELECT id, balance FROM users ORDER BY id")
+                    .fetch_all(pool)
+                    .await
+                    .map_err(|err| vec![format!("database: {err}")])?;
+                users_from_rows(rows).map_err(|errors| {
+                    errors
+                        .into_iter()
+                        .map(|err| format!("database: {err}"))
+                        .collect()
+                })
+            }
+        }
+    }
+}
+
+impl UserSource {
+    /// Saves a change to the source.
+    ///
+    /// # Arguments
+    ///
+    /// * `change`: The change to save, which is executed on a database.
+    /// * `users`: All users after the change, which are written to a file.
+    pub async fn save(&self, change: &UserChange, users: &[UserData]) -> Result<(), String> {
+        match self {
+            UserSource::File(path) => {
+                let (path, users) = (path.clone(), users.to_vec());
+                rocket::tokio::task::spawn_blocking(move || write_users(&path, &users))
+                    .await
+                    .map_err(|err| err.to_string())?
+                    .map_err(|err| format!("{}: {err}", self))
+            }
+            UserSource::Database(pool) => {
+                let query = match change {
+                    UserChange::Insert(user) => {
+                        sqlx::query("INSERT INTO users (id, balance) VALUES ($1, $2)")
+                            .bind(i64::from(user.id))
+                            .bind(balance_column(user.balance)?)
+                    }
+                    UserChange::UpdateBalance { id, balance } => {
+                        sqlx::query("UPDATE users SET balance = $1 WHERE id = $2")
+                            .bind(balance_column(*balance)?)
+                            .bind(i64::from(*id))
+                    }
+                    UserChange::Remove(id) => {
+                        sqlx::query("DELETE FROM users WHERE id = $1").bind(i64::from(*id))
+                    }
+                };
+                let result = query
+                    .execute(pool)
+                    .await
+                    .map_err(|err| format!("database: {err}"))?;
+                if result.rows_affected() == 0 {
+                    return Err(format!("database: user {} was not found", change.id()));
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl fmt::Display for UserSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UserSource::File(path) => write!(f, "{}", path.display()),
+            UserSource::Database(_) => f.write_str("database"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_users, users_from_rows, UserChange, UserData};
+
+    #[test]
+    fn it_can_parse_users() {
+        let users = parse_users("id,balance\n1,1111\n\n 2, 2222\n3\t3333\n").unwrap();
+        let users: Vec<_> = users.iter().map(|user| (user.id, user.balance)).collect();
+        assert_eq!(users, [(1, 1111), (2, 2222), (3, 3333)]);
+        assert_eq!(parse_users("ID\tBalance\n1\t1111\n").unwrap().len(), 1);
+
+        let errors = parse_users("user,amount\n1,1111\n").unwrap_err();
+        assert_eq!(
+            errors,
+            ["line 1: expected `id,balance` with a u32 id and a u64 balance"]
+        );
+
+        let errors = parse_users("1,1111\n2\n-3,3333\n1,4444\n").unwrap_err();
+        assert_eq!(
+            errors,
+            [
+                "line 2: expected `id,balance` with a u32 id and a u64 balance",
+                "line 3: expected `id,balance` with a u32 id and a u64 balance",
+                "line 4: duplicate user id 1",
+            ]
+        );
+    }
+
+    #[test]
+    fn it_can_insert_users_in_the_order_of_the_source() {
+        let insert = |ordered_by_id| {
+            let mut users: Vec<_> = [1, 3, 5]
+                .into_iter()
+                .map(|id| UserData { id, balance: 0 })
+                .collect();
+            let user = UserData { id: 2, balance: 0 };
+            UserChange::Insert(user)
+                .apply_to(&mut users, ordered_by_id)
+                .unwrap();
+            users.iter().map(|user| user.id).collect::<Vec<_>>()
+        };
+        assert_eq!(insert(false), [1, 3, 5, 2]);
+        assert_eq!(insert(true), [1, 2, 3, 5]);
+    }
+
+    #[test]
+    fn it_can_validate_database_rows() {
+        let users = users_from_rows(vec![(1, 1111), (2, 0)]).unwrap();
+        let users: Vec<_> = users.iter().map(|user| (user.id, user.balance)).collect();
+        assert_eq!(users, [(1, 1111), (2, 0)]);
+
+        let errors = users_from_rows(vec![(-1, 1111), (2, -2222), (1 << 32, 0)]).unwrap_err();
+        assert_eq!(
+            errors,
+            [
+                "user id -1 is not a u32",
+                "user 2 has a negative balance -2222",
+                "user id 4294967296 is not a u32",
+            ]
+        );
+    }
+}